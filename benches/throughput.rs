@@ -2,9 +2,12 @@ use criterion::{
     black_box, criterion_group, criterion_main, measurement::WallTime,
     BenchmarkGroup, Criterion, Throughput,
 };
+use dasp::{
+    sample::{FromSample, ToSample},
+    Frame, Sample,
+};
 use hound::WavReader;
-use noise_gate::{NoiseGate, Sink};
-use sample::{Frame, FromSample, Sample, ToSample};
+use noise_gate::{frames, NoiseGate, Sink};
 use std::{fs, path::Path};
 
 const DATA_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/data/");
@@ -23,21 +26,169 @@ fn bench_throughput(c: &mut Criterion) {
     }
 }
 
-fn add_benchmark(
-    group: &mut BenchmarkGroup<WallTime>,
-    name: &str,
-    path: &Path,
-) {
+/// Roughly 10 minutes of low-level background noise at 48kHz with a short
+/// burst of audio every minute, similar to a quiet radio channel.
+fn mostly_silent() -> Vec<[i16; 1]> {
+    let sample_rate = 48_000;
+    let mut seed = 0x1234_5678_u32;
+
+    (0..10 * 60 * sample_rate)
+        .map(|i| {
+            // xorshift, so the noise is deterministic
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            let noise = (seed % 64) as i16 - 32;
+
+            if i % (60 * sample_rate) < sample_rate {
+                [noise.saturating_mul(200)]
+            } else {
+                [noise]
+            }
+        })
+        .collect()
+}
+
+fn bench_mostly_silent(c: &mut Criterion) {
+    let samples = mostly_silent();
+
+    c.benchmark_group("mostly-silent")
+        .throughput(Throughput::Elements(samples.len() as u64))
+        .bench_function("process_frames", |b| {
+            b.iter(|| {
+                let mut counter = Counter::default();
+                let mut gate = NoiseGate::new(500, 4800);
+                gate.process_frames(&samples, &mut counter);
+            });
+        })
+        .bench_function("parallel", |b| {
+            b.iter(|| {
+                noise_gate::parallel::find_segments(
+                    black_box(&samples),
+                    500,
+                    4800,
+                    4,
+                )
+            });
+        });
+}
+
+/// A minute of 48kHz noise where every channel is loud for the first quarter
+/// of each second, for exercising the generic code with different frame
+/// types.
+fn synthesize<F>() -> Vec<F>
+where
+    F: Frame,
+    F::Sample: FromSample<i16>,
+{
+    let sample_rate = 48_000;
+    let mut seed = 0x8765_4321_u32;
+
+    (0..60 * sample_rate)
+        .map(|i| {
+            let loud = i % sample_rate < sample_rate / 4;
+
+            F::from_fn(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                let noise = (seed % 64) as i16 - 32;
+
+                if loud {
+                    noise.saturating_mul(200).to_sample()
+                } else {
+                    noise.to_sample()
+                }
+            })
+        })
+        .collect()
+}
+
+fn bench_formats(c: &mut Criterion) {
+    let mut group = c.benchmark_group("formats");
+
+    add_synthesized::<[i16; 1]>(&mut group, "i16/mono");
+    add_synthesized::<[f32; 1]>(&mut group, "f32/mono");
+    add_synthesized::<[i16; 2]>(&mut group, "i16/stereo");
+    add_synthesized::<[f32; 2]>(&mut group, "f32/stereo");
+    add_synthesized::<[i16; 8]>(&mut group, "i16/8-channel");
+    add_synthesized::<[f32; 8]>(&mut group, "f32/8-channel");
+}
+
+fn add_synthesized<F>(group: &mut BenchmarkGroup<WallTime>, name: &str)
+where
+    F: Frame,
+    F::Sample: FromSample<i16>,
+{
+    let frames = synthesize::<F>();
+    let threshold = 500_i16.to_sample::<F::Sample>();
+
+    group
+        .throughput(Throughput::Elements(frames.len() as u64))
+        .bench_function(name, |b| {
+            b.iter(|| {
+                let mut counter = Counter::default();
+                let mut gate = NoiseGate::new(threshold, 4800);
+                gate.process_frames(&frames, &mut counter);
+            });
+        });
+}
+
+fn bench_scan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scan");
+
+    for entry in fs::read_dir(DATA_DIR).unwrap() {
+        let entry = entry.unwrap();
+        let path = entry.path();
+
+        if path.is_file() {
+            let name = path.file_stem().unwrap().to_str().unwrap();
+            let (_, samples) = load(&path);
+            // scan for the loudest frame so we need to look at as much of
+            // the recording as possible
+            let threshold = samples.iter().map(|[s]| s.saturating_abs()).max();
+            let threshold = threshold.unwrap_or(i16::MAX);
+
+            group.throughput(Throughput::Elements(samples.len() as u64));
+            group.bench_function(format!("{}/naive", name), |b| {
+                b.iter(|| {
+                    samples
+                        .iter()
+                        .position(|&[s]| s >= threshold || s <= -threshold)
+                });
+            });
+            group.bench_function(format!("{}/blocks", name), |b| {
+                b.iter(|| {
+                    noise_gate::first_above_threshold(
+                        black_box(&samples),
+                        threshold,
+                    )
+                });
+            });
+        }
+    }
+}
+
+fn load(path: &Path) -> (u32, Vec<[i16; 1]>) {
     let reader = WavReader::open(path).unwrap();
 
     let desc = reader.spec();
     assert_eq!(desc.channels, 1, "We've hard-coded frames to be [i16; 1]");
-    let release_time = 2 * desc.sample_rate as usize;
 
-    let samples = reader
-        .into_samples::<i16>()
-        .map(|s| [s.unwrap()])
-        .collect::<Vec<_>>();
+    let samples = frames::try_from_samples(reader.into_samples::<i16>())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    (desc.sample_rate, samples)
+}
+
+fn add_benchmark(
+    group: &mut BenchmarkGroup<WallTime>,
+    name: &str,
+    path: &Path,
+) {
+    let (sample_rate, samples) = load(path);
+    let release_time = 2 * sample_rate as usize;
 
     let noise_threshold = average(&samples);
 
@@ -76,5 +227,11 @@ impl<F> Sink<F> for Counter {
     fn end_of_transmission(&mut self) { self.chunks += black_box(1); }
 }
 
-criterion_group!(benches, bench_throughput);
+criterion_group!(
+    benches,
+    bench_throughput,
+    bench_mostly_silent,
+    bench_formats,
+    bench_scan
+);
 criterion_main!(benches);
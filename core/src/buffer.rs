@@ -0,0 +1,120 @@
+//! A statically sized [`Sink`] for when there's no heap.
+
+use crate::Sink;
+
+/// A [`Sink`] which collects recorded frames into an array of `N` frames,
+/// like a `Vec` with a fixed capacity.
+///
+/// Everything is stored inline and it can be created in a `const` context,
+/// so the whole processor can live in a `static`.
+///
+/// ```rust
+/// use noise_gate_core::{Detection, FixedBuffer, NoiseGate};
+///
+/// const RELEASE_TIME: usize = 2;
+/// const GATE: NoiseGate<i16> =
+///     NoiseGate::new(100, RELEASE_TIME).with_detection(Detection::Downmix);
+///
+/// let mut gate = GATE;
+/// let mut buffer = FixedBuffer::<[i16; 2], 4>::new([0, 0]);
+///
+/// let frames = [[0, 0], [300, 300], [0, 0], [0, 0], [0, 0], [0, 0]];
+/// gate.process_frames(&frames, &mut buffer);
+///
+/// assert_eq!(buffer.frames(), &[[300, 300], [0, 0], [0, 0], [0, 0]]);
+/// assert_eq!(buffer.transmissions(), 1);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixedBuffer<F, const N: usize> {
+    frames: [F; N],
+    len: usize,
+    dropped: usize,
+    transmissions: usize,
+}
+
+impl<F: Copy, const N: usize> FixedBuffer<F, N> {
+    /// Create an empty [`FixedBuffer`], using `fill` for the unused slots.
+    pub const fn new(fill: F) -> Self {
+        FixedBuffer {
+            frames: [fill; N],
+            len: 0,
+            dropped: 0,
+            transmissions: 0,
+        }
+    }
+}
+
+impl<F, const N: usize> FixedBuffer<F, N> {
+    /// The frames which have been recorded so far.
+    pub fn frames(&self) -> &[F] { &self.frames[..self.len] }
+
+    /// The maximum number of frames the buffer can hold.
+    pub const fn capacity(&self) -> usize { N }
+
+    /// The number of frames in the buffer.
+    pub const fn len(&self) -> usize { self.len }
+
+    /// Is the buffer empty?
+    pub const fn is_empty(&self) -> bool { self.len == 0 }
+
+    /// Is there no more room in the buffer?
+    pub const fn is_full(&self) -> bool { self.len == N }
+
+    /// How many frames were thrown away because the buffer was full.
+    pub const fn dropped(&self) -> usize { self.dropped }
+
+    /// How many transmissions have ended.
+    pub const fn transmissions(&self) -> usize { self.transmissions }
+
+    /// Empty the buffer so it can be reused, keeping the counters.
+    pub fn clear(&mut self) { self.len = 0; }
+}
+
+impl<F: Copy, const N: usize> Sink<F> for FixedBuffer<F, N> {
+    fn record(&mut self, frame: F) { self.record_frames(&[frame]); }
+
+    fn record_frames(&mut self, frames: &[F]) {
+        let available = N - self.len;
+        let accepted = frames.len().min(available);
+
+        self.frames[self.len..self.len + accepted]
+            .copy_from_slice(&frames[..accepted]);
+        self.len += accepted;
+        self.dropped += frames.len() - accepted;
+    }
+
+    fn end_of_transmission(&mut self) { self.transmissions += 1; }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NoiseGate;
+    use std::sync::Mutex;
+
+    static PROCESSOR: Mutex<(NoiseGate<i16>, FixedBuffer<[i16; 1], 8>)> =
+        Mutex::new((NoiseGate::new(100, 1), FixedBuffer::new([0])));
+
+    #[test]
+    fn processors_can_live_in_a_static() {
+        let frames: Vec<[i16; 1]> = (0..20)
+            .map(|i| [if i % 4 == 0 { 500 } else { 0 }])
+            .collect();
+        let mut processor = PROCESSOR.lock().unwrap();
+        let (gate, buffer) = &mut *processor;
+
+        gate.process_frames(&frames, buffer);
+
+        assert!(buffer.is_full());
+        assert_eq!(
+            buffer.frames(),
+            &[[500], [0], [0], [500], [0], [0], [500], [0]]
+        );
+        assert_eq!(buffer.dropped(), 7);
+        assert_eq!(buffer.transmissions(), 5);
+
+        buffer.clear();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.capacity(), 8);
+    }
+}
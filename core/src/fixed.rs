@@ -0,0 +1,281 @@
+//! Integer-only building blocks for targets without a floating point unit
+//! (e.g. Cortex-M0).
+//!
+//! When it's given integer samples and [`Detection::AnyChannel`], the
+//! [`NoiseGate`] only ever compares samples, so it doesn't need any floating
+//! point. [`Detection::Downmix`] scales each channel by a float, so use
+//! [`downmix()`] to build a mono sidechain for
+//! [`NoiseGate::process_sidechain()`] instead.
+//!
+//! Everything in this module is only implemented for integer samples, so
+//! code which sticks to it can't accidentally pull in float routines.
+//!
+//! ```rust
+//! use noise_gate_core::{
+//!     fixed::{self, Envelope, Gain},
+//!     NoiseGate,
+//! };
+//!
+//! let frames = [[0_i16, 0], [900, -1100], [0, 0], [0, 0], [0, 0]];
+//!
+//! // smooth the downmixed signal so the gate doesn't chatter
+//! let mut envelope = Envelope::new(1);
+//! let sidechain: Vec<[i16; 1]> = frames
+//!     .iter()
+//!     .map(|&frame| [envelope.process([fixed::downmix(frame)])])
+//!     .collect();
+//! assert_eq!(sidechain, vec![[0], [100], [50], [25], [13]]);
+//!
+//! let mut recorded = Vec::new();
+//! NoiseGate::new(40, 0).process_sidechain(&frames, &sidechain, &mut recorded);
+//! assert_eq!(recorded, vec![[900, -1100], [0, 0], [0, 0]]);
+//!
+//! // then fade in using Q1.15 gains
+//! let faded: Vec<[i16; 2]> = recorded
+//!     .iter()
+//!     .enumerate()
+//!     .map(|(i, &frame)| Gain::ramp(i + 1, 2).apply(frame))
+//!     .collect();
+//! assert_eq!(faded, vec![[450, -550], [0, 0], [0, 0]]);
+//! ```
+//!
+//! [`Detection::AnyChannel`]: crate::Detection::AnyChannel
+//! [`Detection::Downmix`]: crate::Detection::Downmix
+//! [`NoiseGate`]: crate::NoiseGate
+//! [`NoiseGate::process_sidechain()`]: crate::NoiseGate::process_sidechain
+
+use dasp::{
+    sample::{I24, U24},
+    Frame, Sample,
+};
+
+/// An integer sample which can be widened to an `i64` for fixed-point math.
+///
+/// This is implemented for every integer format up to 32 bits wide.
+pub trait FixedSample: Sample {
+    /// The sample's signed distance from [`Sample::EQUILIBRIUM`].
+    fn to_fixed(self) -> i64;
+    /// Convert a signed distance from [`Sample::EQUILIBRIUM`] back to a
+    /// sample, saturating if it's out of range.
+    fn from_fixed(value: i64) -> Self;
+}
+
+macro_rules! impl_fixed_sample {
+    ($( $sample:ty => $repr:ty, $min:expr, $max:expr, $offset:expr, $new:expr, $inner:expr; )*) => {
+        $(
+            impl FixedSample for $sample {
+                #[allow(clippy::redundant_closure_call)]
+                fn to_fixed(self) -> i64 {
+                    i64::from(($inner)(self)) - $offset
+                }
+
+                #[allow(clippy::redundant_closure_call)]
+                fn from_fixed(value: i64) -> Self {
+                    let value = value.saturating_add($offset).clamp($min, $max);
+                    ($new)(value as $repr)
+                }
+            }
+        )*
+    };
+}
+
+impl_fixed_sample! {
+    i8 => i8, -0x80, 0x7F, 0, |s| s, |s| s;
+    i16 => i16, -0x8000, 0x7FFF, 0, |s| s, |s| s;
+    I24 => i32, -0x80_0000, 0x7F_FFFF, 0, I24::new_unchecked, I24::inner;
+    i32 => i32, -0x8000_0000, 0x7FFF_FFFF, 0, |s| s, |s| s;
+    u8 => u8, 0, 0xFF, 0x80, |s| s, |s| s;
+    u16 => u16, 0, 0xFFFF, 0x8000, |s| s, |s| s;
+    U24 => i32, 0, 0xFF_FFFF, 0x80_0000, U24::new_unchecked, U24::inner;
+    u32 => u32, 0, 0xFFFF_FFFF, 0x8000_0000, |s| s, |s| s;
+}
+
+/// Average a frame's channels down to mono, using integer math.
+///
+/// This is the fixed-point equivalent of [`Detection::Downmix`], rounding
+/// towards equilibrium.
+///
+/// [`Detection::Downmix`]: crate::Detection::Downmix
+pub fn downmix<F>(frame: F) -> F::Sample
+where
+    F: Frame,
+    F::Sample: FixedSample,
+{
+    let sum: i64 = frame.channels().map(FixedSample::to_fixed).sum();
+
+    F::Sample::from_fixed(sum / F::CHANNELS.max(1) as i64)
+}
+
+/// A gain in Q1.15 fixed-point format, where `32768` leaves samples
+/// untouched and `0` silences them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Gain(u16);
+
+impl Gain {
+    /// The number of fractional bits.
+    pub const FRACTIONAL_BITS: u32 = 15;
+    /// Leave the signal untouched.
+    pub const UNITY: Gain = Gain(1 << Gain::FRACTIONAL_BITS);
+    /// Silence.
+    pub const ZERO: Gain = Gain(0);
+
+    /// Create a gain from its raw Q1.15 value, clamping it to
+    /// [`Gain::UNITY`].
+    pub const fn from_raw(raw: u16) -> Self {
+        if raw > Gain::UNITY.0 {
+            Gain::UNITY
+        } else {
+            Gain(raw)
+        }
+    }
+
+    /// The raw Q1.15 value.
+    pub const fn raw(self) -> u16 { self.0 }
+
+    /// The gain `position` frames into a linear fade-in lasting `length`
+    /// frames, reaching [`Gain::UNITY`] once the fade is finished.
+    ///
+    /// For a fade-out, count the position backwards from the end.
+    pub const fn ramp(position: usize, length: usize) -> Self {
+        if position >= length {
+            return Gain::UNITY;
+        }
+
+        let unity = Gain::UNITY.0 as u64;
+        Gain((position as u64 * unity / length as u64) as u16)
+    }
+
+    /// Scale every channel in a frame, rounding to the nearest value.
+    pub fn apply<F>(self, frame: F) -> F
+    where
+        F: Frame,
+        F::Sample: FixedSample,
+    {
+        let gain = i64::from(self.0);
+        let half = 1_i64 << (Gain::FRACTIONAL_BITS - 1);
+
+        frame.map(|sample| {
+            let scaled =
+                (sample.to_fixed() * gain + half) >> Gain::FRACTIONAL_BITS;
+            F::Sample::from_fixed(scaled)
+        })
+    }
+}
+
+/// A peak envelope follower which only uses integer math.
+///
+/// The envelope jumps straight up to any frame which is louder than it, then
+/// decays towards quieter frames by `1 / 2^release_shift` of the difference
+/// each frame (i.e. with a time constant of roughly `2^release_shift`
+/// frames).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    release_shift: u32,
+    level: i64,
+}
+
+impl Envelope {
+    /// Create a new [`Envelope`] which starts at silence.
+    pub const fn new(release_shift: u32) -> Self {
+        Envelope {
+            release_shift,
+            level: 0,
+        }
+    }
+
+    /// How quickly the envelope decays.
+    pub const fn release_shift(&self) -> u32 { self.release_shift }
+
+    /// The current level, as a distance from equilibrium.
+    pub const fn level(&self) -> i64 { self.level }
+
+    /// Follow the loudest channel of the next frame, returning the new level
+    /// as a sample (above equilibrium) so it can be used as a sidechain.
+    pub fn process<F>(&mut self, frame: F) -> F::Sample
+    where
+        F: Frame,
+        F::Sample: FixedSample,
+    {
+        let peak = frame
+            .channels()
+            .map(|sample| sample.to_fixed().abs())
+            .max()
+            .unwrap_or(0);
+
+        if peak >= self.level {
+            self.level = peak;
+        } else {
+            let shift = self.release_shift.min(63);
+            // always decay by at least a step, so we actually reach the peak
+            let step = ((self.level - peak) >> shift).max(1);
+            self.level -= step;
+        }
+
+        F::Sample::from_fixed(self.level)
+    }
+
+    /// Reset the envelope back to silence.
+    pub fn reset(&mut self) { self.level = 0; }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_samples_round_trip() {
+        for &value in &[-0x8000_i64, -1, 0, 1, 0x7FFF] {
+            assert_eq!(i16::from_fixed(value).to_fixed(), value);
+        }
+        assert_eq!(<u8 as Sample>::EQUILIBRIUM.to_fixed(), 0);
+        assert_eq!(u8::from_fixed(-0x80), 0);
+        assert_eq!(U24::from_fixed(0).to_fixed(), 0);
+        assert_eq!(I24::from_fixed(i64::MIN).inner(), -0x80_0000);
+        assert_eq!(u32::from_fixed(i64::MAX), u32::MAX);
+    }
+
+    #[test]
+    fn downmix_averages_channels_without_overflowing() {
+        assert_eq!(downmix([i16::MAX, i16::MAX]), i16::MAX);
+        assert_eq!(downmix([i16::MIN, i16::MAX]), 0);
+        assert_eq!(downmix([100_u8, 200]), 150);
+        assert_eq!(downmix([7_i32, -8, 3]), 0);
+    }
+
+    #[test]
+    fn gains_scale_samples() {
+        assert_eq!(
+            Gain::UNITY.apply([i16::MIN, i16::MAX]),
+            [i16::MIN, i16::MAX]
+        );
+        assert_eq!(Gain::ZERO.apply([1000_i16]), [0]);
+        assert_eq!(Gain::ramp(1, 2).apply([1000_i16, -1001]), [500, -500]);
+        assert_eq!(Gain::ramp(1, 4).apply([0_u8]), [96]);
+        assert_eq!(Gain::ramp(5, 4), Gain::UNITY);
+        assert_eq!(Gain::ramp(0, 0), Gain::UNITY);
+        assert_eq!(Gain::from_raw(u16::MAX), Gain::UNITY);
+    }
+
+    #[test]
+    fn envelope_attacks_instantly_and_releases_slowly() {
+        let mut envelope = Envelope::new(2);
+        let input = [0_i16, 1000, 0, 0, -1200, 0];
+
+        let got: Vec<i16> =
+            input.iter().map(|&s| envelope.process([s])).collect();
+
+        assert_eq!(got, vec![0, 1000, 750, 563, 1200, 900]);
+    }
+
+    #[test]
+    fn envelope_settles_on_the_input() {
+        let mut envelope = Envelope::new(8);
+        envelope.process([i32::MAX]);
+
+        for _ in 0..100_000 {
+            envelope.process([0_i32]);
+        }
+
+        assert_eq!(envelope.level(), 0);
+    }
+}
@@ -0,0 +1,179 @@
+//! Running the gate as an iterator adapter, with the transmission boundaries
+//! interleaved between the frames.
+
+use crate::NoiseGate;
+use core::{fmt, slice};
+use dasp::Frame;
+
+/// Something yielded by [`Gated`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GatedItem<F> {
+    /// The gate has just opened, and the next frame is frame number
+    /// `position` (see [`NoiseGate::position()`]).
+    Started {
+        /// The index of the first frame in the transmission.
+        position: u64,
+    },
+    /// A frame which was let through the gate.
+    Frame(F),
+    /// The gate has closed, ending the current transmission.
+    Ended,
+}
+
+/// An iterator adapter which passes frames through a [`NoiseGate`], yielding
+/// the frames it lets through with a [`GatedItem::Started`] and
+/// [`GatedItem::Ended`] around each transmission.
+///
+/// This gives exactly the same sequence of calls a [`Sink`][crate::Sink]
+/// would see, but as a single ordered stream the caller can pull from. If
+/// the gate is still open when the frames run out, there's no
+/// [`GatedItem::Ended`].
+///
+/// ```rust
+/// use noise_gate_core::{Gated, GatedItem, NoiseGate};
+///
+/// let frames = vec![[0_i16], [500], [600], [0], [0], [0]];
+///
+/// let items: Vec<_> = Gated::new(NoiseGate::new(100, 0), frames).collect();
+///
+/// assert_eq!(
+///     items,
+///     vec![
+///         GatedItem::Started { position: 1 },
+///         GatedItem::Frame([500]),
+///         GatedItem::Frame([600]),
+///         GatedItem::Frame([0]),
+///         GatedItem::Ended,
+///     ]
+/// );
+/// ```
+///
+/// A `dasp` signal can be gated the same way, using
+/// `Signal::until_exhausted()` to turn it into an iterator.
+pub struct Gated<I, F: Frame> {
+    gate: NoiseGate<F::Sample>,
+    frames: I,
+    /// A frame which opened the gate, to be yielded after the
+    /// [`GatedItem::Started`].
+    pending: Option<F>,
+}
+
+impl<I, F: Frame> Gated<I, F> {
+    /// Pass `frames` through a `gate`.
+    pub fn new<T>(gate: NoiseGate<F::Sample>, frames: T) -> Self
+    where
+        T: IntoIterator<IntoIter = I, Item = F>,
+    {
+        Gated {
+            gate,
+            frames: frames.into_iter(),
+            pending: None,
+        }
+    }
+
+    /// Get a reference to the [`NoiseGate`].
+    pub fn gate(&self) -> &NoiseGate<F::Sample> { &self.gate }
+
+    /// Get a mutable reference to the [`NoiseGate`] (e.g. to adjust its
+    /// threshold part-way through).
+    pub fn gate_mut(&mut self) -> &mut NoiseGate<F::Sample> { &mut self.gate }
+
+    /// Consume the adapter, returning the [`NoiseGate`] and the frames which
+    /// haven't been read yet.
+    ///
+    /// If the last item was a [`GatedItem::Started`], the frame which
+    /// opened the gate is lost.
+    pub fn into_inner(self) -> (NoiseGate<F::Sample>, I) {
+        (self.gate, self.frames)
+    }
+}
+
+impl<I, F> Iterator for Gated<I, F>
+where
+    I: Iterator<Item = F>,
+    F: Frame,
+{
+    type Item = GatedItem<F>;
+
+    fn next(&mut self) -> Option<GatedItem<F>> {
+        if let Some(frame) = self.pending.take() {
+            // we already know this frame opened the gate, so it'll be
+            // recorded
+            self.gate.next_run(slice::from_ref(&frame));
+            return Some(GatedItem::Frame(frame));
+        }
+
+        loop {
+            let frame = self.frames.next()?;
+            let run = self.gate.next_run(slice::from_ref(&frame));
+
+            if run.recorded > 0 {
+                return Some(GatedItem::Frame(frame));
+            } else if run.closed {
+                return Some(GatedItem::Ended);
+            } else if run.opened {
+                // the gate opens *before* the loud frame, so it hasn't been
+                // consumed yet
+                self.pending = Some(frame);
+                return Some(GatedItem::Started {
+                    position: self.gate.position(),
+                });
+            }
+        }
+    }
+}
+
+impl<I, F> fmt::Debug for Gated<I, F>
+where
+    I: fmt::Debug,
+    F: Frame + fmt::Debug,
+    F::Sample: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Gated")
+            .field("gate", &self.gate)
+            .field("frames", &self.frames)
+            .field("pending", &self.pending)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sink;
+
+    #[derive(Debug, Default)]
+    struct Items(Vec<GatedItem<[i16; 1]>>);
+
+    impl Sink<[i16; 1]> for Items {
+        fn record(&mut self, frame: [i16; 1]) {
+            self.0.push(GatedItem::Frame(frame));
+        }
+
+        fn end_of_transmission(&mut self) { self.0.push(GatedItem::Ended); }
+
+        fn transmission_started(&mut self, position: u64) {
+            self.0.push(GatedItem::Started { position });
+        }
+    }
+
+    #[test]
+    fn the_same_items_a_sink_would_see() {
+        let frames: Vec<[i16; 1]> = (0..500_i16)
+            .map(|i| if (i / 20) % 3 == 0 { [i] } else { [0] })
+            .collect();
+
+        for &release_time in &[0, 1, 5, 30] {
+            let mut expected = Items::default();
+            NoiseGate::new(100, release_time)
+                .process_frames(&frames, &mut expected);
+
+            let gate = NoiseGate::new(100, release_time);
+            let got: Vec<_> =
+                Gated::new(gate, frames.iter().copied()).collect();
+
+            assert_eq!(got, expected.0, "release time: {}", release_time);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! A ring buffer of the most recent frames, for keeping some pre-roll
+//! around in case the gate opens.
+
+use crate::Sink;
+use alloc::collections::VecDeque;
+
+/// A fixed-capacity ring buffer which remembers the last few frames, like a
+/// wake-word detector keeps the audio from just before it triggered.
+///
+/// Always-on listeners normally push every frame the gate skips into a
+/// [`HistoryBuffer`], then [drain it into the sink][Self::drain_into] from
+/// [`Sink::transmission_started()`] so each clip starts a little before
+/// whatever opened the gate. The `noise-gate` crate's `PreRoll` does exactly
+/// this.
+///
+/// All the storage is allocated up front, so pushing frames never allocates
+/// and the buffer can be used from an audio thread.
+///
+/// ```rust
+/// use noise_gate_core::HistoryBuffer;
+///
+/// let mut history = HistoryBuffer::new(3);
+/// history.extend([[1_i16], [2], [3], [4], [5]]);
+/// assert!(history.is_full());
+///
+/// let mut recording = Vec::new();
+/// history.drain_into(&mut recording);
+///
+/// // only the most recent frames are kept
+/// assert_eq!(recording, vec![[3], [4], [5]]);
+/// assert!(history.is_empty());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryBuffer<F> {
+    frames: VecDeque<F>,
+    capacity: usize,
+}
+
+impl<F> HistoryBuffer<F> {
+    /// Create an empty [`HistoryBuffer`] which holds up to `capacity`
+    /// frames.
+    pub fn new(capacity: usize) -> Self {
+        HistoryBuffer {
+            frames: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// The maximum number of frames the buffer can hold.
+    pub fn capacity(&self) -> usize { self.capacity }
+
+    /// The number of frames in the buffer.
+    pub fn len(&self) -> usize { self.frames.len() }
+
+    /// Is the buffer empty?
+    pub fn is_empty(&self) -> bool { self.frames.is_empty() }
+
+    /// Has the buffer filled up, so each new frame pushes out the oldest
+    /// one?
+    pub fn is_full(&self) -> bool { self.frames.len() == self.capacity }
+
+    /// Add a frame to the buffer, returning the oldest frame if it had to
+    /// be pushed out to make room.
+    pub fn push(&mut self, frame: F) -> Option<F> {
+        if self.capacity == 0 {
+            return Some(frame);
+        }
+
+        let oldest = if self.is_full() {
+            self.frames.pop_front()
+        } else {
+            None
+        };
+        self.frames.push_back(frame);
+
+        oldest
+    }
+
+    /// Iterate over the frames, from oldest to newest.
+    pub fn iter(&self) -> impl Iterator<Item = &F> + '_ { self.frames.iter() }
+
+    /// Throw away every frame in the buffer.
+    pub fn clear(&mut self) { self.frames.clear(); }
+
+    /// Pass every frame to the `sink`, oldest first, leaving the buffer
+    /// empty.
+    ///
+    /// Only [`Sink::record_frames()`] is called, so it's up to the caller to
+    /// start and end transmissions.
+    pub fn drain_into<K>(&mut self, sink: &mut K)
+    where
+        F: Copy,
+        K: Sink<F>,
+    {
+        let (front, back) = self.frames.as_slices();
+
+        if !front.is_empty() {
+            sink.record_frames(front);
+        }
+        if !back.is_empty() {
+            sink.record_frames(back);
+        }
+
+        self.frames.clear();
+    }
+}
+
+impl<F> Extend<F> for HistoryBuffer<F> {
+    fn extend<I: IntoIterator<Item = F>>(&mut self, frames: I) {
+        for frame in frames {
+            self.push(frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NoiseGate;
+
+    /// Records each transmission, starting with whatever was in the
+    /// history when the gate opened.
+    #[derive(Debug)]
+    struct PreRoll {
+        history: HistoryBuffer<[i16; 1]>,
+        clips: Vec<Vec<[i16; 1]>>,
+    }
+
+    impl Sink<[i16; 1]> for PreRoll {
+        fn record(&mut self, frame: [i16; 1]) {
+            self.clips.last_mut().unwrap().push(frame);
+        }
+
+        fn end_of_transmission(&mut self) {}
+
+        fn transmission_started(&mut self, _position: u64) {
+            let mut clip = Vec::new();
+            self.history.drain_into(&mut clip);
+            self.clips.push(clip);
+        }
+    }
+
+    #[test]
+    fn clips_start_with_the_pre_roll() {
+        let frames = [[1], [2], [3], [500], [0], [4], [5], [600], [0]];
+        let mut gate = NoiseGate::new(100, 0);
+        let mut sink = PreRoll {
+            history: HistoryBuffer::new(2),
+            clips: Vec::new(),
+        };
+
+        // feed the gate one frame at a time, remembering whatever it skips
+        for &frame in &frames {
+            gate.process_frames(&[frame], &mut sink);
+
+            if gate.is_closed() {
+                sink.history.push(frame);
+            }
+        }
+
+        assert_eq!(
+            sink.clips,
+            vec![vec![[2], [3], [500], [0]], vec![[4], [5], [600], [0]]]
+        );
+    }
+
+    #[test]
+    fn the_oldest_frame_is_pushed_out() {
+        let mut history = HistoryBuffer::new(2);
+
+        assert_eq!(history.push(1), None);
+        assert_eq!(history.push(2), None);
+        assert_eq!(history.push(3), Some(1));
+        assert_eq!(history.iter().copied().collect::<Vec<_>>(), vec![2, 3]);
+
+        let mut empty = HistoryBuffer::new(0);
+        assert_eq!(empty.push(1), Some(1));
+        assert!(empty.is_empty());
+    }
+}
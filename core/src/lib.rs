@@ -0,0 +1,1337 @@
+//! The detection logic and state machine behind the [`noise-gate`][crate]
+//! crate, without any I/O.
+//!
+//! This crate is `#![no_std]`, and only needs an allocator for the [`Sink`]
+//! implementations on `Vec` and `VecDeque` and the [`HistoryBuffer`] (the
+//! default `alloc` feature).
+//! Most people will want to use [`noise-gate`][crate] instead, which
+//! re-exports everything here alongside a collection of sinks and analysis
+//! tools.
+//!
+//! [crate]: https://docs.rs/noise-gate
+#![cfg_attr(not(test), no_std)]
+#![forbid(unsafe_code)]
+#![deny(
+    missing_docs,
+    missing_debug_implementations,
+    rust_2018_idioms,
+    future_incompatible,
+    bare_trait_objects,
+    elided_lifetimes_in_paths,
+    trivial_casts,
+    unreachable_pub
+)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+mod buffer;
+pub mod fixed;
+mod gated;
+#[cfg(feature = "alloc")]
+mod history;
+pub mod low_level;
+mod scan;
+mod segments;
+
+pub use buffer::FixedBuffer;
+pub use gated::{Gated, GatedItem};
+#[cfg(feature = "alloc")]
+pub use history::HistoryBuffer;
+pub use scan::first_above_threshold;
+pub use segments::Segments;
+
+use dasp::{sample::SignedSample, Frame, Sample};
+use __private::Run;
+use low_level::State;
+
+#[cfg(feature = "alloc")]
+use alloc::{collections::VecDeque, vec::Vec};
+
+/// A [*Noise Gate*][wiki] which can be used to split a stream of audio based
+/// on volume, skipping periods of silence.
+///
+/// # Real-Time Safety
+///
+/// Once it has been created, the gate never allocates, locks, or panics
+/// while processing frames, so it can be used directly from an audio
+/// callback. Anything which needs to buffer frames (e.g. `FadeEdges` from the
+/// `noise-gate` crate) must allocate all its storage up front.
+///
+/// [wiki]: https://en.wikipedia.org/wiki/Noise_gate
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseGate<S> {
+    /// The volume level at which the gate will open (begin recording).
+    ///
+    /// This is measured relative to [`Sample::EQUILIBRIUM`], so for unsigned
+    /// formats a `u8` threshold of `178` or `78` both mean "50 steps away
+    /// from silence".
+    pub open_threshold: S,
+    /// The amount of time (in samples) the gate takes to go from open to fully
+    /// closed.
+    pub release_time: usize,
+    /// What to do with `NaN` or infinite samples.
+    pub non_finite: NonFinite,
+    /// How a multi-channel frame's level is measured.
+    pub detection: Detection,
+    /// Which channels are listened to when deciding whether a frame is loud.
+    pub channel_mask: ChannelMask,
+    /// What [`NoiseGate::finish()`] does with a transmission which is still
+    /// going when the input runs out.
+    pub end_of_input: EndOfInput,
+    state: State,
+    position: u64,
+    dropped: u64,
+}
+
+impl<S> NoiseGate<S> {
+    /// Create a new [`NoiseGate`].
+    pub const fn new(open_threshold: S, release_time: usize) -> Self {
+        NoiseGate {
+            open_threshold,
+            release_time,
+            non_finite: NonFinite::Loud,
+            detection: Detection::AnyChannel,
+            channel_mask: ChannelMask::ALL,
+            end_of_input: EndOfInput::Flush,
+            state: State::Closed,
+            position: 0,
+            dropped: 0,
+        }
+    }
+
+    /// The index of the next frame to be processed, counting every frame
+    /// the gate has seen since it was created or [reset][Self::reset].
+    ///
+    /// This is the same clock used by [`Sink::transmission_started()`].
+    pub fn position(&self) -> u64 { self.position }
+
+    /// The total number of frames reported by
+    /// [`NoiseGate::frames_dropped()`].
+    pub fn dropped_frames(&self) -> u64 { self.dropped }
+
+    /// Close the gate and restart the [position][Self::position] (and the
+    /// number of [dropped frames][Self::dropped_frames]) from `0`, without
+    /// telling the [`Sink`] anything.
+    pub fn reset(&mut self) {
+        self.state = State::Closed;
+        self.position = 0;
+        self.dropped = 0;
+    }
+
+    /// Is the gate currently passing samples through to the [`Sink`]?
+    pub fn is_open(&self) -> bool { self.state.is_open() }
+
+    /// The gate's current [`State`].
+    pub fn state(&self) -> State { self.state }
+
+    /// Is the gate currently ignoring silence?
+    pub fn is_closed(&self) -> bool { !self.is_open() }
+
+    /// Close the gate straight away, telling the `sink` the transmission has
+    /// ended if the gate was open.
+    ///
+    /// The gate will open again as soon as it sees another loud frame.
+    pub fn force_close<F, K>(&mut self, sink: &mut K)
+    where
+        K: Sink<F>,
+    {
+        if self.is_open() {
+            self.state = State::Closed;
+            sink.end_of_transmission();
+        }
+    }
+
+    /// There's no more input, so deal with any transmission which is still
+    /// in progress according to [`NoiseGate::end_of_input`].
+    ///
+    /// With [`EndOfInput::Flush`] the transmission is ended like normal, so
+    /// sinks which hold frames back (e.g. for a fade-out) will pass them on.
+    /// With [`EndOfInput::Discard`] the `sink` is asked to throw the partial
+    /// transmission away (see [`Sink::discard_transmission()`]).
+    ///
+    /// Either way the gate is left closed, ready for more input.
+    pub fn finish<F, K>(&mut self, sink: &mut K)
+    where
+        K: Sink<F>,
+    {
+        match self.end_of_input {
+            EndOfInput::Flush => self.force_close(sink),
+            EndOfInput::Discard => {
+                if self.is_open() {
+                    self.state = State::Closed;
+                    sink.discard_transmission();
+                }
+            },
+        }
+    }
+
+    /// [`NoiseGate::finish()`] for an [`InterleavedSink`], when the number
+    /// of channels is only known at runtime.
+    pub fn finish_interleaved<K>(&mut self, sink: &mut K)
+    where
+        K: InterleavedSink<S>,
+    {
+        if self.is_closed() {
+            return;
+        }
+
+        self.state = State::Closed;
+        match self.end_of_input {
+            EndOfInput::Flush => sink.end_of_transmission(),
+            EndOfInput::Discard => sink.discard_transmission(),
+        }
+    }
+
+    /// Let the gate know `count` frames of input were lost (e.g. because a
+    /// capture buffer overran), so its [position][Self::position] stays in
+    /// step with the audio.
+    ///
+    /// The frames on either side of the gap aren't contiguous, so any
+    /// transmission in progress is ended. Whenever the gate next opens, the
+    /// position given to [`Sink::transmission_started()`] accounts for the
+    /// missing frames.
+    pub fn frames_dropped<F, K>(&mut self, count: u64, sink: &mut K)
+    where
+        K: Sink<F>,
+    {
+        if count > 0 {
+            self.position += count;
+            self.dropped += count;
+            self.force_close(sink);
+        }
+    }
+}
+
+impl<S: Copy> NoiseGate<S> {
+    /// Set [`NoiseGate::non_finite`], for use in `const` contexts.
+    pub const fn with_non_finite(self, non_finite: NonFinite) -> Self {
+        NoiseGate { non_finite, ..self }
+    }
+
+    /// Set [`NoiseGate::detection`], for use in `const` contexts.
+    pub const fn with_detection(self, detection: Detection) -> Self {
+        NoiseGate { detection, ..self }
+    }
+
+    /// Set [`NoiseGate::channel_mask`], for use in `const` contexts.
+    pub const fn with_channel_mask(self, channel_mask: ChannelMask) -> Self {
+        NoiseGate {
+            channel_mask,
+            ..self
+        }
+    }
+
+    /// Set [`NoiseGate::end_of_input`], for use in `const` contexts.
+    pub const fn with_end_of_input(self, end_of_input: EndOfInput) -> Self {
+        NoiseGate {
+            end_of_input,
+            ..self
+        }
+    }
+}
+
+impl<S: Sample> NoiseGate<S> {
+    /// Process a batch of frames, passing spans of noise through to a `sink`.
+    ///
+    /// The gate remembers its state between calls, so a long recording can be
+    /// streamed through in chunks of any size and the `sink` will see exactly
+    /// the same thing as if it were processed in one go.
+    pub fn process_frames<K, F>(&mut self, frames: &[F], sink: &mut K)
+    where
+        F: Frame<Sample = S>,
+        K: Sink<F>,
+    {
+        self.process_runs(frames, sink, |_, _| {});
+    }
+
+    /// The guts of [`NoiseGate::process_frames()`], with a callback that gets
+    /// told about each run and the state the gate was in when it started.
+    pub(crate) fn process_runs<K, F, R>(
+        &mut self,
+        frames: &[F],
+        sink: &mut K,
+        mut on_run: R,
+    ) where
+        F: Frame<Sample = S>,
+        K: Sink<F>,
+        R: FnMut(State, &Run),
+    {
+        let mut remaining = frames;
+
+        while !remaining.is_empty() {
+            let state = self.state;
+            let run = self.next_run(remaining);
+            on_run(state, &run);
+
+            if run.recorded > 0 {
+                sink.record_frames(&remaining[..run.recorded]);
+            }
+            if run.closed {
+                sink.end_of_transmission();
+            }
+            if run.opened {
+                sink.transmission_started(self.position);
+            }
+
+            remaining = &remaining[run.len..];
+        }
+    }
+
+    /// Process a batch of interleaved samples where the number of channels
+    /// is only known at runtime (e.g. a 16-channel ambisonic capture, or
+    /// whatever a sound card happens to give you).
+    ///
+    /// This behaves exactly like [`NoiseGate::process_frames()`], except the
+    /// `sink` is given runs of whole interleaved frames. Any samples left
+    /// over at the end which don't make up a whole frame are ignored.
+    ///
+    /// ```rust
+    /// use noise_gate_core::NoiseGate;
+    ///
+    /// let channels = 3;
+    /// let samples = [0_i16, 0, 0, 0, 500, 0, 0, 0, 0, 0, 0, 0];
+    /// let mut recorded = Vec::new();
+    ///
+    /// NoiseGate::new(100, 0).process_interleaved(&samples, channels, &mut recorded);
+    ///
+    /// assert_eq!(recorded, vec![0, 500, 0, 0, 0, 0]);
+    /// ```
+    pub fn process_interleaved<K>(
+        &mut self,
+        samples: &[S],
+        channels: usize,
+        sink: &mut K,
+    ) where
+        K: InterleavedSink<S>,
+    {
+        let mut remaining = scan::Interleaved::new(samples, channels);
+
+        while !remaining.samples().is_empty() {
+            let run = self.scan_run(remaining);
+
+            if run.recorded > 0 {
+                sink.record_interleaved(
+                    remaining.head_samples(run.recorded),
+                    remaining.channels(),
+                );
+            }
+            if run.closed {
+                sink.end_of_transmission();
+            }
+            if run.opened {
+                sink.transmission_started(self.position);
+            }
+
+            remaining = remaining.tail(run.len);
+        }
+    }
+
+    /// Process several buffers one after the other, as if they were one
+    /// contiguous stream of frames.
+    pub fn process_buffers<K, F>(&mut self, buffers: &[&[F]], sink: &mut K)
+    where
+        F: Frame<Sample = S>,
+        K: Sink<F>,
+    {
+        for buffer in buffers {
+            self.process_frames(buffer, sink);
+        }
+    }
+
+    /// Process a batch of frames, deciding when to open and close based on a
+    /// separate `sidechain` signal instead of the frames themselves.
+    ///
+    /// This lets the detector listen to a filtered or downmixed copy of the
+    /// audio (see `noise_gate::processors`) while the `sink` still gets the
+    /// original.
+    /// The `i`'th sidechain frame decides what happens to the `i`'th frame,
+    /// and if one buffer is longer than the other the extra frames are
+    /// ignored.
+    pub fn process_sidechain<K, F, D>(
+        &mut self,
+        frames: &[F],
+        sidechain: &[D],
+        sink: &mut K,
+    ) where
+        F: Frame,
+        D: Frame<Sample = S>,
+        K: Sink<F>,
+    {
+        let len = frames.len().min(sidechain.len());
+        let mut frames = &frames[..len];
+        let mut sidechain = &sidechain[..len];
+
+        while !sidechain.is_empty() {
+            let run = self.scan_run(sidechain);
+
+            if run.recorded > 0 {
+                sink.record_frames(&frames[..run.recorded]);
+            }
+            if run.closed {
+                sink.end_of_transmission();
+            }
+            if run.opened {
+                sink.transmission_started(self.position);
+            }
+
+            frames = &frames[run.len..];
+            sidechain = &sidechain[run.len..];
+        }
+    }
+
+    /// Find the spans of noise in a buffer without copying anything,
+    /// returning each one's position in the buffer alongside the frames
+    /// themselves.
+    ///
+    /// This is mainly intended for recordings which are already in memory.
+    /// The gate's state carries over between calls like with
+    /// [`NoiseGate::process_frames()`], so if the gate is still open when the
+    /// iterator finishes, the last segment will carry on into the next
+    /// buffer.
+    ///
+    /// ```rust
+    /// use noise_gate_core::NoiseGate;
+    ///
+    /// let frames = [[0_i16], [500], [600], [0], [0], [0], [700], [0], [0]];
+    /// let mut gate = NoiseGate::new(100, 1);
+    ///
+    /// let segments: Vec<_> = gate.segments(&frames).collect();
+    ///
+    /// assert_eq!(
+    ///     segments,
+    ///     vec![(1..5, &frames[1..5]), (6..9, &frames[6..9])]
+    /// );
+    /// assert!(gate.is_open());
+    /// ```
+    pub fn segments<'a, F>(&'a mut self, frames: &'a [F]) -> Segments<'a, F>
+    where
+        F: Frame<Sample = S>,
+    {
+        Segments::new(self, frames)
+    }
+
+    fn limits(&self) -> scan::Limits<S> {
+        scan::Limits::new(self.open_threshold)
+            .with_non_finite(self.non_finite)
+            .with_detection(self.detection)
+            .with_channel_mask(self.channel_mask)
+    }
+
+    /// Figure out what happens to the frames at the start of a buffer, up
+    /// until the next time the gate changes state.
+    pub(crate) fn next_run<F>(&mut self, frames: &[F]) -> Run
+    where
+        F: Frame<Sample = S>,
+    {
+        self.scan_run(frames)
+    }
+
+    /// Restore a state previously read with [`NoiseGate::state()`].
+    pub(crate) fn set_state(&mut self, state: State) { self.state = state; }
+
+    /// The guts of [`NoiseGate::next_run()`], for any kind of buffer.
+    ///
+    /// Rather than stepping the state machine for every frame, we look for
+    /// the next frame which could change the state and handle everything
+    /// before it as a single run.
+    fn scan_run<B>(&mut self, frames: B) -> Run
+    where
+        B: scan::Frames<S>,
+    {
+        let run = match self.state {
+            State::Open => self.open_run(frames),
+            State::Closing { remaining_samples } => {
+                self.closing_run(frames, remaining_samples)
+            },
+            State::Closed => self.closed_run(frames),
+        };
+        self.position += run.len as u64;
+
+        run
+    }
+
+    /// Pass loud frames through until one is quiet enough to start closing
+    /// the gate.
+    fn open_run<B>(&mut self, frames: B) -> Run
+    where
+        B: scan::Frames<S>,
+    {
+        match frames.first_quiet(self.limits()) {
+            Some(index) => {
+                // the quiet frame is still recorded while we start closing
+                self.state = State::Closing {
+                    remaining_samples: self.release_time,
+                };
+                Run::recorded(index + 1)
+            },
+            None => Run::recorded(frames.frame_count()),
+        }
+    }
+
+    /// Keep recording until either a loud frame re-opens the gate or we've
+    /// seen enough silence to close it.
+    fn closing_run<B>(&mut self, frames: B, remaining_samples: usize) -> Run
+    where
+        B: scan::Frames<S>,
+    {
+        // Only the first `remaining_samples + 1` frames matter, the last of
+        // those will close the gate if everything before it was quiet
+        let window_length = frames
+            .frame_count()
+            .min(remaining_samples.saturating_add(1));
+        let window = frames.head(window_length);
+
+        match window.first_loud(self.limits()) {
+            Some(index) => {
+                self.state = State::Open;
+                Run::recorded(index + 1)
+            },
+            None if window_length > remaining_samples => {
+                self.state = State::Closed;
+                Run {
+                    len: window_length,
+                    recorded: remaining_samples,
+                    closed: true,
+                    opened: false,
+                }
+            },
+            None => {
+                self.state = State::Closing {
+                    remaining_samples: remaining_samples - window_length,
+                };
+                Run::recorded(window_length)
+            },
+        }
+    }
+
+    /// Skip over silence until a frame is loud enough to open the gate.
+    fn closed_run<B>(&mut self, frames: B) -> Run
+    where
+        B: scan::Frames<S>,
+    {
+        match frames.first_loud(self.limits()) {
+            Some(index) => {
+                // the loud frame gets recorded as part of the open run
+                self.state = State::Open;
+                Run {
+                    opened: true,
+                    ..Run::skipped(index)
+                }
+            },
+            None => Run::skipped(frames.frame_count()),
+        }
+    }
+}
+
+/// Hooks into the gate's internals for the `noise-gate` crate.
+///
+/// Nothing in here is part of the public API, and it may change at any time.
+#[doc(hidden)]
+pub mod __private {
+    use crate::{low_level::State, NoiseGate, Sink};
+    use dasp::{sample::SignedSample, Frame, Sample};
+
+    /// What happened to a run of frames at the start of a buffer.
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    pub struct Run {
+        /// How many frames were consumed.
+        pub len: usize,
+        /// How many of those frames were passed through to the [`Sink`].
+        pub recorded: usize,
+        /// Did the gate close at the end of the run?
+        pub closed: bool,
+        /// Did the gate open at the end of the run, so the next frame starts a
+        /// transmission?
+        pub opened: bool,
+    }
+
+    impl Run {
+        pub(crate) const fn recorded(len: usize) -> Self {
+            Run {
+                len,
+                recorded: len,
+                closed: false,
+                opened: false,
+            }
+        }
+
+        pub(crate) const fn skipped(len: usize) -> Self {
+            Run {
+                len,
+                recorded: 0,
+                closed: false,
+                opened: false,
+            }
+        }
+    }
+
+    /// See `NoiseGate::process_runs()`.
+    pub fn process_runs<S, K, F, R>(
+        gate: &mut NoiseGate<S>,
+        frames: &[F],
+        sink: &mut K,
+        on_run: R,
+    ) where
+        S: Sample,
+        F: Frame<Sample = S>,
+        K: Sink<F>,
+        R: FnMut(State, &Run),
+    {
+        gate.process_runs(frames, sink, on_run);
+    }
+
+    /// See `NoiseGate::next_run()`.
+    pub fn next_run<S, F>(gate: &mut NoiseGate<S>, frames: &[F]) -> Run
+    where
+        S: Sample,
+        F: Frame<Sample = S>,
+    {
+        gate.next_run(frames)
+    }
+
+    /// See `NoiseGate::set_state()`.
+    pub fn set_state<S: Sample>(gate: &mut NoiseGate<S>, state: State) {
+        gate.set_state(state);
+    }
+
+    /// See `negated_abs()`.
+    pub fn negated_abs<S: SignedSample>(sample: S) -> S {
+        crate::negated_abs(sample)
+    }
+}
+
+/// What a [`NoiseGate`] should do with samples which aren't finite (`NaN` or
+/// infinity).
+///
+/// Floating point streams from buggy drivers or plugins will sometimes
+/// contain these, and they can't be meaningfully compared with a threshold.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum NonFinite {
+    /// Treat them as being louder than any threshold, so they're passed
+    /// through for someone to notice (the default).
+    #[default]
+    Loud,
+    /// Treat them as silence.
+    Silent,
+}
+
+/// What [`NoiseGate::finish()`] does when the input runs out part-way
+/// through a transmission.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum EndOfInput {
+    /// End the transmission, keeping whatever was recorded (the default).
+    #[default]
+    Flush,
+    /// Throw the partial transmission away (e.g. because the recording was
+    /// cut off mid-sentence).
+    Discard,
+}
+
+/// How a [`NoiseGate`] decides whether a multi-channel frame is loud.
+///
+/// Either way, the [`Sink`] always gets the original frames.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Detection {
+    /// The frame is loud if any channel reaches the threshold (the default).
+    #[default]
+    AnyChannel,
+    /// Average the channels down to mono and compare that with the threshold.
+    ///
+    /// This is more stable when one channel is noisier than the others, but
+    /// sounds which are out of phase between channels will cancel out.
+    Downmix,
+}
+
+/// The channels a [`NoiseGate`] listens to when deciding whether a frame is
+/// loud (e.g. to ignore a click track or a noisy camera mic).
+///
+/// Masked out channels are treated as silence by [`Detection::AnyChannel`]
+/// and left out of the average by [`Detection::Downmix`], but the [`Sink`]
+/// still gets every channel. Only the first 64 channels can be masked out,
+/// anything after that is always listened to.
+///
+/// ```rust
+/// use noise_gate_core::{ChannelMask, NoiseGate};
+///
+/// // the second channel is a click track
+/// let frames = [[0_i16, 500], [0, 0], [300, 500], [0, 0]];
+/// let mut gate = NoiseGate::new(100, 0)
+///     .with_channel_mask(ChannelMask::NONE.with(0));
+///
+/// let segments: Vec<_> = gate.segments(&frames).collect();
+///
+/// assert_eq!(segments, vec![(2..4, &frames[2..4])]);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ChannelMask(u64);
+
+impl ChannelMask {
+    /// Listen to every channel (the default).
+    pub const ALL: ChannelMask = ChannelMask(u64::MAX);
+    /// Only listen to channels from index 64 onwards, if there are any.
+    pub const NONE: ChannelMask = ChannelMask(0);
+
+    /// Create a mask where bit `i` is set if channel `i` should be listened
+    /// to.
+    pub const fn from_bits(bits: u64) -> Self { ChannelMask(bits) }
+
+    /// The mask as a bit set, where bit `i` is set if channel `i` is
+    /// listened to.
+    pub const fn bits(self) -> u64 { self.0 }
+
+    /// Also listen to a channel.
+    pub const fn with(self, channel: usize) -> Self {
+        if channel < 64 {
+            ChannelMask(self.0 | 1 << channel)
+        } else {
+            self
+        }
+    }
+
+    /// Stop listening to a channel.
+    pub const fn without(self, channel: usize) -> Self {
+        if channel < 64 {
+            ChannelMask(self.0 & !(1 << channel))
+        } else {
+            self
+        }
+    }
+
+    /// Is this channel listened to?
+    pub const fn contains(self, channel: usize) -> bool {
+        channel >= 64 || self.0 & 1 << channel != 0
+    }
+}
+
+impl Default for ChannelMask {
+    fn default() -> Self { ChannelMask::ALL }
+}
+
+/// Get the negative of a sample's absolute value, `-|sample|`.
+///
+/// Unlike `|sample|` this can't overflow, because every positive integer can
+/// be negated but `-i16::MIN` can't be represented.
+pub(crate) fn negated_abs<S: SignedSample>(sample: S) -> S {
+    if sample > S::EQUILIBRIUM {
+        -sample
+    } else {
+        sample
+    }
+}
+
+/// A consumer of [`Frame`]s.
+pub trait Sink<F> {
+    /// Add a frame to the current recording, starting a new recording if
+    /// necessary.
+    fn record(&mut self, frame: F);
+    /// Add a run of consecutive frames to the current recording.
+    ///
+    /// The gate passes frames through in runs wherever it can, so sinks which
+    /// are able to handle several frames at once may want to override this.
+    fn record_frames(&mut self, frames: &[F])
+    where
+        F: Copy,
+    {
+        for &frame in frames {
+            self.record(frame);
+        }
+    }
+    /// Reached the end of the samples, do necessary cleanup (e.g. flush to
+    /// disk).
+    fn end_of_transmission(&mut self);
+    /// The input ran out part-way through a transmission, and whatever has
+    /// been recorded since it started should be thrown away (see
+    /// [`EndOfInput::Discard`]).
+    ///
+    /// Sinks which can't take back frames they've already passed on just
+    /// end the transmission, which is the default. Adapters should drop
+    /// anything they're holding back and pass this on to whatever they
+    /// wrap.
+    fn discard_transmission(&mut self) { self.end_of_transmission(); }
+    /// The gate has just opened, and the next frame to be recorded is frame
+    /// number `position` (see [`NoiseGate::position()`]).
+    ///
+    /// Adapters should pass this on to whatever they wrap.
+    fn transmission_started(&mut self, _position: u64) {}
+    /// How many frames this sink holds back before passing them on, so hosts
+    /// can compensate for the delay.
+    ///
+    /// Adapters should include the latency of whatever they wrap.
+    fn latency_samples(&self) -> usize { 0 }
+    /// How many errors (e.g. failed writes) this sink has run into so far.
+    ///
+    /// Sinks have no way to return errors from [`Sink::record()`], so this
+    /// lets whatever is driving the gate notice them. Adapters should
+    /// include the errors of whatever they wrap.
+    fn errors(&self) -> usize { 0 }
+}
+
+impl<F, S: Sink<F> + ?Sized> Sink<F> for &mut S {
+    fn record(&mut self, frame: F) { (**self).record(frame); }
+
+    fn record_frames(&mut self, frames: &[F])
+    where
+        F: Copy,
+    {
+        (**self).record_frames(frames);
+    }
+
+    fn end_of_transmission(&mut self) { (**self).end_of_transmission(); }
+
+    fn discard_transmission(&mut self) { (**self).discard_transmission(); }
+
+    fn transmission_started(&mut self, position: u64) {
+        (**self).transmission_started(position);
+    }
+
+    fn latency_samples(&self) -> usize { (**self).latency_samples() }
+
+    fn errors(&self) -> usize { (**self).errors() }
+}
+
+/// Collect every frame which gets recorded, ignoring transmission
+/// boundaries.
+#[cfg(feature = "alloc")]
+impl<F> Sink<F> for Vec<F> {
+    fn record(&mut self, frame: F) { self.push(frame); }
+
+    fn record_frames(&mut self, frames: &[F])
+    where
+        F: Copy,
+    {
+        self.extend_from_slice(frames);
+    }
+
+    fn end_of_transmission(&mut self) {}
+}
+
+/// Collect every frame which gets recorded, ignoring transmission
+/// boundaries.
+#[cfg(feature = "alloc")]
+impl<F> Sink<F> for VecDeque<F> {
+    fn record(&mut self, frame: F) { self.push_back(frame); }
+
+    fn record_frames(&mut self, frames: &[F])
+    where
+        F: Copy,
+    {
+        self.extend(frames.iter().copied());
+    }
+
+    fn end_of_transmission(&mut self) {}
+}
+
+/// A consumer of interleaved samples, for use with
+/// [`NoiseGate::process_interleaved()`] when the number of channels isn't
+/// known at compile time.
+pub trait InterleavedSink<S> {
+    /// Add one or more whole frames of interleaved samples to the current
+    /// recording, starting a new recording if necessary.
+    fn record_interleaved(&mut self, samples: &[S], channels: usize);
+    /// Reached the end of the samples, do necessary cleanup (e.g. flush to
+    /// disk).
+    fn end_of_transmission(&mut self);
+    /// The input ran out part-way through a transmission, and whatever has
+    /// been recorded since it started should be thrown away (see
+    /// [`Sink::discard_transmission()`]).
+    fn discard_transmission(&mut self) { self.end_of_transmission(); }
+    /// The gate has just opened, and the next frame to be recorded is frame
+    /// number `position` (see [`NoiseGate::position()`]).
+    fn transmission_started(&mut self, _position: u64) {}
+}
+
+impl<S, K: InterleavedSink<S> + ?Sized> InterleavedSink<S> for &mut K {
+    fn record_interleaved(&mut self, samples: &[S], channels: usize) {
+        (**self).record_interleaved(samples, channels);
+    }
+
+    fn end_of_transmission(&mut self) { (**self).end_of_transmission(); }
+
+    fn discard_transmission(&mut self) { (**self).discard_transmission(); }
+
+    fn transmission_started(&mut self, position: u64) {
+        (**self).transmission_started(position);
+    }
+}
+
+/// Collect every sample which gets recorded, ignoring transmission
+/// boundaries.
+#[cfg(feature = "alloc")]
+impl<S: Copy> InterleavedSink<S> for Vec<S> {
+    fn record_interleaved(&mut self, samples: &[S], _channels: usize) {
+        self.extend_from_slice(samples);
+    }
+
+    fn end_of_transmission(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dasp::sample::I24;
+
+    const OPEN_THRESHOLD: i16 = 100;
+    const RELEASE_TIME: usize = 5;
+
+    macro_rules! test_state_transition {
+        ($name:ident: $from:expr, $sample:expr => $expected:expr) => {
+            #[test]
+            fn $name() {
+                let start: State = $from;
+                let expected: State = $expected;
+                let frame: [i16; 1] = [$sample];
+
+                let got = low_level::step(
+                    start,
+                    low_level::Level::of(frame, OPEN_THRESHOLD),
+                    &low_level::Params {
+                        release_time: RELEASE_TIME,
+                    },
+                );
+
+                assert_eq!(got, expected);
+            }
+        };
+    }
+
+    test_state_transition!(open_to_open: State::Open, 101 => State::Open);
+    test_state_transition!(open_to_closing: State::Open, 40 => State::Closing { remaining_samples: RELEASE_TIME });
+    test_state_transition!(closing_to_closed: State::Closing { remaining_samples: 0 }, 40 => State::Closed);
+    test_state_transition!(closing_to_closing: State::Closing { remaining_samples: 1 }, 40 => State::Closing { remaining_samples: 0 });
+    test_state_transition!(reopen_when_closing: State::Closing { remaining_samples: 1 }, 101 => State::Open);
+    test_state_transition!(closed_to_closed: State::Closed, 40 => State::Closed);
+    test_state_transition!(closed_to_open: State::Closed, 101 => State::Open);
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Clips {
+        finished: Vec<Vec<[i16; 1]>>,
+        current: Vec<[i16; 1]>,
+    }
+
+    impl Sink<[i16; 1]> for Clips {
+        fn record(&mut self, frame: [i16; 1]) { self.current.push(frame); }
+
+        fn end_of_transmission(&mut self) {
+            self.finished.push(std::mem::take(&mut self.current));
+        }
+
+        fn discard_transmission(&mut self) { self.current.clear(); }
+    }
+
+    fn signal() -> Vec<[i16; 1]> {
+        (0..1000_i16)
+            .map(|i| if (i / 100) % 3 == 0 { [0] } else { [i] })
+            .collect()
+    }
+
+    /// Process frames the slow way, stepping the state machine for every
+    /// single frame.
+    fn process_naively(frames: &[[i16; 1]], sink: &mut Clips) {
+        let mut state = State::Closed;
+
+        for &frame in frames {
+            let previously_open = state != State::Closed;
+            state = low_level::step(
+                state,
+                low_level::Level::of(frame, OPEN_THRESHOLD),
+                &low_level::Params {
+                    release_time: RELEASE_TIME,
+                },
+            );
+
+            if state != State::Closed {
+                sink.record(frame);
+            } else if previously_open {
+                sink.end_of_transmission();
+            }
+        }
+    }
+
+    #[test]
+    fn one_frame_blocks_match_the_gate() {
+        let frames = signal();
+
+        let mut expected = Clips::default();
+        NoiseGate::new(OPEN_THRESHOLD, RELEASE_TIME)
+            .process_frames(&frames, &mut expected);
+
+        let mut got = Clips::default();
+        let mut gate = low_level::BlockGate::new(RELEASE_TIME);
+        for frame in &frames {
+            let level = low_level::Level::of(*frame, OPEN_THRESHOLD);
+            gate.process_block(core::slice::from_ref(frame), level, &mut got);
+        }
+
+        assert_eq!(got, expected);
+        assert_eq!(gate.position(), frames.len() as u64);
+    }
+
+    #[test]
+    fn skipping_silence_gives_the_same_result() {
+        let mut frames = signal();
+        // add a couple isolated blips in the middle of the silence
+        frames[20] = [OPEN_THRESHOLD];
+        frames[320] = [-OPEN_THRESHOLD];
+        frames[999] = [500];
+
+        let mut expected = Clips::default();
+        process_naively(&frames, &mut expected);
+
+        let mut got = Clips::default();
+        NoiseGate::new(OPEN_THRESHOLD, RELEASE_TIME)
+            .process_frames(&frames, &mut got);
+
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn reopening_while_closing_gives_the_same_result() {
+        // quiet gaps either side of the release time, so the gate sometimes
+        // re-opens and sometimes closes
+        let mut frames = Vec::new();
+        for gap in 0..2 * RELEASE_TIME + 2 {
+            frames.push([OPEN_THRESHOLD + 1]);
+            frames.push([-OPEN_THRESHOLD * 2]);
+            frames.resize(frames.len() + gap, [7]);
+        }
+
+        let mut expected = Clips::default();
+        process_naively(&frames, &mut expected);
+
+        for &chunk_size in &[1, 2, 3, RELEASE_TIME, frames.len()] {
+            let mut gate = NoiseGate::new(OPEN_THRESHOLD, RELEASE_TIME);
+            let mut got = Clips::default();
+
+            let buffers: Vec<_> = frames.chunks(chunk_size).collect();
+            gate.process_buffers(&buffers, &mut got);
+
+            assert_eq!(got, expected, "chunk size: {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn the_sidechain_decides_what_gets_recorded() {
+        let sidechain = signal();
+        // the audio itself is a ramp, so we can tell which frames came out
+        let frames: Vec<[i16; 1]> = (0..1000).map(|i| [i]).collect();
+
+        let mut expected = Clips::default();
+        process_naively(&sidechain, &mut expected);
+        let to_indices = |clips: &[Vec<[i16; 1]>]| -> Vec<Vec<usize>> {
+            clips
+                .iter()
+                .map(|clip| clip.iter().map(|f| f[0] as usize).collect())
+                .collect()
+        };
+        let expected_lengths: Vec<_> =
+            expected.finished.iter().map(Vec::len).collect();
+
+        let mut gate = NoiseGate::new(OPEN_THRESHOLD, RELEASE_TIME);
+        let mut got = Clips::default();
+        gate.process_sidechain(&frames, &sidechain, &mut got);
+
+        let got_lengths: Vec<_> = got.finished.iter().map(Vec::len).collect();
+        assert_eq!(got_lengths, expected_lengths);
+        for clip in to_indices(&got.finished) {
+            assert!(clip.windows(2).all(|w| w[1] == w[0] + 1));
+            assert!(sidechain[clip[0]][0].abs() >= OPEN_THRESHOLD);
+        }
+    }
+
+    #[test]
+    fn downmixed_detection_passes_every_channel_through() {
+        // one noisy channel and one quiet one
+        let frames = [[150_i16, 0], [150, 100], [-300, -250], [0, 0], [0, 0]];
+        let mut gate = NoiseGate::new(OPEN_THRESHOLD, 0);
+        gate.detection = Detection::Downmix;
+
+        let segments: Vec<_> = gate.segments(&frames).collect();
+
+        assert_eq!(segments, vec![(1..4, &frames[1..4])]);
+    }
+
+    #[test]
+    fn masked_channels_are_ignored_but_still_recorded() {
+        // the last channel is a click track
+        let frames: Vec<[i16; 3]> = (0..20)
+            .map(|i| [if i == 10 { 500 } else { 0 }, 0, 1000])
+            .collect();
+        let samples: Vec<i16> = frames.iter().flatten().copied().collect();
+
+        for &detection in &[Detection::AnyChannel, Detection::Downmix] {
+            let mut gate = NoiseGate::new(OPEN_THRESHOLD, 0)
+                .with_detection(detection)
+                .with_channel_mask(ChannelMask::ALL.without(2));
+
+            let segments: Vec<_> = gate
+                .clone()
+                .segments(&frames)
+                .map(|(range, _)| range)
+                .collect();
+            assert_eq!(segments, vec![10..12]);
+
+            let mut got = Vec::new();
+            gate.process_interleaved(&samples, 3, &mut got);
+            assert_eq!(got, &samples[30..36]);
+        }
+    }
+
+    #[test]
+    fn unsigned_samples_open_the_gate_in_both_directions() {
+        let frames = [[128_u8], [20], [128], [128], [240], [128], [128]];
+        let mut gate = NoiseGate::new(178_u8, 0);
+
+        let segments: Vec<_> =
+            gate.segments(&frames).map(|(range, _)| range).collect();
+
+        assert_eq!(segments, vec![1..3, 4..6]);
+    }
+
+    #[test]
+    fn i24_samples_are_gated_like_any_other_integer() {
+        let loud = I24::new(1_000_000).unwrap();
+        let quiet = I24::new(-1_000).unwrap();
+        let frames = [[quiet], [loud], [-loud], [quiet], [quiet], [quiet]];
+        let mut gate = NoiseGate::new(I24::new(100_000).unwrap(), 1);
+
+        let segments: Vec<_> =
+            gate.segments(&frames).map(|(range, _)| range).collect();
+
+        assert_eq!(segments, vec![1..5]);
+    }
+
+    #[test]
+    fn interleaved_matches_fixed_size_frames() {
+        let frames: Vec<[i16; 16]> = (0..500)
+            .map(|i| {
+                let mut frame = [0; 16];
+                if i % 50 < 10 {
+                    frame[i % 16] = 2000;
+                }
+                frame
+            })
+            .collect();
+        let samples: Vec<i16> = frames.iter().flatten().copied().collect();
+
+        for &detection in &[Detection::AnyChannel, Detection::Downmix] {
+            let mut gate = NoiseGate::new(OPEN_THRESHOLD, RELEASE_TIME);
+            gate.detection = detection;
+            let mut expected = Vec::new();
+            gate.clone().process_frames(&frames, &mut expected);
+
+            let mut got = Vec::new();
+            for chunk in samples.chunks(16 * 7) {
+                gate.process_interleaved(chunk, 16, &mut got);
+            }
+
+            let expected: Vec<i16> =
+                expected.iter().flatten().copied().collect();
+            assert_eq!(got, expected);
+        }
+    }
+
+    #[test]
+    fn partial_interleaved_frames_are_ignored() {
+        let mut gate = NoiseGate::new(OPEN_THRESHOLD, RELEASE_TIME);
+        let mut got = Vec::new();
+
+        gate.process_interleaved(&[500, 500, 500, 500, 500], 2, &mut got);
+
+        assert_eq!(got, vec![500; 4]);
+    }
+
+    #[test]
+    fn containers_and_references_are_sinks() {
+        let frames = [[0_i16], [500], [600], [0], [0], [700]];
+
+        let mut vec = Vec::new();
+        NoiseGate::new(100, 0).process_frames(&frames, &mut vec);
+        assert_eq!(vec, vec![[500], [600], [0], [700]]);
+
+        fn record_twice<K: Sink<[i16; 1]>>(mut sink: K) {
+            sink.record([1]);
+            sink.record_frames(&[[2]]);
+        }
+        let mut deque = VecDeque::new();
+        record_twice(&mut deque);
+        record_twice(&mut &mut deque);
+        assert_eq!(deque, vec![[1], [2], [1], [2]]);
+    }
+
+    #[test]
+    fn force_closing_ends_the_transmission() {
+        let mut clips = Clips::default();
+        let mut gate = NoiseGate::new(100, 50);
+
+        gate.process_frames(&[[500], [0]], &mut clips);
+        gate.force_close(&mut clips);
+        gate.force_close(&mut clips);
+
+        assert!(gate.is_closed());
+        assert_eq!(clips.finished, vec![vec![[500], [0]]]);
+    }
+
+    #[test]
+    fn finishing_flushes_or_discards_the_last_transmission() {
+        let frames = [[500], [0], [0], [600], [0]];
+
+        let mut clips = Clips::default();
+        let mut gate = NoiseGate::new(100, 0);
+        gate.process_frames(&frames, &mut clips);
+        gate.finish(&mut clips);
+        assert!(gate.is_closed());
+        assert_eq!(clips.finished, vec![vec![[500], [0]], vec![[600], [0]]]);
+
+        let mut clips = Clips::default();
+        let mut gate =
+            NoiseGate::new(100, 5).with_end_of_input(EndOfInput::Discard);
+        gate.process_frames(&frames, &mut clips);
+        gate.finish(&mut clips);
+        gate.finish(&mut clips);
+        assert!(gate.is_closed());
+        assert!(clips.finished.is_empty());
+        assert!(clips.current.is_empty());
+    }
+
+    /// [`Clips`], but for interleaved mono samples.
+    #[derive(Debug, Default)]
+    struct InterleavedClips(Clips);
+
+    impl InterleavedSink<i16> for InterleavedClips {
+        fn record_interleaved(&mut self, samples: &[i16], _channels: usize) {
+            self.0.current.extend(samples.iter().map(|&s| [s]));
+        }
+
+        fn end_of_transmission(&mut self) {
+            Sink::<[i16; 1]>::end_of_transmission(&mut self.0);
+        }
+
+        fn discard_transmission(&mut self) { self.0.current.clear(); }
+    }
+
+    #[test]
+    fn interleaved_input_can_be_finished() {
+        let samples = [500, 0, 0, 600, 0];
+
+        let mut clips = InterleavedClips::default();
+        let mut gate = NoiseGate::new(100, 5);
+        gate.process_interleaved(&samples, 1, &mut clips);
+        gate.finish_interleaved(&mut clips);
+        assert!(gate.is_closed());
+        assert_eq!(clips.0.finished, vec![vec![[500], [0], [0], [600], [0]]]);
+
+        let mut clips = InterleavedClips::default();
+        let mut gate =
+            NoiseGate::new(100, 5).with_end_of_input(EndOfInput::Discard);
+        gate.process_interleaved(&samples, 1, &mut clips);
+        gate.finish_interleaved(&mut clips);
+        assert!(gate.is_closed());
+        assert!(clips.0.finished.is_empty());
+        assert!(clips.0.current.is_empty());
+    }
+
+    #[derive(Debug, Default)]
+    struct Starts(Vec<u64>);
+
+    impl Sink<[i16; 1]> for Starts {
+        fn record(&mut self, _: [i16; 1]) {}
+
+        fn end_of_transmission(&mut self) {}
+
+        fn transmission_started(&mut self, position: u64) {
+            self.0.push(position);
+        }
+    }
+
+    impl InterleavedSink<i16> for Starts {
+        fn record_interleaved(&mut self, _: &[i16], _: usize) {}
+
+        fn end_of_transmission(&mut self) {}
+
+        fn transmission_started(&mut self, position: u64) {
+            self.0.push(position);
+        }
+    }
+
+    #[test]
+    fn sinks_are_told_where_each_transmission_starts() {
+        let frames = signal();
+        let samples: Vec<i16> = frames.iter().map(|&[s]| s).collect();
+        let expected: Vec<u64> = NoiseGate::new(OPEN_THRESHOLD, RELEASE_TIME)
+            .segments(&frames)
+            .map(|(range, _)| range.start as u64)
+            .collect();
+        assert!(expected.len() > 1);
+        let mut gate = NoiseGate::new(OPEN_THRESHOLD, RELEASE_TIME);
+
+        let mut starts = Starts::default();
+        for chunk in frames.chunks(37) {
+            gate.process_frames(chunk, &mut starts);
+        }
+        assert_eq!(starts.0, expected);
+        assert_eq!(gate.position(), 1000);
+
+        // every way of processing frames shares the same clock
+        gate.reset();
+        let mut starts = Starts::default();
+        gate.process_interleaved(&samples[..500], 1, &mut starts);
+        gate.process_sidechain(&frames[500..], &frames[500..], &mut starts);
+        assert_eq!(starts.0, expected);
+        assert_eq!(gate.position(), 1000);
+    }
+
+    #[test]
+    fn dropped_frames_end_the_transmission_and_move_the_clock() {
+        let mut clips = Clips::default();
+        let mut starts = Starts::default();
+        let mut gate = NoiseGate::new(100, 50);
+
+        gate.process_frames(&[[0], [500], [0]], &mut clips);
+        gate.frames_dropped(1000, &mut clips);
+        gate.process_frames(&[[0], [600]], &mut starts);
+        gate.process_frames(&[[0], [600]], &mut clips);
+
+        assert_eq!(clips.finished, vec![vec![[500], [0]]]);
+        assert_eq!(starts.0, vec![1004]);
+        assert_eq!(gate.position(), 1007);
+        assert_eq!(gate.dropped_frames(), 1000);
+        gate.reset();
+        assert_eq!((gate.position(), gate.dropped_frames()), (0, 0));
+    }
+
+    #[test]
+    fn nan_can_be_treated_as_silence() {
+        let frames = [[0.0], [f64::NAN], [0.0], [0.9], [0.0], [f64::INFINITY]];
+
+        let mut gate = NoiseGate::new(0.5, 0);
+        let loud: Vec<_> =
+            gate.segments(&frames).map(|(range, _)| range).collect();
+        assert_eq!(loud, vec![1..6]);
+
+        let mut gate = NoiseGate::new(0.5, 0);
+        gate.non_finite = NonFinite::Silent;
+        let silent: Vec<_> =
+            gate.segments(&frames).map(|(range, _)| range).collect();
+        assert_eq!(silent, vec![3..5]);
+    }
+
+    #[test]
+    fn processing_in_chunks_is_the_same_as_all_at_once() {
+        let frames = signal();
+        let mut expected = Clips::default();
+        NoiseGate::new(OPEN_THRESHOLD, RELEASE_TIME)
+            .process_frames(&frames, &mut expected);
+
+        for &chunk_size in &[1, 7, 100, 333] {
+            let mut gate = NoiseGate::new(OPEN_THRESHOLD, RELEASE_TIME);
+            let mut got = Clips::default();
+
+            for chunk in frames.chunks(chunk_size) {
+                gate.process_frames(chunk, &mut got);
+            }
+
+            assert_eq!(got, expected, "chunk size: {}", chunk_size);
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! The state machine behind [`NoiseGate`], for people who want to drive it
+//! themselves.
+//!
+//! [`NoiseGate::process_frames()`] is the right choice almost all of the
+//! time, but sometimes you need a different detector (e.g. one based on a
+//! filtered sidechain or a neural network) or your own buffering scheme. The
+//! [`step()`] function lets you reuse the gate's transition logic with any
+//! notion of "loud" you like.
+//!
+//! ```rust
+//! use noise_gate_core::low_level::{self, Level, Params, State};
+//!
+//! let params = Params { release_time: 2 };
+//! let detections = [true, false, false, false, true];
+//! let mut state = State::Closed;
+//! let mut states = Vec::new();
+//!
+//! for &loud in &detections {
+//!     let level = if loud { Level::Loud } else { Level::Quiet };
+//!     state = low_level::step(state, level, &params);
+//!     states.push(state);
+//! }
+//!
+//! assert_eq!(
+//!     states,
+//!     [
+//!         State::Open,
+//!         State::Closing { remaining_samples: 2 },
+//!         State::Closing { remaining_samples: 1 },
+//!         State::Closing { remaining_samples: 0 },
+//!         State::Open,
+//!     ]
+//! );
+//! ```
+//!
+//! Block-based detectors (RMS windows, VAD frames, etc.) only make one
+//! decision per block, so counting the release time in samples would mean
+//! faking a per-sample countdown. A [`BlockGate`] steps the state machine
+//! once per detector frame instead, with the release time counted in
+//! blocks.
+//!
+//! [`NoiseGate`]: crate::NoiseGate
+//! [`NoiseGate::process_frames()`]: crate::NoiseGate::process_frames
+
+use crate::Sink;
+use dasp::{Frame, Sample};
+
+/// The state a gate can be in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum State {
+    /// Frames are being passed through.
+    Open,
+    /// The signal has gone quiet and the gate is counting down to closing.
+    /// Frames are still passed through while closing.
+    Closing {
+        /// The number of quiet frames left before the gate closes.
+        remaining_samples: usize,
+    },
+    /// Frames are being dropped.
+    Closed,
+}
+
+impl State {
+    /// Are frames in this state passed through to the
+    /// [`Sink`][crate::Sink]?
+    pub fn is_open(self) -> bool { self != State::Closed }
+}
+
+/// What a detector thinks of the current frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Level {
+    /// The frame is louder than the threshold and should open the gate.
+    Loud,
+    /// The frame is quiet.
+    Quiet,
+}
+
+impl Level {
+    /// The detector [`NoiseGate`][crate::NoiseGate] uses, where a frame is
+    /// loud if any channel reaches the threshold.
+    ///
+    /// Like the gate, this treats non-finite samples as loud.
+    pub fn of<F: Frame>(frame: F, threshold: F::Sample) -> Self {
+        let threshold = crate::negated_abs(threshold.to_signed_sample());
+        let quiet = frame
+            .channels()
+            .map(|sample| crate::negated_abs(sample.to_signed_sample()))
+            .all(|sample| sample > threshold);
+
+        if quiet {
+            Level::Quiet
+        } else {
+            Level::Loud
+        }
+    }
+}
+
+/// The parameters used by the state machine.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Params {
+    /// How many quiet frames to wait before closing the gate (see
+    /// [`NoiseGate::release_time`][crate::NoiseGate::release_time]).
+    ///
+    /// A "frame" is whatever [`step()`] gets called for, so for a
+    /// [`BlockGate`] this is measured in detector blocks.
+    pub release_time: usize,
+}
+
+/// Work out the gate's next state after seeing a frame with a particular
+/// [`Level`].
+///
+/// This is the exact transition logic [`NoiseGate`][crate::NoiseGate] uses:
+///
+/// - A loud frame always opens the gate
+/// - The first quiet frame after being open starts closing, with
+///   `params.release_time` quiet frames to go
+/// - A quiet frame while closing counts down, and closes the gate when there
+///   are no frames left
+///
+/// The frame should be passed through if the new state
+/// [`is_open()`][State::is_open], and the end of a transmission is when the
+/// state goes from open to [`State::Closed`].
+pub fn step(state: State, level: Level, params: &Params) -> State {
+    match (state, level) {
+        (_, Level::Loud) => State::Open,
+        (State::Open, Level::Quiet) => State::Closing {
+            remaining_samples: params.release_time,
+        },
+        (
+            State::Closing {
+                remaining_samples: 0,
+            },
+            Level::Quiet,
+        ) => State::Closed,
+        (State::Closing { remaining_samples }, Level::Quiet) => {
+            State::Closing {
+                remaining_samples: remaining_samples - 1,
+            }
+        },
+        (State::Closed, Level::Quiet) => State::Closed,
+    }
+}
+
+/// A gate for block-based detectors, which steps the state machine once per
+/// detector frame and passes whole blocks through to a [`Sink`].
+///
+/// ```rust
+/// use noise_gate_core::low_level::{BlockGate, Level};
+///
+/// // a VAD which makes one decision per 4-frame block
+/// let blocks = [[1_i16; 4], [2; 4], [3; 4], [4; 4], [5; 4]];
+/// let decisions = [Level::Loud, Level::Quiet, Level::Quiet, Level::Quiet, Level::Loud];
+///
+/// // wait one quiet block before closing
+/// let mut gate = BlockGate::new(1);
+/// let mut recorded = Vec::new();
+///
+/// for (block, &level) in blocks.iter().zip(&decisions) {
+///     gate.process_block(block, level, &mut recorded);
+/// }
+///
+/// assert_eq!(recorded, [[1; 4], [2; 4], [3; 4], [5; 4]].concat());
+/// assert_eq!(gate.position(), 20);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BlockGate {
+    /// The parameters used by the state machine, where
+    /// [`Params::release_time`] is counted in blocks.
+    pub params: Params,
+    state: State,
+    position: u64,
+}
+
+impl BlockGate {
+    /// Create a [`BlockGate`] which waits for `release_blocks` quiet blocks
+    /// before closing.
+    pub const fn new(release_blocks: usize) -> Self {
+        BlockGate {
+            params: Params {
+                release_time: release_blocks,
+            },
+            state: State::Closed,
+            position: 0,
+        }
+    }
+
+    /// Create a [`BlockGate`] from a release time in samples, rounding up to
+    /// a whole number of `block_size` frame blocks.
+    pub fn from_samples(release_time: usize, block_size: usize) -> Self {
+        BlockGate::new(release_time.div_ceil(block_size.max(1)))
+    }
+
+    /// The gate's current [`State`].
+    pub fn state(&self) -> State { self.state }
+
+    /// Is the gate currently passing blocks through to the [`Sink`]?
+    pub fn is_open(&self) -> bool { self.state.is_open() }
+
+    /// The index of the next frame to be processed, counting every frame in
+    /// every block the gate has seen.
+    ///
+    /// This is the same clock used by [`Sink::transmission_started()`].
+    pub fn position(&self) -> u64 { self.position }
+
+    /// Use the detector's decision for a block of `frames` to step the
+    /// state machine, recording the whole block if the gate is open.
+    ///
+    /// Blocks don't need to be the same length, so a partial block at the
+    /// end of a recording is fine.
+    pub fn process_block<F, K>(
+        &mut self,
+        frames: &[F],
+        level: Level,
+        sink: &mut K,
+    ) where
+        F: Copy,
+        K: Sink<F>,
+    {
+        let previous = self.state;
+        self.state = step(previous, level, &self.params);
+
+        if self.state.is_open() {
+            if !previous.is_open() {
+                sink.transmission_started(self.position);
+            }
+            sink.record_frames(frames);
+        } else if previous.is_open() {
+            sink.end_of_transmission();
+        }
+
+        self.position += frames.len() as u64;
+    }
+
+    /// Close the gate straight away, telling the `sink` the transmission
+    /// has ended if the gate was open.
+    pub fn force_close<F, K>(&mut self, sink: &mut K)
+    where
+        K: Sink<F>,
+    {
+        if self.is_open() {
+            self.state = State::Closed;
+            sink.end_of_transmission();
+        }
+    }
+}
@@ -0,0 +1,525 @@
+//! Quickly scanning through runs of frames.
+
+use crate::{ChannelMask, Detection, NonFinite};
+use dasp::{Frame, Sample};
+
+/// How many frames are checked at a time in the branch-free inner loop.
+///
+/// This needs to be wide enough that the autovectorizer can turn the loop
+/// into SIMD comparisons, but narrow enough that we don't waste too much time
+/// re-scanning the block once we know it contains a match.
+const BLOCK_SIZE: usize = 16;
+
+/// Find the first frame which would open the gate (i.e. where any channel is
+/// at or above `threshold`).
+///
+/// This is equivalent to checking each frame in turn, but frames are
+/// compared in blocks without any early returns so the compiler can vectorize
+/// the comparisons. It's much faster when scanning long periods of silence.
+///
+/// ```rust
+/// let frames = [[0_i16], [12], [-30], [250], [7]];
+///
+/// let index = noise_gate_core::first_above_threshold(&frames, 100);
+///
+/// assert_eq!(index, Some(3));
+/// ```
+pub fn first_above_threshold<F>(
+    frames: &[F],
+    threshold: F::Sample,
+) -> Option<usize>
+where
+    F: Frame,
+{
+    first_loud(frames, Limits::new(threshold))
+}
+
+/// Find the first frame which is loud enough to open the gate.
+pub(crate) fn first_loud<F>(
+    frames: &[F],
+    limits: Limits<F::Sample>,
+) -> Option<usize>
+where
+    F: Frame,
+{
+    first_matching(frames, |frame| limits.is_loud(frame))
+}
+
+/// Find the first frame which is quiet enough to start closing the gate.
+pub(crate) fn first_quiet<F>(
+    frames: &[F],
+    limits: Limits<F::Sample>,
+) -> Option<usize>
+where
+    F: Frame,
+{
+    first_matching(frames, |frame| !limits.is_loud(frame))
+}
+
+fn first_matching<F, P>(frames: &[F], predicate: P) -> Option<usize>
+where
+    F: Frame,
+    P: Fn(F) -> bool,
+{
+    let mut blocks = frames.chunks_exact(BLOCK_SIZE);
+    let mut offset = 0;
+
+    for block in blocks.by_ref() {
+        // Note: deliberately uses a non-short-circuiting fold so there are
+        // no branches inside the block
+        let found = block
+            .iter()
+            .fold(false, |found, &frame| found | predicate(frame));
+
+        if found {
+            return block
+                .iter()
+                .position(|&frame| predicate(frame))
+                .map(|i| offset + i);
+        }
+
+        offset += BLOCK_SIZE;
+    }
+
+    blocks
+        .remainder()
+        .iter()
+        .position(|&frame| predicate(frame))
+        .map(|i| offset + i)
+}
+
+/// A buffer of frames the gate can scan through.
+///
+/// This lets the state machine work with both slices of [`Frame`]s and
+/// interleaved buffers where the number of channels is only known at
+/// runtime.
+pub(crate) trait Frames<S: Sample>: Copy {
+    /// How many frames are in the buffer.
+    fn frame_count(self) -> usize;
+
+    /// The first `frames` frames.
+    fn head(self, frames: usize) -> Self;
+
+    /// Find the first frame which is loud enough to open the gate.
+    fn first_loud(self, limits: Limits<S>) -> Option<usize>;
+
+    /// Find the first frame which is quiet enough to start closing the gate.
+    fn first_quiet(self, limits: Limits<S>) -> Option<usize>;
+}
+
+impl<F: Frame> Frames<F::Sample> for &[F] {
+    fn frame_count(self) -> usize { self.len() }
+
+    fn head(self, frames: usize) -> Self { &self[..frames] }
+
+    fn first_loud(self, limits: Limits<F::Sample>) -> Option<usize> {
+        first_loud(self, limits)
+    }
+
+    fn first_quiet(self, limits: Limits<F::Sample>) -> Option<usize> {
+        first_quiet(self, limits)
+    }
+}
+
+/// Interleaved samples with a runtime channel count, where any trailing
+/// partial frame is ignored.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct Interleaved<'a, S> {
+    samples: &'a [S],
+    channels: usize,
+}
+
+impl<'a, S> Interleaved<'a, S> {
+    pub(crate) fn new(samples: &'a [S], channels: usize) -> Self {
+        let channels = channels.max(1);
+        let whole_frames = samples.len() / channels * channels;
+
+        Interleaved {
+            samples: &samples[..whole_frames],
+            channels,
+        }
+    }
+
+    pub(crate) fn samples(self) -> &'a [S] { self.samples }
+
+    pub(crate) fn channels(self) -> usize { self.channels }
+
+    /// Everything after the first `frames` frames.
+    pub(crate) fn tail(self, frames: usize) -> Self {
+        Interleaved {
+            samples: &self.samples[frames * self.channels..],
+            ..self
+        }
+    }
+
+    /// The first `frames` frames, as interleaved samples.
+    pub(crate) fn head_samples(self, frames: usize) -> &'a [S] {
+        &self.samples[..frames * self.channels]
+    }
+}
+
+impl<S: Sample> Frames<S> for Interleaved<'_, S> {
+    fn frame_count(self) -> usize { self.samples.len() / self.channels }
+
+    fn head(self, frames: usize) -> Self {
+        Interleaved {
+            samples: self.head_samples(frames),
+            ..self
+        }
+    }
+
+    fn first_loud(self, limits: Limits<S>) -> Option<usize> {
+        self.samples
+            .chunks_exact(self.channels)
+            .position(|frame| limits.is_loud_channels(frame))
+    }
+
+    fn first_quiet(self, limits: Limits<S>) -> Option<usize> {
+        self.samples
+            .chunks_exact(self.channels)
+            .position(|frame| !limits.is_loud_channels(frame))
+    }
+}
+
+/// The (signed) levels a sample needs to stay within for it to be considered
+/// silent.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct Limits<S: Sample> {
+    /// The threshold's magnitude, negated.
+    ///
+    /// Working with negative magnitudes means we never need to take the
+    /// absolute value of the most negative integer, which would overflow.
+    negated_threshold: S::Signed,
+    silence_non_finite: bool,
+    downmix: bool,
+    mask: ChannelMask,
+}
+
+impl<S: Sample> Limits<S> {
+    pub(crate) fn new(threshold: S) -> Self {
+        // Note: unsigned samples are centred around some mid-scale
+        // equilibrium, so we need to compare in the signed domain (where
+        // equilibrium is always zero) for the limits to be symmetric
+        let negated_threshold =
+            crate::negated_abs(threshold.to_signed_sample());
+
+        Limits {
+            negated_threshold,
+            silence_non_finite: false,
+            downmix: false,
+            mask: ChannelMask::ALL,
+        }
+    }
+
+    pub(crate) fn with_non_finite(self, non_finite: NonFinite) -> Self {
+        Limits {
+            silence_non_finite: non_finite == NonFinite::Silent,
+            ..self
+        }
+    }
+
+    pub(crate) fn with_detection(self, detection: Detection) -> Self {
+        Limits {
+            downmix: detection == Detection::Downmix,
+            ..self
+        }
+    }
+
+    pub(crate) fn with_channel_mask(self, mask: ChannelMask) -> Self {
+        Limits { mask, ..self }
+    }
+
+    /// Is any channel in this frame outside the limits?
+    pub(crate) fn is_loud<F>(&self, frame: F) -> bool
+    where
+        F: Frame<Sample = S>,
+    {
+        if self.downmix && F::CHANNELS > 1 {
+            return downmix(frame.channels(), F::CHANNELS, self.mask)
+                .is_some_and(|mix| self.is_loud_signed(mix));
+        }
+
+        let mask = self.mask;
+
+        // The channels() iterator doesn't always get optimised away, so mono
+        // and stereo frames (by far the most common) get a fast path. The
+        // branch is on a constant, so only one arm survives monomorphization.
+        match F::CHANNELS {
+            1 => mask.contains(0) & self.is_loud_sample(channel(&frame, 0)),
+            2 => {
+                (mask.contains(0) & self.is_loud_sample(channel(&frame, 0)))
+                    | (mask.contains(1)
+                        & self.is_loud_sample(channel(&frame, 1)))
+            },
+            _ => frame.channels().enumerate().fold(false, |loud, (i, sample)| {
+                loud | (mask.contains(i) & self.is_loud_sample(sample))
+            }),
+        }
+    }
+
+    /// Is any channel in this frame outside the limits, for frames which
+    /// are slices of samples?
+    pub(crate) fn is_loud_channels(&self, frame: &[S]) -> bool {
+        if self.downmix && frame.len() > 1 {
+            return downmix(frame.iter().copied(), frame.len(), self.mask)
+                .is_some_and(|mix| self.is_loud_signed(mix));
+        }
+
+        frame.iter().enumerate().fold(false, |loud, (i, &sample)| {
+            loud | (self.mask.contains(i) & self.is_loud_sample(sample))
+        })
+    }
+
+    fn is_loud_sample(&self, sample: S) -> bool {
+        self.is_loud_signed(sample.to_signed_sample())
+    }
+
+    fn is_loud_signed(&self, sample: S::Signed) -> bool {
+        // |sample| < |threshold|  <=>  -|sample| > -|threshold|
+        // (negated so NaN and infinity are loud unless told otherwise)
+        #[allow(clippy::neg_cmp_op_on_partial_ord)]
+        let loud = !(crate::negated_abs(sample) > self.negated_threshold);
+
+        if self.silence_non_finite {
+            // Note: x - x is always zero, except for NaN and infinity where
+            // it's NaN. This works for every sample type and doesn't need to
+            // branch.
+            #[allow(clippy::eq_op)]
+            let non_finite = sample - sample != S::Signed::EQUILIBRIUM;
+            loud & !non_finite
+        } else {
+            loud
+        }
+    }
+}
+
+/// Average the channels in a frame which aren't masked out, or `None` if
+/// they all are.
+///
+/// Each channel is scaled down before they're added together, so the sum
+/// can never overflow.
+fn downmix<S, I>(
+    samples: I,
+    channels: usize,
+    mask: ChannelMask,
+) -> Option<S::Signed>
+where
+    S: Sample,
+    I: Iterator<Item = S>,
+{
+    let selected = if mask == ChannelMask::ALL {
+        channels
+    } else {
+        (0..channels).filter(|&i| mask.contains(i)).count()
+    };
+    if selected == 0 {
+        return None;
+    }
+
+    let scale =
+        (1.0 / selected as f64).to_sample::<<S::Signed as Sample>::Float>();
+
+    let mix = samples
+        .enumerate()
+        .filter(|&(i, _)| mask.contains(i))
+        .fold(S::Signed::EQUILIBRIUM, |mix, (_, sample)| {
+            mix + sample.to_signed_sample().mul_amp(scale)
+        });
+
+    Some(mix)
+}
+
+fn channel<F: Frame>(frame: &F, index: usize) -> F::Sample {
+    frame
+        .channel(index)
+        .copied()
+        .unwrap_or(F::Sample::EQUILIBRIUM)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::low_level::Level;
+
+    fn naive<F: Frame>(frames: &[F], threshold: F::Sample) -> Option<usize> {
+        frames
+            .iter()
+            .position(|&frame| Level::of(frame, threshold) == Level::Loud)
+    }
+
+    #[test]
+    fn same_result_as_checking_each_frame() {
+        let mut frames = vec![[3_i16, -4]; 100];
+
+        for &loud in &[0, 1, 15, 16, 17, 63, 95, 99] {
+            frames[loud] = [3, -120];
+
+            for start in 0..frames.len() {
+                let got = first_above_threshold(&frames[start..], 100);
+
+                assert_eq!(got, naive(&frames[start..], 100), "{}", start);
+            }
+
+            frames[loud] = [3, -4];
+        }
+    }
+
+    #[test]
+    fn every_channel_is_checked() {
+        let threshold = 100_i16;
+        let quiet = [[0_i16; 1], [0; 1]];
+        assert_eq!(first_above_threshold(&quiet, threshold), None);
+
+        for channel in 0..2 {
+            let mut frames = [[0_i16; 2]; 3];
+            frames[1][channel] = -threshold;
+            assert_eq!(first_above_threshold(&frames, threshold), Some(1));
+        }
+
+        for channel in 0..5 {
+            let mut frames = [[0_i16; 5]; 3];
+            frames[2][channel] = threshold;
+            assert_eq!(first_above_threshold(&frames, threshold), Some(2));
+        }
+    }
+
+    fn assert_detected<S>(threshold: S, quiet: &[S], loud: &[S])
+    where
+        S: Sample + std::fmt::Debug,
+    {
+        let limits = Limits::new(threshold);
+
+        for &sample in quiet {
+            assert!(!limits.is_loud([sample]), "{:?} is quiet", sample);
+        }
+        for &sample in loud {
+            assert!(limits.is_loud([sample]), "{:?} is loud", sample);
+        }
+    }
+
+    #[test]
+    fn unsigned_thresholds_are_relative_to_equilibrium() {
+        let quiet = [128, 129, 177, 79, 100];
+        let loud = [178, 200, 255, 78, 1];
+        assert_detected(178_u8, &quiet, &loud);
+        // thresholds below equilibrium mean exactly the same thing
+        assert_detected(78_u8, &quiet, &loud);
+
+        let quiet = [32768, 33767, 31769];
+        let loud = [33768, 65535, 31768, 1];
+        assert_detected(33768_u16, &quiet, &loud);
+        assert_detected(31768_u16, &quiet, &loud);
+    }
+
+    #[test]
+    fn signed_and_float_thresholds_are_symmetric() {
+        assert_detected(
+            1000_i32,
+            &[0, 999, -999],
+            &[1000, -1000, i32::MAX, i32::MIN + 1],
+        );
+        assert_detected(-1000_i32, &[0, 999, -999], &[1000, -1000]);
+        assert_detected(0.5_f32, &[0.0, 0.49, -0.49], &[0.5, -0.5, 1.0, -1.0]);
+        assert_detected(0.5_f64, &[0.0, 0.49, -0.49], &[0.5, -0.5, 1.0, -1.0]);
+    }
+
+    #[test]
+    fn non_finite_samples_follow_the_policy() {
+        let weird = [f32::NAN, f32::INFINITY, f32::NEG_INFINITY];
+        let loud = Limits::new(0.5_f32);
+        let silent = Limits::new(0.5_f32).with_non_finite(NonFinite::Silent);
+
+        for &sample in &weird {
+            assert!(loud.is_loud([sample]), "{}", sample);
+            assert!(!silent.is_loud([sample]), "{}", sample);
+            // a real signal on another channel still opens the gate
+            assert!(silent.is_loud([sample, 0.75]), "{}", sample);
+        }
+
+        for &sample in &[0.0, 0.25, f32::MIN_POSITIVE, -0.0] {
+            assert!(!silent.is_loud([sample]));
+        }
+        for &sample in &[0.5, -0.5, 1.0, f32::MAX, f32::MIN] {
+            assert!(silent.is_loud([sample]));
+        }
+    }
+
+    /// Check the limits against a widened calculation which can't overflow,
+    /// using every combination of extreme values for an integer type.
+    macro_rules! extremes {
+        ($($name:ident: $ty:ty),* $(,)?) => {
+            $(
+                #[test]
+                fn $name() {
+                    let equilibrium = <$ty as Sample>::EQUILIBRIUM;
+                    let values = [
+                        <$ty>::MIN,
+                        <$ty>::MIN + 1,
+                        equilibrium - 1,
+                        equilibrium,
+                        equilibrium + 1,
+                        <$ty>::MAX - 1,
+                        <$ty>::MAX,
+                    ];
+                    let magnitude =
+                        |s: $ty| (s as i128 - equilibrium as i128).abs();
+
+                    for &threshold in &values {
+                        let limits = Limits::new(threshold);
+
+                        for &sample in &values {
+                            let expected =
+                                magnitude(sample) >= magnitude(threshold);
+                            assert_eq!(
+                                limits.is_loud([sample]),
+                                expected,
+                                "sample: {}, threshold: {}",
+                                sample,
+                                threshold,
+                            );
+                        }
+                    }
+                }
+            )*
+        };
+    }
+
+    extremes! {
+        i8_extremes: i8,
+        i16_extremes: i16,
+        i32_extremes: i32,
+        i64_extremes: i64,
+        u8_extremes: u8,
+        u16_extremes: u16,
+        u32_extremes: u32,
+        u64_extremes: u64,
+    }
+
+    #[test]
+    fn nothing_found_in_silence() {
+        let frames = vec![[0.01_f32]; 1000];
+
+        assert_eq!(first_above_threshold(&frames, 0.5), None);
+        assert_eq!(first_above_threshold::<[f32; 1]>(&[], 0.5), None);
+    }
+
+    #[test]
+    fn find_the_first_quiet_frame() {
+        let mut frames = vec![[300_i16, -4]; 100];
+
+        for &quiet in &[0, 15, 16, 17, 99] {
+            frames[quiet] = [3, -4];
+
+            for start in 0..frames.len() {
+                let got = first_quiet(&frames[start..], Limits::new(100));
+                let expected = frames[start..]
+                    .iter()
+                    .position(|&frame| Level::of(frame, 100) == Level::Quiet);
+
+                assert_eq!(got, expected, "{}", start);
+            }
+
+            frames[quiet] = [300, -4];
+        }
+    }
+}
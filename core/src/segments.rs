@@ -0,0 +1,131 @@
+//! Zero-copy iteration over the spans of noise in a buffer.
+
+use crate::NoiseGate;
+use core::{fmt, ops::Range};
+use dasp::Frame;
+
+/// An iterator over the spans of noise in a buffer, created by
+/// [`NoiseGate::segments()`].
+///
+/// Each item is the segment's position in the buffer alongside the frames
+/// it covers.
+pub struct Segments<'a, F: Frame> {
+    gate: &'a mut NoiseGate<F::Sample>,
+    frames: &'a [F],
+    position: usize,
+}
+
+impl<'a, F: Frame> Segments<'a, F> {
+    pub(crate) fn new(
+        gate: &'a mut NoiseGate<F::Sample>,
+        frames: &'a [F],
+    ) -> Self {
+        Segments {
+            gate,
+            frames,
+            position: 0,
+        }
+    }
+}
+
+impl<'a, F: Frame> Iterator for Segments<'a, F> {
+    type Item = (Range<usize>, &'a [F]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut segment: Option<Range<usize>> = None;
+
+        while self.position < self.frames.len() {
+            let run = self.gate.next_run(&self.frames[self.position..]);
+
+            if run.recorded > 0 {
+                // recorded runs are always contiguous while the gate is open
+                let end = self.position + run.recorded;
+                let start = segment.map_or(self.position, |s| s.start);
+                segment = Some(start..end);
+            }
+
+            self.position += run.len;
+
+            if run.closed && segment.is_some() {
+                break;
+            }
+        }
+
+        let frames = self.frames;
+        segment.map(|range| (range.clone(), &frames[range]))
+    }
+}
+
+impl<'a, F> fmt::Debug for Segments<'a, F>
+where
+    F: Frame,
+    F::Sample: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Segments")
+            .field("gate", &self.gate)
+            .field("frames", &self.frames.len())
+            .field("position", &self.position)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sink;
+
+    #[derive(Debug, Default)]
+    struct Clips(Vec<Vec<[i16; 2]>>, Vec<[i16; 2]>);
+
+    impl Sink<[i16; 2]> for Clips {
+        fn record(&mut self, frame: [i16; 2]) { self.1.push(frame); }
+
+        fn end_of_transmission(&mut self) {
+            self.0.push(std::mem::take(&mut self.1));
+        }
+    }
+
+    fn signal() -> Vec<[i16; 2]> {
+        (0..500_i16)
+            .map(|i| match i % 70 {
+                0..=9 => [i, 0],
+                20..=22 => [0, -i],
+                _ => [1, -1],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn segments_match_the_frames_passed_to_a_sink() {
+        let frames = signal();
+        let mut clips = Clips::default();
+        NoiseGate::new(50, 4).process_frames(&frames, &mut clips);
+        clips.end_of_transmission();
+
+        let mut gate = NoiseGate::new(50, 4);
+        let segments: Vec<_> = gate
+            .segments(&frames)
+            .map(|(range, segment)| {
+                assert_eq!(&frames[range], segment);
+                segment.to_vec()
+            })
+            .collect();
+
+        assert_eq!(segments, clips.0);
+    }
+
+    #[test]
+    fn segments_continue_into_the_next_buffer() {
+        let frames = signal();
+        let (first, second) = frames.split_at(75);
+        let mut gate = NoiseGate::new(50, 4);
+
+        let last = gate.segments(first).last().unwrap();
+        assert_eq!(last.0, 70..75);
+        assert!(gate.is_open());
+
+        let next = gate.segments(second).next().unwrap();
+        assert_eq!(next.0, 0..10);
+    }
+}
@@ -0,0 +1,107 @@
+//! Exporting the gate's decisions as a gain automation curve, instead of
+//! cutting the recording into clips.
+
+use crate::{preview, Options};
+use noise_gate::{automation, NoiseGate};
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+pub struct Args {
+    #[structopt(help = "The WAV file to analyse")]
+    pub input_file: PathBuf,
+    #[structopt(
+        long = "format",
+        help = "Which kind of automation to write",
+        default_value = "csv",
+        possible_values = &["csv", "reaper"]
+    )]
+    pub format: CurveFormat,
+    #[structopt(
+        long = "output",
+        help = "Where to save the curve [default: the input file, with a \
+                \".csv\" or \".envelope.txt\" extension]"
+    )]
+    pub output: Option<PathBuf>,
+    #[structopt(
+        long = "ramp",
+        help = "How long the gain takes to ramp up or down around each clip",
+        default_value = "10ms",
+        parse(try_from_str = crate::parse_duration)
+    )]
+    pub ramp: Duration,
+    #[structopt(flatten)]
+    pub options: Options,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CurveFormat {
+    Csv,
+    Reaper,
+}
+
+impl CurveFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            CurveFormat::Csv => "csv",
+            CurveFormat::Reaper => "envelope.txt",
+        }
+    }
+}
+
+impl FromStr for CurveFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(CurveFormat::Csv),
+            "reaper" => Ok(CurveFormat::Reaper),
+            other => Err(format!("Unknown automation format \"{}\"", other)),
+        }
+    }
+}
+
+pub fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let settings = args.options.resolve()?;
+    let (sample_rate, levels) = preview::read_levels(&args.input_file)?;
+
+    let release_frames = crate::to_frames(settings.release_time, sample_rate);
+    let mut gate = NoiseGate::new(settings.noise_threshold, release_frames)
+        .with_detection(settings.detector);
+    let segments: Vec<_> =
+        gate.segments(&levels).map(|(range, _)| range).collect();
+
+    let ramp = crate::to_frames(args.ramp, sample_rate);
+    let curve = automation::gain_curve(&segments, levels.len(), ramp);
+
+    let output = args.output.clone().unwrap_or_else(|| {
+        args.input_file.with_extension(args.format.extension())
+    });
+    let mut writer = BufWriter::new(File::create(&output)?);
+    match args.format {
+        CurveFormat::Csv => {
+            automation::write_csv(&curve, sample_rate, &mut writer)?
+        },
+        CurveFormat::Reaper => {
+            automation::write_reaper_envelope(&curve, sample_rate, &mut writer)?
+        },
+    }
+    writer.flush()?;
+
+    log!(
+        Info,
+        "saved automation",
+        path = output.display(),
+        points = curve.len(),
+        clips = segments.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+//! Embedding Broadcast Wave Format (BWF) metadata in clips.
+//!
+//! See [EBU Tech 3285][spec] for the full specification.
+//!
+//! [spec]: https://tech.ebu.ch/docs/tech/tech3285.pdf
+
+use std::{
+    error::Error,
+    fs::OpenOptions,
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+/// Append a `bext` chunk to an existing WAV file, recording which recording
+/// the clip came from and the sample it started at.
+///
+/// The start position is stored in the `TimeReference` field, so DAWs which
+/// understand BWF will place the clip at its original position on the
+/// timeline (treating the start of the original recording as `00:00:00`).
+pub fn append_bext(
+    clip: &Path,
+    source: &Path,
+    start_sample: u64,
+) -> Result<(), Box<dyn Error>> {
+    let source_name = source
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let description = format!(
+        "Clip from {} starting at sample {}",
+        source_name, start_sample
+    );
+
+    let chunk = bext_chunk(&description, &source_name, start_sample);
+
+    let mut f = OpenOptions::new().read(true).write(true).open(clip)?;
+
+    let mut header = [0; 12];
+    f.read_exact(&mut header)?;
+    if &header[..4] != b"RIFF" || &header[8..] != b"WAVE" {
+        return Err(format!("\"{}\" isn't a WAV file", clip.display()).into());
+    }
+
+    let end = f.seek(SeekFrom::End(0))?;
+    f.write_all(b"bext")?;
+    f.write_all(&(chunk.len() as u32).to_le_bytes())?;
+    f.write_all(&chunk)?;
+    if chunk.len() % 2 == 1 {
+        // chunks are always padded to an even length
+        f.write_all(&[0])?;
+    }
+
+    // update the RIFF header's size to include the new chunk
+    let riff_size = f.seek(SeekFrom::End(0))? - 8;
+    debug_assert!(riff_size > end - 8);
+    f.seek(SeekFrom::Start(4))?;
+    f.write_all(&(riff_size as u32).to_le_bytes())?;
+
+    Ok(())
+}
+
+fn bext_chunk(
+    description: &str,
+    originator_reference: &str,
+    time_reference: u64,
+) -> Vec<u8> {
+    let mut chunk = Vec::with_capacity(602);
+
+    push_fixed(&mut chunk, description, 256);
+    push_fixed(&mut chunk, "noise-gate", 32);
+    push_fixed(&mut chunk, originator_reference, 32);
+    // we don't know when the original recording was made
+    push_fixed(&mut chunk, "", 10); // OriginationDate
+    push_fixed(&mut chunk, "", 8); // OriginationTime
+    chunk.extend_from_slice(&time_reference.to_le_bytes());
+    chunk.extend_from_slice(&1_u16.to_le_bytes()); // Version
+    chunk.extend_from_slice(&[0; 64]); // UMID
+    chunk.extend_from_slice(&[0; 10]); // loudness values
+    chunk.extend_from_slice(&[0; 180]); // reserved
+
+    debug_assert_eq!(chunk.len(), 602);
+    chunk
+}
+
+/// Write a fixed-width, null-padded ASCII field.
+fn push_fixed(buffer: &mut Vec<u8>, value: &str, width: usize) {
+    let bytes: Vec<u8> = value
+        .chars()
+        .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+        .take(width)
+        .collect();
+
+    buffer.extend_from_slice(&bytes);
+    buffer.resize(buffer.len() + width - bytes.len(), 0);
+}
@@ -0,0 +1,447 @@
+//! Loading the splitter's settings from the command-line, config files, and
+//! named presets.
+//!
+//! Config files are TOML, using the same names as the command-line options:
+//!
+//! ```toml
+//! # use the ham radio preset, but with a lower threshold
+//! preset = "ham"
+//! threshold = 200
+//! release-time = "750ms"
+//! trim-threshold = 50
+//! detector = "downmix"
+//! output-dir = "clips"
+//! bwf = true
+//! ```
+
+use crate::naming::{Naming, StartTime};
+use noise_gate::{presets::Preset, Detection};
+use serde::Deserialize;
+use std::{error::Error, fs, path::PathBuf, time::Duration};
+use structopt::StructOpt;
+
+/// Options controlling how audio gets split into clips.
+///
+/// Anything passed on the command-line takes precedence over the config
+/// file, which in turn takes precedence over the preset.
+#[derive(Debug, Clone, StructOpt)]
+pub struct Options {
+    #[structopt(
+        short = "c",
+        long = "config",
+        help = "A config file to load settings from"
+    )]
+    pub config: Option<PathBuf>,
+    #[structopt(
+        long = "preset",
+        help = "Start from a named preset (podcast, ham, field-recording)"
+    )]
+    pub preset: Option<String>,
+    #[structopt(flatten)]
+    pub overrides: Overrides,
+}
+
+impl Options {
+    /// Figure out the final [`Settings`] to use.
+    pub fn resolve(&self) -> Result<Settings, Box<dyn Error>> {
+        let mut overrides = self.overrides.clone();
+
+        if let Some(path) = &self.config {
+            let src = fs::read_to_string(path).map_err(|e| {
+                format!("Unable to read \"{}\": {}", path.display(), e)
+            })?;
+            let file = parse_config(&src).map_err(|e| {
+                format!("Unable to load \"{}\": {}", path.display(), e)
+            })?;
+            overrides = overrides.or(file);
+        }
+
+        let preset_name =
+            self.preset.clone().or_else(|| overrides.preset.clone());
+        if let Some(name) = preset_name {
+            overrides = overrides.or(preset(&name)?);
+        }
+
+        overrides.into_settings()
+    }
+}
+
+/// Settings which may or may not have been provided.
+#[derive(Debug, Default, Clone, PartialEq, StructOpt)]
+pub struct Overrides {
+    #[structopt(skip)]
+    pub preset: Option<String>,
+    #[structopt(short = "t", long = "threshold", help = "The noise threshold")]
+    pub noise_threshold: Option<i16>,
+    #[structopt(
+        short = "r",
+        long = "release-time",
+        help = "The release time (e.g. \"0.25\", \"250ms\", or \"1.5s\") \
+                [default: 0.25]",
+        parse(try_from_str = crate::parse_duration)
+    )]
+    pub release_time: Option<Duration>,
+    #[structopt(
+        long = "fade-edges",
+        help = "How long to fade the start and end of each clip for \
+                [default: 0ms]",
+        parse(try_from_str = crate::parse_duration)
+    )]
+    pub fade_edges: Option<Duration>,
+    #[structopt(
+        long = "trim-threshold",
+        help = "Trim anything quieter than this from the start and end of \
+                each clip"
+    )]
+    pub trim_threshold: Option<i16>,
+    #[structopt(
+        long = "max-clip-length",
+        help = "Start a new file when a clip gets this long (e.g. \"600s\")",
+        parse(try_from_str = crate::parse_duration)
+    )]
+    pub max_clip_length: Option<Duration>,
+    #[structopt(
+        long = "max-clip-size",
+        help = "Start a new file before a clip gets bigger than this many \
+                bytes"
+    )]
+    pub max_clip_size: Option<u64>,
+    #[structopt(
+        long = "quota",
+        help = "Delete the oldest clips in the output directory whenever \
+                they use more than this many bytes"
+    )]
+    pub quota: Option<u64>,
+    #[structopt(
+        long = "watchdog",
+        help = "Cut off any clip which stays open for longer than this \
+                (e.g. \"1800s\"), in case the squelch gets stuck open",
+        parse(try_from_str = crate::parse_duration)
+    )]
+    pub watchdog: Option<Duration>,
+    #[structopt(
+        long = "merge-gaps",
+        help = "Join clips separated by less than this much silence (e.g. \
+                \"2s\") into one, keeping the silence between them",
+        parse(try_from_str = crate::parse_duration)
+    )]
+    pub merge_gaps: Option<Duration>,
+    #[structopt(
+        long = "roger-beep",
+        help = "Append a 1 kHz beep this long (e.g. \"150ms\") to the end of \
+                every clip",
+        parse(try_from_str = crate::parse_duration)
+    )]
+    pub roger_beep: Option<Duration>,
+    #[structopt(
+        short = "o",
+        long = "output-dir",
+        help = "Where to write the split files [default: .]"
+    )]
+    pub output_dir: Option<PathBuf>,
+    #[structopt(
+        short = "p",
+        long = "prefix",
+        help = "A prefix to insert before each clip [default: clip_]"
+    )]
+    pub prefix: Option<String>,
+    #[structopt(
+        long = "detector",
+        help = "How to decide whether multi-channel audio is loud, either \
+                \"any-channel\" or \"downmix\" [default: any-channel]",
+        parse(try_from_str = parse_detector)
+    )]
+    pub detector: Option<Detection>,
+    #[structopt(
+        long = "bwf",
+        help = "Embed each clip's position in the original recording as \
+                Broadcast Wave (bext) metadata",
+        parse(from_flag = flag)
+    )]
+    pub bwf: Flag,
+    #[structopt(
+        long = "naming",
+        help = "How to name clips, either \"number\" or \"timestamp\" \
+                [default: number]"
+    )]
+    pub naming: Option<Naming>,
+    #[structopt(
+        long = "start-time",
+        help = "When the recording started, used when naming clips by \
+                timestamp. Either \"file\" (based on when the file was last \
+                modified), \"now\", or a time like \"2024-05-03T14:23:07\" \
+                [default: file]"
+    )]
+    pub start_time: Option<StartTime>,
+    #[structopt(
+        long = "webhook",
+        help = "Call this URL whenever a clip is written, where \"{path}\", \
+                \"{start_ms}\", and \"{duration_ms}\" are replaced with the \
+                clip's details (needs the \"webhook\" feature)"
+    )]
+    pub webhook: Option<String>,
+}
+
+impl Overrides {
+    /// Fill in any missing settings using `fallback`.
+    fn or(self, fallback: Overrides) -> Overrides {
+        Overrides {
+            preset: self.preset.or(fallback.preset),
+            noise_threshold: self.noise_threshold.or(fallback.noise_threshold),
+            release_time: self.release_time.or(fallback.release_time),
+            fade_edges: self.fade_edges.or(fallback.fade_edges),
+            trim_threshold: self.trim_threshold.or(fallback.trim_threshold),
+            max_clip_length: self.max_clip_length.or(fallback.max_clip_length),
+            max_clip_size: self.max_clip_size.or(fallback.max_clip_size),
+            quota: self.quota.or(fallback.quota),
+            watchdog: self.watchdog.or(fallback.watchdog),
+            merge_gaps: self.merge_gaps.or(fallback.merge_gaps),
+            roger_beep: self.roger_beep.or(fallback.roger_beep),
+            output_dir: self.output_dir.or(fallback.output_dir),
+            prefix: self.prefix.or(fallback.prefix),
+            detector: self.detector.or(fallback.detector),
+            bwf: self.bwf.or(fallback.bwf),
+            naming: self.naming.or(fallback.naming),
+            start_time: self.start_time.or(fallback.start_time),
+            webhook: self.webhook.or(fallback.webhook),
+        }
+    }
+
+    fn into_settings(self) -> Result<Settings, Box<dyn Error>> {
+        let noise_threshold = self.noise_threshold.ok_or(
+            "No threshold was provided. Use --threshold, a config file, or \
+             a preset",
+        )?;
+
+        if let Some(trim) = self.trim_threshold {
+            if trim.unsigned_abs() > noise_threshold.unsigned_abs() {
+                return Err(format!(
+                    "The trim threshold ({}) can't be higher than the noise \
+                     threshold ({})",
+                    trim, noise_threshold
+                )
+                .into());
+            }
+        }
+
+        Ok(Settings {
+            noise_threshold,
+            release_time: self
+                .release_time
+                .unwrap_or_else(|| Duration::from_millis(250)),
+            fade_edges: self.fade_edges.unwrap_or_default(),
+            trim_threshold: self.trim_threshold,
+            max_clip_length: self.max_clip_length,
+            max_clip_size: self.max_clip_size,
+            quota: self.quota,
+            watchdog: self.watchdog,
+            merge_gaps: self.merge_gaps,
+            roger_beep: self.roger_beep,
+            output_dir: self.output_dir.unwrap_or_else(|| PathBuf::from(".")),
+            prefix: self.prefix.unwrap_or_else(|| String::from("clip_")),
+            detector: self.detector.unwrap_or_default(),
+            bwf: self.bwf.unwrap_or(false),
+            naming: self.naming.unwrap_or(Naming::Numbered),
+            start_time: self.start_time.unwrap_or(StartTime::File),
+            webhook: self.webhook,
+        })
+    }
+}
+
+/// The fully resolved settings used when splitting a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub noise_threshold: i16,
+    pub release_time: Duration,
+    pub fade_edges: Duration,
+    /// Anything quieter than this gets trimmed from the ends of each clip.
+    pub trim_threshold: Option<i16>,
+    /// Clips longer than this are split across several files.
+    pub max_clip_length: Option<Duration>,
+    /// Clips are split across several files to stay under this many bytes.
+    pub max_clip_size: Option<u64>,
+    /// The most space clips in the output directory may use.
+    pub quota: Option<u64>,
+    /// Clips are cut off (rather than split) once they get this long.
+    pub watchdog: Option<Duration>,
+    /// Clips separated by less silence than this are joined together.
+    pub merge_gaps: Option<Duration>,
+    /// How long the beep marking the end of each clip is.
+    pub roger_beep: Option<Duration>,
+    pub output_dir: PathBuf,
+    pub prefix: String,
+    /// How multi-channel audio is checked against the threshold.
+    pub detector: Detection,
+    pub bwf: bool,
+    pub naming: Naming,
+    pub start_time: StartTime,
+    /// A URL template for the webhook to call whenever a clip is written.
+    pub webhook: Option<String>,
+}
+
+/// Look up one of the built-in presets.
+fn preset(name: &str) -> Result<Overrides, Box<dyn Error>> {
+    let preset = match name {
+        "podcast" => Preset::PODCAST,
+        "ham" => Preset::HAM_RADIO,
+        "field-recording" => Preset::FIELD_RECORDING,
+        other => {
+            return Err(format!(
+                "Unknown preset \"{}\", expected one of podcast, ham, or \
+                 field-recording",
+                other
+            )
+            .into())
+        },
+    };
+
+    Ok(Overrides {
+        // thresholds are always relative to 16-bit audio
+        noise_threshold: Some(preset.threshold::<i16>()),
+        release_time: Some(preset.release_time),
+        fade_edges: Some(preset.fade_time),
+        ..Default::default()
+    })
+}
+
+/// A `--flag` which may not have been set, so a config file can still turn
+/// it off. The alias stops structopt from expecting `--flag <value>`.
+pub type Flag = Option<bool>;
+
+fn flag(present: bool) -> Flag { Some(true).filter(|_| present) }
+
+fn parse_detector(src: &str) -> Result<Detection, String> {
+    match src {
+        "any-channel" => Ok(Detection::AnyChannel),
+        "downmix" => Ok(Detection::Downmix),
+        other => Err(format!(
+            "Unknown detector \"{}\", expected \"any-channel\" or \"downmix\"",
+            other
+        )),
+    }
+}
+
+/// The contents of a config file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct ConfigFile {
+    preset: Option<String>,
+    threshold: Option<i16>,
+    release_time: Option<DurationValue>,
+    fade_edges: Option<DurationValue>,
+    trim_threshold: Option<i16>,
+    max_clip_length: Option<DurationValue>,
+    max_clip_size: Option<u64>,
+    quota: Option<u64>,
+    watchdog: Option<DurationValue>,
+    merge_gaps: Option<DurationValue>,
+    roger_beep: Option<DurationValue>,
+    output_dir: Option<PathBuf>,
+    prefix: Option<String>,
+    detector: Option<String>,
+    bwf: Option<bool>,
+    naming: Option<String>,
+    start_time: Option<String>,
+    webhook: Option<String>,
+}
+
+/// A duration, either as a number of seconds or a string like `"250ms"`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum DurationValue {
+    Seconds(f64),
+    Text(String),
+}
+
+impl DurationValue {
+    fn parse(self, key: &str) -> Result<Duration, String> {
+        let parsed = match self {
+            DurationValue::Seconds(seconds) => {
+                crate::parse_duration(&seconds.to_string())
+            },
+            DurationValue::Text(text) => crate::parse_duration(&text),
+        };
+
+        parsed.map_err(|e| format!("Invalid {}: {}", key, e))
+    }
+}
+
+fn parse_config(src: &str) -> Result<Overrides, String> {
+    let file: ConfigFile = toml::from_str(src).map_err(|e| e.to_string())?;
+    let duration = |value: Option<DurationValue>, key: &str| {
+        value.map(|value| value.parse(key)).transpose()
+    };
+
+    Ok(Overrides {
+        preset: file.preset,
+        noise_threshold: file.threshold,
+        release_time: duration(file.release_time, "release-time")?,
+        fade_edges: duration(file.fade_edges, "fade-edges")?,
+        trim_threshold: file.trim_threshold,
+        max_clip_length: duration(file.max_clip_length, "max-clip-length")?,
+        max_clip_size: file.max_clip_size,
+        quota: file.quota,
+        watchdog: duration(file.watchdog, "watchdog")?,
+        merge_gaps: duration(file.merge_gaps, "merge-gaps")?,
+        roger_beep: duration(file.roger_beep, "roger-beep")?,
+        output_dir: file.output_dir,
+        prefix: file.prefix,
+        detector: file.detector.as_deref().map(parse_detector).transpose()?,
+        bwf: file.bwf,
+        naming: file.naming.map(|n| n.parse()).transpose()?,
+        start_time: file.start_time.map(|t| t.parse()).transpose()?,
+        webhook: file.webhook,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_a_config_file() {
+        let src = r#"
+            # use the ham radio preset, but with a lower threshold
+            preset = "ham"
+            threshold = 200
+            release-time = "750ms"
+            fade-edges = 0.5
+            detector = "downmix"
+            output-dir = "clips"
+            bwf = false
+        "#;
+
+        let overrides = parse_config(src).unwrap();
+
+        assert_eq!(overrides.preset.as_deref(), Some("ham"));
+        assert_eq!(overrides.noise_threshold, Some(200));
+        assert_eq!(overrides.release_time, Some(Duration::from_millis(750)));
+        assert_eq!(overrides.fade_edges, Some(Duration::from_millis(500)));
+        assert_eq!(overrides.detector, Some(Detection::Downmix));
+        assert_eq!(overrides.output_dir, Some(PathBuf::from("clips")));
+        assert_eq!(overrides.bwf, Some(false));
+    }
+
+    #[test]
+    fn the_config_file_can_turn_bwf_off() {
+        let cli = Overrides::default();
+        let file = Overrides {
+            bwf: Some(false),
+            ..Default::default()
+        };
+        let preset = Overrides {
+            bwf: Some(true),
+            ..Default::default()
+        };
+
+        assert_eq!(cli.or(file).or(preset).bwf, Some(false));
+    }
+
+    #[test]
+    fn mistakes_are_reported() {
+        assert!(parse_config("threshhold = 200").is_err());
+        assert!(parse_config("release-time = \"soon\"").is_err());
+        assert!(parse_config("detector = \"loudest\"").is_err());
+        assert!(parse_config("bwf = \"yes\"").is_err());
+    }
+}
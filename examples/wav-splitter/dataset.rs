@@ -0,0 +1,283 @@
+//! Writing a manifest of every clip in the layouts speech dataset tools
+//! expect.
+
+use crate::report::FileReport;
+use serde::Serialize;
+use std::{
+    error::Error,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// The layouts a dataset manifest can be written in.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ManifestFormat {
+    /// Kaldi's `wav.scp`, `segments`, and `utt2dur` files.
+    Kaldi,
+    /// A `manifest.csv` with one row per clip.
+    Csv,
+    /// A `manifest.jsonl` with one JSON object per clip.
+    Jsonl,
+}
+
+impl FromStr for ManifestFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "kaldi" => Ok(ManifestFormat::Kaldi),
+            "csv" => Ok(ManifestFormat::Csv),
+            "jsonl" => Ok(ManifestFormat::Jsonl),
+            other => Err(format!(
+                "Unknown manifest format \"{}\", expected one of kaldi, csv, \
+                 or jsonl",
+                other
+            )),
+        }
+    }
+}
+
+/// A single clip, as it appears in the manifest.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Utterance {
+    /// A unique ID for the clip, prefixed with the recording's ID so they
+    /// sort in order.
+    pub utterance_id: String,
+    pub path: PathBuf,
+    /// The clip's length in seconds.
+    pub duration: f64,
+    pub channels: u16,
+    pub recording_id: String,
+    pub recording: PathBuf,
+    /// When the clip started in the original recording, in seconds.
+    pub start: f64,
+}
+
+/// Get every clip which was written while splitting these files.
+pub fn utterances(files: &[FileReport]) -> Vec<Utterance> {
+    let mut utterances = Vec::new();
+
+    for file in files {
+        let summary = match &file.summary {
+            Some(summary) => summary,
+            None => continue,
+        };
+        let recording_id = recording_id(&file.input);
+
+        for (i, clip) in summary.clips.iter().enumerate() {
+            utterances.push(Utterance {
+                utterance_id: format!("{}-{:05}", recording_id, i),
+                path: clip.path.clone(),
+                duration: clip.duration,
+                channels: summary.channels,
+                recording_id: recording_id.clone(),
+                recording: file.input.clone(),
+                start: clip.start,
+            });
+        }
+    }
+
+    // Kaldi requires everything to be sorted by ID
+    utterances.sort_by(|a, b| a.utterance_id.cmp(&b.utterance_id));
+    utterances
+}
+
+/// Write the manifest to `dir`, returning the files that were created.
+pub fn write(
+    format: ManifestFormat,
+    dir: &Path,
+    utterances: &[Utterance],
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut written = Vec::new();
+
+    for (name, contents) in render(format, utterances)? {
+        let path = dir.join(name);
+        fs::write(&path, contents)?;
+        log!(Info, "wrote manifest", path = path.display());
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+/// Generate the contents of each file in the manifest.
+fn render(
+    format: ManifestFormat,
+    utterances: &[Utterance],
+) -> Result<Vec<(&'static str, String)>, Box<dyn Error>> {
+    match format {
+        ManifestFormat::Kaldi => Ok(kaldi(utterances)),
+        ManifestFormat::Csv => Ok(vec![("manifest.csv", csv(utterances))]),
+        ManifestFormat::Jsonl => {
+            let mut jsonl = String::new();
+            for utterance in utterances {
+                jsonl.push_str(&serde_json::to_string(utterance)?);
+                jsonl.push('\n');
+            }
+            Ok(vec![("manifest.jsonl", jsonl)])
+        },
+    }
+}
+
+/// Kaldi describes utterances as segments of a longer recording, so
+/// `wav.scp` lists the original recordings rather than the clips.
+fn kaldi(utterances: &[Utterance]) -> Vec<(&'static str, String)> {
+    let mut wav_scp = String::new();
+    let mut segments = String::new();
+    let mut utt2dur = String::new();
+    let mut previous_recording = None;
+
+    for utt in utterances {
+        if previous_recording != Some(&utt.recording_id) {
+            writeln!(
+                wav_scp,
+                "{} {}",
+                utt.recording_id,
+                utt.recording.display()
+            )
+            .unwrap();
+            previous_recording = Some(&utt.recording_id);
+        }
+
+        writeln!(
+            segments,
+            "{} {} {:.3} {:.3}",
+            utt.utterance_id,
+            utt.recording_id,
+            utt.start,
+            utt.start + utt.duration
+        )
+        .unwrap();
+        writeln!(utt2dur, "{} {:.3}", utt.utterance_id, utt.duration).unwrap();
+    }
+
+    vec![
+        ("wav.scp", wav_scp),
+        ("segments", segments),
+        ("utt2dur", utt2dur),
+    ]
+}
+
+fn csv(utterances: &[Utterance]) -> String {
+    let mut csv = String::from(
+        "utterance_id,path,duration,channels,recording_id,recording,start\n",
+    );
+
+    for utt in utterances {
+        writeln!(
+            csv,
+            "{},{},{:.3},{},{},{},{:.3}",
+            quote(&utt.utterance_id),
+            quote(&utt.path.display().to_string()),
+            utt.duration,
+            utt.channels,
+            quote(&utt.recording_id),
+            quote(&utt.recording.display().to_string()),
+            utt.start,
+        )
+        .unwrap();
+    }
+
+    csv
+}
+
+/// Quote a CSV field, if necessary.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Use the recording's file name as its ID, replacing whitespace because
+/// Kaldi uses it as a separator.
+fn recording_id(input: &Path) -> String {
+    input
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_whitespace() { '_' } else { c })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{Clip, Summary};
+
+    fn files() -> Vec<FileReport> {
+        let clip = |path: &str, start_frame, frames| {
+            let mut clip = Clip::new(PathBuf::from(path));
+            clip.start_frame = start_frame;
+            clip.frames = frames;
+            clip
+        };
+        let clips = vec![
+            clip("out/clip_0.wav", 100, 50),
+            clip("out/clip_1.wav", 400, 100),
+        ];
+        let summary = Summary::new(100, 1000, 1, clips, Vec::new());
+
+        vec![FileReport::new(
+            PathBuf::from("data/Night Net.wav"),
+            Ok(summary),
+        )]
+    }
+
+    #[test]
+    fn kaldi_layout() {
+        let got = render(ManifestFormat::Kaldi, &utterances(&files())).unwrap();
+
+        assert_eq!(
+            got,
+            vec![
+                ("wav.scp", String::from("Night_Net data/Night Net.wav\n")),
+                (
+                    "segments",
+                    String::from(
+                        "Night_Net-00000 Night_Net 1.000 1.500\n\
+                         Night_Net-00001 Night_Net 4.000 5.000\n"
+                    )
+                ),
+                (
+                    "utt2dur",
+                    String::from(
+                        "Night_Net-00000 0.500\nNight_Net-00001 1.000\n"
+                    )
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn csv_layout() {
+        let got = render(ManifestFormat::Csv, &utterances(&files())).unwrap();
+
+        assert_eq!(got[0].0, "manifest.csv");
+        let rows: Vec<&str> = got[0].1.lines().collect();
+        assert_eq!(
+            rows[1],
+            "Night_Net-00000,out/clip_0.wav,0.500,1,Night_Net,data/Night Net.wav,1.000"
+        );
+        assert_eq!(quote("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+
+    #[test]
+    fn jsonl_layout() {
+        let got = render(ManifestFormat::Jsonl, &utterances(&files())).unwrap();
+
+        let lines: Vec<serde_json::Value> = got[0]
+            .1
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["utterance_id"], "Night_Net-00001");
+        assert_eq!(lines[1]["duration"], 1.0);
+        assert_eq!(lines[1]["channels"], 1);
+    }
+}
@@ -0,0 +1,86 @@
+//! A tiny structured logger which writes `key=value` pairs to stderr.
+//!
+//! Warnings are always shown, `-v` enables informational messages (e.g. the
+//! parameters being used and each file that gets created), and `-vv` also
+//! logs every time the gate opens or closes.
+
+use std::{
+    fmt::{Display, Write},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        OnceLock,
+    },
+    time::Instant,
+};
+
+/// How important a log message is.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Warn = 0,
+    Info = 1,
+    Debug = 2,
+}
+
+impl Level {
+    fn name(self) -> &'static str {
+        match self {
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+        }
+    }
+}
+
+static VERBOSITY: AtomicUsize = AtomicUsize::new(0);
+static START: OnceLock<Instant> = OnceLock::new();
+
+/// Set how verbose the logger should be (i.e. the number of `-v`'s).
+pub fn init(verbosity: usize) {
+    VERBOSITY.store(verbosity, Ordering::Relaxed);
+    START.get_or_init(Instant::now);
+}
+
+/// Would a message at this [`Level`] be written?
+pub fn enabled(level: Level) -> bool {
+    level as usize <= VERBOSITY.load(Ordering::Relaxed)
+}
+
+/// Write a log message. Prefer the [`log!()`] macro.
+pub fn write(level: Level, msg: &str, fields: &[(&str, &dyn Display)]) {
+    let elapsed = START.get_or_init(Instant::now).elapsed();
+    let mut line = format!(
+        "elapsed={:.3} level={} msg={:?}",
+        elapsed.as_secs_f64(),
+        level.name(),
+        msg
+    );
+
+    for (key, value) in fields {
+        let value = value.to_string();
+
+        if value.is_empty() || value.contains(|c: char| c.is_whitespace()) {
+            let _ = write!(line, " {}={:?}", key, value);
+        } else {
+            let _ = write!(line, " {}={}", key, value);
+        }
+    }
+
+    eprintln!("{}", line);
+}
+
+/// Log a message with some `key = value` fields.
+///
+/// ```rust,ignore
+/// log!(Info, "created clip", path = filename.display());
+/// ```
+macro_rules! log {
+    ($level:ident, $msg:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        if $crate::logging::enabled($crate::logging::Level::$level) {
+            $crate::logging::write(
+                $crate::logging::Level::$level,
+                &$msg,
+                &[$((stringify!($key), &$value as &dyn std::fmt::Display)),*],
+            );
+        }
+    };
+}
@@ -0,0 +1,169 @@
+//! A command-line tool which uses a [`noise_gate::NoiseGate`] to split WAV
+//! files into clips.
+//!
+//! # Exit Codes
+//!
+//! The exit code is stable so the splitter can be used from scripts and
+//! automated pipelines:
+//!
+//! | Code | Meaning                                                       |
+//! | ---- | ------------------------------------------------------------- |
+//! | 0    | Success                                                       |
+//! | 1    | General failure (e.g. invalid arguments or an IO error)       |
+//! | 2    | The recordings were processed, but no clips were found        |
+//! | 3    | A recording couldn't be decoded or uses an unsupported format |
+//! | 4    | Some of the recordings in a batch failed                      |
+
+#[macro_use]
+mod logging;
+mod automation;
+mod bwf;
+mod config;
+mod dataset;
+mod meter;
+mod multitrack;
+mod naming;
+mod plot;
+mod preview;
+mod quota;
+mod reassemble;
+mod report;
+mod resume;
+mod serve;
+mod split;
+mod tune;
+mod watch;
+mod wav;
+
+pub use config::{Options, Settings};
+
+use report::{OutputFormat, Status};
+use std::{process::ExitCode, time::Duration};
+use structopt::StructOpt;
+
+fn main() -> ExitCode {
+    let args = Args::from_args();
+    logging::init(args.verbose);
+    let format = args.output_format;
+
+    let result = match args.cmd {
+        Cmd::Split(args) => split::run(&args, format),
+        Cmd::Watch(args) => watch::run(&args, format),
+        Cmd::Preview(args) => preview::run(&args).map(|_| Status::Success),
+        Cmd::Plot(args) => plot::run(&args).map(|_| Status::Success),
+        Cmd::Serve(args) => serve::run(&args).map(|_| Status::Success),
+        Cmd::Automation(args) => {
+            automation::run(&args).map(|_| Status::Success)
+        },
+        Cmd::Meter(args) => meter::run(&args).map(|_| Status::Success),
+        Cmd::Reassemble(args) => reassemble::run(&args, format),
+        Cmd::Tune(args) => tune::run(&args, format),
+    };
+
+    let status = match result {
+        Ok(status) => status,
+        Err(e) => {
+            let status = Status::from_error(&*e);
+            match format {
+                OutputFormat::Text => eprintln!("Error: {}", e),
+                OutputFormat::Json => println!(
+                    "{}",
+                    serde_json::json!({
+                        "status": status,
+                        "exit_code": status.exit_code(),
+                        "error": e.to_string(),
+                    })
+                ),
+            }
+            status
+        },
+    };
+
+    ExitCode::from(status.exit_code())
+}
+
+#[derive(Debug, Clone, StructOpt)]
+struct Args {
+    #[structopt(
+        short = "v",
+        long = "verbose",
+        help = "Log more information (can be repeated)",
+        parse(from_occurrences),
+        global = true
+    )]
+    verbose: usize,
+    #[structopt(
+        long = "output-format",
+        help = "How results should be printed",
+        default_value = "text",
+        possible_values = &["text", "json"],
+        global = true
+    )]
+    output_format: OutputFormat,
+    #[structopt(subcommand)]
+    cmd: Cmd,
+}
+
+#[derive(Debug, Clone, StructOpt)]
+enum Cmd {
+    /// Split one or more WAV files into clips.
+    #[structopt(name = "split")]
+    Split(split::Args),
+    /// Watch a directory, splitting new recordings as they appear.
+    #[structopt(name = "watch")]
+    Watch(watch::Args),
+    /// Interactively tune the threshold and release time for a recording.
+    #[structopt(name = "preview")]
+    Preview(preview::Args),
+    /// Save an SVG of the waveform, highlighting the parts which would be
+    /// kept.
+    #[structopt(name = "plot")]
+    Plot(plot::Args),
+    /// Serve the clips a recording would be split into on localhost, so
+    /// they can be auditioned in a browser.
+    #[structopt(name = "serve")]
+    Serve(serve::Args),
+    /// Save the gate's decisions as a gain automation curve, to apply them
+    /// non-destructively in an editor.
+    #[structopt(name = "automation")]
+    Automation(automation::Args),
+    /// Show a live level meter for raw 16-bit audio piped in through stdin.
+    #[structopt(name = "meter")]
+    Meter(meter::Args),
+    /// Rebuild a recording from its clips, restoring the gaps between them.
+    #[structopt(name = "reassemble")]
+    Reassemble(reassemble::Args),
+    /// Find the threshold and release time which best match a recording
+    /// with hand-labelled speech.
+    #[structopt(name = "tune")]
+    Tune(tune::Args),
+}
+
+/// Parse a duration like `"5ms"` or `"1.5s"`, treating a bare number as
+/// seconds.
+fn parse_duration(src: &str) -> Result<Duration, String> {
+    let src = src.trim();
+    let (number, scale) = if let Some(ms) = src.strip_suffix("ms") {
+        (ms, 1e-3)
+    } else if let Some(s) = src.strip_suffix('s') {
+        (s, 1.0)
+    } else {
+        (src, 1.0)
+    };
+
+    let seconds: f64 = number
+        .trim()
+        .parse()
+        .map_err(|e| format!("Unable to parse \"{}\": {}", src, e))?;
+
+    if seconds.is_finite() && seconds >= 0.0 {
+        Ok(Duration::from_secs_f64(seconds * scale))
+    } else {
+        Err(format!("\"{}\" isn't a valid duration", src))
+    }
+}
+
+/// Convert a [`Duration`] to a number of frames at the given sample rate.
+fn to_frames(duration: Duration, sample_rate: u32) -> usize {
+    (duration.as_secs_f64() * sample_rate as f64).round() as usize
+}
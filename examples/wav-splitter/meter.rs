@@ -0,0 +1,162 @@
+//! A live level meter, for setting the threshold by watching it while
+//! speaking.
+//!
+//! Audio is read from stdin as raw 16-bit little-endian PCM, so any capture
+//! tool can feed it (e.g. `arecord -f S16_LE -r 48000 -c 1`).
+
+use noise_gate::{NoiseGate, Sink};
+use std::{
+    error::Error,
+    io::{self, ErrorKind, Read, Write},
+    time::Duration,
+};
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+pub struct Args {
+    #[structopt(
+        short = "t",
+        long = "threshold",
+        help = "The noise threshold",
+        default_value = "300"
+    )]
+    pub noise_threshold: i16,
+    #[structopt(
+        short = "r",
+        long = "release-time",
+        help = "The release time",
+        default_value = "0.25",
+        parse(try_from_str = crate::parse_duration)
+    )]
+    pub release_time: Duration,
+    #[structopt(
+        long = "sample-rate",
+        help = "The input's sample rate",
+        default_value = "48000"
+    )]
+    pub sample_rate: u32,
+    #[structopt(
+        long = "channels",
+        help = "The number of interleaved channels",
+        default_value = "1"
+    )]
+    pub channels: usize,
+}
+
+/// How much audio each update of the meter covers.
+const UPDATE_INTERVAL: Duration = Duration::from_millis(50);
+/// The quietest level shown on the meter.
+const FLOOR_DBFS: f64 = -60.0;
+/// The width of the meter's bar, in characters.
+const METER_WIDTH: usize = 50;
+
+pub fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let channels = args.channels.max(1);
+    let release_frames = crate::to_frames(args.release_time, args.sample_rate);
+    let frames_per_update =
+        crate::to_frames(UPDATE_INTERVAL, args.sample_rate).max(1);
+
+    let mut gate = NoiseGate::new(args.noise_threshold, release_frames);
+    let mut input = io::stdin().lock();
+    let mut stderr = io::stderr();
+    let mut bytes = vec![0; frames_per_update * channels * 2];
+    let mut levels = Vec::with_capacity(frames_per_update);
+
+    loop {
+        match input.read_exact(&mut bytes) {
+            Ok(()) => {},
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+
+        // the gate only cares about each frame's loudest channel
+        levels.clear();
+        levels.extend(bytes.chunks_exact(channels * 2).map(|frame| {
+            let loudest = frame
+                .chunks_exact(2)
+                .map(|b| i16::from_le_bytes([b[0], b[1]]).saturating_abs())
+                .max()
+                .unwrap_or(0);
+            [loudest]
+        }));
+
+        gate.process_frames(&levels, &mut Discard);
+
+        let peak = levels.iter().map(|&[level]| level).max().unwrap_or(0);
+        let line = render(
+            to_dbfs(peak),
+            to_dbfs(args.noise_threshold),
+            gate.is_open(),
+        );
+        write!(stderr, "\r{}", line)?;
+        stderr.flush()?;
+    }
+
+    writeln!(stderr)?;
+    Ok(())
+}
+
+struct Discard;
+
+impl Sink<[i16; 1]> for Discard {
+    fn record(&mut self, _: [i16; 1]) {}
+
+    fn end_of_transmission(&mut self) {}
+}
+
+fn to_dbfs(level: i16) -> f64 {
+    noise_gate::analysis::to_dbfs(f64::from(level) / 32768.0)
+}
+
+/// Draw the meter, e.g. `[#########----|--------] -23.4 dBFS  OPEN `.
+fn render(level_dbfs: f64, threshold_dbfs: f64, open: bool) -> String {
+    let position = |db: f64| {
+        let fraction = ((db - FLOOR_DBFS) / -FLOOR_DBFS).clamp(0.0, 1.0);
+        (fraction * METER_WIDTH as f64).round() as usize
+    };
+    let filled = position(level_dbfs);
+    let marker = position(threshold_dbfs).min(METER_WIDTH - 1);
+
+    let bar: String = (0..METER_WIDTH)
+        .map(|i| match (i == marker, i < filled) {
+            (true, _) => '|',
+            (false, true) => '#',
+            (false, false) => '-',
+        })
+        .collect();
+
+    format!(
+        "[{}] {:>6.1} dBFS  {}",
+        bar,
+        level_dbfs.max(FLOOR_DBFS),
+        if open { "OPEN  " } else { "closed" }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_threshold_is_marked() {
+        let line = render(-30.0, -18.0, true);
+
+        assert_eq!(
+            line,
+            format!(
+                "[{}{}|{}]  -30.0 dBFS  OPEN  ",
+                "#".repeat(25),
+                "-".repeat(10),
+                "-".repeat(14),
+            )
+        );
+    }
+
+    #[test]
+    fn silence_is_clamped_to_the_floor() {
+        let line = render(f64::NEG_INFINITY, -40.0, false);
+
+        assert!(line.starts_with("[----"));
+        assert!(line.ends_with(" -60.0 dBFS  closed"));
+    }
+}
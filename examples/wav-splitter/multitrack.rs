@@ -0,0 +1,338 @@
+//! Splitting several synchronised recordings (e.g. one file per microphone)
+//! at the same points, for `split --align`.
+
+use crate::{
+    naming::ClipNamer,
+    report::{Summary, UnsupportedFormat},
+    split::{self, Sink},
+    wav::WavSample,
+    Settings,
+};
+use dasp::{
+    sample::{Duplex, I24},
+    Frame, Sample,
+};
+use hound::{SampleFormat, WavReader, WavSpec};
+use noise_gate::{
+    align::{Key, Multitrack},
+    frames,
+    sinks::FadeEdges,
+    NoiseGate,
+};
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Parse the argument to `--align`, either `"loudest"` or the position of
+/// the key recording (starting from `1`).
+pub fn parse_key(src: &str) -> Result<Key, String> {
+    if src.eq_ignore_ascii_case("loudest") {
+        return Ok(Key::Loudest);
+    }
+
+    match src.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(Key::Track(n - 1)),
+        _ => Err(format!(
+            "Expected \"loudest\" or the number of an input file, found \
+             \"{}\"",
+            src
+        )),
+    }
+}
+
+/// Split every recording at the same points, using `key` to decide where
+/// the clips are.
+///
+/// The recordings need to be in the same format. Each one is loaded into
+/// memory, and only the release time and fades are applied so the clips
+/// stay sample-aligned.
+pub fn split_aligned(
+    input_files: &[PathBuf],
+    settings: &Settings,
+    key: Key,
+) -> Result<Vec<Summary>, Box<dyn Error>> {
+    if let Key::Track(index) = key {
+        if index >= input_files.len() {
+            return Err(format!(
+                "Can't align to recording {}, there are only {}",
+                index + 1,
+                input_files.len()
+            )
+            .into());
+        }
+    }
+
+    let mut readers = Vec::new();
+    for input in input_files {
+        readers.push(WavReader::open(input)?);
+    }
+    let header = readers[0].spec();
+    if let Some(i) = readers.iter().position(|r| r.spec() != header) {
+        return Err(format!(
+            "\"{}\" isn't in the same format as \"{}\", so they can't be \
+             aligned",
+            input_files[i].display(),
+            input_files[0].display()
+        )
+        .into());
+    }
+
+    let threshold = settings.noise_threshold;
+    let inputs = Inputs {
+        files: input_files,
+        readers,
+        settings,
+        key,
+    };
+
+    match (header.sample_format, header.bits_per_sample) {
+        (SampleFormat::Int, 8) => {
+            inputs.split_samples(threshold.to_sample::<i8>())
+        },
+        (SampleFormat::Int, 16) => inputs.split_samples(threshold),
+        (SampleFormat::Int, 24) => {
+            inputs.split_samples(threshold.to_sample::<I24>())
+        },
+        (SampleFormat::Int, 32) => {
+            inputs.split_samples(threshold.to_sample::<i32>())
+        },
+        (SampleFormat::Float, 32) => {
+            inputs.split_samples(threshold.to_sample::<f32>())
+        },
+        (format, bits) => Err(UnsupportedFormat(format!(
+            "{}-bit {} audio isn't supported",
+            bits,
+            match format {
+                SampleFormat::Int => "integer",
+                SampleFormat::Float => "floating point",
+            }
+        ))
+        .into()),
+    }
+}
+
+struct Inputs<'a> {
+    files: &'a [PathBuf],
+    readers: Vec<WavReader<BufReader<File>>>,
+    settings: &'a Settings,
+    key: Key,
+}
+
+impl Inputs<'_> {
+    fn split_samples<S>(
+        self,
+        threshold: S,
+    ) -> Result<Vec<Summary>, Box<dyn Error>>
+    where
+        S: WavSample + Duplex<f64>,
+    {
+        let header = self.readers[0].spec();
+
+        macro_rules! dispatch {
+            ($($channels:literal),*) => {
+                match header.channels {
+                    $(
+                        $channels => {
+                            self.split_frames::<[S; $channels]>(threshold)
+                        },
+                    )*
+                    other => Err(UnsupportedFormat(format!(
+                        "{}-channel audio isn't supported",
+                        other
+                    ))
+                    .into()),
+                }
+            };
+        }
+
+        dispatch!(1, 2, 3, 4, 5, 6, 7, 8)
+    }
+
+    fn split_frames<F>(
+        self,
+        threshold: F::Sample,
+    ) -> Result<Vec<Summary>, Box<dyn Error>>
+    where
+        F: Frame,
+        F::Sample: Duplex<f64> + WavSample,
+    {
+        let header = self.readers[0].spec();
+        let settings = self.settings;
+        let release_time =
+            crate::to_frames(settings.release_time, header.sample_rate);
+        let fade_length =
+            crate::to_frames(settings.fade_edges, header.sample_rate);
+
+        let mut tracks = Vec::new();
+        for reader in self.readers {
+            tracks.push(read_frames::<F>(reader)?);
+        }
+        let track_refs: Vec<&[F]> = tracks.iter().map(|t| &t[..]).collect();
+
+        let gate = NoiseGate::new(threshold, release_time)
+            .with_detection(settings.detector);
+        let ranges = Multitrack::new(gate, self.key).segments(&track_refs);
+        let total_frames = track_refs.iter().map(|t| t.len()).min();
+        let total_frames = total_frames.unwrap_or(0);
+        log!(
+            Info,
+            "found aligned clips",
+            recordings = tracks.len(),
+            clips = ranges.len(),
+        );
+
+        fs::create_dir_all(&settings.output_dir)?;
+        let mut summaries = Vec::new();
+
+        for (input, track) in self.files.iter().zip(&track_refs) {
+            let sink = clip_sink(input, settings, header, total_frames)?;
+            let mut sink = FadeEdges::new(sink, fade_length);
+
+            for range in &ranges {
+                sink.inner_mut().clip_started(range.start);
+                noise_gate::Sink::record_frames(
+                    &mut sink,
+                    &track[range.clone()],
+                );
+                noise_gate::Sink::<F>::end_of_transmission(&mut sink);
+
+                if let Some(e) = sink.inner_mut().take_error() {
+                    return Err(e.into());
+                }
+            }
+
+            summaries.push(Summary::new(
+                header.sample_rate,
+                total_frames,
+                header.channels,
+                sink.into_inner().into_clips(),
+                Vec::new(),
+            ));
+        }
+
+        Ok(summaries)
+    }
+}
+
+/// Create the [`Sink`] for one of the recordings, naming its clips after
+/// the file so they don't overwrite each other.
+fn clip_sink(
+    input: &Path,
+    settings: &Settings,
+    header: WavSpec,
+    total_frames: usize,
+) -> Result<Sink, Box<dyn Error>> {
+    let prefix = split::clip_prefix(input, &settings.prefix)?;
+    let duration = Duration::from_secs_f64(
+        total_frames as f64 / header.sample_rate as f64,
+    );
+    let start_time = settings.start_time.resolve(input, duration)?;
+    let namer =
+        ClipNamer::new(prefix, settings.naming, start_time, header.sample_rate);
+
+    let mut sink = Sink::new(settings.output_dir.clone(), namer, header);
+    if let Some(url) = &settings.webhook {
+        sink.set_webhook(url)?;
+    }
+
+    Ok(sink)
+}
+
+fn read_frames<F>(
+    reader: WavReader<BufReader<File>>,
+) -> Result<Vec<F>, Box<dyn Error>>
+where
+    F: Frame,
+    F::Sample: WavSample,
+{
+    let samples = reader
+        .into_samples::<<F::Sample as WavSample>::Raw>()
+        .map(|sample| sample.map(F::Sample::from_raw));
+    let frames = frames::try_from_samples(samples).collect::<Result<_, _>>()?;
+
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::naming::{Naming, StartTime};
+    use hound::WavWriter;
+
+    #[test]
+    fn recordings_are_split_at_the_same_points() {
+        let dir = std::env::temp_dir()
+            .join(format!("wav-splitter-align-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 1000,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let write = |name: &str, loud: &[std::ops::Range<usize>]| {
+            let path = dir.join(name);
+            let mut writer = WavWriter::create(&path, spec).unwrap();
+            for i in 0..1000 {
+                let loud = loud.iter().any(|r| r.contains(&i));
+                writer
+                    .write_sample(if loud { 5000_i16 } else { 10 })
+                    .unwrap();
+            }
+            writer.finalize().unwrap();
+            path
+        };
+        let inputs = [
+            write("vocals.wav", &[100..200]),
+            write("drums.wav", &[500..550]),
+        ];
+        let settings = Settings {
+            noise_threshold: 1000,
+            release_time: Duration::from_millis(10),
+            fade_edges: Duration::from_millis(0),
+            trim_threshold: None,
+            max_clip_length: None,
+            max_clip_size: None,
+            quota: None,
+            watchdog: None,
+            merge_gaps: None,
+            roger_beep: None,
+            output_dir: dir.join("clips"),
+            prefix: String::from("clip"),
+            bwf: false,
+            detector: Default::default(),
+            naming: Naming::Numbered,
+            start_time: StartTime::At(0.0),
+            webhook: None,
+        };
+
+        let loudest = split_aligned(&inputs, &settings, Key::Loudest).unwrap();
+        let vocals = split_aligned(&inputs, &settings, Key::Track(0)).unwrap();
+        let bounds = |summaries: &[Summary]| -> Vec<Vec<(usize, usize)>> {
+            summaries
+                .iter()
+                .map(|s| {
+                    s.clips.iter().map(|c| (c.start_frame, c.frames)).collect()
+                })
+                .collect()
+        };
+
+        assert_eq!(bounds(&loudest), vec![vec![(100, 111), (500, 61)]; 2]);
+        assert_eq!(bounds(&vocals), vec![vec![(100, 111)]; 2]);
+        assert!(split_aligned(&inputs, &settings, Key::Track(2)).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parse_keys() {
+        assert_eq!(parse_key("loudest"), Ok(Key::Loudest));
+        assert_eq!(parse_key("2"), Ok(Key::Track(1)));
+        assert!(parse_key("0").is_err());
+        assert!(parse_key("vocals").is_err());
+    }
+}
@@ -0,0 +1,224 @@
+//! Deciding what each clip should be called.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    path::Path,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// How clips should be named.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Naming {
+    /// `clip_0.wav`, `clip_1.wav`, ...
+    Numbered,
+    /// Use the time each clip started, e.g. `clip_2024-05-03T14-23-07.wav`.
+    Timestamp,
+}
+
+impl FromStr for Naming {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "number" => Ok(Naming::Numbered),
+            "timestamp" => Ok(Naming::Timestamp),
+            other => Err(format!(
+                "Unknown naming scheme \"{}\", expected \"number\" or \
+                 \"timestamp\"",
+                other
+            )),
+        }
+    }
+}
+
+/// When the recording started, used when naming clips by timestamp.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum StartTime {
+    /// Work it out from when the file was last modified (i.e. when the
+    /// recording finished) and how long it is.
+    File,
+    /// Use the current time, for live recordings.
+    Now,
+    /// An explicit timestamp, in seconds since the Unix epoch.
+    At(f64),
+}
+
+impl StartTime {
+    /// Get the start time as seconds since the Unix epoch.
+    pub fn resolve(
+        self,
+        input_file: &Path,
+        duration: Duration,
+    ) -> Result<f64, Box<dyn Error>> {
+        match self {
+            StartTime::File => {
+                let modified = fs::metadata(input_file)?.modified()?;
+                let started =
+                    modified.checked_sub(duration).unwrap_or(modified);
+                Ok(unix_seconds(started))
+            },
+            StartTime::Now => Ok(unix_seconds(SystemTime::now())),
+            StartTime::At(seconds) => Ok(seconds),
+        }
+    }
+}
+
+impl FromStr for StartTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "file" => Ok(StartTime::File),
+            "now" => Ok(StartTime::Now),
+            other => parse_timestamp(other).map(StartTime::At),
+        }
+    }
+}
+
+/// Generates the name for each clip.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipNamer {
+    prefix: String,
+    naming: Naming,
+    start_time: f64,
+    sample_rate: u32,
+    clips: usize,
+    names_used: HashMap<String, usize>,
+}
+
+impl ClipNamer {
+    pub fn new(
+        prefix: String,
+        naming: Naming,
+        start_time: f64,
+        sample_rate: u32,
+    ) -> Self {
+        ClipNamer {
+            prefix,
+            naming,
+            start_time,
+            sample_rate,
+            clips: 0,
+            names_used: HashMap::new(),
+        }
+    }
+
+    /// Get the file name for a clip starting at a particular frame.
+    pub fn next_name(&mut self, start_frame: usize) -> String {
+        let number = self.clips;
+        self.clips += 1;
+
+        match self.naming {
+            Naming::Numbered => format!("{}{}.wav", self.prefix, number),
+            Naming::Timestamp => {
+                let time = self.start_time
+                    + start_frame as f64 / self.sample_rate as f64;
+                let stem = format!("{}{}", self.prefix, format_timestamp(time));
+
+                // two clips may start within the same second
+                let count = self.names_used.entry(stem.clone()).or_insert(0);
+                *count += 1;
+
+                if *count == 1 {
+                    format!("{}.wav", stem)
+                } else {
+                    format!("{}_{}.wav", stem, count)
+                }
+            },
+        }
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> f64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => elapsed.as_secs_f64(),
+        Err(e) => -e.duration().as_secs_f64(),
+    }
+}
+
+/// Format a Unix timestamp as `YYYY-MM-DDTHH-MM-SS` (using `-` instead of
+/// `:` so it can be used in a file name on every OS).
+pub fn format_timestamp(unix_seconds: f64) -> String {
+    let seconds = unix_seconds.floor() as i64;
+    let days = seconds.div_euclid(86_400);
+    let time_of_day = seconds.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}-{:02}-{:02}",
+        year,
+        month,
+        day,
+        time_of_day / 3600,
+        time_of_day / 60 % 60,
+        time_of_day % 60
+    )
+}
+
+/// Parse a `YYYY-MM-DDTHH:MM:SS` timestamp (`-` may be used in place of `:`
+/// and a trailing `Z` is ignored), returning seconds since the Unix epoch.
+pub fn parse_timestamp(src: &str) -> Result<f64, String> {
+    let err = || {
+        format!(
+            "Expected a timestamp like \"2024-05-03T14:23:07\", found \"{}\"",
+            src
+        )
+    };
+
+    let trimmed = src.trim().trim_end_matches('Z');
+    let (date, time) = trimmed.split_once('T').ok_or_else(err)?;
+
+    let date: Vec<i64> = date
+        .split('-')
+        .map(|part| part.parse().map_err(|_| err()))
+        .collect::<Result<_, _>>()?;
+    let time: Vec<f64> = time
+        .split([':', '-'])
+        .map(|part| part.parse().map_err(|_| err()))
+        .collect::<Result<_, _>>()?;
+
+    match (date.as_slice(), time.as_slice()) {
+        (&[year, month, day], &[hours, minutes, seconds])
+            if (1..=12).contains(&month) && (1..=31).contains(&day) =>
+        {
+            let days = days_from_civil(year, month, day);
+            Ok(days as f64 * 86_400.0
+                + hours * 3600.0
+                + minutes * 60.0
+                + seconds)
+        },
+        _ => Err(err()),
+    }
+}
+
+// These two conversions use Howard Hinnant's algorithms for converting
+// between days since the epoch and the proleptic Gregorian calendar. See
+// http://howardhinnant.github.io/date_algorithms.html for the derivation.
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146_097 + doe - 719_468
+}
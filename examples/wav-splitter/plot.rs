@@ -0,0 +1,205 @@
+//! Rendering a recording's waveform as an SVG, with the parts the gate would
+//! keep highlighted.
+
+use crate::{preview, Options};
+use noise_gate::NoiseGate;
+use std::{error::Error, fmt::Write as _, fs, ops::Range, path::PathBuf};
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+pub struct Args {
+    #[structopt(help = "The WAV file to plot")]
+    pub input_file: PathBuf,
+    #[structopt(
+        long = "svg",
+        help = "Where to save the SVG [default: the input file, with an \
+                \".svg\" extension]"
+    )]
+    pub output: Option<PathBuf>,
+    #[structopt(
+        long = "width",
+        help = "The image's width in pixels",
+        default_value = "1200"
+    )]
+    pub width: usize,
+    #[structopt(
+        long = "height",
+        help = "The image's height in pixels",
+        default_value = "240"
+    )]
+    pub height: usize,
+    #[structopt(flatten)]
+    pub options: Options,
+}
+
+pub fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let settings = args.options.resolve()?;
+    let (sample_rate, levels) = preview::read_levels(&args.input_file)?;
+
+    let release_frames = crate::to_frames(settings.release_time, sample_rate);
+    let mut gate = NoiseGate::new(settings.noise_threshold, release_frames)
+        .with_detection(settings.detector);
+    let segments: Vec<_> =
+        gate.segments(&levels).map(|(range, _)| range).collect();
+
+    let plot = Plot {
+        width: args.width.max(1),
+        height: args.height.max(2),
+        sample_rate,
+    };
+    let svg = plot.render(&levels, &segments, settings.noise_threshold);
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.input_file.with_extension("svg"));
+    fs::write(&output, svg)?;
+    log!(Info, "saved plot", path = output.display());
+
+    Ok(())
+}
+
+struct Plot {
+    width: usize,
+    height: usize,
+    sample_rate: u32,
+}
+
+impl Plot {
+    fn render(
+        &self,
+        levels: &[[i16; 1]],
+        segments: &[Range<usize>],
+        threshold: i16,
+    ) -> String {
+        let mut svg = String::new();
+        let frames_per_pixel =
+            (levels.len() as f64 / self.width as f64).max(1.0);
+        let x = |frame: usize| frame as f64 / frames_per_pixel;
+        let middle = self.height as f64 / 2.0;
+        let y = |level: i16| f64::from(level) / 32768.0 * middle;
+
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+            w = self.width,
+            h = self.height,
+        );
+        let _ = writeln!(
+            svg,
+            r##"<rect width="100%" height="100%" fill="#ffffff"/>"##
+        );
+
+        // the parts of the recording the gate would keep
+        for segment in segments {
+            let _ = writeln!(
+                svg,
+                r##"<rect x="{:.1}" y="0" width="{:.1}" height="{}" fill="#4caf50" fill-opacity="0.25"/>"##,
+                x(segment.start),
+                (x(segment.end) - x(segment.start)).max(1.0),
+                self.height,
+            );
+        }
+
+        // the waveform's envelope, one vertical line per pixel
+        let mut path = String::new();
+        for column in 0..self.width {
+            let start = (column as f64 * frames_per_pixel) as usize;
+            let end = ((column + 1) as f64 * frames_per_pixel) as usize;
+            let loudest = levels
+                .get(start..end.min(levels.len()))
+                .unwrap_or_default()
+                .iter()
+                .map(|&[level]| level)
+                .max()
+                .unwrap_or(0);
+            let height = y(loudest).max(0.5);
+
+            let _ = write!(
+                path,
+                "M{} {:.1}V{:.1}",
+                column,
+                middle - height,
+                middle + height
+            );
+        }
+        let _ = writeln!(
+            svg,
+            r##"<path d="{}" stroke="#1e5aa8" stroke-width="1"/>"##,
+            path
+        );
+
+        // the threshold, above and below the centre line
+        for &offset in &[-y(threshold), y(threshold)] {
+            let _ = writeln!(
+                svg,
+                r##"<line x1="0" x2="{}" y1="{y:.1}" y2="{y:.1}" stroke="#d32f2f" stroke-dasharray="4 3"/>"##,
+                self.width,
+                y = middle + offset,
+            );
+        }
+
+        self.write_time_axis(&mut svg, levels.len(), frames_per_pixel);
+        svg.push_str("</svg>\n");
+
+        svg
+    }
+
+    /// Label the time axis roughly every 100 pixels, using a "nice" number
+    /// of seconds.
+    fn write_time_axis(
+        &self,
+        svg: &mut String,
+        total_frames: usize,
+        frames_per_pixel: f64,
+    ) {
+        let duration = total_frames as f64 / f64::from(self.sample_rate);
+        let rough_step = duration * 100.0 / self.width as f64;
+        let step = [0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 600.0]
+            .iter()
+            .copied()
+            .find(|&step| step >= rough_step)
+            .unwrap_or(3600.0);
+
+        let precision = if step < 1.0 { 1 } else { 0 };
+
+        for i in 1.. {
+            let seconds = step * i as f64;
+            if seconds >= duration {
+                break;
+            }
+
+            let x = seconds * f64::from(self.sample_rate) / frames_per_pixel;
+            let _ = writeln!(
+                svg,
+                r##"<text x="{:.1}" y="{}" font-size="10" font-family="sans-serif" fill="#555555">{:.*}s</text>"##,
+                x + 2.0,
+                self.height - 4,
+                precision,
+                seconds
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_regions_are_highlighted() {
+        let plot = Plot {
+            width: 100,
+            height: 50,
+            sample_rate: 100,
+        };
+        let levels = vec![[1000_i16]; 1000];
+
+        let svg = plot.render(&levels, &[100..300, 500..510], 500);
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains(r#"<rect x="10.0" y="0" width="20.0""#));
+        assert!(svg.contains(r#"<rect x="50.0" y="0" width="1.0""#));
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+}
@@ -0,0 +1,279 @@
+//! An interactive mode for finding good parameters for a recording.
+//!
+//! This reads commands from stdin one line at a time, so it works in any
+//! terminal (or over a pipe) without needing raw mode.
+
+use crate::{report::UnsupportedFormat, wav::WavSample};
+use dasp::{sample::I24, Sample};
+use hound::{SampleFormat, WavReader};
+use noise_gate::{NoiseGate, Sink};
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+pub struct Args {
+    #[structopt(help = "The WAV file to preview")]
+    pub input_file: PathBuf,
+    #[structopt(
+        short = "t",
+        long = "threshold",
+        help = "The initial noise threshold",
+        default_value = "100"
+    )]
+    pub noise_threshold: i16,
+    #[structopt(
+        short = "r",
+        long = "release-time",
+        help = "The initial release time",
+        default_value = "0.25",
+        parse(try_from_str = crate::parse_duration)
+    )]
+    pub release_time: Duration,
+}
+
+const HELP: &str = "\
+Commands:
+  t <threshold>   set the threshold
+  t+ / t-         nudge the threshold up or down by 1 dB
+  r <duration>    set the release time (e.g. \"300ms\")
+  r+ / r-         nudge the release time up or down by 50ms
+  h               show this help
+  q               quit, printing the final parameters";
+
+pub fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let (sample_rate, levels) = read_levels(&args.input_file)?;
+    let histogram = Histogram::new(&levels);
+
+    let mut threshold = args.noise_threshold.max(1);
+    let mut release_time = args.release_time;
+
+    println!("{}", HELP);
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    loop {
+        let release_frames = crate::to_frames(release_time, sample_rate);
+        let stats = simulate(&levels, threshold, release_frames);
+
+        println!();
+        histogram.print(threshold);
+        println!(
+            "threshold={} ({:.1} dBFS) release={}ms => {} clips, {:.1}% active",
+            threshold,
+            to_dbfs(threshold),
+            release_time.as_millis(),
+            stats.clips,
+            stats.duty_cycle(levels.len()) * 100.0
+        );
+        print!("> ");
+        io::stdout().flush()?;
+
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        let mut words = line.split_whitespace();
+
+        match (words.next(), words.next()) {
+            (Some("t+"), None) => threshold = nudge(threshold, 1.0),
+            (Some("t-"), None) => threshold = nudge(threshold, -1.0),
+            (Some("t"), Some(value)) => match value.parse::<i16>() {
+                Ok(value) if value > 0 => threshold = value,
+                _ => println!("Invalid threshold, \"{}\"", value),
+            },
+            (Some("r+"), None) => release_time += Duration::from_millis(50),
+            (Some("r-"), None) => {
+                release_time = release_time
+                    .checked_sub(Duration::from_millis(50))
+                    .unwrap_or_default();
+            },
+            (Some("r"), Some(value)) => match crate::parse_duration(value) {
+                Ok(value) => release_time = value,
+                Err(e) => println!("{}", e),
+            },
+            (Some("h"), None) => println!("{}", HELP),
+            (Some("q"), None) => break,
+            (None, _) => {},
+            _ => println!("Unknown command. Type \"h\" for help."),
+        }
+    }
+
+    println!(
+        "--threshold {} --release-time {}ms",
+        threshold,
+        release_time.as_millis()
+    );
+
+    Ok(())
+}
+
+/// Read the recording, reducing each frame to its loudest channel on a
+/// 16-bit scale.
+///
+/// The gate only closes when every channel is below the threshold, so
+/// running it over these levels gives the same result as the full recording.
+pub fn read_levels(path: &Path) -> Result<(u32, Levels), Box<dyn Error>> {
+    let reader = WavReader::open(path)?;
+    let spec = reader.spec();
+
+    let levels = match (spec.sample_format, spec.bits_per_sample) {
+        (SampleFormat::Int, 8) => levels(reader, |s: i8| s.to_sample()),
+        (SampleFormat::Int, 16) => levels(reader, |s: i16| s),
+        (SampleFormat::Int, 24) => levels(reader, |s: I24| s.to_sample()),
+        (SampleFormat::Int, 32) => levels(reader, |s: i32| s.to_sample()),
+        (SampleFormat::Float, 32) => levels(reader, |s: f32| s.to_sample()),
+        (_, bits) => Err(UnsupportedFormat(format!(
+            "{}-bit audio isn't supported",
+            bits
+        ))
+        .into()),
+    }?;
+
+    Ok((spec.sample_rate, levels))
+}
+
+/// The level of each frame, as a mono 16-bit signal.
+pub type Levels = Vec<[i16; 1]>;
+
+fn levels<S, C>(
+    reader: WavReader<BufReader<File>>,
+    to_i16: C,
+) -> Result<Levels, Box<dyn Error>>
+where
+    S: WavSample,
+    C: Fn(S) -> i16,
+{
+    let channels = usize::from(reader.spec().channels.max(1));
+    let mut levels = Vec::with_capacity(reader.len() as usize / channels);
+    let mut loudest = 0_i16;
+
+    for (i, sample) in reader.into_samples::<S::Raw>().enumerate() {
+        loudest = loudest.max(to_i16(S::from_raw(sample?)).saturating_abs());
+
+        if (i + 1) % channels == 0 {
+            levels.push([loudest]);
+            loudest = 0;
+        }
+    }
+
+    Ok(levels)
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    clips: usize,
+    active_frames: usize,
+}
+
+impl Stats {
+    fn duty_cycle(&self, total_frames: usize) -> f64 {
+        self.active_frames as f64 / total_frames.max(1) as f64
+    }
+}
+
+impl Sink<[i16; 1]> for Stats {
+    fn record(&mut self, _: [i16; 1]) { self.active_frames += 1; }
+
+    fn end_of_transmission(&mut self) { self.clips += 1; }
+}
+
+fn simulate(levels: &[[i16; 1]], threshold: i16, release: usize) -> Stats {
+    let mut gate = NoiseGate::new(threshold, release);
+    let mut stats = Stats::default();
+    gate.process_frames(levels, &mut stats);
+
+    if gate.is_open() {
+        stats.clips += 1;
+    }
+
+    stats
+}
+
+fn to_dbfs(level: i16) -> f64 {
+    20.0 * (f64::from(level.max(1)) / 32768.0).log10()
+}
+
+fn nudge(threshold: i16, db: f64) -> i16 {
+    let scaled = f64::from(threshold) * 10_f64.powf(db / 20.0);
+    // make sure we always move by at least one step
+    let nudged = if db > 0.0 {
+        scaled.ceil().max(f64::from(threshold) + 1.0)
+    } else {
+        scaled.floor().min(f64::from(threshold) - 1.0)
+    };
+
+    nudged.clamp(1.0, f64::from(i16::MAX)) as i16
+}
+
+/// A histogram of frame levels, in 6 dB buckets.
+struct Histogram {
+    buckets: [usize; BUCKETS],
+    total: usize,
+}
+
+const BUCKETS: usize = 16;
+const BUCKET_WIDTH_DB: f64 = 6.0;
+
+impl Histogram {
+    fn new(levels: &[[i16; 1]]) -> Self {
+        let mut buckets = [0; BUCKETS];
+
+        for &[level] in levels {
+            buckets[Histogram::bucket(level)] += 1;
+        }
+
+        Histogram {
+            buckets,
+            total: levels.len(),
+        }
+    }
+
+    fn bucket(level: i16) -> usize {
+        let db = if level == 0 {
+            f64::NEG_INFINITY
+        } else {
+            to_dbfs(level)
+        };
+        let index = (-db / BUCKET_WIDTH_DB).floor();
+
+        if index.is_finite() {
+            (index.max(0.0) as usize).min(BUCKETS - 1)
+        } else {
+            BUCKETS - 1
+        }
+    }
+
+    fn print(&self, threshold: i16) {
+        let most = self.buckets.iter().copied().max().unwrap_or(0).max(1);
+        let threshold_bucket = Histogram::bucket(threshold);
+
+        for (i, &count) in self.buckets.iter().enumerate() {
+            let bar = "#".repeat(count * 40 / most);
+            let marker = if i == threshold_bucket {
+                "<- threshold"
+            } else {
+                ""
+            };
+            let label = if i == BUCKETS - 1 {
+                format!("< -{:.0}", i as f64 * BUCKET_WIDTH_DB)
+            } else {
+                format!("-{:.0}", (i + 1) as f64 * BUCKET_WIDTH_DB)
+            };
+
+            println!(
+                "{:>6} dBFS |{:<40}| {:>5.1}% {}",
+                label,
+                bar,
+                count as f64 * 100.0 / self.total.max(1) as f64,
+                marker
+            );
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Keeping the clips in the output directory under a disk quota by deleting
+//! the oldest ones.
+
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Tracks how much space the clips in a directory use, deleting the oldest
+/// clips whenever the total goes over the quota.
+///
+/// Every WAV file in the directory counts towards the quota (including ones
+/// left over from previous runs), so the output directory shouldn't be used
+/// for anything else.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quota {
+    max_bytes: u64,
+    total_bytes: u64,
+    /// Every clip we know about, oldest first.
+    clips: VecDeque<(PathBuf, u64)>,
+}
+
+impl Quota {
+    /// Start tracking the clips which are already in `dir`.
+    pub fn scan(dir: &Path, max_bytes: u64) -> Result<Self, Box<dyn Error>> {
+        let mut existing = Vec::new();
+
+        for path in crate::watch::wav_files(dir)? {
+            let meta = fs::metadata(&path)?;
+            let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            existing.push((modified, path, meta.len()));
+        }
+
+        existing.sort();
+
+        let mut quota = Quota {
+            max_bytes,
+            total_bytes: 0,
+            clips: VecDeque::new(),
+        };
+        for (_, path, bytes) in existing {
+            quota.track(path, bytes);
+        }
+
+        Ok(quota)
+    }
+
+    /// The total size of every clip being tracked.
+    pub fn total_bytes(&self) -> u64 { self.total_bytes }
+
+    /// Record that a new clip was written, deleting older clips until we're
+    /// back under the quota.
+    ///
+    /// The newest clip is never deleted, even if it's bigger than the quota
+    /// on its own.
+    pub fn add(&mut self, path: PathBuf) -> io::Result<Vec<PathBuf>> {
+        let bytes = fs::metadata(&path)?.len();
+        // the clip may have overwritten one we already know about (e.g. when
+        // resuming, or re-running with numbered names)
+        self.untrack(&path);
+        self.track(path, bytes);
+
+        let mut evicted = Vec::new();
+
+        while self.total_bytes > self.max_bytes && self.clips.len() > 1 {
+            let (oldest, bytes) = self.clips.pop_front().unwrap();
+
+            match fs::remove_file(&oldest) {
+                Ok(()) => {},
+                // someone else already deleted it
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+                Err(e) => {
+                    self.clips.push_front((oldest, bytes));
+                    return Err(e);
+                },
+            }
+
+            self.total_bytes -= bytes;
+            log!(
+                Info,
+                "deleted clip to stay under quota",
+                path = oldest.display()
+            );
+            evicted.push(oldest);
+        }
+
+        Ok(evicted)
+    }
+
+    fn track(&mut self, path: PathBuf, bytes: u64) {
+        self.total_bytes += bytes;
+        self.clips.push_back((path, bytes));
+    }
+
+    fn untrack(&mut self, path: &Path) {
+        if let Some(index) = self.clips.iter().position(|(p, _)| p == path) {
+            if let Some((_, bytes)) = self.clips.remove(index) {
+                self.total_bytes -= bytes;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_oldest_clips_are_deleted_first() {
+        let dir = std::env::temp_dir()
+            .join(format!("wav-splitter-quota-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("old.wav"), [0; 40]).unwrap();
+        // not a clip, so it doesn't count
+        fs::write(dir.join("notes.txt"), [0; 1000]).unwrap();
+
+        let mut quota = Quota::scan(&dir, 100).unwrap();
+        assert_eq!(quota.total_bytes(), 40);
+
+        let write = |name: &str, bytes: usize| {
+            let path = dir.join(name);
+            fs::write(&path, vec![0; bytes]).unwrap();
+            path
+        };
+
+        assert!(quota.add(write("a.wav", 50)).unwrap().is_empty());
+        assert_eq!(
+            quota.add(write("b.wav", 30)).unwrap(),
+            vec![dir.join("old.wav")]
+        );
+        assert_eq!(quota.total_bytes(), 80);
+        assert!(!dir.join("old.wav").exists());
+
+        // a huge clip pushes everything else out, but is kept itself
+        let evicted = quota.add(write("c.wav", 500)).unwrap();
+        assert_eq!(evicted, vec![dir.join("a.wav"), dir.join("b.wav")]);
+        assert!(dir.join("c.wav").exists());
+        assert!(dir.join("notes.txt").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn overwritten_clips_are_only_counted_once() {
+        let dir = std::env::temp_dir()
+            .join(format!("wav-splitter-overwrite-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("clip0.wav"), [0; 40]).unwrap();
+        fs::write(dir.join("clip1.wav"), [0; 40]).unwrap();
+
+        let mut quota = Quota::scan(&dir, 100).unwrap();
+        assert_eq!(quota.total_bytes(), 80);
+
+        // a second run writes over the first clip with something bigger
+        fs::write(dir.join("clip0.wav"), [0; 50]).unwrap();
+        let evicted = quota.add(dir.join("clip0.wav")).unwrap();
+
+        assert!(evicted.is_empty());
+        assert_eq!(quota.total_bytes(), 90);
+        assert!(dir.join("clip0.wav").exists());
+
+        // the stale entry is gone, so the other clip is the oldest
+        fs::write(dir.join("clip2.wav"), [0; 20]).unwrap();
+        let evicted = quota.add(dir.join("clip2.wav")).unwrap();
+        assert_eq!(evicted, vec![dir.join("clip1.wav")]);
+        assert!(dir.join("clip0.wav").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
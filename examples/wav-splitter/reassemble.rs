@@ -0,0 +1,421 @@
+//! Rebuild a recording from its clips, using the report written by
+//! `split --json`.
+
+use crate::{
+    report::{OutputFormat, Status, UnsupportedFormat},
+    wav::WavSample,
+};
+use dasp::{sample::I24, Frame};
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use serde::Deserialize;
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+pub struct Args {
+    #[structopt(
+        help = "The JSON report from \"split --json\", or a CSV file with \
+                \"path\" and \"start_frame\" (or \"start\", in seconds) \
+                columns"
+    )]
+    pub manifest: PathBuf,
+    #[structopt(
+        short = "o",
+        long = "output",
+        help = "Where to write the reassembled recording"
+    )]
+    pub output: PathBuf,
+    #[structopt(
+        long = "input",
+        help = "Which recording to reassemble, when the report covers several"
+    )]
+    pub input: Option<PathBuf>,
+    #[structopt(
+        long = "total-length",
+        help = "The original recording's length, for CSV manifests \
+                (defaults to the end of the last clip)",
+        parse(try_from_str = crate::parse_duration)
+    )]
+    pub total_length: Option<std::time::Duration>,
+}
+
+pub fn run(
+    args: &Args,
+    format: OutputFormat,
+) -> Result<Status, Box<dyn Error>> {
+    let manifest = Manifest::load(
+        &args.manifest,
+        args.input.as_deref(),
+        args.total_length,
+    )?;
+
+    if manifest.clips.is_empty() {
+        return Err("The manifest doesn't contain any clips".into());
+    }
+
+    let spec = WavReader::open(&manifest.clips[0].path)?.spec();
+    let frames = write_recording(&manifest, spec, &args.output)?;
+    let duration = frames as f64 / spec.sample_rate as f64;
+
+    match format {
+        OutputFormat::Text => println!(
+            "Wrote {} clips to \"{}\" ({:.2}s)",
+            manifest.clips.len(),
+            args.output.display(),
+            duration
+        ),
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::json!({
+                "output": args.output,
+                "clips": manifest.clips.len(),
+                "total_frames": frames,
+                "total_duration": duration,
+            })
+        ),
+    }
+
+    Ok(Status::Success)
+}
+
+/// The clips which make up a recording.
+#[derive(Debug, Clone, PartialEq)]
+struct Manifest {
+    clips: Vec<ManifestClip>,
+    /// The length of the original recording in frames, if known.
+    total_frames: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ManifestClip {
+    path: PathBuf,
+    start: Start,
+    /// How many frames at the end of the file are a marker (i.e. a roger
+    /// beep) rather than part of the recording.
+    marker_frames: usize,
+}
+
+/// Where a clip started, in whichever units the manifest used.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Start {
+    Frame(usize),
+    Seconds(f64),
+}
+
+impl Start {
+    fn to_frames(self, sample_rate: u32) -> usize {
+        match self {
+            Start::Frame(frame) => frame,
+            Start::Seconds(seconds) => {
+                (seconds * sample_rate as f64).round() as usize
+            },
+        }
+    }
+}
+
+impl Manifest {
+    fn load(
+        path: &Path,
+        input: Option<&Path>,
+        total_length: Option<std::time::Duration>,
+    ) -> Result<Manifest, Box<dyn Error>> {
+        let text = fs::read_to_string(path)?;
+        let is_csv = path.extension().is_some_and(|ext| ext == "csv");
+
+        let mut manifest = if is_csv {
+            parse_csv(&text)?
+        } else {
+            parse_report(&text, input)?
+        };
+
+        // clip paths are usually relative to wherever the splitter was run,
+        // but fall back to looking next to the manifest
+        let base = path.parent().unwrap_or_else(|| Path::new("."));
+        for clip in &mut manifest.clips {
+            if !clip.path.exists() && base.join(&clip.path).exists() {
+                clip.path = base.join(&clip.path);
+            }
+        }
+
+        if let (Some(length), Some(clip)) =
+            (total_length, manifest.clips.first())
+        {
+            let sample_rate = WavReader::open(&clip.path)?.spec().sample_rate;
+            manifest.total_frames = Some(crate::to_frames(length, sample_rate));
+        }
+
+        Ok(manifest)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Report {
+    files: Vec<FileReport>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileReport {
+    input: PathBuf,
+    summary: Option<Summary>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Summary {
+    clips: Vec<Clip>,
+    total_frames: Option<usize>,
+    total_duration: f64,
+    sample_rate: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Clip {
+    path: PathBuf,
+    start: f64,
+    start_frame: Option<usize>,
+    #[serde(default)]
+    marker_frames: usize,
+}
+
+/// Parse the JSON report written by `split --json`.
+fn parse_report(
+    text: &str,
+    input: Option<&Path>,
+) -> Result<Manifest, Box<dyn Error>> {
+    let report: Report = serde_json::from_str(text)?;
+
+    let file = match (input, report.files.as_slice()) {
+        (None, [file]) => file,
+        (None, files) => {
+            let inputs: Vec<_> = files
+                .iter()
+                .map(|f| f.input.display().to_string())
+                .collect();
+            return Err(format!(
+                "The report covers several recordings, use --input to pick \
+                 one of {}",
+                inputs.join(", ")
+            )
+            .into());
+        },
+        (Some(input), files) => files
+            .iter()
+            .find(|f| {
+                f.input == input
+                    || f.input.file_name() == Some(input.as_os_str())
+            })
+            .ok_or_else(|| {
+                format!("The report doesn't mention \"{}\"", input.display())
+            })?,
+    };
+
+    let summary = file.summary.as_ref().ok_or_else(|| {
+        format!("\"{}\" wasn't split successfully", file.input.display())
+    })?;
+
+    let clips = summary
+        .clips
+        .iter()
+        .map(|clip| ManifestClip {
+            path: clip.path.clone(),
+            start: match clip.start_frame {
+                Some(frame) => Start::Frame(frame),
+                None => Start::Seconds(clip.start),
+            },
+            marker_frames: clip.marker_frames,
+        })
+        .collect();
+
+    // older reports only have the duration in seconds
+    let total_frames = summary.total_frames.or_else(|| {
+        summary
+            .sample_rate
+            .map(|rate| (summary.total_duration * rate as f64).round() as usize)
+    });
+
+    Ok(Manifest {
+        clips,
+        total_frames,
+    })
+}
+
+/// Parse a CSV manifest with a header row. Quoting isn't supported, so paths
+/// can't contain commas.
+fn parse_csv(text: &str) -> Result<Manifest, Box<dyn Error>> {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let header: Vec<&str> = lines
+        .next()
+        .ok_or("The manifest is empty")?
+        .split(',')
+        .map(str::trim)
+        .collect();
+    let column = |name: &str| header.iter().position(|&h| h == name);
+
+    let path_column = column("path").ok_or("No \"path\" column")?;
+    let (start_column, in_frames) =
+        match (column("start_frame"), column("start")) {
+            (Some(c), _) => (c, true),
+            (None, Some(c)) => (c, false),
+            (None, None) => {
+                return Err("No \"start_frame\" or \"start\" column".into())
+            },
+        };
+
+    let mut clips = Vec::new();
+
+    for (i, line) in lines.enumerate() {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let field = |column: usize| {
+            fields.get(column).copied().ok_or_else(|| {
+                format!("Row {} is missing column {}", i + 1, column + 1)
+            })
+        };
+
+        let start = field(start_column)?;
+        let start = if in_frames {
+            Start::Frame(start.parse()?)
+        } else {
+            Start::Seconds(start.parse()?)
+        };
+
+        clips.push(ManifestClip {
+            path: PathBuf::from(field(path_column)?),
+            start,
+            marker_frames: 0,
+        });
+    }
+
+    Ok(Manifest {
+        clips,
+        total_frames: None,
+    })
+}
+
+/// Write the reassembled recording, returning its length in frames.
+fn write_recording(
+    manifest: &Manifest,
+    spec: WavSpec,
+    output: &Path,
+) -> Result<usize, Box<dyn Error>> {
+    macro_rules! dispatch {
+        ($sample:ty; $($channels:literal),*) => {
+            match spec.channels {
+                $(
+                    $channels => write_frames::<[$sample; $channels]>(
+                        manifest, spec, output,
+                    ),
+                )*
+                other => Err(UnsupportedFormat(format!(
+                    "{}-channel audio isn't supported",
+                    other
+                ))
+                .into()),
+            }
+        };
+    }
+
+    match (spec.sample_format, spec.bits_per_sample) {
+        (SampleFormat::Int, 8) => dispatch!(i8; 1, 2, 3, 4, 5, 6, 7, 8),
+        (SampleFormat::Int, 16) => dispatch!(i16; 1, 2, 3, 4, 5, 6, 7, 8),
+        (SampleFormat::Int, 24) => dispatch!(I24; 1, 2, 3, 4, 5, 6, 7, 8),
+        (SampleFormat::Int, 32) => dispatch!(i32; 1, 2, 3, 4, 5, 6, 7, 8),
+        (SampleFormat::Float, 32) => dispatch!(f32; 1, 2, 3, 4, 5, 6, 7, 8),
+        (format, bits) => Err(UnsupportedFormat(format!(
+            "{}-bit {:?} audio isn't supported",
+            bits, format
+        ))
+        .into()),
+    }
+}
+
+fn write_frames<F>(
+    manifest: &Manifest,
+    spec: WavSpec,
+    output: &Path,
+) -> Result<usize, Box<dyn Error>>
+where
+    F: Frame,
+    F::Sample: WavSample,
+{
+    let mut clips = Vec::new();
+
+    for clip in &manifest.clips {
+        let reader = WavReader::open(&clip.path)?;
+        if reader.spec() != spec {
+            return Err(format!(
+                "\"{}\" doesn't have the same format as the other clips",
+                clip.path.display()
+            )
+            .into());
+        }
+
+        let samples: Vec<F::Sample> = reader
+            .into_samples()
+            .map(|sample| sample.map(F::Sample::from_raw))
+            .collect::<Result<_, _>>()?;
+        let mut frames: Vec<F> = samples
+            .chunks_exact(F::CHANNELS)
+            .map(|s| F::from_fn(|channel| s[channel]))
+            .collect();
+        // the marker was never in the original recording
+        frames.truncate(frames.len().saturating_sub(clip.marker_frames));
+
+        clips.push((clip.start.to_frames(spec.sample_rate), frames));
+    }
+
+    clips.sort_by_key(|(start, _)| *start);
+
+    let end_of_last_clip = clips
+        .iter()
+        .map(|(start, frames)| start + frames.len())
+        .max()
+        .unwrap_or(0);
+    let total_frames = manifest.total_frames.unwrap_or(end_of_last_clip);
+
+    let writer = WavWriter::new(BufWriter::new(File::create(output)?), spec)?;
+    let mut sink = Sink {
+        writer: Some(writer),
+        error: None,
+    };
+
+    noise_gate::timeline::reassemble(clips, total_frames, &mut sink);
+
+    match sink.error {
+        Some(e) => Err(e.into()),
+        None => Ok(total_frames),
+    }
+}
+
+/// Writes frames to a WAV file, remembering the first error.
+struct Sink {
+    writer: Option<WavWriter<BufWriter<File>>>,
+    error: Option<hound::Error>,
+}
+
+impl<F> noise_gate::Sink<F> for Sink
+where
+    F: Frame,
+    F::Sample: WavSample,
+{
+    fn record(&mut self, frame: F) {
+        if let Some(writer) = &mut self.writer {
+            for sample in frame.channels() {
+                if let Err(e) = writer.write_sample(sample.into_raw()) {
+                    self.error.get_or_insert(e);
+                }
+            }
+        }
+    }
+
+    fn end_of_transmission(&mut self) {
+        if let Some(writer) = self.writer.take() {
+            if let Err(e) = writer.finalize() {
+                self.error.get_or_insert(e);
+            }
+        }
+    }
+}
@@ -0,0 +1,335 @@
+use noise_gate::analysis::{self, Artifact};
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io,
+    path::PathBuf,
+    str::FromStr,
+};
+
+/// Information about a single clip written to disk.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Clip {
+    pub path: PathBuf,
+    /// The frame the clip started at in the original recording.
+    pub start_frame: usize,
+    /// The clip's length in frames, not counting the marker.
+    pub frames: usize,
+    /// How many frames of marker (i.e. a roger beep) were appended to the
+    /// end of the file.
+    #[serde(default)]
+    pub marker_frames: usize,
+    /// When the clip started in the original recording, in seconds.
+    pub start: f64,
+    /// The clip's length in seconds.
+    pub duration: f64,
+}
+
+impl Clip {
+    pub fn new(path: PathBuf) -> Self {
+        Clip {
+            path,
+            start_frame: 0,
+            frames: 0,
+            marker_frames: 0,
+            start: 0.0,
+            duration: 0.0,
+        }
+    }
+}
+
+/// A problem with the recording (e.g. clipping) which may have affected the
+/// clips.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Warning {
+    pub kind: ArtifactKind,
+    pub start_frame: usize,
+    pub frames: usize,
+    /// When the problem started, in seconds.
+    pub start: f64,
+    /// How long it lasted, in seconds.
+    pub duration: f64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArtifactKind {
+    Clipping,
+    Dropout,
+}
+
+impl From<analysis::ArtifactKind> for ArtifactKind {
+    fn from(kind: analysis::ArtifactKind) -> Self {
+        match kind {
+            analysis::ArtifactKind::Clipping => ArtifactKind::Clipping,
+            analysis::ArtifactKind::Dropout => ArtifactKind::Dropout,
+        }
+    }
+}
+
+impl From<ArtifactKind> for analysis::ArtifactKind {
+    fn from(kind: ArtifactKind) -> Self {
+        match kind {
+            ArtifactKind::Clipping => analysis::ArtifactKind::Clipping,
+            ArtifactKind::Dropout => analysis::ArtifactKind::Dropout,
+        }
+    }
+}
+
+/// A summary of the clips found in a recording.
+///
+/// All durations are in seconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub clips: Vec<Clip>,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub total_frames: usize,
+    pub total_duration: f64,
+    pub active_duration: f64,
+    pub silent_duration: f64,
+    /// The fraction of the recording where the gate was open.
+    pub duty_cycle: f64,
+    pub average_clip_length: f64,
+    /// Any clipping or dropouts found in the recording.
+    pub warnings: Vec<Warning>,
+}
+
+impl Summary {
+    pub fn new(
+        sample_rate: u32,
+        total_frames: usize,
+        channels: u16,
+        mut clips: Vec<Clip>,
+        artifacts: Vec<Artifact>,
+    ) -> Self {
+        let seconds = |frames: usize| frames as f64 / sample_rate as f64;
+
+        for clip in &mut clips {
+            clip.start = seconds(clip.start_frame);
+            clip.duration = seconds(clip.frames);
+        }
+
+        let warnings = artifacts
+            .into_iter()
+            .map(|artifact| Warning {
+                kind: artifact.kind.into(),
+                start_frame: artifact.frames.start,
+                frames: artifact.frames.len(),
+                start: seconds(artifact.frames.start),
+                duration: seconds(artifact.frames.len()),
+            })
+            .collect();
+
+        let active_frames: usize = clips.iter().map(|c| c.frames).sum();
+        let silent_frames = total_frames.saturating_sub(active_frames);
+
+        Summary {
+            sample_rate,
+            channels,
+            total_frames,
+            total_duration: seconds(total_frames),
+            active_duration: seconds(active_frames),
+            silent_duration: seconds(silent_frames),
+            duty_cycle: ratio(active_frames, total_frames),
+            average_clip_length: seconds(active_frames)
+                / clips.len().max(1) as f64,
+            clips,
+            warnings,
+        }
+    }
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Clips:               {}", self.clips.len())?;
+        writeln!(f, "Total duration:      {:.2}s", self.total_duration)?;
+        writeln!(f, "Active duration:     {:.2}s", self.active_duration)?;
+        writeln!(f, "Silent duration:     {:.2}s", self.silent_duration)?;
+        writeln!(f, "Duty cycle:          {:.1}%", self.duty_cycle * 100.0)?;
+        writeln!(f, "Average clip length: {:.2}s", self.average_clip_length)?;
+
+        for warning in &self.warnings {
+            let kind = match warning.kind {
+                ArtifactKind::Clipping => "Clipping",
+                ArtifactKind::Dropout => "Dropout",
+            };
+            writeln!(
+                f,
+                "Warning: {} at {:.2}s ({:.3}s)",
+                kind, warning.start, warning.duration
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// How results should be printed to stdout.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            other => Err(format!("Unknown output format, \"{}\"", other)),
+        }
+    }
+}
+
+/// The overall outcome of running a command, which determines the exit code.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    Success,
+    Error,
+    NoClipsFound,
+    DecodeError,
+    PartialFailure,
+}
+
+impl Status {
+    /// Classify an error.
+    pub fn from_error(e: &(dyn Error + 'static)) -> Status {
+        if e.is::<UnsupportedFormat>() {
+            return Status::DecodeError;
+        }
+
+        match e.downcast_ref::<hound::Error>() {
+            Some(hound::Error::IoError(io))
+                if io.kind() != io::ErrorKind::UnexpectedEof =>
+            {
+                Status::Error
+            },
+            Some(_) => Status::DecodeError,
+            None => Status::Error,
+        }
+    }
+
+    /// The documented exit code for this [`Status`].
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Status::Success => 0,
+            Status::Error => 1,
+            Status::NoClipsFound => 2,
+            Status::DecodeError => 3,
+            Status::PartialFailure => 4,
+        }
+    }
+}
+
+/// The outcome of splitting a single file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileReport {
+    pub input: PathBuf,
+    pub status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<Summary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl FileReport {
+    pub fn new(
+        input: PathBuf,
+        outcome: Result<Summary, Box<dyn Error>>,
+    ) -> FileReport {
+        match outcome {
+            Ok(summary) => FileReport {
+                input,
+                status: if summary.clips.is_empty() {
+                    Status::NoClipsFound
+                } else {
+                    Status::Success
+                },
+                summary: Some(summary),
+                error: None,
+            },
+            Err(e) => FileReport {
+                input,
+                status: Status::from_error(&*e),
+                summary: None,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+
+    pub fn print(&self, format: OutputFormat) {
+        match format {
+            OutputFormat::Text => match (&self.summary, &self.error) {
+                (Some(summary), _) => print!("{}", summary),
+                (_, Some(error)) => eprintln!(
+                    "Error: Unable to split \"{}\": {}",
+                    self.input.display(),
+                    error
+                ),
+                (None, None) => {},
+            },
+            OutputFormat::Json => match serde_json::to_string(self) {
+                Ok(json) => println!("{}", json),
+                Err(e) => eprintln!("Error: {}", e),
+            },
+        }
+    }
+}
+
+/// The results from splitting several files in one go.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BatchReport {
+    pub status: Status,
+    pub exit_code: u8,
+    pub files: Vec<FileReport>,
+}
+
+impl BatchReport {
+    pub fn new(files: Vec<FileReport>) -> BatchReport {
+        let failed = |f: &&FileReport| f.error.is_some();
+        let failures = files.iter().filter(failed).count();
+
+        let status = if failures == 0 {
+            if files.iter().all(|f| f.status == Status::NoClipsFound) {
+                Status::NoClipsFound
+            } else {
+                Status::Success
+            }
+        } else if failures < files.len() {
+            Status::PartialFailure
+        } else if files.iter().all(|f| f.status == Status::DecodeError) {
+            Status::DecodeError
+        } else {
+            Status::Error
+        };
+
+        BatchReport {
+            status,
+            exit_code: status.exit_code(),
+            files,
+        }
+    }
+}
+
+/// An error indicating the recording uses a format we can't handle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsupportedFormat(pub String);
+
+impl Display for UnsupportedFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result { f.write_str(&self.0) }
+}
+
+impl Error for UnsupportedFormat {}
@@ -0,0 +1,303 @@
+//! Remembering how far `split --resume` got, so an interrupted batch job can
+//! pick up where it stopped.
+//!
+//! Progress is kept in a small JSON journal in the output directory. Files
+//! which were split successfully are skipped (re-using their report), and a
+//! file which was interrupted part-way through restarts from its last
+//! checkpoint.
+//!
+//! A checkpoint is only taken while the gate is closed and every clip so far
+//! has been written to disk, so the gate and sinks can be started from
+//! scratch at that frame and produce exactly the clips an uninterrupted run
+//! would have. Clip names are handed out in the same order as before, so a
+//! clip which was half-written when the job stopped is overwritten rather
+//! than duplicated.
+
+use crate::{
+    report::{ArtifactKind, Clip, FileReport},
+    Settings,
+};
+use noise_gate::analysis::Artifact;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The journal's file name, inside the output directory.
+const FILE_NAME: &str = ".wav-splitter-progress.json";
+
+/// Progress through a batch of recordings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Journal {
+    path: PathBuf,
+    saved: Saved,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+struct Saved {
+    /// The settings the job was started with, so we don't mix clips made
+    /// with different settings.
+    settings: String,
+    files: BTreeMap<PathBuf, FileProgress>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+enum FileProgress {
+    Finished { report: FileReport },
+    InProgress { checkpoint: Checkpoint },
+}
+
+/// A point part-way through a recording where splitting can restart.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The frame to restart from.
+    pub frame: usize,
+    /// The clips written before `frame`.
+    pub clips: Vec<Clip>,
+    /// Any artifacts found before `frame`.
+    pub artifacts: Vec<SavedArtifact>,
+}
+
+/// An [`Artifact`] in a form which can be saved to the journal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedArtifact {
+    pub kind: ArtifactKind,
+    pub start_frame: usize,
+    pub frames: usize,
+}
+
+impl From<&Artifact> for SavedArtifact {
+    fn from(artifact: &Artifact) -> Self {
+        SavedArtifact {
+            kind: artifact.kind.into(),
+            start_frame: artifact.frames.start,
+            frames: artifact.frames.len(),
+        }
+    }
+}
+
+impl From<&SavedArtifact> for Artifact {
+    fn from(saved: &SavedArtifact) -> Self {
+        Artifact {
+            kind: saved.kind.into(),
+            frames: saved.start_frame..saved.start_frame + saved.frames,
+        }
+    }
+}
+
+impl Journal {
+    /// Load the journal from the output directory, starting a new one if
+    /// this is the first run.
+    pub fn open(settings: &Settings) -> Result<Journal, Box<dyn Error>> {
+        let path = settings.output_dir.join(FILE_NAME);
+        let fingerprint = format!("{:?}", settings);
+
+        let saved = if path.exists() {
+            let src = fs::read_to_string(&path)?;
+            let saved: Saved = serde_json::from_str(&src).map_err(|e| {
+                format!("Unable to load \"{}\": {}", path.display(), e)
+            })?;
+
+            if saved.settings != fingerprint {
+                return Err(format!(
+                    "The settings have changed since the job was \
+                     interrupted. Delete \"{}\" to start again from scratch",
+                    path.display()
+                )
+                .into());
+            }
+
+            log!(
+                Info,
+                "resuming an interrupted job",
+                finished = saved
+                    .files
+                    .values()
+                    .filter(|f| matches!(f, FileProgress::Finished { .. }))
+                    .count(),
+            );
+            saved
+        } else {
+            Saved {
+                settings: fingerprint,
+                files: BTreeMap::new(),
+            }
+        };
+
+        Ok(Journal { path, saved })
+    }
+
+    /// The report for a recording which has already been split.
+    pub fn finished(&self, input: &Path) -> Option<&FileReport> {
+        match self.saved.files.get(input)? {
+            FileProgress::Finished { report } => Some(report),
+            FileProgress::InProgress { .. } => None,
+        }
+    }
+
+    /// Where to restart a recording which was interrupted.
+    pub fn checkpoint(&self, input: &Path) -> Option<&Checkpoint> {
+        match self.saved.files.get(input)? {
+            FileProgress::InProgress { checkpoint } => Some(checkpoint),
+            FileProgress::Finished { .. } => None,
+        }
+    }
+
+    /// Save a checkpoint for a recording which is being split.
+    pub fn save_checkpoint(
+        &mut self,
+        input: &Path,
+        checkpoint: Checkpoint,
+    ) -> Result<(), Box<dyn Error>> {
+        self.saved.files.insert(
+            input.to_path_buf(),
+            FileProgress::InProgress { checkpoint },
+        );
+        self.save()
+    }
+
+    /// Record that a recording has been split, unless splitting failed (in
+    /// which case it'll be retried next time).
+    pub fn finish(
+        &mut self,
+        report: &FileReport,
+    ) -> Result<(), Box<dyn Error>> {
+        if report.error.is_some() {
+            return Ok(());
+        }
+
+        self.saved.files.insert(
+            report.input.clone(),
+            FileProgress::Finished {
+                report: report.clone(),
+            },
+        );
+        self.save()
+    }
+
+    /// Delete the journal once the whole job is done, so the next job
+    /// starts from scratch.
+    pub fn remove(self) -> Result<(), Box<dyn Error>> {
+        if self.path.exists() {
+            fs::remove_file(&self.path)?;
+        }
+
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), Box<dyn Error>> {
+        // write to a temporary file first so being interrupted part-way
+        // through saving doesn't corrupt the journal
+        let temp = self.path.with_extension("json.tmp");
+        fs::write(&temp, serde_json::to_vec_pretty(&self.saved)?)?;
+        fs::rename(&temp, &self.path)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        naming::{Naming, StartTime},
+        split,
+    };
+    use hound::{SampleFormat, WavSpec, WavWriter};
+    use std::time::Duration;
+
+    fn write_recording(path: &Path, frames: usize) {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 1000,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(path, spec).unwrap();
+
+        for i in 0..frames {
+            let loud = [1000, 5000, 10_000, 15_000]
+                .iter()
+                .any(|&start| (start..start + 50).contains(&i));
+            writer.write_sample(if loud { 5000_i16 } else { 0 }).unwrap();
+        }
+
+        writer.finalize().unwrap();
+    }
+
+    fn settings(output_dir: PathBuf) -> Settings {
+        Settings {
+            noise_threshold: 1000,
+            release_time: Duration::from_millis(0),
+            fade_edges: Duration::from_millis(0),
+            trim_threshold: None,
+            max_clip_length: None,
+            max_clip_size: None,
+            quota: None,
+            watchdog: None,
+            merge_gaps: None,
+            roger_beep: None,
+            output_dir,
+            prefix: String::from("clip"),
+            bwf: false,
+            detector: Default::default(),
+            naming: Naming::Numbered,
+            start_time: StartTime::At(0.0),
+            webhook: None,
+        }
+    }
+
+    #[test]
+    fn resuming_gives_the_same_clips_as_an_uninterrupted_run() {
+        let dir = std::env::temp_dir()
+            .join(format!("wav-splitter-resume-{}", std::process::id()));
+        let (fresh, resumed) = (dir.join("fresh"), dir.join("resumed"));
+        fs::create_dir_all(&fresh).unwrap();
+        fs::create_dir_all(&resumed).unwrap();
+        let input = dir.join("input.wav");
+        let names = |clips: &[Clip]| -> Vec<_> {
+            clips
+                .iter()
+                .map(|c| (c.path.file_name().unwrap().to_owned(), c.frames))
+                .collect()
+        };
+
+        write_recording(&input, 20_000);
+        let expected =
+            split::split_file(&input, &settings(fresh), "clip").unwrap();
+
+        // pretend the job was killed after the first 8192 frames
+        let settings = settings(resumed.clone());
+        let mut journal = Journal::open(&settings).unwrap();
+        write_recording(&input, 8192);
+        split::split_file_resumable(
+            &input,
+            &settings,
+            "clip",
+            Some(&mut journal),
+        )
+        .unwrap();
+        assert_eq!(journal.checkpoint(&input).unwrap().frame, 8192);
+
+        let mut journal = Journal::open(&settings).unwrap();
+        write_recording(&input, 20_000);
+        let got = split::split_file_resumable(
+            &input,
+            &settings,
+            "clip",
+            Some(&mut journal),
+        )
+        .unwrap();
+
+        assert_eq!(names(&got.clips), names(&expected.clips));
+        assert_eq!(got.total_frames, expected.total_frames);
+        let written = crate::watch::wav_files(&resumed).unwrap();
+        assert_eq!(written.len(), expected.clips.len());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,424 @@
+//! A small HTTP server for auditioning the clips a recording would be split
+//! into, without writing anything to disk.
+//!
+//! Only `GET` requests are supported, and connections are handled one at a
+//! time, which is plenty for a single browser on the same machine.
+
+use crate::{preview, Options};
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use noise_gate::NoiseGate;
+use std::{
+    error::Error,
+    fmt::Write as _,
+    io::{BufRead, BufReader, Cursor, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    ops::Range,
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+pub struct Args {
+    #[structopt(help = "The WAV file to preview")]
+    pub input_file: PathBuf,
+    #[structopt(
+        long = "port",
+        help = "The port to listen on (only on localhost)",
+        default_value = "8000"
+    )]
+    pub port: u16,
+    #[structopt(flatten)]
+    pub options: Options,
+}
+
+pub fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let preview = Preview::load(args)?;
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, args.port))?;
+    log!(
+        Info,
+        "serving clips",
+        url = format!("http://{}/", listener.local_addr()?),
+        clips = preview.segments.len(),
+    );
+
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| preview.handle(stream));
+        if let Err(e) = result {
+            log!(Warn, "unable to handle a request", error = e);
+        }
+    }
+
+    Ok(())
+}
+
+/// A recording, and the segments the gate would keep.
+struct Preview {
+    name: String,
+    spec: WavSpec,
+    samples: Samples,
+    segments: Vec<Range<usize>>,
+}
+
+/// Every sample in the recording, in a type `hound` can write back out
+/// without changing the format.
+enum Samples {
+    Int(Vec<i32>),
+    Float(Vec<f32>),
+}
+
+impl Preview {
+    fn load(args: &Args) -> Result<Self, Box<dyn Error>> {
+        let settings = args.options.resolve()?;
+        let (sample_rate, levels) = preview::read_levels(&args.input_file)?;
+        let release_frames =
+            crate::to_frames(settings.release_time, sample_rate);
+        let mut gate =
+            NoiseGate::new(settings.noise_threshold, release_frames)
+                .with_detection(settings.detector);
+        let segments = gate.segments(&levels).map(|(range, _)| range).collect();
+
+        let reader = WavReader::open(&args.input_file)?;
+        let spec = reader.spec();
+        let samples = match spec.sample_format {
+            SampleFormat::Int => {
+                Samples::Int(reader.into_samples().collect::<Result<_, _>>()?)
+            },
+            SampleFormat::Float => {
+                Samples::Float(reader.into_samples().collect::<Result<_, _>>()?)
+            },
+        };
+        let name = args
+            .input_file
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        Ok(Preview {
+            name,
+            spec,
+            samples,
+            segments,
+        })
+    }
+
+    fn handle(&self, stream: TcpStream) -> std::io::Result<()> {
+        let mut reader = BufReader::new(&stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+
+        let mut range = None;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("range") {
+                    range = Some(value.trim().to_string());
+                }
+            }
+        }
+
+        let mut words = request_line.split_whitespace();
+        let response = match (words.next(), words.next()) {
+            (Some("GET"), Some(path)) => self.respond(path, range.as_deref()),
+            _ => Response::error(405, "Method Not Allowed"),
+        };
+        log!(
+            Debug,
+            "handled a request",
+            request = request_line.trim(),
+            status = response.status,
+        );
+
+        response.write_to(&mut &stream)
+    }
+
+    fn respond(&self, path: &str, range: Option<&str>) -> Response {
+        let path = path.split('?').next().unwrap_or(path);
+
+        match path {
+            "/" | "/index.html" => {
+                Response::ok("text/html; charset=utf-8", self.index_html())
+            },
+            "/segments.json" => {
+                Response::ok("application/json", self.index_json())
+            },
+            _ => match clip_number(path) {
+                Some(n) if n < self.segments.len() => match self.encode(n) {
+                    Ok(wav) => Response::ok("audio/wav", wav).with_range(range),
+                    Err(e) => Response::error(500, &e.to_string()),
+                },
+                _ => Response::error(404, "Not Found"),
+            },
+        }
+    }
+
+    fn index_json(&self) -> Vec<u8> {
+        let seconds =
+            |frame: usize| frame as f64 / self.spec.sample_rate as f64;
+        let segments: Vec<_> = self
+            .segments
+            .iter()
+            .enumerate()
+            .map(|(i, segment)| {
+                serde_json::json!({
+                    "index": i,
+                    "url": format!("/segments/{}.wav", i),
+                    "start_frame": segment.start,
+                    "frames": segment.len(),
+                    "start": seconds(segment.start),
+                    "duration": seconds(segment.len()),
+                })
+            })
+            .collect();
+
+        serde_json::json!({
+            "recording": self.name,
+            "sample_rate": self.spec.sample_rate,
+            "channels": self.spec.channels,
+            "segments": segments,
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    fn index_html(&self) -> Vec<u8> {
+        let mut html = String::new();
+        let seconds =
+            |frame: usize| frame as f64 / self.spec.sample_rate as f64;
+        let name = escape(&self.name);
+
+        let _ = writeln!(
+            html,
+            "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n<body>",
+            name
+        );
+        let _ = writeln!(
+            html,
+            "<h1>{}</h1>\n<p>{} clips (<a href=\"/segments.json\">JSON</a>)</p>\n<ol start=\"0\">",
+            name,
+            self.segments.len()
+        );
+        for (i, segment) in self.segments.iter().enumerate() {
+            let _ = writeln!(
+                html,
+                "<li>{:.3}s to {:.3}s <audio controls preload=\"none\" src=\"/segments/{}.wav\"></audio></li>",
+                seconds(segment.start),
+                seconds(segment.end),
+                i
+            );
+        }
+        html.push_str("</ol>\n</body>\n</html>\n");
+
+        html.into_bytes()
+    }
+
+    /// Encode one of the segments as a WAV file, in the recording's format.
+    fn encode(&self, n: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+        let channels = usize::from(self.spec.channels);
+        let segment = &self.segments[n];
+        let samples = segment.start * channels..segment.end * channels;
+
+        let mut wav = Vec::new();
+        let mut writer = WavWriter::new(Cursor::new(&mut wav), self.spec)?;
+        match &self.samples {
+            Samples::Int(all) => {
+                for &sample in &all[samples] {
+                    writer.write_sample(sample)?;
+                }
+            },
+            Samples::Float(all) => {
+                for &sample in &all[samples] {
+                    writer.write_sample(sample)?;
+                }
+            },
+        }
+        writer.finalize()?;
+
+        Ok(wav)
+    }
+}
+
+#[derive(Debug)]
+struct Response {
+    status: u16,
+    reason: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn ok(content_type: &str, body: Vec<u8>) -> Self {
+        Response {
+            status: 200,
+            reason: "OK",
+            headers: vec![("Content-Type", content_type.to_string())],
+            body,
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Response {
+            status,
+            reason: match status {
+                404 => "Not Found",
+                405 => "Method Not Allowed",
+                416 => "Range Not Satisfiable",
+                _ => "Internal Server Error",
+            },
+            headers: vec![("Content-Type", String::from("text/plain"))],
+            body: message.as_bytes().to_vec(),
+        }
+    }
+
+    /// Only send part of the body if a `Range` header asked for it, so the
+    /// browser can seek through long clips.
+    fn with_range(mut self, range: Option<&str>) -> Self {
+        let len = self.body.len();
+        self.headers.push(("Accept-Ranges", String::from("bytes")));
+
+        match range.map(|range| parse_range(range, len)) {
+            None => self,
+            Some(Some(range)) => {
+                self.status = 206;
+                self.reason = "Partial Content";
+                self.headers.push((
+                    "Content-Range",
+                    format!("bytes {}-{}/{}", range.start, range.end - 1, len),
+                ));
+                self.body = self.body[range].to_vec();
+                self
+            },
+            Some(None) => {
+                let mut response =
+                    Response::error(416, "Range Not Satisfiable");
+                response
+                    .headers
+                    .push(("Content-Range", format!("bytes */{}", len)));
+                response
+            },
+        }
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        write!(writer, "HTTP/1.1 {} {}\r\n", self.status, self.reason)?;
+        for (name, value) in &self.headers {
+            write!(writer, "{}: {}\r\n", name, value)?;
+        }
+        write!(
+            writer,
+            "Content-Length: {}\r\nConnection: close\r\n\r\n",
+            self.body.len()
+        )?;
+        writer.write_all(&self.body)?;
+        writer.flush()
+    }
+}
+
+/// Parse a `Range` header like `"bytes=100-199"`, `"bytes=100-"`, or
+/// `"bytes=-100"` into the requested part of a `len` byte body.
+///
+/// Only a single range is supported.
+fn parse_range(header: &str, len: usize) -> Option<Range<usize>> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+
+    let range = if start.is_empty() {
+        // the last few bytes
+        let suffix: usize = end.parse().ok()?;
+        len.saturating_sub(suffix)..len
+    } else {
+        let start: usize = start.parse().ok()?;
+        let end = if end.is_empty() {
+            len
+        } else {
+            end.parse::<usize>().ok()?.saturating_add(1).min(len)
+        };
+        start..end
+    };
+
+    if range.start < range.end {
+        Some(range)
+    } else {
+        None
+    }
+}
+
+/// Get `n` from a path like `"/segments/n.wav"`.
+fn clip_number(path: &str) -> Option<usize> {
+    path.strip_prefix("/segments/")?
+        .strip_suffix(".wav")?
+        .parse()
+        .ok()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preview() -> Preview {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 1000,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+
+        Preview {
+            name: String::from("<test>.wav"),
+            spec,
+            samples: Samples::Int((0..200).collect()),
+            segments: vec![10..20, 50..100],
+        }
+    }
+
+    #[test]
+    fn the_index_lists_every_segment() {
+        let preview = preview();
+
+        let json = preview.respond("/segments.json", None);
+        let json: serde_json::Value =
+            serde_json::from_slice(&json.body).unwrap();
+        assert_eq!(json["segments"].as_array().unwrap().len(), 2);
+        assert_eq!(json["segments"][1]["start_frame"], 50);
+        assert_eq!(json["segments"][1]["duration"], 0.05);
+
+        let html = preview.respond("/", None);
+        let html = String::from_utf8(html.body).unwrap();
+        assert!(html.contains("&lt;test&gt;.wav"));
+        assert!(html.contains("src=\"/segments/1.wav\""));
+    }
+
+    #[test]
+    fn segments_can_be_fetched_in_parts() {
+        let preview = preview();
+
+        let whole = preview.respond("/segments/0.wav", None);
+        assert_eq!(whole.status, 200);
+        let reader = WavReader::new(&whole.body[..]).unwrap();
+        let samples: Vec<i16> =
+            reader.into_samples().collect::<Result<_, _>>().unwrap();
+        assert_eq!(samples, (20..40).collect::<Vec<_>>());
+
+        let part = preview.respond("/segments/0.wav", Some("bytes=4-7"));
+        assert_eq!(part.status, 206);
+        assert_eq!(part.body, &whole.body[4..8]);
+        let end = preview.respond("/segments/0.wav", Some("bytes=-4"));
+        assert_eq!(end.body, &whole.body[whole.body.len() - 4..]);
+        let past_the_end =
+            preview.respond("/segments/0.wav", Some("bytes=1000-"));
+        assert_eq!(past_the_end.status, 416);
+
+        assert_eq!(preview.respond("/segments/2.wav", None).status, 404);
+        assert_eq!(preview.respond("/nope", None).status, 404);
+    }
+}
@@ -0,0 +1,864 @@
+use crate::{
+    bwf,
+    dataset::{self, ManifestFormat},
+    multitrack,
+    naming::ClipNamer,
+    quota::Quota,
+    report::{
+        BatchReport, Clip, FileReport, OutputFormat, Status, Summary,
+        UnsupportedFormat,
+    },
+    resume::{Checkpoint, Journal, SavedArtifact},
+    wav::WavSample,
+    Options, Settings,
+};
+use dasp::{
+    sample::{Duplex, I24},
+    Frame, Sample,
+};
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use noise_gate::{
+    align::Key,
+    analysis::{Artifact, ArtifactDetector},
+    frames,
+    sinks::{
+        FadeEdges, MergeGaps, RogerBeep, RotateFiles, TrimSilence, Watchdog,
+    },
+    NoiseGate,
+};
+
+#[cfg(feature = "webhook")]
+use noise_gate::{
+    upload::RetryPolicy,
+    webhook::{Notification, Webhook},
+};
+#[cfg(feature = "webhook")]
+use std::time::SystemTime;
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fs::{self, File},
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+pub struct Args {
+    #[structopt(help = "The WAV files to read", required = true)]
+    pub input_files: Vec<PathBuf>,
+    #[structopt(long = "json", help = "Write a summary report to a JSON file")]
+    pub json: Option<PathBuf>,
+    #[structopt(
+        long = "manifest",
+        help = "Write a dataset manifest to the output directory",
+        possible_values = &["kaldi", "csv", "jsonl"]
+    )]
+    pub manifest: Option<ManifestFormat>,
+    #[structopt(
+        long = "align",
+        help = "Treat the inputs as synchronised recordings of the same \
+                event and split them all at the same points, listening to \
+                either the \"loudest\" recording or a particular one (e.g. \
+                \"1\" for the first)",
+        parse(try_from_str = crate::multitrack::parse_key)
+    )]
+    pub align: Option<Key>,
+    #[structopt(
+        long = "resume",
+        help = "Keep track of progress in the output directory, so an \
+                interrupted job can carry on where it stopped"
+    )]
+    pub resume: bool,
+    #[structopt(flatten)]
+    pub options: Options,
+}
+
+pub fn run(
+    args: &Args,
+    format: OutputFormat,
+) -> Result<Status, Box<dyn Error>> {
+    let settings = args.options.resolve()?;
+    let batch = args.input_files.len() > 1;
+    let mut files = Vec::new();
+    let mut journal = if args.resume {
+        if args.align.is_some() {
+            return Err("--resume can't be used with --align".into());
+        }
+        fs::create_dir_all(&settings.output_dir)?;
+        Some(Journal::open(&settings)?)
+    } else {
+        None
+    };
+    let mut aligned = match args.align {
+        Some(key) => {
+            multitrack::split_aligned(&args.input_files, &settings, key)?
+        },
+        None => Vec::new(),
+    }
+    .into_iter();
+
+    for input in &args.input_files {
+        let finished = journal.as_ref().and_then(|j| j.finished(input));
+        if let Some(report) = finished {
+            log!(
+                Info,
+                "skipping a recording which was already split",
+                path = input.display(),
+            );
+            files.push(report.clone());
+            continue;
+        }
+
+        let outcome = if args.align.is_some() {
+            aligned.next().ok_or_else(|| "missing aligned clips".into())
+        } else if batch {
+            // make sure clips from different recordings don't overwrite
+            // each other
+            clip_prefix(input, &settings.prefix).and_then(|prefix| {
+                split_file_resumable(
+                    input,
+                    &settings,
+                    &prefix,
+                    journal.as_mut(),
+                )
+            })
+        } else {
+            split_file_resumable(
+                input,
+                &settings,
+                &settings.prefix,
+                journal.as_mut(),
+            )
+        };
+
+        let report = FileReport::new(input.clone(), outcome);
+        if let Some(journal) = journal.as_mut() {
+            journal.finish(&report)?;
+        }
+        if format == OutputFormat::Text {
+            if batch {
+                println!("{}:", input.display());
+            }
+            report.print(format);
+        }
+        files.push(report);
+    }
+
+    let report = BatchReport::new(files);
+
+    if let Some(journal) = journal {
+        // keep the journal around if anything failed, so the next run only
+        // retries the failures
+        if report.files.iter().all(|f| f.error.is_none()) {
+            journal.remove()?;
+        }
+    }
+
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    }
+
+    if let Some(path) = &args.json {
+        let f = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(f, &report)?;
+    }
+
+    if let Some(manifest) = args.manifest {
+        let utterances = dataset::utterances(&report.files);
+        dataset::write(manifest, &settings.output_dir, &utterances)?;
+    }
+
+    Ok(report.status)
+}
+
+/// Get the prefix to use for clips from a particular recording when
+/// processing several recordings at once.
+pub fn clip_prefix(
+    input_file: &Path,
+    prefix: &str,
+) -> Result<String, Box<dyn Error>> {
+    let stem = input_file
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or("The file name isn't valid UTF-8")?;
+
+    Ok(format!("{}_{}", stem, prefix))
+}
+
+/// Split a WAV file into clips, writing them to the output directory with
+/// the provided prefix.
+pub fn split_file(
+    input_file: &Path,
+    settings: &Settings,
+    prefix: &str,
+) -> Result<Summary, Box<dyn Error>> {
+    split_file_resumable(input_file, settings, prefix, None)
+}
+
+/// Split a WAV file into clips like [`split_file()`], saving checkpoints to
+/// the `journal` (if there is one) and restarting from the last one.
+pub fn split_file_resumable(
+    input_file: &Path,
+    settings: &Settings,
+    prefix: &str,
+    journal: Option<&mut Journal>,
+) -> Result<Summary, Box<dyn Error>> {
+    // open the WAV file
+    let reader = WavReader::open(input_file)?;
+    // we need the header to determine the sample format, sample rate, and
+    // channel count
+    let header = reader.spec();
+
+    // The threshold is always given relative to 16-bit audio, so it means
+    // the same thing regardless of what format the recording uses
+    let threshold = settings.noise_threshold;
+
+    match (header.sample_format, header.bits_per_sample) {
+        (SampleFormat::Int, 8) => split_samples(
+            input_file,
+            reader,
+            threshold.to_sample::<i8>(),
+            settings,
+            prefix,
+            journal,
+        ),
+        (SampleFormat::Int, 16) => split_samples(
+            input_file,
+            reader,
+            threshold,
+            settings,
+            prefix,
+            journal,
+        ),
+        (SampleFormat::Int, 24) => split_samples(
+            input_file,
+            reader,
+            threshold.to_sample::<I24>(),
+            settings,
+            prefix,
+            journal,
+        ),
+        (SampleFormat::Int, 32) => split_samples(
+            input_file,
+            reader,
+            threshold.to_sample::<i32>(),
+            settings,
+            prefix,
+            journal,
+        ),
+        (SampleFormat::Float, 32) => split_samples(
+            input_file,
+            reader,
+            threshold.to_sample::<f32>(),
+            settings,
+            prefix,
+            journal,
+        ),
+        (format, bits) => Err(UnsupportedFormat(format!(
+            "{}-bit {} audio isn't supported",
+            bits,
+            match format {
+                SampleFormat::Int => "integer",
+                SampleFormat::Float => "floating point",
+            }
+        ))
+        .into()),
+    }
+}
+
+fn split_samples<S>(
+    input_file: &Path,
+    reader: WavReader<BufReader<File>>,
+    threshold: S,
+    settings: &Settings,
+    prefix: &str,
+    journal: Option<&mut Journal>,
+) -> Result<Summary, Box<dyn Error>>
+where
+    S: WavSample + Duplex<f64>,
+{
+    let header = reader.spec();
+
+    // The gate works with fixed-size frames, so we need to pick the right
+    // frame type for the number of channels
+    macro_rules! dispatch {
+        ($($channels:literal),*) => {
+            match header.channels {
+                $(
+                    $channels => split_frames::<[S; $channels]>(
+                        input_file, reader, threshold, settings, prefix,
+                        journal,
+                    ),
+                )*
+                other => Err(UnsupportedFormat(format!(
+                    "{}-channel audio isn't supported",
+                    other
+                ))
+                .into()),
+            }
+        };
+    }
+
+    dispatch!(1, 2, 3, 4, 5, 6, 7, 8)
+}
+
+fn split_frames<F>(
+    input_file: &Path,
+    mut reader: WavReader<BufReader<File>>,
+    threshold: F::Sample,
+    settings: &Settings,
+    prefix: &str,
+    mut journal: Option<&mut Journal>,
+) -> Result<Summary, Box<dyn Error>>
+where
+    F: Frame,
+    F::Sample: Duplex<f64> + WavSample,
+{
+    let header = reader.spec();
+    let release_time =
+        crate::to_frames(settings.release_time, header.sample_rate);
+    let fade_length = crate::to_frames(settings.fade_edges, header.sample_rate);
+
+    log!(
+        Info,
+        "splitting recording",
+        sample_rate = header.sample_rate,
+        channels = header.channels,
+        bits_per_sample = header.bits_per_sample,
+        threshold = settings.noise_threshold,
+        release_frames = release_time,
+        fade_frames = fade_length,
+        trim_threshold = settings
+            .trim_threshold
+            .map_or_else(|| String::from("none"), |t| t.to_string()),
+    );
+
+    // make sure the output directory exists
+    fs::create_dir_all(&settings.output_dir)?;
+    // initialize our sink, fading each clip in and out to avoid clicks
+    let duration = Duration::from_secs_f64(
+        reader.duration() as f64 / header.sample_rate as f64,
+    );
+    let start_time = settings.start_time.resolve(input_file, duration)?;
+    let namer = ClipNamer::new(
+        prefix.to_string(),
+        settings.naming,
+        start_time,
+        header.sample_rate,
+    );
+    let mut sink = Sink::new(settings.output_dir.clone(), namer, header);
+    let checkpoint = journal
+        .as_deref()
+        .and_then(|journal| journal.checkpoint(input_file))
+        .cloned();
+    if let Some(checkpoint) = &checkpoint {
+        sink.resume(checkpoint.clips.clone());
+    }
+    if let Some(max_bytes) = settings.quota {
+        let quota = Quota::scan(&settings.output_dir, max_bytes)?;
+        log!(
+            Debug,
+            "enforcing a quota",
+            max_bytes = max_bytes,
+            used_bytes = quota.total_bytes(),
+        );
+        sink.quota = Some(quota);
+    }
+    if let Some(url) = &settings.webhook {
+        sink.set_webhook(url)?;
+    }
+    // beep after the fade-out, so the beep itself isn't faded. The beep
+    // goes straight into the Sink so it can be left out of the clip's length
+    let beep_length = settings
+        .roger_beep
+        .map_or(0, |beep| crate::to_frames(beep, header.sample_rate));
+    sink.marker_frames = beep_length;
+    let sink = RogerBeep::tone(
+        sink,
+        BEEP_FREQUENCY,
+        header.sample_rate,
+        beep_length,
+        0.25,
+    );
+    // leave room for the beep at the end of each file
+    let max_frames = max_clip_frames(settings, header)
+        .saturating_sub(beep_length)
+        .max(1);
+    let sink = RotateFiles::new(sink, max_frames);
+    let sink = FadeEdges::new(sink, fade_length);
+    // cut off stuck clips before they get faded, so they still fade out
+    let max_open = settings.watchdog.map_or(usize::MAX, |limit| {
+        crate::to_frames(limit, header.sample_rate)
+    });
+    let sample_rate = header.sample_rate;
+    let sink = Watchdog::new(sink, max_open, move |timeout| {
+        log!(
+            Warn,
+            "the gate was open for too long, cutting the clip off",
+            clip = timeout.transmission,
+            seconds =
+                format!("{:.3}", timeout.frames as f64 / sample_rate as f64),
+        );
+    });
+    // trim before fading, so the fades are applied to the trimmed clip
+    let trim_threshold = match settings.trim_threshold {
+        Some(trim) => trim.to_sample::<f64>().to_sample(),
+        // nothing is quieter than silence, so this never trims anything
+        None => F::Sample::EQUILIBRIUM,
+    };
+    let sink = TrimSilence::with_capacity(sink, trim_threshold, release_time);
+    // the merged gaps still get trimmed and faded like any other audio
+    let max_gap = settings
+        .merge_gaps
+        .map_or(0, |gap| crate::to_frames(gap, header.sample_rate));
+    let mut sink = MergeGaps::new(sink, max_gap);
+
+    // set up the NoiseGate
+    let mut gate = NoiseGate::new(threshold, release_time)
+        .with_detection(settings.detector);
+    let mut detector = ArtifactDetector::default();
+    let mut total_frames = 0;
+    let mut previous_artifacts = Vec::new();
+
+    if let Some(checkpoint) = checkpoint {
+        log!(
+            Info,
+            "resuming from a checkpoint",
+            frame = checkpoint.frame,
+            clips = checkpoint.clips.len(),
+        );
+        reader.seek(checkpoint.frame as u32)?;
+        // the skipped frames count as dropped, so the gate's clock (and the
+        // start of each clip) still lines up with the recording
+        gate.frames_dropped(checkpoint.frame as u64, sink.inner_mut());
+        total_frames = checkpoint.frame;
+        previous_artifacts = checkpoint.artifacts;
+    }
+
+    // the detector only sees frames from where we started
+    let start_frame = total_frames;
+    let artifacts_so_far = |detector: &ArtifactDetector| {
+        let new = detector.artifacts().iter().map(|artifact| {
+            let mut saved = SavedArtifact::from(artifact);
+            saved.start_frame += start_frame;
+            saved
+        });
+        previous_artifacts.iter().cloned().chain(new).collect::<Vec<_>>()
+    };
+    // the clips written so far, from the bottom of the chain of adapters
+    macro_rules! clips_so_far {
+        () => {
+            sink.inner()
+                .inner()
+                .inner()
+                .inner()
+                .inner()
+                .inner()
+                .clips()
+        };
+    }
+    // stop at the first error writing a clip (e.g. because the disk is full)
+    macro_rules! check_for_errors {
+        () => {
+            let error = sink
+                .inner_mut()
+                .inner_mut()
+                .inner_mut()
+                .inner_mut()
+                .inner_mut()
+                .inner_mut()
+                .take_error();
+            if let Some(e) = error {
+                return Err(e.into());
+            }
+        };
+    }
+    let mut checkpointed_clips = clips_so_far!().len();
+
+    // Stream the recording through the gate one chunk at a time so memory
+    // usage stays bounded, no matter how long the recording is
+    let samples = reader
+        .into_samples::<<F::Sample as WavSample>::Raw>()
+        .map(|sample| sample.map(F::Sample::from_raw));
+    let mut frames = frames::try_from_samples::<F, _, _>(samples);
+    let mut buffer: Vec<F> = Vec::with_capacity(CHUNK_SIZE);
+
+    loop {
+        buffer.clear();
+
+        while buffer.len() < CHUNK_SIZE {
+            match frames.next().transpose()? {
+                Some(frame) => buffer.push(frame),
+                None => break,
+            }
+        }
+
+        if buffer.is_empty() {
+            break;
+        }
+
+        detector.process_frames(&buffer);
+        // the Sink logs when each clip starts and ends
+        sink.process_frames(&mut gate, &buffer);
+        check_for_errors!();
+
+        total_frames += buffer.len();
+
+        // only save a checkpoint once a clip has been completely written,
+        // so nothing needs to be carried over when restarting from it
+        let clips = clips_so_far!();
+        let idle = gate.is_closed() && !sink.is_waiting();
+        if let Some(journal) = journal.as_deref_mut() {
+            if idle && clips.len() != checkpointed_clips {
+                let checkpoint = Checkpoint {
+                    frame: total_frames,
+                    clips: clips.to_vec(),
+                    artifacts: artifacts_so_far(&detector),
+                };
+                journal.save_checkpoint(input_file, checkpoint)?;
+                checkpointed_clips = clips.len();
+            }
+        }
+    }
+
+    // the recording may have finished part-way through a clip (or while
+    // waiting for the next one), make sure it gets flushed to disk
+    sink.finish();
+    check_for_errors!();
+    if sink.merged() > 0 {
+        log!(Debug, "merged clips", gaps = sink.merged());
+    }
+
+    let clips = sink
+        .into_inner()
+        .into_inner()
+        .into_inner()
+        .into_inner()
+        .into_inner()
+        .into_inner()
+        .into_clips();
+
+    if settings.bwf {
+        // clips may have already been deleted to stay under the quota
+        for clip in clips.iter().filter(|clip| clip.path.exists()) {
+            bwf::append_bext(&clip.path, input_file, clip.start_frame as u64)?;
+        }
+    }
+
+    log!(
+        Info,
+        "finished splitting",
+        frames = total_frames,
+        clips = clips.len()
+    );
+
+    let new_artifacts = detector.finish().into_iter().map(|mut artifact| {
+        let frames = artifact.frames;
+        artifact.frames = frames.start + start_frame..frames.end + start_frame;
+        artifact
+    });
+    let artifacts: Vec<Artifact> = previous_artifacts
+        .iter()
+        .map(Artifact::from)
+        .chain(new_artifacts)
+        .collect();
+    for artifact in &artifacts {
+        log!(
+            Warn,
+            "found an artifact in the recording",
+            kind = format!("{:?}", artifact.kind),
+            start_frame = artifact.frames.start,
+            frames = artifact.frames.len(),
+        );
+    }
+
+    Ok(Summary::new(
+        header.sample_rate,
+        total_frames,
+        header.channels,
+        clips,
+        artifacts,
+    ))
+}
+
+/// The longest a clip can be before it gets split across several files.
+fn max_clip_frames(settings: &Settings, header: WavSpec) -> usize {
+    let by_length = settings
+        .max_clip_length
+        .map(|length| crate::to_frames(length, header.sample_rate));
+    let by_size = settings.max_clip_size.map(|bytes| {
+        let bytes_per_frame = usize::from(header.channels)
+            * usize::from(header.bits_per_sample).div_ceil(8);
+        // leave room for the WAV header
+        (bytes as usize).saturating_sub(WAV_HEADER_BYTES)
+            / bytes_per_frame.max(1)
+    });
+
+    by_length
+        .into_iter()
+        .chain(by_size)
+        .min()
+        .unwrap_or(usize::MAX)
+}
+
+/// hound's header for integer PCM, not counting the `bext` chunk added by
+/// `--bwf`.
+const WAV_HEADER_BYTES: usize = 44;
+
+/// The number of frames to read from disk at a time.
+const CHUNK_SIZE: usize = 4096;
+
+/// The pitch of the `--roger-beep`, in Hz.
+const BEEP_FREQUENCY: f64 = 1000.0;
+
+pub struct Sink {
+    output_dir: PathBuf,
+    namer: ClipNamer,
+    spec: WavSpec,
+    writer: Option<WavWriter<BufWriter<File>>>,
+    clips: Vec<Clip>,
+    /// Where clips started in the original recording, for clips which
+    /// haven't been created yet.
+    pending_starts: VecDeque<usize>,
+    /// Used to delete old clips when the output directory gets too big.
+    pub quota: Option<Quota>,
+    /// How many frames of marker (i.e. the roger beep) are appended to the
+    /// end of each clip, which aren't part of the original recording.
+    pub marker_frames: usize,
+    /// The first error while writing a clip, after which nothing else is
+    /// written.
+    error: Option<hound::Error>,
+    /// Called whenever a clip is written.
+    #[cfg(feature = "webhook")]
+    webhook: Option<Webhook>,
+}
+
+impl Sink {
+    pub fn new(output_dir: PathBuf, namer: ClipNamer, spec: WavSpec) -> Self {
+        Sink {
+            output_dir,
+            namer,
+            spec,
+            writer: None,
+            clips: Vec::new(),
+            pending_starts: VecDeque::new(),
+            quota: None,
+            marker_frames: 0,
+            error: None,
+            #[cfg(feature = "webhook")]
+            webhook: None,
+        }
+    }
+
+    /// Call a webhook (see [`Webhook`]) whenever a clip is written.
+    #[cfg(feature = "webhook")]
+    pub fn set_webhook(&mut self, url: &str) -> Result<(), Box<dyn Error>> {
+        self.webhook = Some(Webhook::new(url)?);
+        Ok(())
+    }
+
+    #[cfg(not(feature = "webhook"))]
+    pub fn set_webhook(&mut self, _url: &str) -> Result<(), Box<dyn Error>> {
+        Err("wav-splitter was compiled without the \"webhook\" feature".into())
+    }
+
+    /// Let the webhook know the last clip has been written.
+    #[cfg(feature = "webhook")]
+    fn notify(&self) {
+        let (webhook, clip) = match (&self.webhook, self.clips.last()) {
+            (Some(webhook), Some(clip)) => (webhook, clip),
+            _ => return,
+        };
+        let notification = Notification {
+            index: self.clips.len() - 1,
+            path: Some(clip.path.clone()),
+            start_frame: clip.start_frame as u64,
+            frames: clip.frames,
+            channels: usize::from(self.spec.channels),
+            sample_rate: self.spec.sample_rate,
+            finished: SystemTime::now(),
+        };
+
+        if let Err(e) =
+            RetryPolicy::default().run(|| webhook.notify(&notification))
+        {
+            log!(
+                Warn,
+                "unable to call the webhook",
+                error = e,
+                path = clip.path.display(),
+            );
+        }
+    }
+
+    /// Let the [`Sink`] know the gate opened at a particular frame.
+    ///
+    /// Any adapters between the gate and the [`Sink`] may delay frames, so
+    /// we can't assume the next clip will be created straight away.
+    pub fn clip_started(&mut self, start_frame: usize) {
+        log!(Debug, "gate opened", time = self.timestamp(start_frame));
+        self.pending_starts.push_back(start_frame);
+    }
+
+    /// Pick up from a checkpoint, where `clips` have already been written.
+    pub fn resume(&mut self, clips: Vec<Clip>) {
+        // hand out the same names as last time, so a clip which was only
+        // half-written gets overwritten instead of duplicated
+        for clip in &clips {
+            self.namer.next_name(clip.start_frame);
+        }
+        self.clips = clips;
+    }
+
+    /// A frame's position in the original recording, in seconds.
+    fn timestamp(&self, frame: usize) -> String {
+        format!("{:.3}", frame as f64 / self.spec.sample_rate as f64)
+    }
+
+    /// Flush the current clip to disk, where the last `marker_frames` frames
+    /// were a marker instead of audio from the recording.
+    fn finish_clip(&mut self, marker_frames: usize) {
+        // if we were previously recording a transmission, remove the writer
+        // and let it flush to disk
+        if let Some(writer) = self.writer.take() {
+            if let Err(e) = writer.finalize() {
+                self.fail(e);
+                return;
+            }
+
+            if let Some(clip) = self.clips.last_mut() {
+                clip.marker_frames = marker_frames.min(clip.frames);
+                clip.frames -= clip.marker_frames;
+            }
+
+            // long clips are split across files, so this isn't always where
+            // the gate closed
+            if let Some(clip) = self.clips.last() {
+                log!(
+                    Debug,
+                    "finished clip",
+                    path = clip.path.display(),
+                    end = self.timestamp(clip.start_frame + clip.frames),
+                );
+            }
+
+            if let (Some(quota), Some(clip)) =
+                (self.quota.as_mut(), self.clips.last())
+            {
+                if let Err(e) = quota.add(clip.path.clone()) {
+                    log!(
+                        Warn,
+                        "unable to enforce the quota",
+                        error = e,
+                        path = clip.path.display(),
+                    );
+                }
+            }
+
+            #[cfg(feature = "webhook")]
+            self.notify();
+        } else {
+            // nothing was recorded, so an adapter must have thrown the
+            // whole segment away and its start will never be used
+            self.pending_starts.pop_front();
+        }
+    }
+
+    /// Information about every clip written so far.
+    pub fn clips(&self) -> &[Clip] { &self.clips }
+
+    /// Take the first error encountered while writing clips, if any.
+    pub fn take_error(&mut self) -> Option<hound::Error> { self.error.take() }
+
+    /// Remember an error so it can be reported once the gate is done with
+    /// the current chunk, since [`noise_gate::Sink`] methods can't fail.
+    fn fail(&mut self, e: hound::Error) {
+        log!(Warn, "unable to write a clip", error = e);
+        self.error.get_or_insert(e);
+    }
+
+    /// Get information about every clip that was written.
+    pub fn into_clips(self) -> Vec<Clip> { self.clips }
+
+    fn get_writer(&mut self) -> Option<&mut WavWriter<BufWriter<File>>> {
+        if self.error.is_some() {
+            return None;
+        }
+
+        if self.writer.is_none() {
+            // Lazily initialize the writer. This lets us drop the writer when
+            // sent an end_of_transmission and have it automatically start
+            // writing to a new clip when necessary.
+            // every adapter reports where its transmissions start (including
+            // RotateFiles when a long clip rolls over into a new file), so
+            // this guess is only a fallback
+            let start_frame =
+                self.pending_starts.pop_front().unwrap_or_else(|| {
+                    self.clips
+                        .last()
+                        .map(|clip| clip.start_frame + clip.frames)
+                        .unwrap_or(0)
+                });
+            let filename =
+                self.output_dir.join(self.namer.next_name(start_frame));
+            match WavWriter::create(&filename, self.spec) {
+                Ok(writer) => self.writer = Some(writer),
+                Err(e) => {
+                    self.fail(e);
+                    return None;
+                },
+            }
+            log!(Info, "created clip", path = filename.display());
+
+            let mut clip = Clip::new(filename);
+            clip.start_frame = start_frame;
+            self.clips.push(clip);
+        }
+
+        self.writer.as_mut()
+    }
+}
+
+impl<F> noise_gate::Sink<F> for Sink
+where
+    F: Frame,
+    F::Sample: WavSample,
+{
+    fn record(&mut self, frame: F) {
+        let writer = match self.get_writer() {
+            Some(writer) => writer,
+            None => return,
+        };
+
+        // write all the channels as interlaced audio
+        let written = frame
+            .channels()
+            .try_for_each(|channel| writer.write_sample(channel.into_raw()));
+        if let Err(e) = written {
+            self.fail(e);
+            return;
+        }
+
+        if let Some(clip) = self.clips.last_mut() {
+            clip.frames += 1;
+        }
+    }
+
+    fn transmission_started(&mut self, position: u64) {
+        self.clip_started(position as usize);
+    }
+
+    fn errors(&self) -> usize { usize::from(self.error.is_some()) }
+
+    fn end_of_transmission(&mut self) {
+        let marker_frames = self.marker_frames;
+        self.finish_clip(marker_frames);
+    }
+
+    fn discard_transmission(&mut self) {
+        // the marker is only added when a transmission ends normally
+        self.finish_clip(0);
+    }
+}
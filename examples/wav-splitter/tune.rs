@@ -0,0 +1,240 @@
+//! Searching for the threshold and release time which best match a labelled
+//! recording.
+
+use crate::{
+    preview,
+    report::{OutputFormat, Status},
+};
+use noise_gate::{
+    eval,
+    tune::{self, Objective, Trial},
+};
+use std::{error::Error, fs, path::PathBuf, time::Duration};
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+pub struct Args {
+    #[structopt(help = "The WAV file to tune against")]
+    pub input_file: PathBuf,
+    #[structopt(
+        short = "l",
+        long = "labels",
+        help = "An Audacity label track marking the parts which should be kept"
+    )]
+    pub labels: PathBuf,
+    #[structopt(
+        long = "min-threshold",
+        help = "The lowest threshold to try",
+        default_value = "20"
+    )]
+    pub min_threshold: i16,
+    #[structopt(
+        long = "max-threshold",
+        help = "The highest threshold to try",
+        default_value = "4000"
+    )]
+    pub max_threshold: i16,
+    #[structopt(
+        long = "threshold-steps",
+        help = "How many thresholds to try (spaced logarithmically)",
+        default_value = "24"
+    )]
+    pub threshold_steps: usize,
+    #[structopt(
+        long = "min-release",
+        help = "The shortest release time to try",
+        default_value = "0ms",
+        parse(try_from_str = crate::parse_duration)
+    )]
+    pub min_release: Duration,
+    #[structopt(
+        long = "max-release",
+        help = "The longest release time to try",
+        default_value = "1s",
+        parse(try_from_str = crate::parse_duration)
+    )]
+    pub max_release: Duration,
+    #[structopt(
+        long = "release-steps",
+        help = "How many release times to try (spaced linearly)",
+        default_value = "11"
+    )]
+    pub release_steps: usize,
+    #[structopt(
+        long = "objective",
+        help = "What to optimise for",
+        default_value = "frames",
+        possible_values = &["frames", "segments"]
+    )]
+    pub objective: Objective,
+    #[structopt(
+        long = "top",
+        help = "How many of the best parameters to show",
+        default_value = "5"
+    )]
+    pub top: usize,
+}
+
+pub fn run(
+    args: &Args,
+    format: OutputFormat,
+) -> Result<Status, Box<dyn Error>> {
+    let (sample_rate, levels) = preview::read_levels(&args.input_file)?;
+    let labels =
+        eval::parse_audacity_labels(&fs::read_to_string(&args.labels)?)
+            .map_err(|e| {
+                format!("Unable to parse \"{}\": {}", args.labels.display(), e)
+            })?;
+    let reference: Vec<_> =
+        labels.iter().map(|l| l.to_frames(sample_rate)).collect();
+
+    let thresholds = thresholds(
+        args.min_threshold,
+        args.max_threshold,
+        args.threshold_steps,
+    );
+    let release_times: Vec<usize> =
+        release_times(args.min_release, args.max_release, args.release_steps)
+            .into_iter()
+            .map(|d| crate::to_frames(d, sample_rate))
+            .collect();
+    log!(
+        Info,
+        "tuning",
+        labels = labels.len(),
+        thresholds = thresholds.len(),
+        release_times = release_times.len()
+    );
+
+    let trials = tune::grid_search(
+        &levels,
+        &reference,
+        &thresholds,
+        &release_times,
+        args.objective,
+    );
+    let best = &trials[..trials.len().min(args.top.max(1))];
+    let seconds = |frames: usize| frames as f64 / f64::from(sample_rate);
+
+    match format {
+        OutputFormat::Text => {
+            println!(
+                "{:>9}  {:>8}  {:>6}  {:>9}  {:>6}",
+                "threshold", "release", "score", "precision", "recall"
+            );
+            for trial in best {
+                let scores = scores(trial, args.objective);
+                println!(
+                    "{:>9}  {:>7.3}s  {:>6.3}  {:>9.3}  {:>6.3}",
+                    trial.open_threshold,
+                    seconds(trial.release_time),
+                    trial.score,
+                    scores.precision(),
+                    scores.recall()
+                );
+            }
+            if let Some(trial) = best.first() {
+                println!();
+                println!(
+                    "Best: --threshold {} --release-time {:.3}s",
+                    trial.open_threshold,
+                    seconds(trial.release_time)
+                );
+            }
+        },
+        OutputFormat::Json => {
+            let trials: Vec<_> = best
+                .iter()
+                .map(|trial| {
+                    let scores = scores(trial, args.objective);
+                    serde_json::json!({
+                        "threshold": trial.open_threshold,
+                        "release_time": seconds(trial.release_time),
+                        "score": trial.score,
+                        "precision": scores.precision(),
+                        "recall": scores.recall(),
+                    })
+                })
+                .collect();
+            println!(
+                "{}",
+                serde_json::json!({
+                    "objective": match args.objective {
+                        Objective::FrameF1 => "frames",
+                        Objective::SegmentF1 => "segments",
+                    },
+                    "trials": trials,
+                })
+            );
+        },
+    }
+
+    Ok(Status::Success)
+}
+
+fn scores(trial: &Trial<i16>, objective: Objective) -> eval::Scores {
+    match objective {
+        Objective::FrameF1 => trial.evaluation.frames,
+        Objective::SegmentF1 => trial.evaluation.segments,
+    }
+}
+
+/// Thresholds spaced logarithmically between `min` and `max` (inclusive),
+/// without any duplicates.
+fn thresholds(min: i16, max: i16, steps: usize) -> Vec<i16> {
+    let (min, max) = (min.max(1), max.max(1));
+    let (low, high) = (f64::from(min.min(max)), f64::from(min.max(max)));
+    let steps = steps.max(2);
+
+    let mut thresholds: Vec<i16> = (0..steps)
+        .map(|i| {
+            let ratio = i as f64 / (steps - 1) as f64;
+            (low * (high / low).powf(ratio)).round() as i16
+        })
+        .collect();
+    thresholds.dedup();
+
+    thresholds
+}
+
+/// Release times spaced linearly between `min` and `max` (inclusive).
+fn release_times(min: Duration, max: Duration, steps: usize) -> Vec<Duration> {
+    let (low, high) = (min.min(max), min.max(max));
+    let steps = steps.max(2);
+
+    let mut times: Vec<Duration> = (0..steps)
+        .map(|i| low + (high - low).mul_f64(i as f64 / (steps - 1) as f64))
+        .collect();
+    times.dedup();
+
+    times
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thresholds_are_spaced_logarithmically() {
+        assert_eq!(thresholds(10, 1000, 3), vec![10, 100, 1000]);
+        assert_eq!(thresholds(1000, 10, 3), vec![10, 100, 1000]);
+        assert_eq!(thresholds(5, 6, 10), vec![5, 6]);
+    }
+
+    #[test]
+    fn release_times_are_spaced_linearly() {
+        let got = release_times(Duration::ZERO, Duration::from_secs(1), 5);
+
+        assert_eq!(
+            got,
+            vec![
+                Duration::ZERO,
+                Duration::from_millis(250),
+                Duration::from_millis(500),
+                Duration::from_millis(750),
+                Duration::from_secs(1),
+            ]
+        );
+        assert_eq!(release_times(Duration::ZERO, Duration::ZERO, 5).len(), 1);
+    }
+}
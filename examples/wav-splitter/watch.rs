@@ -0,0 +1,160 @@
+use crate::{
+    report::{FileReport, OutputFormat, Status, Summary},
+    split, Options, Settings,
+};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+use structopt::StructOpt;
+
+#[derive(Debug, Clone, StructOpt)]
+pub struct Args {
+    #[structopt(help = "The directory to watch for new WAV files")]
+    pub dir: PathBuf,
+    #[structopt(
+        long = "processed-dir",
+        help = "Where to move recordings once they've been split (defaults \
+                to a \"processed\" folder inside the watched directory)"
+    )]
+    pub processed_dir: Option<PathBuf>,
+    #[structopt(
+        long = "settle-time",
+        help = "How long a recording needs to go without changing before \
+                it's split",
+        default_value = "5s",
+        parse(try_from_str = crate::parse_duration)
+    )]
+    pub settle_time: Duration,
+    #[structopt(flatten)]
+    pub options: Options,
+}
+
+pub fn run(
+    args: &Args,
+    format: OutputFormat,
+) -> Result<Status, Box<dyn Error>> {
+    let processed_dir = args
+        .processed_dir
+        .clone()
+        .unwrap_or_else(|| args.dir.join("processed"));
+    fs::create_dir_all(&processed_dir)?;
+    let settings = args.options.resolve()?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher: RecommendedWatcher = notify::recommended_watcher(tx)?;
+    watcher.watch(&args.dir, RecursiveMode::NonRecursive)?;
+    log!(
+        Info,
+        "watching for recordings",
+        dir = args.dir.display(),
+        processed_dir = processed_dir.display(),
+    );
+
+    // When each recording last changed. A recording is only split once it
+    // has gone `settle_time` without changing, so we don't pick up
+    // recordings which are still being written.
+    let now = Instant::now();
+    let mut pending: HashMap<PathBuf, Instant> = wav_files(&args.dir)?
+        .into_iter()
+        .map(|path| (path, now))
+        .collect();
+    // Recordings we weren't able to split, so we don't keep retrying them.
+    let mut failed: HashSet<PathBuf> = HashSet::new();
+
+    loop {
+        let next_deadline = pending
+            .values()
+            .map(|&changed| changed + args.settle_time)
+            .min();
+        let timeout = next_deadline.map_or(args.settle_time, |deadline| {
+            deadline.saturating_duration_since(Instant::now())
+        });
+
+        match rx.recv_timeout(timeout) {
+            Ok(event) => {
+                let changed = Instant::now();
+
+                for path in event?.paths {
+                    if is_wav(&path) && !failed.contains(&path) {
+                        pending.insert(path, changed);
+                    }
+                }
+            },
+            Err(mpsc::RecvTimeoutError::Timeout) => {},
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err("The file watcher stopped unexpectedly".into());
+            },
+        }
+
+        let settled: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, &changed)| changed.elapsed() >= args.settle_time)
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        for path in settled {
+            pending.remove(&path);
+
+            // the recording may have been moved or deleted since it changed
+            let empty = fs::metadata(&path).map_or(true, |m| m.len() == 0);
+            if !path.is_file() || empty {
+                continue;
+            }
+
+            let outcome = process(&path, &processed_dir, &settings);
+            let report = FileReport::new(path.clone(), outcome);
+            report.print(format);
+
+            if report.error.is_some() {
+                log!(
+                    Warn,
+                    "the recording will be skipped from now on",
+                    path = path.display(),
+                );
+                failed.insert(path);
+            }
+        }
+    }
+}
+
+fn process(
+    path: &Path,
+    processed_dir: &Path,
+    settings: &Settings,
+) -> Result<Summary, Box<dyn Error>> {
+    let prefix = split::clip_prefix(path, &settings.prefix)?;
+    let summary = split::split_file(path, settings, &prefix)?;
+
+    let file_name = path.file_name().ok_or("The path has no file name")?;
+    let destination = processed_dir.join(file_name);
+    fs::rename(path, &destination)?;
+    log!(Info, "moved recording", path = destination.display());
+
+    Ok(summary)
+}
+
+/// Get all the WAV files directly inside a directory.
+pub fn wav_files(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_file() && is_wav(&path) {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+fn is_wav(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
+}
@@ -0,0 +1,296 @@
+//! Converting between the samples `hound` reads and writes and the sample
+//! types the gate works with.
+
+use dasp::{sample::I24, Sample};
+
+/// A sample type which can be read from and written to a WAV file.
+///
+/// Most formats map directly onto a [`hound::Sample`], but `hound` gives us
+/// 24-bit audio in the low bits of an `i32`. Treating those as `i32`s would
+/// make them 256 times quieter than they really are, so they get converted
+/// to a proper [`I24`] instead.
+///
+/// 8-bit WAV files are unsigned, but `hound` converts them to and from `i8`
+/// for us, so they're gated just like any other signed format.
+pub trait WavSample: Sample {
+    /// The type `hound` uses for this sample.
+    type Raw: hound::Sample;
+
+    /// Convert a sample read by `hound`.
+    fn from_raw(raw: Self::Raw) -> Self;
+
+    /// Convert the sample back so `hound` can write it.
+    fn into_raw(self) -> Self::Raw;
+}
+
+macro_rules! native {
+    ($($sample:ty),*) => {
+        $(
+            impl WavSample for $sample {
+                type Raw = $sample;
+
+                fn from_raw(raw: $sample) -> Self { raw }
+
+                fn into_raw(self) -> $sample { self }
+            }
+        )*
+    };
+}
+
+native!(i8, i16, i32, f32);
+
+impl WavSample for I24 {
+    type Raw = i32;
+
+    fn from_raw(raw: i32) -> Self {
+        // hound should never give us anything out of range, but clamp just
+        // in case
+        I24::new_unchecked(raw.clamp(-8_388_608, 8_388_607))
+    }
+
+    fn into_raw(self) -> i32 { self.inner() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        naming::{Naming, StartTime},
+        report::Status,
+        split, Settings,
+    };
+    use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+    use std::time::Duration;
+
+    #[test]
+    fn i24_samples_use_the_full_scale() {
+        let loudest = I24::from_raw(8_388_607);
+
+        assert!((loudest.to_sample::<f64>() - 1.0).abs() < 1e-6);
+        assert_eq!(loudest.to_sample::<i16>(), i16::MAX);
+        assert_eq!(loudest.into_raw(), 8_388_607);
+    }
+
+    #[test]
+    fn split_24_bit_recordings_without_losing_precision() {
+        let dir = std::env::temp_dir()
+            .join(format!("wav-splitter-i24-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input.wav");
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 1000,
+            bits_per_sample: 24,
+            sample_format: SampleFormat::Int,
+        };
+
+        // a burst which is loud enough to open the gate, but would be far
+        // too quiet if it was treated as a 32-bit sample
+        let burst: Vec<i32> = (0..100).map(|i| 1_000_000 + i).collect();
+        let mut writer = WavWriter::create(&input, spec).unwrap();
+        for &sample in [0; 100].iter().chain(&burst).chain(&[0; 100]) {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let settings = Settings {
+            noise_threshold: 1000,
+            release_time: Duration::from_millis(0),
+            fade_edges: Duration::from_millis(0),
+            trim_threshold: None,
+            max_clip_length: None,
+            max_clip_size: None,
+            quota: None,
+            watchdog: None,
+            merge_gaps: None,
+            roger_beep: None,
+            output_dir: dir.clone(),
+            prefix: String::from("clip"),
+            bwf: false,
+            detector: Default::default(),
+            naming: Naming::Numbered,
+            start_time: StartTime::At(0.0),
+            webhook: None,
+        };
+        let summary = split::split_file(&input, &settings, "clip").unwrap();
+
+        assert_eq!(summary.clips.len(), 1);
+        let clip = WavReader::open(&summary.clips[0].path).unwrap();
+        assert_eq!(clip.spec(), spec);
+        let samples: Vec<i32> =
+            clip.into_samples().collect::<Result<_, _>>().unwrap();
+        assert_eq!(&samples[..burst.len()], &burst[..]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn split_8_bit_recordings() {
+        let dir = std::env::temp_dir()
+            .join(format!("wav-splitter-u8-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input.wav");
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 1000,
+            bits_per_sample: 8,
+            sample_format: SampleFormat::Int,
+        };
+
+        // 8-bit WAVs are unsigned on disk, with silence at 128
+        let burst: Vec<i8> = (0..50).map(|i| 40 + i).collect();
+        let mut writer = WavWriter::create(&input, spec).unwrap();
+        for &sample in [0; 100].iter().chain(&burst).chain(&[0; 100]) {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+        let raw = std::fs::read(&input).unwrap();
+        assert_eq!(raw[raw.len() - 1], 128);
+
+        let settings = Settings {
+            noise_threshold: 1000,
+            release_time: Duration::from_millis(0),
+            fade_edges: Duration::from_millis(0),
+            trim_threshold: None,
+            max_clip_length: None,
+            max_clip_size: None,
+            quota: None,
+            watchdog: None,
+            merge_gaps: None,
+            roger_beep: None,
+            output_dir: dir.clone(),
+            prefix: String::from("clip"),
+            bwf: false,
+            detector: Default::default(),
+            naming: Naming::Numbered,
+            start_time: StartTime::At(0.0),
+            webhook: None,
+        };
+        let summary = split::split_file(&input, &settings, "clip").unwrap();
+
+        assert_eq!(summary.clips.len(), 1);
+        assert_eq!(summary.clips[0].start_frame, 100);
+        let clip = WavReader::open(&summary.clips[0].path).unwrap();
+        assert_eq!(clip.spec(), spec);
+        let samples: Vec<i8> =
+            clip.into_samples().collect::<Result<_, _>>().unwrap();
+        assert_eq!(&samples[..burst.len()], &burst[..]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn roger_beeps_arent_counted_as_part_of_the_clip() {
+        let dir = std::env::temp_dir()
+            .join(format!("wav-splitter-beep-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input.wav");
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 1000,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+
+        let mut writer = WavWriter::create(&input, spec).unwrap();
+        for &sample in [0; 100].iter().chain(&[5000; 100]).chain(&[0; 100]) {
+            writer.write_sample(sample as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let split = |roger_beep| {
+            let settings = Settings {
+                noise_threshold: 1000,
+                release_time: Duration::from_millis(0),
+                fade_edges: Duration::from_millis(0),
+                trim_threshold: None,
+                max_clip_length: Some(Duration::from_millis(60)),
+                max_clip_size: None,
+                quota: None,
+                watchdog: None,
+                merge_gaps: None,
+                roger_beep,
+                output_dir: dir.clone(),
+                prefix: String::from("clip"),
+                bwf: false,
+                detector: Default::default(),
+                naming: Naming::Numbered,
+                start_time: StartTime::At(0.0),
+                webhook: None,
+            };
+            split::split_file(&input, &settings, "clip").unwrap().clips
+        };
+
+        let plain = split(None);
+        let beeped = split(Some(Duration::from_millis(20)));
+
+        // every file has a beep on the end, and the clips still line up
+        // with the original recording
+        let frames = |clips: &[crate::report::Clip]| {
+            clips.iter().map(|c| c.frames).sum::<usize>()
+        };
+        assert_eq!(frames(&beeped), frames(&plain));
+        assert_eq!(beeped[0].start_frame, plain[0].start_frame);
+        for (previous, clip) in beeped.iter().zip(&beeped[1..]) {
+            assert_eq!(
+                clip.start_frame,
+                previous.start_frame + previous.frames
+            );
+        }
+        for clip in &beeped {
+            assert_eq!(clip.marker_frames, 20);
+            let len = WavReader::open(&clip.path).unwrap().duration();
+            assert_eq!(len as usize, clip.frames + 20);
+            assert!(len <= 60);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn errors_writing_clips_are_returned() {
+        let dir = std::env::temp_dir()
+            .join(format!("wav-splitter-unwritable-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input.wav");
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 1000,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+
+        let mut writer = WavWriter::create(&input, spec).unwrap();
+        for &sample in [0_i16; 100].iter().chain(&[5000; 100]) {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+        // something is already in the way of the first clip
+        std::fs::create_dir_all(dir.join("clip0.wav")).unwrap();
+
+        let settings = Settings {
+            noise_threshold: 1000,
+            release_time: Duration::from_millis(0),
+            fade_edges: Duration::from_millis(0),
+            trim_threshold: None,
+            max_clip_length: None,
+            max_clip_size: None,
+            quota: None,
+            watchdog: None,
+            merge_gaps: None,
+            roger_beep: None,
+            output_dir: dir.clone(),
+            prefix: String::from("clip"),
+            bwf: false,
+            detector: Default::default(),
+            naming: Naming::Numbered,
+            start_time: StartTime::At(0.0),
+            webhook: None,
+        };
+        let e = split::split_file(&input, &settings, "clip").unwrap_err();
+
+        assert_eq!(Status::from_error(&*e), Status::Error);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
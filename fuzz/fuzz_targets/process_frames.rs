@@ -0,0 +1,131 @@
+//! Feed arbitrary bytes through the gate as `i16` and `f32` frames, with
+//! arbitrary parameters, looking for panics and overflows.
+//!
+//! Run with `cargo +nightly fuzz run process_frames`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use noise_gate::{sinks::FadeEdges, NoiseGate, NonFinite, Sink};
+use std::convert::TryInto;
+
+/// A sink which checks the events it receives make sense.
+#[derive(Debug, Default)]
+struct Checker {
+    recording: bool,
+    frames: usize,
+}
+
+impl<F> Sink<F> for Checker {
+    fn record(&mut self, _: F) {
+        self.recording = true;
+        self.frames += 1;
+    }
+
+    fn end_of_transmission(&mut self) {
+        assert!(self.recording, "Ended a transmission that never started");
+        self.recording = false;
+    }
+}
+
+/// The parameters are taken from the start of the input.
+struct Parameters {
+    release_time: usize,
+    chunk_size: usize,
+    fade_length: usize,
+    non_finite: NonFinite,
+    threshold: [u8; 4],
+}
+
+impl Parameters {
+    const LEN: usize = 10;
+
+    fn parse(data: &[u8]) -> Option<(Self, &[u8])> {
+        if data.len() < Self::LEN {
+            return None;
+        }
+        let (header, rest) = data.split_at(Self::LEN);
+
+        // occasionally use enormous release times to look for overflows
+        let release_time = match header[0] {
+            0xff => usize::MAX,
+            0xfe => usize::MAX - 1,
+            _ => u16::from_le_bytes([header[1], header[2]]) as usize,
+        };
+        let non_finite = if header[3] & 1 == 0 {
+            NonFinite::Loud
+        } else {
+            NonFinite::Silent
+        };
+
+        let parameters = Parameters {
+            release_time,
+            chunk_size: header[4] as usize + 1,
+            fade_length: header[5] as usize,
+            non_finite,
+            threshold: header[6..10].try_into().unwrap(),
+        };
+
+        Some((parameters, rest))
+    }
+}
+
+fn check<F>(parameters: &Parameters, threshold: F::Sample, frames: &[F])
+where
+    F: dasp::Frame,
+{
+    let mut gate = NoiseGate::new(threshold, parameters.release_time);
+    gate.non_finite = parameters.non_finite;
+    let mut sink = Checker::default();
+
+    for chunk in frames.chunks(parameters.chunk_size) {
+        gate.process_frames(chunk, &mut sink);
+    }
+    assert!(sink.frames <= frames.len());
+
+    // the zero-copy API should see exactly the same frames
+    let mut gate = NoiseGate::new(threshold, parameters.release_time);
+    gate.non_finite = parameters.non_finite;
+    let segmented: usize = gate.segments(frames).map(|(_, s)| s.len()).sum();
+    assert_eq!(segmented, sink.frames);
+
+    // and the fade adapter shouldn't lose or invent frames
+    let mut gate = NoiseGate::new(threshold, parameters.release_time);
+    gate.non_finite = parameters.non_finite;
+    let mut faded = FadeEdges::new(Checker::default(), parameters.fade_length);
+    gate.process_frames(frames, &mut faded);
+    if gate.is_open() {
+        faded.end_of_transmission();
+    }
+    assert_eq!(faded.inner().frames, sink.frames);
+}
+
+fuzz_target!(|data: &[u8]| {
+    let (parameters, data) = match Parameters::parse(data) {
+        Some(p) => p,
+        None => return,
+    };
+    let [a, b, c, d] = parameters.threshold;
+
+    let samples: Vec<i16> = data
+        .chunks_exact(2)
+        .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+        .collect();
+    let threshold = i16::from_le_bytes([a, b]);
+    let mono: Vec<[i16; 1]> = samples.iter().map(|&s| [s]).collect();
+    check(&parameters, threshold, &mono);
+    let stereo: Vec<[i16; 2]> =
+        samples.chunks_exact(2).map(|s| [s[0], s[1]]).collect();
+    check(&parameters, threshold, &stereo);
+
+    let samples: Vec<f32> = data
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+        .collect();
+    let threshold = f32::from_le_bytes([a, b, c, d]);
+    let mono: Vec<[f32; 1]> = samples.iter().map(|&s| [s]).collect();
+    check(&parameters, threshold, &mono);
+    let stereo: Vec<[f32; 2]> =
+        samples.chunks_exact(2).map(|s| [s[0], s[1]]).collect();
+    check(&parameters, threshold, &stereo);
+});
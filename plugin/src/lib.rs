@@ -0,0 +1,279 @@
+//! The noise gate, wrapped up as a VST3 and CLAP plugin so it can be used
+//! directly inside a DAW.
+//!
+//! Frames are gated one at a time using the same detector and state machine
+//! as [`noise_gate::NoiseGate`], with a short gain ramp when the gate opens
+//! (attack) and closes (release) so it doesn't click. How far the signal is
+//! being turned down is published as a [`GainReduction`] meter.
+
+use nih_plug::prelude::*;
+use noise_gate::{
+    analysis::{from_dbfs, to_dbfs},
+    low_level::{self, Level, Params as StateParams, State},
+};
+use std::{
+    num::NonZeroU32,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+/// The most gain reduction the meter will show, in dB.
+const MAX_GAIN_REDUCTION: f64 = 100.0;
+
+/// The noise gate plugin.
+pub struct NoiseGatePlugin {
+    params: Arc<GateParams>,
+    gain_reduction: Arc<GainReduction>,
+    sample_rate: f32,
+    state: State,
+    gain: f32,
+}
+
+/// A meter showing how much the signal was turned down by during the last
+/// block, shared between the audio thread and an editor.
+#[derive(Debug, Default)]
+pub struct GainReduction(AtomicU32);
+
+impl GainReduction {
+    /// The gain reduction in dB, where `0.0` means the signal is untouched.
+    pub fn db(&self) -> f32 { f32::from_bits(self.0.load(Ordering::Relaxed)) }
+
+    fn set(&self, db: f32) { self.0.store(db.to_bits(), Ordering::Relaxed); }
+}
+
+#[derive(Params)]
+struct GateParams {
+    /// The level the gate opens at.
+    #[id = "threshold"]
+    threshold: FloatParam,
+    /// How long it takes to fade in once the gate opens.
+    #[id = "attack"]
+    attack: FloatParam,
+    /// How long the signal needs to stay quiet before the gate starts
+    /// closing (the [`noise_gate::NoiseGate::release_time`]).
+    #[id = "hold"]
+    hold: FloatParam,
+    /// How long it takes to fade out once the gate closes.
+    #[id = "release"]
+    release: FloatParam,
+}
+
+impl Default for GateParams {
+    fn default() -> Self {
+        let milliseconds = |name: &str, default: f32, max: f32| {
+            FloatParam::new(
+                name,
+                default,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(0.1)
+        };
+
+        GateParams {
+            threshold: FloatParam::new(
+                "Threshold",
+                -40.0,
+                FloatRange::Linear {
+                    min: -90.0,
+                    max: 0.0,
+                },
+            )
+            .with_unit(" dB")
+            .with_step_size(0.1),
+            attack: milliseconds("Attack", 5.0, 100.0),
+            hold: milliseconds("Hold", 250.0, 5000.0),
+            release: milliseconds("Release", 20.0, 1000.0),
+        }
+    }
+}
+
+impl Default for NoiseGatePlugin {
+    fn default() -> Self {
+        NoiseGatePlugin {
+            params: Arc::new(GateParams::default()),
+            gain_reduction: Arc::new(GainReduction::default()),
+            sample_rate: 44_100.0,
+            state: State::Closed,
+            gain: 0.0,
+        }
+    }
+}
+
+impl NoiseGatePlugin {
+    /// The gain reduction meter, for an editor to display.
+    pub fn gain_reduction(&self) -> Arc<GainReduction> {
+        Arc::clone(&self.gain_reduction)
+    }
+
+    fn to_frames(&self, milliseconds: f32) -> usize {
+        (milliseconds / 1000.0 * self.sample_rate).round() as usize
+    }
+
+    /// How much the gain changes by each frame when ramping over `ms`.
+    fn ramp_step(&self, milliseconds: f32) -> f32 {
+        1.0 / self.to_frames(milliseconds).max(1) as f32
+    }
+
+    /// Gate a block of audio in place, where each channel has its own slice.
+    fn process_channels(&mut self, channels: &mut [&mut [f32]]) {
+        let threshold = from_dbfs(f64::from(self.params.threshold.value()));
+        let threshold = threshold as f32;
+        let params = StateParams {
+            release_time: self.to_frames(self.params.hold.value()),
+        };
+        let attack = self.ramp_step(self.params.attack.value());
+        let release = self.ramp_step(self.params.release.value());
+
+        let frames = channels.first().map_or(0, |channel| channel.len());
+        let mut lowest_gain = self.gain;
+
+        for i in 0..frames {
+            // we only ever have one or two channels, and repeating a mono
+            // channel doesn't change whether the frame is loud
+            let left = channels[0][i];
+            let right = channels.get(1).map_or(left, |channel| channel[i]);
+            let level = Level::of([left, right], threshold);
+            self.state = low_level::step(self.state, level, &params);
+
+            self.gain = if self.state.is_open() {
+                (self.gain + attack).min(1.0)
+            } else {
+                (self.gain - release).max(0.0)
+            };
+            lowest_gain = lowest_gain.min(self.gain);
+
+            for channel in channels.iter_mut() {
+                channel[i] *= self.gain;
+            }
+        }
+
+        let reduction = -to_dbfs(f64::from(lowest_gain));
+        self.gain_reduction.set(reduction.min(MAX_GAIN_REDUCTION) as f32);
+    }
+}
+
+impl Plugin for NoiseGatePlugin {
+    type BackgroundTask = ();
+    type SysExMessage = ();
+
+    const AUDIO_IO_LAYOUTS: &'static [AudioIOLayout] = &[
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(2),
+            main_output_channels: NonZeroU32::new(2),
+            ..AudioIOLayout::const_default()
+        },
+        AudioIOLayout {
+            main_input_channels: NonZeroU32::new(1),
+            main_output_channels: NonZeroU32::new(1),
+            ..AudioIOLayout::const_default()
+        },
+    ];
+    const EMAIL: &'static str = "michaelfbryan@gmail.com";
+    const NAME: &'static str = "Noise Gate";
+    const SAMPLE_ACCURATE_AUTOMATION: bool = true;
+    const URL: &'static str = "https://github.com/Michael-F-Bryan/noise-gate";
+    const VENDOR: &'static str = "Michael-F-Bryan";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    fn params(&self) -> Arc<dyn Params> { self.params.clone() }
+
+    fn initialize(
+        &mut self,
+        _layout: &AudioIOLayout,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.sample_rate = buffer_config.sample_rate;
+        true
+    }
+
+    fn reset(&mut self) {
+        self.state = State::Closed;
+        self.gain = 0.0;
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer<'_>,
+        _aux: &mut AuxiliaryBuffers<'_>,
+        _context: &mut impl ProcessContext<Self>,
+    ) -> ProcessStatus {
+        // parameter changes split the buffer (see SAMPLE_ACCURATE_AUTOMATION),
+        // so the parameters are constant for each call
+        self.process_channels(buffer.as_slice());
+
+        ProcessStatus::Normal
+    }
+}
+
+impl ClapPlugin for NoiseGatePlugin {
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("Silences everything below a threshold");
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::Gate,
+        ClapFeature::Mono,
+        ClapFeature::Stereo,
+    ];
+    const CLAP_ID: &'static str = "com.michael-f-bryan.noise-gate";
+    const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+}
+
+impl Vst3Plugin for NoiseGatePlugin {
+    const VST3_CLASS_ID: [u8; 16] = *b"MFBryanNoiseGate";
+    const VST3_SUBCATEGORIES: &'static [Vst3SubCategory] =
+        &[Vst3SubCategory::Fx, Vst3SubCategory::Dynamics];
+}
+
+nih_export_clap!(NoiseGatePlugin);
+nih_export_vst3!(NoiseGatePlugin);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quiet_audio_is_silenced() {
+        let mut plugin = NoiseGatePlugin::default();
+        let mut left = vec![0.001_f32; 512];
+        let mut right = vec![-0.001_f32; 512];
+
+        plugin.process_channels(&mut [&mut left, &mut right]);
+
+        assert!(left.iter().chain(&right).all(|&sample| sample == 0.0));
+        assert_eq!(plugin.gain_reduction().db(), MAX_GAIN_REDUCTION as f32);
+    }
+
+    #[test]
+    fn loud_audio_fades_in_and_passes_through() {
+        let mut plugin = NoiseGatePlugin::default();
+        let mut first = vec![0.5_f32; 4096];
+        let mut second = vec![0.5_f32; 512];
+
+        plugin.process_channels(&mut [&mut first]);
+        plugin.process_channels(&mut [&mut second]);
+
+        assert!(first[0] < first[100]);
+        assert_eq!(first[4095], 0.5);
+        assert!(second.iter().all(|&sample| sample == 0.5));
+        assert_eq!(plugin.gain_reduction().db(), 0.0);
+    }
+
+    #[test]
+    fn non_finite_samples_open_the_gate_like_the_core() {
+        let mut plugin = NoiseGatePlugin::default();
+        let mut samples = vec![0.0, f32::NAN, 0.0];
+
+        plugin.process_channels(&mut [&mut samples]);
+
+        assert!(plugin.state.is_open());
+    }
+}
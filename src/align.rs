@@ -0,0 +1,246 @@
+//! Splitting several synchronised recordings of the same event (e.g. one
+//! file per microphone) at exactly the same points, so the clips stay
+//! sample-aligned across every track.
+//!
+//! ```rust
+//! use noise_gate::{
+//!     align::{Key, Multitrack},
+//!     NoiseGate,
+//! };
+//!
+//! let vocals = [[0_i16], [0], [800], [700], [0], [0], [0], [0]];
+//! let guitar = [[0_i16], [0], [0], [0], [0], [0], [900], [0]];
+//!
+//! // only listen to the vocal mic
+//! let mut multitrack = Multitrack::new(NoiseGate::new(500, 0), Key::Track(0));
+//! assert_eq!(multitrack.segments(&[&vocals, &guitar]), vec![2..5]);
+//!
+//! // or open whenever any of the mics is loud
+//! let mut multitrack = Multitrack::new(NoiseGate::new(500, 0), Key::Loudest);
+//! assert_eq!(
+//!     multitrack.segments(&[&vocals, &guitar]),
+//!     vec![2..5, 6..8]
+//! );
+//! ```
+
+use crate::{NoiseGate, Sink};
+use dasp::{Frame, Sample};
+use noise_gate_core::__private;
+use std::ops::Range;
+
+/// Which track decides where the recordings are split.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Key {
+    /// Only listen to the track with this index.
+    ///
+    /// If there is no such track, the gate never opens.
+    Track(usize),
+    /// Listen to every channel of every track, opening whenever any of
+    /// them is loud (regardless of the gate's
+    /// [`Detection`][crate::Detection]).
+    Loudest,
+}
+
+/// A [`NoiseGate`] which decides when to open using one [`Key`] signal, then
+/// applies the same decision to every track.
+///
+/// All tracks need to start at the same time. If they are different lengths,
+/// anything past the end of the shortest track is ignored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Multitrack<S> {
+    gate: NoiseGate<S>,
+    key: Key,
+    sidechain: Vec<[S; 1]>,
+}
+
+impl<S> Multitrack<S> {
+    /// Create a new [`Multitrack`] gate.
+    pub fn new(gate: NoiseGate<S>, key: Key) -> Self {
+        Multitrack {
+            gate,
+            key,
+            sidechain: Vec::new(),
+        }
+    }
+
+    /// Which track decides where the recordings are split.
+    pub fn key(&self) -> Key { self.key }
+
+    /// Get a reference to the underlying [`NoiseGate`].
+    pub fn gate(&self) -> &NoiseGate<S> { &self.gate }
+
+    /// Get a mutable reference to the underlying [`NoiseGate`] (e.g. to
+    /// change its threshold).
+    pub fn gate_mut(&mut self) -> &mut NoiseGate<S> { &mut self.gate }
+
+    /// Consume the [`Multitrack`], returning the underlying [`NoiseGate`].
+    pub fn into_inner(self) -> NoiseGate<S> { self.gate }
+}
+
+impl<S: Sample> Multitrack<S> {
+    /// Gate a chunk of every track, passing the `i`'th track through to the
+    /// `i`'th sink.
+    ///
+    /// Every sink sees exactly the same frame numbers, and transmissions
+    /// start and end at the same time. Like [`NoiseGate::process_frames()`],
+    /// the gate remembers its state between calls.
+    pub fn process<F, K>(&mut self, tracks: &[&[F]], sinks: &mut [K])
+    where
+        F: Frame<Sample = S>,
+        K: Sink<F>,
+    {
+        let len = shortest(tracks);
+
+        match self.key {
+            Key::Track(index) => {
+                let key = tracks.get(index).map_or(&[][..], |t| &t[..len]);
+                process(&mut self.gate, key, tracks, sinks);
+            },
+            Key::Loudest => {
+                update_sidechain(&mut self.sidechain, tracks);
+                process(&mut self.gate, &self.sidechain, tracks, sinks);
+            },
+        }
+    }
+
+    /// Find where every track should be split, as frame ranges which apply
+    /// to all of them.
+    ///
+    /// As with [`NoiseGate::segments()`], the last range may belong to a
+    /// transmission which continues past the end of the tracks.
+    pub fn segments<F>(&mut self, tracks: &[&[F]]) -> Vec<Range<usize>>
+    where
+        F: Frame<Sample = S>,
+    {
+        let len = shortest(tracks);
+
+        match self.key {
+            Key::Track(index) => {
+                let key = tracks.get(index).map_or(&[][..], |t| &t[..len]);
+                ranges(&mut self.gate, key)
+            },
+            Key::Loudest => {
+                update_sidechain(&mut self.sidechain, tracks);
+                ranges(&mut self.gate, &self.sidechain)
+            },
+        }
+    }
+}
+
+fn process<S, F, D, K>(
+    gate: &mut NoiseGate<S>,
+    key: &[D],
+    tracks: &[&[F]],
+    sinks: &mut [K],
+) where
+    S: Sample,
+    F: Frame<Sample = S>,
+    D: Frame<Sample = S>,
+    K: Sink<F>,
+{
+    let before = gate.state();
+
+    for (track, sink) in tracks.iter().zip(sinks) {
+        // every track sees the same key, so they all make the same decisions
+        __private::set_state(gate, before);
+        gate.process_sidechain(track, key, sink);
+    }
+
+    // the gate still needs to follow the key when there are no sinks
+    __private::set_state(gate, before);
+    gate.segments(key).for_each(drop);
+}
+
+fn ranges<S, D>(gate: &mut NoiseGate<S>, key: &[D]) -> Vec<Range<usize>>
+where
+    S: Sample,
+    D: Frame<Sample = S>,
+{
+    gate.segments(key).map(|(range, _)| range).collect()
+}
+
+fn shortest<F>(tracks: &[&[F]]) -> usize {
+    tracks.iter().map(|track| track.len()).min().unwrap_or(0)
+}
+
+/// Collapse every channel of every track into a single mono key.
+fn update_sidechain<F: Frame>(
+    sidechain: &mut Vec<[F::Sample; 1]>,
+    tracks: &[&[F]],
+) {
+    sidechain.clear();
+    sidechain.extend((0..shortest(tracks)).map(|i| {
+        let frames = tracks.iter().map(|track| loudest(track[i]));
+        [frames.fold(F::Sample::EQUILIBRIUM, louder)]
+    }));
+}
+
+/// The sample in a frame which is furthest from silence, so a mono key
+/// opens whenever any channel would.
+fn loudest<F: Frame>(frame: F) -> F::Sample {
+    frame.channels().fold(F::Sample::EQUILIBRIUM, louder)
+}
+
+fn louder<S: Sample>(a: S, b: S) -> S {
+    let level = |s: S| s.to_float_sample().to_sample::<f64>().abs();
+
+    // non-finite samples always win, so the gate's NonFinite setting
+    // still applies
+    if level(b).is_nan() || level(b) > level(a) {
+        b
+    } else {
+        a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_track_is_split_at_the_same_points() {
+        let tracks: Vec<Vec<[i16; 2]>> = (0..3_i16)
+            .map(|mic| {
+                (0..200_i16)
+                    .map(|i| {
+                        let loud = i % (40 + 10 * mic) < 5;
+                        [if loud { 1000 } else { mic }, i]
+                    })
+                    .collect()
+            })
+            .collect();
+        let refs: Vec<&[[i16; 2]]> = tracks.iter().map(|t| &t[..]).collect();
+        let gate = NoiseGate::new(500, 3);
+        let expected =
+            Multitrack::new(gate.clone(), Key::Loudest).segments(&refs[..]);
+        let mut multitrack = Multitrack::new(gate, Key::Loudest);
+        let mut sinks = vec![Vec::new(); 3];
+
+        // streaming in chunks is the same as doing it all at once
+        for start in (0..200).step_by(64) {
+            let end = (start + 64).min(200);
+            let chunks: Vec<&[[i16; 2]]> =
+                refs.iter().map(|t| &t[start..end]).collect();
+            multitrack.process(&chunks, &mut sinks);
+        }
+
+        assert!(expected.len() > 5);
+        let positions: Vec<i16> =
+            expected.into_iter().flatten().map(|i| i as i16).collect();
+        for sink in &sinks {
+            let got: Vec<i16> = sink.iter().map(|frame| frame[1]).collect();
+            assert_eq!(got, positions);
+        }
+    }
+
+    #[test]
+    fn the_gate_follows_the_key_without_any_sinks() {
+        let key = [[0_i16], [900], [900]];
+        let mut multitrack =
+            Multitrack::new(NoiseGate::new(500, 0), Key::Track(1));
+
+        multitrack.process::<_, Vec<_>>(&[&[[0]; 3], &key], &mut []);
+
+        assert!(multitrack.gate().is_open());
+    }
+}
@@ -0,0 +1,227 @@
+use crate::analysis::frame_peak;
+use dasp::{sample::Duplex, Frame};
+use std::ops::Range;
+
+/// A problem with the recording itself.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Artifact {
+    /// What went wrong.
+    pub kind: ArtifactKind,
+    /// Which frames were affected.
+    pub frames: Range<usize>,
+}
+
+/// The kinds of [`Artifact`] an [`ArtifactDetector`] looks for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ArtifactKind {
+    /// The signal was at (or very close to) full scale for several frames in
+    /// a row, which usually means the input gain was set too high.
+    Clipping,
+    /// The signal dropped to exact digital silence part-way through the
+    /// recording, which usually means a buffer underrun or a loose cable.
+    Dropout,
+}
+
+/// Looks for clipping and digital dropouts in a stream of audio.
+///
+/// These confuse threshold selection and tend to end up as garbage clips,
+/// so it's worth reporting them alongside the gate's output. Like the
+/// [`NoiseGate`][crate::NoiseGate], a recording can be streamed through in
+/// chunks of any size.
+///
+/// ```rust
+/// use noise_gate::analysis::{Artifact, ArtifactDetector, ArtifactKind};
+///
+/// let mut frames = vec![[0.1_f32]; 1000];
+/// frames[100..105].copy_from_slice(&[[1.0]; 5]);
+/// frames[500..700].copy_from_slice(&[[0.0]; 200]);
+///
+/// let mut detector = ArtifactDetector::default();
+/// detector.process_frames(&frames);
+///
+/// assert_eq!(
+///     detector.finish(),
+///     vec![
+///         Artifact { kind: ArtifactKind::Clipping, frames: 100..105 },
+///         Artifact { kind: ArtifactKind::Dropout, frames: 500..700 },
+///     ]
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArtifactDetector {
+    /// A frame is clipped when any channel reaches this level, where `1.0`
+    /// is full scale.
+    pub clip_level: f64,
+    /// How many clipped frames need to be in a row before it's reported.
+    pub min_clipped_frames: usize,
+    /// How many frames of digital silence need to be in a row before it's
+    /// reported as a dropout.
+    pub min_dropout_frames: usize,
+    position: usize,
+    clipped_since: Option<usize>,
+    silent_since: Option<usize>,
+    seen_audio: bool,
+    artifacts: Vec<Artifact>,
+}
+
+impl ArtifactDetector {
+    /// Create a new [`ArtifactDetector`].
+    pub fn new(
+        clip_level: f64,
+        min_clipped_frames: usize,
+        min_dropout_frames: usize,
+    ) -> Self {
+        ArtifactDetector {
+            clip_level,
+            min_clipped_frames,
+            min_dropout_frames,
+            position: 0,
+            clipped_since: None,
+            silent_since: None,
+            seen_audio: false,
+            artifacts: Vec::new(),
+        }
+    }
+
+    /// The artifacts found so far.
+    pub fn artifacts(&self) -> &[Artifact] { &self.artifacts }
+
+    /// Look for artifacts in the next batch of frames.
+    pub fn process_frames<F>(&mut self, frames: &[F])
+    where
+        F: Frame,
+        F::Sample: Duplex<f64>,
+    {
+        for &frame in frames {
+            let level = frame_peak(frame);
+
+            if level >= self.clip_level {
+                self.clipped_since.get_or_insert(self.position);
+            } else {
+                self.end_clipping();
+            }
+
+            if level == 0.0 {
+                self.silent_since.get_or_insert(self.position);
+            } else {
+                self.end_silence();
+                self.seen_audio = true;
+            }
+
+            self.position += 1;
+        }
+    }
+
+    /// Finish processing, returning every artifact that was found.
+    ///
+    /// Digital silence at the very start or end of a recording isn't
+    /// counted as a dropout.
+    pub fn finish(mut self) -> Vec<Artifact> {
+        self.end_clipping();
+        self.artifacts
+    }
+
+    fn end_clipping(&mut self) {
+        if let Some(start) = self.clipped_since.take() {
+            self.report(ArtifactKind::Clipping, start, self.min_clipped_frames);
+        }
+    }
+
+    fn end_silence(&mut self) {
+        if let Some(start) = self.silent_since.take() {
+            if self.seen_audio {
+                self.report(
+                    ArtifactKind::Dropout,
+                    start,
+                    self.min_dropout_frames,
+                );
+            }
+        }
+    }
+
+    fn report(&mut self, kind: ArtifactKind, start: usize, min_len: usize) {
+        if self.position - start >= min_len.max(1) {
+            self.artifacts.push(Artifact {
+                kind,
+                frames: start..self.position,
+            });
+        }
+    }
+}
+
+impl Default for ArtifactDetector {
+    /// Report 3 or more frames within 0.1 dB of full scale as clipping, and
+    /// 64 or more frames of digital silence as a dropout.
+    fn default() -> Self { ArtifactDetector::new(0.99, 3, 64) }
+}
+
+/// Find clipping and dropouts in a recording that's already in memory,
+/// using the [`ArtifactDetector`]'s default settings.
+pub fn find_artifacts<F>(frames: &[F]) -> Vec<Artifact>
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    let mut detector = ArtifactDetector::default();
+    detector.process_frames(frames);
+    detector.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_blips_are_ignored() {
+        let mut frames = vec![[1000_i16, -1000]; 500];
+        frames[10] = [i16::MAX, 0];
+        frames[11] = [0, i16::MIN];
+        frames[100..110].copy_from_slice(&[[0, 0]; 10]);
+
+        assert!(find_artifacts(&frames).is_empty());
+    }
+
+    #[test]
+    fn clipping_on_any_channel_counts() {
+        let mut frames = vec![[1000_i16, -1000]; 500];
+        frames[10..20].copy_from_slice(&[[i16::MAX, 0]; 10]);
+        frames[20..25].copy_from_slice(&[[0, i16::MIN]; 5]);
+        frames[495..].copy_from_slice(&[[i16::MIN, i16::MIN]; 5]);
+
+        let got = find_artifacts(&frames);
+
+        assert_eq!(
+            got,
+            vec![
+                Artifact {
+                    kind: ArtifactKind::Clipping,
+                    frames: 10..25
+                },
+                Artifact {
+                    kind: ArtifactKind::Clipping,
+                    frames: 495..500
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn silence_at_the_ends_isnt_a_dropout() {
+        let mut frames = vec![[128_u8]; 1000];
+        frames[200..300].copy_from_slice(&[[100]; 100]);
+        frames[400..500].copy_from_slice(&[[150]; 100]);
+
+        let mut detector = ArtifactDetector::default();
+        for chunk in frames.chunks(7) {
+            detector.process_frames(chunk);
+        }
+
+        assert_eq!(
+            detector.finish(),
+            vec![Artifact {
+                kind: ArtifactKind::Dropout,
+                frames: 300..400
+            }]
+        );
+    }
+}
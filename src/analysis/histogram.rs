@@ -0,0 +1,146 @@
+use super::{frame_peak, from_dbfs, percentile_threshold, to_dbfs};
+use dasp::{sample::Duplex, Frame, Sample};
+
+/// The number of buckets, which covers the range of 16-bit audio.
+const BUCKETS: usize = 32;
+/// How far above the noise floor the suggested threshold is, in dB.
+const MARGIN_DB: f64 = 6.0;
+/// The quietest threshold [`analyze()`] will suggest, so digital silence
+/// doesn't give a threshold of zero.
+const MIN_THRESHOLD_DB: f64 = -90.0;
+
+/// How many frames fell into each level range, in 3 dB buckets.
+///
+/// Bucket `i` counts the frames whose level (their loudest channel) is
+/// within `(-3(i + 1), -3i]` dBFS. The last bucket also counts everything
+/// quieter than that, including digital silence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    /// The number of frames in each bucket, loudest first.
+    pub counts: Vec<usize>,
+}
+
+impl Histogram {
+    /// The width of each bucket, in dB.
+    pub const BUCKET_WIDTH_DB: f64 = 3.0;
+
+    /// Which bucket a level (where `1.0` is full scale) belongs to.
+    pub fn bucket(level: f64) -> usize {
+        let index = (-to_dbfs(level) / Histogram::BUCKET_WIDTH_DB).floor();
+
+        if index.is_nan() {
+            BUCKETS - 1
+        } else {
+            // this also sends silence (-inf dB) to the last bucket
+            (index.max(0.0) as usize).min(BUCKETS - 1)
+        }
+    }
+
+    /// The loudest level (in dBFS) a frame in bucket `index` can have.
+    pub fn upper_bound_dbfs(index: usize) -> f64 {
+        -(index as f64) * Histogram::BUCKET_WIDTH_DB
+    }
+
+    /// The total number of frames counted.
+    pub fn total(&self) -> usize { self.counts.iter().sum() }
+}
+
+/// Everything an auto-configuration UI needs to know about a recording,
+/// from [`analyze()`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis<S> {
+    /// How the frame levels are distributed.
+    pub histogram: Histogram,
+    /// The estimated noise floor (see [`percentile_threshold()`]).
+    pub noise_floor: S,
+    /// A threshold a little above the noise floor.
+    pub suggested_threshold: S,
+    /// The fraction of frames at or above the suggested threshold, between
+    /// `0.0` and `1.0`.
+    ///
+    /// This is how much of the recording a gate with no release time would
+    /// keep, so the real duty cycle will be a bit higher.
+    pub duty_cycle: f64,
+}
+
+/// Look at a whole recording, estimating its noise floor and suggesting a
+/// threshold.
+///
+/// The noise floor is the 95th percentile of the quietest tenth of the
+/// recording, and the suggested threshold is 6 dB above that. Returns
+/// `None` if there aren't any (finite) frames to look at.
+///
+/// ```rust
+/// use noise_gate::analysis::{analyze, Histogram};
+///
+/// // 0.01 hiss, with clicks every few frames in the second half
+/// let frames: Vec<[f32; 1]> = (0..4000)
+///     .map(|i| if i % 4 == 0 && i >= 2000 { [0.5] } else { [0.01] })
+///     .collect();
+///
+/// let analysis = analyze(&frames).unwrap();
+///
+/// assert_eq!(analysis.noise_floor, 0.01);
+/// assert!((analysis.suggested_threshold - 0.02).abs() < 0.001);
+/// assert_eq!(analysis.duty_cycle, 0.125);
+/// assert_eq!(analysis.histogram.counts[Histogram::bucket(0.5)], 500);
+/// ```
+pub fn analyze<F>(frames: &[F]) -> Option<Analysis<F::Sample>>
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    let noise_floor = percentile_threshold(frames, frames.len() / 10, 95.0)?;
+
+    let floor_db = to_dbfs(noise_floor.to_sample::<f64>().abs());
+    let threshold = from_dbfs((floor_db + MARGIN_DB).max(MIN_THRESHOLD_DB));
+
+    let mut counts = vec![0; BUCKETS];
+    let mut above = 0;
+    let mut total = 0;
+    for &frame in frames {
+        let level = frame_peak(frame);
+        if !level.is_finite() {
+            continue;
+        }
+
+        counts[Histogram::bucket(level)] += 1;
+        total += 1;
+        if level >= threshold {
+            above += 1;
+        }
+    }
+
+    Some(Analysis {
+        histogram: Histogram { counts },
+        noise_floor,
+        suggested_threshold: threshold.to_sample(),
+        duty_cycle: above as f64 / total.max(1) as f64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_go_in_3db_buckets() {
+        assert_eq!(Histogram::bucket(1.0), 0);
+        assert_eq!(Histogram::bucket(0.8), 0);
+        assert_eq!(Histogram::bucket(0.5), 2);
+        assert_eq!(Histogram::bucket(0.0), BUCKETS - 1);
+        assert_eq!(Histogram::bucket(f64::NAN), BUCKETS - 1);
+        assert_eq!(Histogram::upper_bound_dbfs(2), -6.0);
+    }
+
+    #[test]
+    fn silence_still_gets_a_threshold() {
+        let analysis = analyze(&[[0_i16]; 100]).unwrap();
+
+        assert_eq!(analysis.noise_floor, 0);
+        assert!(analysis.suggested_threshold > 0);
+        assert_eq!(analysis.duty_cycle, 0.0);
+        assert_eq!(analysis.histogram.total(), 100);
+        assert!(analyze::<[i16; 1]>(&[]).is_none());
+    }
+}
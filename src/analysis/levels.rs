@@ -0,0 +1,196 @@
+use dasp::{sample::Duplex, Frame, Sample};
+
+/// The magnitude of a frame's loudest channel, where `1.0` is full scale.
+///
+/// A `NaN` in any channel makes the whole frame `NaN`.
+pub fn frame_peak<F>(frame: F) -> f64
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    frame
+        .channels()
+        .map(|sample| sample.to_sample::<f64>().abs())
+        .fold(0.0, |loudest, level| {
+            if loudest.is_nan() || level <= loudest {
+                loudest
+            } else {
+                level
+            }
+        })
+}
+
+/// The RMS level of a single frame's channels, where `1.0` is full scale.
+pub fn frame_rms<F>(frame: F) -> f64
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    let sum: f64 = frame
+        .channels()
+        .map(|sample| {
+            let sample = sample.to_sample::<f64>();
+            sample * sample
+        })
+        .sum();
+
+    (sum / F::CHANNELS as f64).sqrt()
+}
+
+/// The magnitude of the loudest sample in a buffer, where `1.0` is full
+/// scale.
+///
+/// `NaN` and infinite samples are ignored.
+pub fn peak<F>(frames: &[F]) -> f64
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    frames
+        .iter()
+        .flat_map(|frame| frame.channels())
+        .map(|sample| sample.to_sample::<f64>().abs())
+        .filter(|level| level.is_finite())
+        .fold(0.0, f64::max)
+}
+
+/// The RMS level of every sample in a buffer, where `1.0` is full scale.
+///
+/// `NaN` and infinite samples are ignored.
+pub fn rms<F>(frames: &[F]) -> f64
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    mean_square(frames).sqrt()
+}
+
+/// The ratio between a buffer's [`peak()`] and [`rms()`] levels, or `None`
+/// if the buffer is completely silent.
+///
+/// Steady tones and noise have a low crest factor (a sine wave's is `√2`),
+/// while speech and percussion tend to be much "peakier".
+pub fn crest_factor<F>(frames: &[F]) -> Option<f64>
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    let rms = rms(frames);
+
+    if rms > 0.0 {
+        Some(peak(frames) / rms)
+    } else {
+        None
+    }
+}
+
+/// Convert a linear level (where `1.0` is full scale) to dBFS.
+///
+/// ```rust
+/// use noise_gate::analysis::{from_dbfs, to_dbfs};
+///
+/// assert_eq!(to_dbfs(1.0), 0.0);
+/// assert_eq!(to_dbfs(0.1), -20.0);
+/// assert_eq!(to_dbfs(0.0), f64::NEG_INFINITY);
+/// assert_eq!(from_dbfs(-40.0), 0.01);
+/// ```
+pub fn to_dbfs(level: f64) -> f64 { 20.0 * level.log10() }
+
+/// Convert a level in dBFS to a linear level, where `1.0` is full scale.
+pub fn from_dbfs(dbfs: f64) -> f64 { 10_f64.powf(dbfs / 20.0) }
+
+/// Turn a level in dBFS into a sample value (e.g. for use as a threshold).
+///
+/// ```rust
+/// use noise_gate::analysis::sample_from_dbfs;
+///
+/// assert_eq!(sample_from_dbfs::<i16>(-6.0), 16422);
+/// assert_eq!(sample_from_dbfs::<u8>(0.0), 255);
+/// ```
+pub fn sample_from_dbfs<S>(dbfs: f64) -> S
+where
+    S: Sample + Duplex<f64>,
+{
+    from_dbfs(dbfs).min(1.0).to_sample()
+}
+
+/// The mean of every finite sample's square.
+pub(crate) fn mean_square<F>(frames: &[F]) -> f64
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    let (sum, count) = frames
+        .iter()
+        .flat_map(|frame| frame.channels())
+        .map(|sample| {
+            let sample = sample.to_sample::<f64>();
+            sample * sample
+        })
+        .filter(|s| s.is_finite())
+        .fold((0.0, 0_usize), |(sum, count), s| (sum + s, count + 1));
+
+    sum / count.max(1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dasp::sample::I24;
+    use std::f64::consts::{PI, SQRT_2};
+
+    #[test]
+    fn sine_wave_statistics() {
+        let frames: Vec<[f64; 1]> = (0..48_000)
+            .map(|i| [0.5 * (2.0 * PI * i as f64 / 480.0).sin()])
+            .collect();
+
+        assert!((peak(&frames) - 0.5).abs() < 1e-9);
+        assert!((rms(&frames) - 0.5 / SQRT_2).abs() < 1e-9);
+        assert!((crest_factor(&frames).unwrap() - SQRT_2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn integer_and_unsigned_samples_use_full_scale() {
+        let frames = [[i16::MIN, 0], [16384, -16384]];
+        assert_eq!(peak(&frames), 1.0);
+        assert_eq!(frame_peak(frames[1]), 0.5);
+        assert_eq!(frame_rms(frames[1]), 0.5);
+
+        let frames = [[128_u8], [64], [192]];
+        assert_eq!(peak(&frames), 0.5);
+    }
+
+    #[test]
+    fn i24_samples_use_full_scale() {
+        let half = I24::new(4_194_304).unwrap();
+        let frames = [[half, -half], [I24::new(0).unwrap(); 2]];
+
+        assert_eq!(peak(&frames), 0.5);
+        assert_eq!(frame_rms(frames[0]), 0.5);
+        assert!((to_dbfs(peak(&frames)) + 6.0206).abs() < 1e-4);
+        assert_eq!(sample_from_dbfs::<I24>(to_dbfs(0.5)), half);
+    }
+
+    #[test]
+    fn non_finite_samples_are_ignored() {
+        let frames = [[0.5_f32], [f32::NAN], [f32::INFINITY], [-0.5]];
+
+        assert_eq!(peak(&frames), 0.5);
+        assert_eq!(rms(&frames), 0.5);
+        assert!(frame_peak([0.1, f32::NAN]).is_nan());
+    }
+
+    #[test]
+    fn silence_has_no_crest_factor() {
+        assert_eq!(crest_factor(&[[0_i16]; 10]), None);
+        assert_eq!(crest_factor::<[f32; 2]>(&[]), None);
+    }
+
+    #[test]
+    fn decibel_round_trip() {
+        for &db in &[-96.0, -40.0, -3.0, 0.0, 6.0] {
+            assert!((to_dbfs(from_dbfs(db)) - db).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Looking at a recording to help pick parameters for the gate.
+
+mod artifacts;
+mod histogram;
+mod levels;
+
+pub use artifacts::{find_artifacts, Artifact, ArtifactDetector, ArtifactKind};
+pub use histogram::{analyze, Analysis, Histogram};
+pub use levels::{
+    crest_factor, frame_peak, frame_rms, from_dbfs, peak, rms,
+    sample_from_dbfs, to_dbfs,
+};
+
+use levels::mean_square;
+
+use dasp::{sample::Duplex, Frame, Sample};
+
+/// Estimate a threshold by finding the quietest `window_length` frames of a
+/// recording and taking the `percentile`'th percentile of the frame levels
+/// in that window.
+///
+/// Unlike the mean level, this isn't skewed by loud sections, so something
+/// like the 95th percentile of the quietest 30 seconds gives a good estimate
+/// of the noise floor. You'll usually want to set the gate's threshold a
+/// little above this.
+///
+/// A frame's level is its loudest channel, and the recording is split into
+/// back-to-back windows (any leftover frames at the end are ignored unless
+/// the recording is shorter than one window). Returns `None` if there aren't
+/// any (finite) frames to look at.
+///
+/// ```rust
+/// use noise_gate::analysis::percentile_threshold;
+///
+/// // 0.01 hiss, with a loud burst in the middle
+/// let frames: Vec<[f32; 1]> = (0..3000)
+///     .map(|i| if (1000..2000).contains(&i) { [0.8] } else { [0.01] })
+///     .collect();
+///
+/// let threshold = percentile_threshold(&frames, 500, 95.0).unwrap();
+///
+/// assert_eq!(threshold, 0.01);
+/// ```
+pub fn percentile_threshold<F>(
+    frames: &[F],
+    window_length: usize,
+    percentile: f64,
+) -> Option<F::Sample>
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    let window = if frames.len() <= window_length || window_length == 0 {
+        frames
+    } else {
+        frames
+            .chunks_exact(window_length)
+            .min_by(|a, b| mean_square(a).total_cmp(&mean_square(b)))?
+    };
+
+    let mut levels: Vec<f64> = window
+        .iter()
+        .map(|&f| frame_peak(f))
+        .filter(|l| l.is_finite())
+        .collect();
+
+    nth_percentile(&mut levels, percentile).map(|level| level.to_sample())
+}
+
+/// The `percentile`'th percentile of every frame's level (i.e. its loudest
+/// channel), using the nearest-rank method.
+///
+/// ```rust
+/// use noise_gate::analysis::percentile;
+///
+/// let frames: Vec<[i16; 2]> = (1..=100).map(|i| [i, -i / 2]).collect();
+///
+/// assert_eq!(percentile(&frames, 50.0), Some(50));
+/// assert_eq!(percentile(&frames, 95.0), Some(95));
+/// assert_eq!(percentile(&frames, 100.0), Some(100));
+/// ```
+pub fn percentile<F>(frames: &[F], percentile: f64) -> Option<F::Sample>
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    let mut levels: Vec<f64> = frames
+        .iter()
+        .map(|&f| frame_peak(f))
+        .filter(|l| l.is_finite())
+        .collect();
+
+    nth_percentile(&mut levels, percentile).map(|level| level.to_sample())
+}
+
+fn nth_percentile(levels: &mut [f64], percentile: f64) -> Option<f64> {
+    if levels.is_empty() {
+        return None;
+    }
+
+    let fraction = (percentile / 100.0).clamp(0.0, 1.0);
+    let rank = (fraction * levels.len() as f64).ceil() as usize;
+    let index = rank.max(1).min(levels.len()) - 1;
+
+    let (_, &mut value, _) =
+        levels.select_nth_unstable_by(index, |a, b| a.total_cmp(b));
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loud_sections_dont_skew_the_estimate() {
+        // the quietest part of this recording is the hiss at the end
+        let frames: Vec<[i16; 1]> = (0..10_000_i16)
+            .map(|i| match i {
+                0..=2999 => [i % 1000],
+                3000..=5999 => [20_000],
+                _ => [i % 50 - 25],
+            })
+            .collect();
+
+        let threshold = percentile_threshold(&frames, 3000, 95.0).unwrap();
+
+        assert_eq!(threshold, 24);
+    }
+
+    #[test]
+    fn short_recordings_use_everything() {
+        let frames = [[0.1_f64], [0.2], [0.3]];
+
+        assert_eq!(percentile_threshold(&frames, 1000, 100.0), Some(0.3));
+        assert_eq!(percentile_threshold(&frames, 0, 0.0), Some(0.1));
+    }
+
+    #[test]
+    fn unsigned_levels_are_relative_to_equilibrium() {
+        let frames = [[128_u8], [118], [148], [138]];
+
+        // the levels are 0, 10, 20, and 10
+        assert_eq!(percentile(&frames, 100.0), Some(148));
+        assert_eq!(percentile(&frames, 75.0), Some(138));
+    }
+
+    #[test]
+    fn nothing_to_estimate() {
+        assert_eq!(percentile::<[f32; 1]>(&[], 50.0), None);
+        assert_eq!(percentile(&[[f32::NAN]], 50.0), None);
+    }
+}
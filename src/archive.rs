@@ -0,0 +1,447 @@
+//! A compact archive format which only stores the noisy parts of a recording,
+//! but remembers where they were so the original timeline can be rebuilt.
+//!
+//! # Format
+//!
+//! Everything is little-endian. The archive starts with a header:
+//!
+//! | Bytes | Contents                                |
+//! | ----- | --------------------------------------- |
+//! | 4     | The magic bytes, `NGAR`                 |
+//! | 1     | The format version (currently `1`)      |
+//! | 1     | The sample type (see [`ArchiveSample`]) |
+//! | 2     | The number of channels                  |
+//! | 4     | The sample rate                         |
+//!
+//! Followed by any number of records, each starting with a tag byte:
+//!
+//! - `1`: a run of frames, followed by the index of its first frame in the
+//!   original recording (`u64`), the number of frames (`u32`), then the
+//!   interleaved samples themselves
+//! - `0`: the end of the archive, followed by the length of the original
+//!   recording in frames (`u64`)
+
+use crate::NoiseGate;
+use dasp::{Frame, Sample};
+use noise_gate_core::__private;
+use std::{
+    convert::TryFrom,
+    io::{self, Read, Write},
+    ops::Range,
+};
+
+const MAGIC: [u8; 4] = *b"NGAR";
+const VERSION: u8 = 1;
+const END: u8 = 0;
+const FRAMES: u8 = 1;
+
+/// A [`NoiseGate`] which writes the noise it finds to an archive.
+///
+/// Writes are small, so you'll probably want to use a
+/// [`std::io::BufWriter`].
+///
+/// ```rust
+/// use noise_gate::{archive::{Archive, ArchiveWriter}, NoiseGate};
+///
+/// let recording = [[0_i16], [500], [600], [0], [0], [0], [700], [0]];
+///
+/// let gate = NoiseGate::new(100, 0);
+/// let mut writer = ArchiveWriter::new(gate, Vec::new(), 8000).unwrap();
+/// writer.process_frames(&recording).unwrap();
+/// let bytes = writer.finish().unwrap();
+///
+/// let archive = Archive::<[i16; 1]>::read(&bytes[..]).unwrap();
+/// assert_eq!(archive.segment_ranges(), vec![1..4, 6..8]);
+/// assert_eq!(
+///     archive.reconstruct(),
+///     vec![[0], [500], [600], [0], [0], [0], [700], [0]],
+/// );
+/// ```
+#[derive(Debug)]
+pub struct ArchiveWriter<F: Frame, W> {
+    gate: NoiseGate<F::Sample>,
+    writer: W,
+    position: u64,
+}
+
+impl<F, W> ArchiveWriter<F, W>
+where
+    F: Frame,
+    F::Sample: ArchiveSample,
+    W: Write,
+{
+    /// Start a new archive, immediately writing the header.
+    pub fn new(
+        gate: NoiseGate<F::Sample>,
+        mut writer: W,
+        sample_rate: u32,
+    ) -> io::Result<Self> {
+        let channels = u16::try_from(F::CHANNELS).map_err(|_| {
+            invalid_input(format!("{} channels is too many", F::CHANNELS))
+        })?;
+
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&[VERSION, F::Sample::TAG])?;
+        writer.write_all(&channels.to_le_bytes())?;
+        writer.write_all(&sample_rate.to_le_bytes())?;
+
+        Ok(ArchiveWriter {
+            gate,
+            writer,
+            position: 0,
+        })
+    }
+
+    /// Run some frames through the gate, adding any noise to the archive.
+    pub fn process_frames(&mut self, frames: &[F]) -> io::Result<()> {
+        let mut remaining = frames;
+
+        while !remaining.is_empty() {
+            let run = __private::next_run(&mut self.gate, remaining);
+            let mut recorded = &remaining[..run.recorded];
+            let mut start = self.position;
+
+            // runs longer than a u32 get split into several records
+            while !recorded.is_empty() {
+                let len = recorded.len().min(u32::MAX as usize);
+                let (chunk, rest) = recorded.split_at(len);
+                self.write_frames(start, chunk)?;
+                start += len as u64;
+                recorded = rest;
+            }
+
+            self.position += run.len as u64;
+            remaining = &remaining[run.len..];
+        }
+
+        Ok(())
+    }
+
+    /// Get a reference to the underlying [`NoiseGate`].
+    pub fn gate(&self) -> &NoiseGate<F::Sample> { &self.gate }
+
+    /// Finish the archive, returning the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.write_all(&[END])?;
+        self.writer.write_all(&self.position.to_le_bytes())?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    fn write_frames(&mut self, start: u64, frames: &[F]) -> io::Result<()> {
+        self.writer.write_all(&[FRAMES])?;
+        self.writer.write_all(&start.to_le_bytes())?;
+        self.writer
+            .write_all(&(frames.len() as u32).to_le_bytes())?;
+
+        for frame in frames {
+            for sample in frame.channels() {
+                sample.write_le(&mut self.writer)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The contents of an archive written by an [`ArchiveWriter`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Archive<F> {
+    /// The sample rate the archive was written with.
+    pub sample_rate: u32,
+    /// The length of the original recording.
+    pub total_frames: u64,
+    /// Each segment of noise, and where it started in the original
+    /// recording.
+    pub segments: Vec<(u64, Vec<F>)>,
+}
+
+impl<F> Archive<F>
+where
+    F: Frame,
+    F::Sample: ArchiveSample,
+{
+    /// Read an archive, making sure it contains the right type of frames.
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut header = [0; 12];
+        reader.read_exact(&mut header)?;
+
+        if header[..4] != MAGIC {
+            return Err(invalid_data("Not a noise gate archive".to_string()));
+        }
+        if header[4] != VERSION {
+            return Err(invalid_data(format!(
+                "Unsupported archive version, {}",
+                header[4]
+            )));
+        }
+
+        let channels = u16::from_le_bytes([header[6], header[7]]);
+        if header[5] != F::Sample::TAG || channels as usize != F::CHANNELS {
+            return Err(invalid_data(format!(
+                "Expected {} channels of sample type {}, but the archive \
+                 contains {} channels of sample type {}",
+                F::CHANNELS,
+                F::Sample::TAG,
+                channels,
+                header[5],
+            )));
+        }
+
+        let sample_rate = u32::from_le_bytes(array(&header[8..12]));
+        let mut segments: Vec<(u64, Vec<F>)> = Vec::new();
+
+        loop {
+            let mut tag = [0];
+            reader.read_exact(&mut tag)?;
+
+            match tag[0] {
+                FRAMES => {
+                    let start = read_u64(&mut reader)?;
+                    let mut len = [0; 4];
+                    reader.read_exact(&mut len)?;
+                    let len = u32::from_le_bytes(len) as usize;
+
+                    let frames = read_frames(&mut reader, len)?;
+
+                    // a run carrying on from where the last one stopped is
+                    // part of the same segment
+                    match segments.last_mut() {
+                        Some((previous_start, previous))
+                            if *previous_start + previous.len() as u64
+                                == start =>
+                        {
+                            previous.extend(frames)
+                        },
+                        _ => segments.push((start, frames)),
+                    }
+                },
+                END => {
+                    let total_frames = read_u64(&mut reader)?;
+
+                    return Ok(Archive {
+                        sample_rate,
+                        total_frames,
+                        segments,
+                    });
+                },
+                other => {
+                    return Err(invalid_data(format!(
+                        "Unknown record type, {}",
+                        other
+                    )))
+                },
+            }
+        }
+    }
+
+    /// Where each segment was in the original recording.
+    pub fn segment_ranges(&self) -> Vec<Range<u64>> {
+        self.segments
+            .iter()
+            .map(|(start, frames)| *start..*start + frames.len() as u64)
+            .collect()
+    }
+
+    /// Rebuild the original recording, filling the gaps between segments
+    /// with silence.
+    pub fn reconstruct(&self) -> Vec<F> {
+        let mut frames = vec![F::EQUILIBRIUM; self.total_frames as usize];
+
+        for (start, segment) in &self.segments {
+            let start = *start as usize;
+            frames[start..start + segment.len()].copy_from_slice(segment);
+        }
+
+        frames
+    }
+}
+
+fn read_frames<F, R>(reader: &mut R, len: usize) -> io::Result<Vec<F>>
+where
+    F: Frame,
+    F::Sample: ArchiveSample,
+    R: Read,
+{
+    // Note: don't trust the length enough to preallocate everything, a
+    // corrupt archive could ask for gigabytes
+    let mut frames = Vec::with_capacity(len.min(4096));
+    let mut error = None;
+
+    for _ in 0..len {
+        let frame = F::from_fn(|_| match F::Sample::read_le(reader) {
+            Ok(sample) => sample,
+            Err(e) => {
+                error.get_or_insert(e);
+                F::Sample::EQUILIBRIUM
+            },
+        });
+
+        if let Some(e) = error {
+            return Err(e);
+        }
+        frames.push(frame);
+    }
+
+    Ok(frames)
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut buffer = [0; 8];
+    reader.read_exact(&mut buffer)?;
+    Ok(u64::from_le_bytes(buffer))
+}
+
+fn array<const N: usize>(bytes: &[u8]) -> [u8; N] {
+    let mut array = [0; N];
+    array.copy_from_slice(bytes);
+    array
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// A [`Sample`] which can be stored in an archive.
+pub trait ArchiveSample: Sample {
+    /// The number used to identify this sample type in an archive header.
+    const TAG: u8;
+
+    /// Write the sample as little-endian bytes.
+    fn write_le<W: Write>(self, writer: &mut W) -> io::Result<()>;
+
+    /// Read a sample that was written with [`ArchiveSample::write_le()`].
+    fn read_le<R: Read>(reader: &mut R) -> io::Result<Self>;
+}
+
+macro_rules! archive_sample {
+    ($($ty:ty => $tag:expr),* $(,)?) => {
+        $(
+            impl ArchiveSample for $ty {
+                const TAG: u8 = $tag;
+
+                fn write_le<W: Write>(self, writer: &mut W) -> io::Result<()> {
+                    writer.write_all(&self.to_le_bytes())
+                }
+
+                fn read_le<R: Read>(reader: &mut R) -> io::Result<Self> {
+                    let mut buffer = [0; std::mem::size_of::<$ty>()];
+                    reader.read_exact(&mut buffer)?;
+                    Ok(<$ty>::from_le_bytes(buffer))
+                }
+            }
+        )*
+    };
+}
+
+archive_sample! {
+    u8 => 1,
+    u16 => 2,
+    u32 => 3,
+    u64 => 4,
+    i8 => 5,
+    i16 => 6,
+    i32 => 7,
+    i64 => 8,
+    f32 => 9,
+    f64 => 10,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signal() -> Vec<[i16; 2]> {
+        (0..5000_i16)
+            .map(|i| match i % 1000 {
+                0..=99 => [i, -i],
+                _ => [1, 2],
+            })
+            .collect()
+    }
+
+    fn archive(frames: &[[i16; 2]], chunk_size: usize) -> Vec<u8> {
+        let mut writer =
+            ArchiveWriter::new(NoiseGate::new(50, 10), Vec::new(), 48_000)
+                .unwrap();
+
+        for chunk in frames.chunks(chunk_size) {
+            writer.process_frames(chunk).unwrap();
+        }
+
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let frames = signal();
+        let bytes = archive(&frames, 4096);
+
+        let archive = Archive::<[i16; 2]>::read(&bytes[..]).unwrap();
+
+        assert_eq!(archive.sample_rate, 48_000);
+        assert_eq!(archive.total_frames, 5000);
+        let mut gate = NoiseGate::new(50, 10);
+        let expected: Vec<_> = gate
+            .segments(&frames)
+            .map(|(range, _)| range.start as u64..range.end as u64)
+            .collect();
+        assert_eq!(archive.segment_ranges(), expected);
+
+        // everything outside the segments gets replaced with silence
+        let reconstructed = archive.reconstruct();
+        assert_eq!(reconstructed.len(), frames.len());
+        for (i, (got, original)) in
+            reconstructed.iter().zip(&frames).enumerate()
+        {
+            if expected.iter().any(|r| r.contains(&(i as u64))) {
+                assert_eq!(got, original);
+            } else {
+                assert_eq!(*got, [0, 0]);
+            }
+        }
+    }
+
+    #[test]
+    fn chunking_gives_the_same_segments() {
+        let frames = signal();
+        let all_at_once =
+            Archive::<[i16; 2]>::read(&archive(&frames, 4096)[..]).unwrap();
+        let in_chunks =
+            Archive::<[i16; 2]>::read(&archive(&frames, 7)[..]).unwrap();
+
+        assert_eq!(in_chunks, all_at_once);
+    }
+
+    #[test]
+    fn silence_is_tiny() {
+        let frames = vec![[0_i16, 0]; 1_000_000];
+
+        assert_eq!(archive(&frames, 4096).len(), 12 + 9);
+    }
+
+    #[test]
+    fn the_wrong_frame_type_is_rejected() {
+        let bytes = archive(&signal(), 4096);
+
+        let err = Archive::<[f32; 2]>::read(&bytes[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = Archive::<[i16; 1]>::read(&bytes[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn truncated_archives_are_an_error() {
+        let bytes = archive(&signal(), 4096);
+
+        for len in &[0, 5, 12, 13, 30, bytes.len() - 1] {
+            let got = Archive::<[i16; 2]>::read(&bytes[..*len]);
+            assert!(got.is_err(), "{}", len);
+        }
+    }
+}
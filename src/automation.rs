@@ -0,0 +1,224 @@
+//! Exporting the gate's decisions as a gain automation curve, so they can be
+//! applied non-destructively in an editor instead of cutting the recording
+//! into clips.
+//!
+//! ```rust
+//! use noise_gate::automation::{self, Point};
+//!
+//! let curve = automation::gain_curve(&[100..200], 1000, 10);
+//!
+//! assert_eq!(
+//!     curve,
+//!     vec![
+//!         Point::new(0, 0.0),
+//!         Point::new(90, 0.0),
+//!         Point::new(100, 1.0),
+//!         Point::new(200, 1.0),
+//!         Point::new(210, 0.0),
+//!         Point::new(1000, 0.0),
+//!     ]
+//! );
+//!
+//! let mut csv = Vec::new();
+//! automation::write_csv(&curve, 100, &mut csv)?;
+//! assert!(String::from_utf8(csv).unwrap().starts_with("time,gain\n0.000000,0\n0.900000,0\n1.000000,1\n"));
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::{
+    io::{self, Write},
+    ops::Range,
+};
+
+/// A breakpoint in an automation curve, where the gain changes linearly
+/// between consecutive points.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Point {
+    /// When the point happens.
+    pub frame: usize,
+    /// The gain at this point, where `0.0` is silent and `1.0` leaves the
+    /// audio untouched.
+    pub gain: f64,
+}
+
+impl Point {
+    /// Create a new [`Point`].
+    pub const fn new(frame: usize, gain: f64) -> Self { Point { frame, gain } }
+}
+
+/// Turn a set of sorted segments into a gain curve covering `total_frames`
+/// frames.
+///
+/// The gain is `1.0` inside each segment and `0.0` everywhere else, ramping
+/// between the two over `ramp` frames just outside the segment so nothing
+/// the gate kept gets attenuated. Segments which are close enough for their
+/// ramps to overlap are joined, rather than dipping in between.
+pub fn gain_curve(
+    segments: &[Range<usize>],
+    total_frames: usize,
+    ramp: usize,
+) -> Vec<Point> {
+    let mut points = Vec::new();
+    push(&mut points, 0, 0.0, total_frames);
+
+    for segment in merge(segments, ramp) {
+        let end = segment.end.min(total_frames);
+
+        push(
+            &mut points,
+            segment.start.saturating_sub(ramp),
+            0.0,
+            total_frames,
+        );
+        push(&mut points, segment.start, 1.0, total_frames);
+        push(&mut points, end, 1.0, total_frames);
+        if end < total_frames {
+            push(&mut points, end.saturating_add(ramp), 0.0, total_frames);
+        }
+    }
+
+    if points.last().is_none_or(|p| p.frame < total_frames) {
+        push(&mut points, total_frames, 0.0, total_frames);
+    }
+
+    points
+}
+
+/// Add a point to the curve, replacing the previous one if it's at the
+/// same frame.
+fn push(points: &mut Vec<Point>, frame: usize, gain: f64, total_frames: usize) {
+    let frame = frame.min(total_frames);
+
+    match points.last_mut() {
+        Some(last) if last.frame == frame => last.gain = gain,
+        _ => points.push(Point::new(frame, gain)),
+    }
+}
+
+/// Join segments whose ramps would overlap.
+fn merge(segments: &[Range<usize>], ramp: usize) -> Vec<Range<usize>> {
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(segments.len());
+
+    for segment in segments {
+        match merged.last_mut() {
+            Some(last)
+                if segment.start.saturating_sub(last.end)
+                    <= ramp.saturating_mul(2) =>
+            {
+                last.end = last.end.max(segment.end);
+            },
+            _ => merged.push(segment.clone()),
+        }
+    }
+
+    merged
+}
+
+/// Write a curve as CSV, with a header and one `time,gain` row per point
+/// (times are in seconds).
+pub fn write_csv<W: Write>(
+    points: &[Point],
+    sample_rate: u32,
+    mut writer: W,
+) -> io::Result<()> {
+    writeln!(writer, "time,gain")?;
+
+    for point in points {
+        writeln!(
+            writer,
+            "{:.6},{}",
+            seconds(point.frame, sample_rate),
+            point.gain
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Write a curve as a REAPER volume envelope chunk, which can be pasted into
+/// a track in a `.rpp` project file.
+///
+/// Times are in seconds from the start of the item, so the recording should
+/// be placed at the start of the project.
+pub fn write_reaper_envelope<W: Write>(
+    points: &[Point],
+    sample_rate: u32,
+    mut writer: W,
+) -> io::Result<()> {
+    writeln!(writer, "<VOLENV2")?;
+    writeln!(writer, "  ACT 1 -1")?;
+    writeln!(writer, "  VIS 1 1 1")?;
+    writeln!(writer, "  ARM 0")?;
+    writeln!(writer, "  DEFSHAPE 0 -1 -1")?;
+
+    for point in points {
+        writeln!(
+            writer,
+            "  PT {:.6} {:.6} 0",
+            seconds(point.frame, sample_rate),
+            point.gain
+        )?;
+    }
+
+    writeln!(writer, ">")
+}
+
+fn seconds(frame: usize, sample_rate: u32) -> f64 {
+    frame as f64 / f64::from(sample_rate.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn close_segments_are_joined() {
+        let got = gain_curve(&[10..20, 25..30, 100..110], 200, 3);
+
+        assert_eq!(
+            got,
+            vec![
+                Point::new(0, 0.0),
+                Point::new(7, 0.0),
+                Point::new(10, 1.0),
+                Point::new(30, 1.0),
+                Point::new(33, 0.0),
+                Point::new(97, 0.0),
+                Point::new(100, 1.0),
+                Point::new(110, 1.0),
+                Point::new(113, 0.0),
+                Point::new(200, 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn segments_at_the_edges_are_clamped() {
+        let got = gain_curve(&[0..10, 95..100], 100, 5);
+
+        assert_eq!(
+            got,
+            vec![
+                Point::new(0, 1.0),
+                Point::new(10, 1.0),
+                Point::new(15, 0.0),
+                Point::new(90, 0.0),
+                Point::new(95, 1.0),
+                Point::new(100, 1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn reaper_envelope() {
+        let points = [Point::new(0, 0.0), Point::new(24_000, 1.0)];
+        let mut buffer = Vec::new();
+
+        write_reaper_envelope(&points, 48_000, &mut buffer).unwrap();
+
+        let got = String::from_utf8(buffer).unwrap();
+        assert!(got.starts_with("<VOLENV2\n"));
+        assert!(got.contains("  PT 0.000000 0.000000 0\n"));
+        assert!(got.ends_with("  PT 0.500000 1.000000 0\n>\n"));
+    }
+}
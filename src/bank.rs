@@ -0,0 +1,215 @@
+//! Running several thresholds at once to classify segments by how loud they
+//! are.
+
+use crate::{
+    low_level::Level,
+    sinks::{Classifier, Verdict},
+    NoiseGate, Segments,
+};
+use dasp::{Frame, Sample};
+use std::ops::Range;
+
+/// One of the levels in a [`GateBank`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Band<L, S> {
+    /// The label given to segments in this band.
+    pub label: L,
+    /// The level a segment must reach to be in this band.
+    pub threshold: S,
+}
+
+/// A bank of thresholds (e.g. "quiet speech", "normal", and "shouting")
+/// which splits a recording and says which band each segment falls into, in
+/// a single pass.
+///
+/// Segments are found by gating on the lowest threshold, then each segment
+/// is put in the loudest band it reaches.
+///
+/// ```rust
+/// use noise_gate::bank::GateBank;
+///
+/// let mut bank = GateBank::new("quiet", 100_i16, 0)
+///     .with_band("shouting", 5000)
+///     .with_band("normal", 1000);
+///
+/// let frames = [[0], [200], [0], [0], [0], [6000], [1200], [0], [0]];
+/// let bands: Vec<_> = bank
+///     .segments(&frames)
+///     .map(|(range, _, band)| (range, *band))
+///     .collect();
+///
+/// assert_eq!(bands, vec![(1..3, "quiet"), (5..8, "shouting")]);
+/// ```
+///
+/// When audio arrives in real time, a [`GateBank`] can also be used as a
+/// [`Classifier`] so each segment is relabelled with its band.
+///
+/// ```rust
+/// use noise_gate::{bank::GateBank, sinks::Classify};
+///
+/// # struct Discard;
+/// # impl noise_gate::Sink<[i16; 1]> for Discard {
+/// #     fn record(&mut self, _: [i16; 1]) {}
+/// #     fn end_of_transmission(&mut self) {}
+/// # }
+/// let bank = GateBank::new("quiet", 100_i16, 0).with_band("loud", 1000);
+/// let mut gate = bank.gate().clone();
+/// let mut sink = Classify::new(Discard, bank);
+///
+/// gate.process_frames(&[[0], [2000], [0], [0], [0]], &mut sink);
+/// # assert_eq!(
+/// #     sink.classifications()[0].verdict,
+/// #     noise_gate::sinks::Verdict::Relabel("loud")
+/// # );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct GateBank<L, S> {
+    gate: NoiseGate<S>,
+    /// Sorted from quietest to loudest.
+    bands: Vec<Band<L, S>>,
+}
+
+impl<L, S: Sample> GateBank<L, S> {
+    /// Create a [`GateBank`] with a single band.
+    pub fn new(label: L, threshold: S, release_time: usize) -> Self {
+        GateBank {
+            gate: NoiseGate::new(threshold, release_time),
+            bands: vec![Band { label, threshold }],
+        }
+    }
+
+    /// Add another band.
+    pub fn with_band(mut self, label: L, threshold: S) -> Self {
+        let level = magnitude(threshold);
+        let index = self
+            .bands
+            .iter()
+            .position(|band| magnitude(band.threshold) < level)
+            .unwrap_or(self.bands.len());
+        self.bands.insert(index, Band { label, threshold });
+
+        self.gate.open_threshold = self.bands[0].threshold;
+
+        self
+    }
+
+    /// The bands, from quietest to loudest.
+    pub fn bands(&self) -> &[Band<L, S>] { &self.bands }
+
+    /// The gate used to find segments, which uses the quietest band's
+    /// threshold.
+    pub fn gate(&self) -> &NoiseGate<S> { &self.gate }
+
+    /// Get a mutable reference to the gate (e.g. to change how non-finite
+    /// samples are handled).
+    pub fn gate_mut(&mut self) -> &mut NoiseGate<S> { &mut self.gate }
+
+    /// Find the loudest band a segment reaches.
+    ///
+    /// Anything which opened the gate is at least in the quietest band, so
+    /// that is used when no band's threshold is reached.
+    pub fn band_of<F>(&self, segment: &[F]) -> &L
+    where
+        F: Frame<Sample = S>,
+    {
+        band_of(&self.bands, segment)
+    }
+
+    /// Split a buffer into segments, labelling each with its band.
+    ///
+    /// Like [`NoiseGate::segments()`], the gate's state carries across
+    /// calls, and a segment which is still open at the end of the buffer is
+    /// yielded anyway.
+    pub fn segments<'a, F>(
+        &'a mut self,
+        frames: &'a [F],
+    ) -> BankSegments<'a, F, L>
+    where
+        F: Frame<Sample = S>,
+    {
+        BankSegments {
+            segments: self.gate.segments(frames),
+            bands: &self.bands,
+        }
+    }
+}
+
+/// The iterator returned by [`GateBank::segments()`].
+#[derive(Debug)]
+pub struct BankSegments<'a, F: Frame, L> {
+    segments: Segments<'a, F>,
+    bands: &'a [Band<L, F::Sample>],
+}
+
+impl<'a, F: Frame, L> Iterator for BankSegments<'a, F, L> {
+    type Item = (Range<usize>, &'a [F], &'a L);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (range, segment) = self.segments.next()?;
+        Some((range, segment, band_of(self.bands, segment)))
+    }
+}
+
+impl<F, L, S> Classifier<F> for GateBank<L, S>
+where
+    F: Frame<Sample = S>,
+    L: Clone,
+    S: Sample,
+{
+    type Label = L;
+
+    fn classify(&mut self, segment: &[F]) -> Verdict<L> {
+        Verdict::Relabel(self.band_of(segment).clone())
+    }
+}
+
+fn band_of<'b, F: Frame, L>(
+    bands: &'b [Band<L, F::Sample>],
+    segment: &[F],
+) -> &'b L {
+    bands
+        .iter()
+        .rev()
+        .find(|band| {
+            segment
+                .iter()
+                .any(|&frame| Level::of(frame, band.threshold) == Level::Loud)
+        })
+        .map(|band| &band.label)
+        .unwrap_or(&bands[0].label)
+}
+
+/// The negated distance from equilibrium, so louder thresholds are smaller
+/// and signed and unsigned samples can be compared the same way.
+fn magnitude<S: Sample>(threshold: S) -> S::Signed {
+    noise_gate_core::__private::negated_abs(threshold.to_signed_sample())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bands_are_kept_in_order() {
+        let bank = GateBank::new("normal", 1000_i16, 0)
+            .with_band("shouting", -5000)
+            .with_band("quiet", 100);
+
+        let labels: Vec<_> = bank.bands().iter().map(|b| b.label).collect();
+        assert_eq!(labels, vec!["quiet", "normal", "shouting"]);
+        assert_eq!(bank.gate().open_threshold, 100);
+    }
+
+    #[test]
+    fn segments_go_in_the_loudest_band_they_reach() {
+        let bank = GateBank::new(0, 0.1_f32, 0)
+            .with_band(1, 0.5)
+            .with_band(2, 0.9);
+
+        assert_eq!(*bank.band_of(&[[0.2], [0.3]]), 0);
+        assert_eq!(*bank.band_of(&[[0.2], [-0.6]]), 1);
+        assert_eq!(*bank.band_of(&[[0.95], [0.0]]), 2);
+        // anything which opened the gate is at least in the quietest band
+        assert_eq!(*bank.band_of(&[[0.0]]), 0);
+    }
+}
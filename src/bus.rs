@@ -0,0 +1,135 @@
+//! Several gates keyed off one shared sidechain, e.g. gating a whole drum
+//! kit's microphones from a single trigger mic.
+
+use crate::{NoiseGate, Sink};
+use dasp::{Frame, Sample};
+
+/// One gate on a [`SidechainBus`], and where its channel goes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route<S, K> {
+    /// The gate deciding when this channel is passed through.
+    pub gate: NoiseGate<S>,
+    /// Where this channel's audio is sent.
+    pub sink: K,
+}
+
+/// A set of [`NoiseGate`]s which all listen to the same sidechain signal
+/// (the "bus"), while each one passes its own channel through to its own
+/// [`Sink`].
+///
+/// Each route has its own gate, so the channels can use different
+/// thresholds or release times (e.g. letting the toms ring out for longer
+/// than the hi-hat) while still opening and closing in step with the
+/// trigger.
+///
+/// ```rust
+/// use noise_gate::{bus::SidechainBus, NoiseGate};
+///
+/// let trigger = [[0_i16], [900], [0], [0], [0], [0]];
+/// let kick = [[1_i16], [2], [3], [4], [5], [6]];
+/// let snare = [[10_i16], [20], [30], [40], [50], [60]];
+///
+/// let mut bus = SidechainBus::new()
+///     .with_route(NoiseGate::new(100, 0), Vec::new())
+///     .with_route(NoiseGate::new(100, 2), Vec::new());
+///
+/// bus.process(&trigger, &[&kick, &snare]);
+///
+/// let routes = bus.into_routes();
+/// assert_eq!(routes[0].sink, vec![[2], [3]]);
+/// assert_eq!(routes[1].sink, vec![[20], [30], [40], [50]]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct SidechainBus<S, K> {
+    routes: Vec<Route<S, K>>,
+}
+
+impl<S, K> SidechainBus<S, K> {
+    /// Create an empty [`SidechainBus`].
+    pub fn new() -> Self { SidechainBus { routes: Vec::new() } }
+
+    /// Add a route to the bus.
+    pub fn with_route(mut self, gate: NoiseGate<S>, sink: K) -> Self {
+        self.add_route(gate, sink);
+        self
+    }
+
+    /// Add a route to the bus, returning its index.
+    pub fn add_route(&mut self, gate: NoiseGate<S>, sink: K) -> usize {
+        self.routes.push(Route { gate, sink });
+        self.routes.len() - 1
+    }
+
+    /// The routes, in the order they were added.
+    pub fn routes(&self) -> &[Route<S, K>] { &self.routes }
+
+    /// Get mutable access to the routes (e.g. to adjust a gate's threshold).
+    pub fn routes_mut(&mut self) -> &mut [Route<S, K>] { &mut self.routes }
+
+    /// Consume the bus, returning its routes.
+    pub fn into_routes(self) -> Vec<Route<S, K>> { self.routes }
+}
+
+impl<S: Sample, K> SidechainBus<S, K> {
+    /// Run every route's gate over the `sidechain`, passing the `i`'th
+    /// channel through to the `i`'th route's sink.
+    ///
+    /// Like [`NoiseGate::process_sidechain()`], the gates remember their
+    /// state between calls and any extra frames or channels are ignored.
+    pub fn process<F, D>(&mut self, sidechain: &[D], channels: &[&[F]])
+    where
+        F: Frame,
+        D: Frame<Sample = S>,
+        K: Sink<F>,
+    {
+        for (route, channel) in self.routes.iter_mut().zip(channels) {
+            route
+                .gate
+                .process_sidechain(channel, sidechain, &mut route.sink);
+        }
+    }
+}
+
+impl<S, K> Default for SidechainBus<S, K> {
+    fn default() -> Self { SidechainBus::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_route_follows_the_trigger() {
+        let trigger: Vec<[i16; 1]> = (0..100)
+            .map(|i| [if i % 25 < 5 { 1000 } else { 0 }])
+            .collect();
+        let channels: Vec<Vec<[i16; 2]>> = (0..8)
+            .map(|mic| (0..100).map(|i| [mic, i]).collect())
+            .collect();
+        let channel_refs: Vec<&[[i16; 2]]> =
+            channels.iter().map(|c| c.as_slice()).collect();
+        let mut bus = SidechainBus::new();
+        for _ in 0..8 {
+            bus.add_route(NoiseGate::new(500, 3), Vec::new());
+        }
+
+        // streaming in chunks is the same as doing it all at once
+        for start in (0..100).step_by(30) {
+            let end = (start + 30).min(100);
+            let chunks: Vec<&[[i16; 2]]> =
+                channel_refs.iter().map(|c| &c[start..end]).collect();
+            bus.process(&trigger[start..end], &chunks);
+        }
+
+        for (mic, route) in bus.routes().iter().enumerate() {
+            let mut expected = Vec::new();
+            NoiseGate::new(500, 3).process_sidechain(
+                &channels[mic],
+                &trigger,
+                &mut expected,
+            );
+            assert_eq!(route.sink, expected);
+            assert!(route.sink.iter().all(|frame| frame[0] == mic as i16));
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Pluggable sources of wall-clock time, so anything which timestamps events
+//! can be tested deterministically.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Something which can tell the current wall-clock time.
+///
+/// This is implemented for closures, so a test can use a fixed or
+/// incrementing time.
+///
+/// ```rust
+/// use noise_gate::clock::Clock;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let mut ticks = 0;
+/// let mut clock = move || {
+///     ticks += 1;
+///     UNIX_EPOCH + Duration::from_secs(ticks)
+/// };
+///
+/// assert_eq!(clock.now(), UNIX_EPOCH + Duration::from_secs(1));
+/// assert_eq!(clock.now(), UNIX_EPOCH + Duration::from_secs(2));
+/// ```
+pub trait Clock {
+    /// Get the current time.
+    fn now(&mut self) -> SystemTime;
+}
+
+impl<F> Clock for F
+where
+    F: FnMut() -> SystemTime,
+{
+    fn now(&mut self) -> SystemTime { self() }
+}
+
+/// A [`Clock`] which uses the system time.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&mut self) -> SystemTime { SystemTime::now() }
+}
+
+/// Format a time as an RFC 3339 UTC timestamp with millisecond precision
+/// (e.g. `"2012-02-15T01:02:03.450Z"`).
+///
+/// Times before the Unix epoch are clamped to the epoch.
+pub fn rfc3339(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let seconds = seconds % 86_400;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Convert a number of days since the Unix epoch to a `(year, month, day)`
+/// date in the proleptic Gregorian calendar.
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    // Howard Hinnant's civil_from_days()
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn format_rfc3339_timestamps() {
+        assert_eq!(rfc3339(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            rfc3339(UNIX_EPOCH + Duration::from_millis(1_329_267_723_450)),
+            "2012-02-15T01:02:03.450Z"
+        );
+        assert_eq!(
+            rfc3339(UNIX_EPOCH + Duration::from_secs(951_782_400 + 86_399)),
+            "2000-02-29T23:59:59.000Z"
+        );
+    }
+}
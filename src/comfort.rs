@@ -0,0 +1,224 @@
+//! Filling the gaps between transmissions with comfort noise.
+
+use crate::{NoiseGate, Sink};
+use dasp::{sample::Duplex, Frame, Sample};
+use noise_gate_core::__private;
+
+/// Anything quieter than this is flushed to zero so the noise floor estimate
+/// never decays into denormal numbers (which are very slow on some CPUs).
+const DENORMAL_LIMIT: f64 = 1e-30;
+
+/// A [`NoiseGate`] which, instead of sending nothing while closed, replaces
+/// the silence with low-level white noise matched to the recording's noise
+/// floor.
+///
+/// This avoids the "dead line" effect in VoIP calls, where listeners assume
+/// the call has dropped because it went completely silent. Every frame is
+/// passed on to the [`Sink`], but [`Sink::transmission_started()`] and
+/// [`Sink::end_of_transmission()`] are still called whenever the gate opens
+/// and closes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComfortNoise<S> {
+    gate: NoiseGate<S>,
+    /// How loud the comfort noise is, relative to the estimated noise floor.
+    pub level: f64,
+    /// Roughly how many frames the noise floor is averaged over.
+    averaging_time: usize,
+    /// The estimated mean square of the noise floor.
+    mean_square: f64,
+    seed: u32,
+}
+
+impl<S> ComfortNoise<S> {
+    /// Wrap a [`NoiseGate`], estimating the noise floor over (roughly) the
+    /// last `averaging_time` frames of silence.
+    pub fn new(gate: NoiseGate<S>, averaging_time: usize) -> Self {
+        ComfortNoise {
+            gate,
+            level: 1.0,
+            averaging_time: averaging_time.max(1),
+            mean_square: 0.0,
+            seed: 0x2545_f491,
+        }
+    }
+
+    /// The estimated RMS level of the noise floor, where `1.0` is full scale.
+    pub fn noise_floor(&self) -> f64 { self.mean_square.sqrt() }
+
+    /// Get a reference to the underlying [`NoiseGate`].
+    pub fn gate(&self) -> &NoiseGate<S> { &self.gate }
+
+    /// Get a mutable reference to the underlying [`NoiseGate`].
+    pub fn gate_mut(&mut self) -> &mut NoiseGate<S> { &mut self.gate }
+}
+
+impl<S: Sample + Duplex<f64>> ComfortNoise<S> {
+    /// Process a batch of frames, passing noise through to the `sink` and
+    /// replacing everything else with comfort noise.
+    pub fn process_frames<K, F>(&mut self, frames: &[F], sink: &mut K)
+    where
+        F: Frame<Sample = S>,
+        K: Sink<F>,
+    {
+        let mut remaining = frames;
+
+        while !remaining.is_empty() {
+            let run = __private::next_run(&mut self.gate, remaining);
+            let (recorded, rest) = remaining.split_at(run.recorded);
+            let (dropped, rest) = rest.split_at(run.len - run.recorded);
+
+            if !recorded.is_empty() {
+                sink.record_frames(recorded);
+            }
+            if run.closed {
+                sink.end_of_transmission();
+            }
+
+            for &frame in dropped {
+                self.update_noise_floor(frame);
+                let noise = self.next_noise_frame();
+                sink.record(noise);
+            }
+
+            if run.opened {
+                sink.transmission_started(self.gate.position());
+            }
+
+            remaining = rest;
+        }
+    }
+
+    fn update_noise_floor<F>(&mut self, frame: F)
+    where
+        F: Frame<Sample = S>,
+    {
+        let sum: f64 = frame
+            .channels()
+            .map(|sample| {
+                let sample = sample.to_sample::<f64>();
+                sample * sample
+            })
+            .sum();
+        let mean_square = sum / F::CHANNELS as f64;
+
+        // skip NaN and infinity so they can't poison the estimate forever
+        if !mean_square.is_finite() {
+            return;
+        }
+
+        let alpha = 1.0 / self.averaging_time as f64;
+        self.mean_square += (mean_square - self.mean_square) * alpha;
+
+        if self.mean_square < DENORMAL_LIMIT {
+            self.mean_square = 0.0;
+        }
+    }
+
+    fn next_noise_frame<F>(&mut self) -> F
+    where
+        F: Frame<Sample = S>,
+    {
+        // uniform noise between -a and a has an RMS of a/sqrt(3)
+        let amplitude = self.noise_floor() * self.level * 3.0_f64.sqrt();
+
+        F::from_fn(|_| {
+            // xorshift, which is plenty random enough for background noise
+            self.seed ^= self.seed << 13;
+            self.seed ^= self.seed >> 17;
+            self.seed ^= self.seed << 5;
+            let uniform = self.seed as f64 / u32::MAX as f64 * 2.0 - 1.0;
+
+            (uniform * amplitude).to_sample()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct Recorder {
+        frames: Vec<[f64; 2]>,
+        transmissions: usize,
+        starts: Vec<u64>,
+    }
+
+    impl Sink<[f64; 2]> for Recorder {
+        fn record(&mut self, frame: [f64; 2]) { self.frames.push(frame); }
+
+        fn end_of_transmission(&mut self) { self.transmissions += 1; }
+
+        fn transmission_started(&mut self, position: u64) {
+            self.starts.push(position);
+        }
+    }
+
+    fn rms(frames: &[[f64; 2]]) -> f64 {
+        let sum: f64 = frames.iter().map(|[l, r]| l * l + r * r).sum();
+        (sum / (2 * frames.len()) as f64).sqrt()
+    }
+
+    fn signal() -> Vec<[f64; 2]> {
+        let mut seed = 1_u32;
+
+        (0..20_000)
+            .map(|i| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                // a +/- 0.01 square wave has an RMS of exactly 0.01
+                let hiss = if seed & 1 == 0 { 0.01 } else { -0.01 };
+
+                if (5000..6000).contains(&i) {
+                    [0.5, -0.5]
+                } else {
+                    [hiss, -hiss]
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn silence_is_replaced_with_matching_noise() {
+        let frames = signal();
+        let mut gate = ComfortNoise::new(NoiseGate::new(0.1, 100), 1000);
+        let mut sink = Recorder::default();
+
+        gate.process_frames(&frames, &mut sink);
+
+        // every frame is accounted for and the loud bits are untouched
+        assert_eq!(sink.frames.len(), frames.len());
+        assert_eq!(sink.frames[5000..6000], frames[5000..6000]);
+        assert_eq!(sink.transmissions, 1);
+        assert_eq!(sink.starts, vec![5000]);
+
+        let floor = rms(&sink.frames[15_000..]);
+        assert!((floor - 0.01).abs() < 0.001, "{}", floor);
+        assert!((gate.noise_floor() - 0.01).abs() < 1e-6);
+    }
+
+    #[test]
+    fn level_scales_the_noise() {
+        let frames = signal();
+        let mut gate = ComfortNoise::new(NoiseGate::new(0.1, 100), 1000);
+        gate.level = 0.5;
+        let mut sink = Recorder::default();
+
+        gate.process_frames(&frames, &mut sink);
+
+        let floor = rms(&sink.frames[15_000..]);
+        assert!((floor - 0.005).abs() < 0.0005, "{}", floor);
+    }
+
+    #[test]
+    fn digital_silence_gives_digital_silence() {
+        let frames = vec![[0.0, 0.0]; 1000];
+        let mut gate = ComfortNoise::new(NoiseGate::new(0.1, 100), 10);
+        let mut sink = Recorder::default();
+
+        gate.process_frames(&frames, &mut sink);
+
+        assert!(sink.frames.iter().all(|&f| f == [0.0, 0.0]));
+    }
+}
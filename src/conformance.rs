@@ -0,0 +1,264 @@
+//! Canonical test vectors for checking that another implementation of the
+//! gate (an FFI wrapper, a WASM build, a plugin, a port to another
+//! language) behaves exactly like this one.
+//!
+//! Every [`Case`] runs the same interleaved stereo [`INPUT`] through a
+//! gate with a different combination of settings, and lists the
+//! [`Transmission`]s it should produce. The input is plain `f32`s and the
+//! expected values are integers, so both are easy to copy into another
+//! language's test suite.
+//!
+//! ```rust
+//! use noise_gate::conformance::{self, CASES};
+//!
+//! for case in CASES {
+//!     // swap this out for the implementation being tested
+//!     let got = conformance::run(case);
+//!     assert_eq!(got, case.expected, "{}", case.name);
+//! }
+//! ```
+
+use crate::{Detection, InterleavedSink, NoiseGate, NonFinite};
+
+/// The number of channels in [`INPUT`].
+pub const CHANNELS: usize = 2;
+
+/// The interleaved stereo input every [`Case`] is run over.
+///
+/// It contains a frame which is only loud in one channel (and cancels out
+/// when downmixed), a `NaN`, a frame exactly at the threshold, and a
+/// transmission which is still going when the input ends.
+pub const INPUT: &[f32] = &[
+    0.0,
+    0.0, // 0
+    0.6,
+    -0.6, // 1: out of phase, so it cancels when downmixed
+    0.0,
+    0.0, // 2
+    0.0,
+    0.0, // 3
+    0.8,
+    0.8, // 4
+    0.0,
+    0.0, // 5
+    f32::NAN,
+    0.0, // 6
+    0.0,
+    0.0, // 7
+    0.0,
+    0.0, // 8
+    0.0,
+    0.0, // 9
+    0.0,
+    0.0, // 10
+    0.0,
+    0.0, // 11
+    0.5,
+    0.5, // 12: exactly at the threshold, which counts as loud
+    0.0,
+    0.0, // 13
+    0.0,
+    0.0, // 14
+    0.0,
+    0.0, // 15
+];
+
+/// The threshold used by every [`Case`].
+pub const THRESHOLD: f32 = 0.5;
+
+/// One combination of gate settings, and what it should do with
+/// [`INPUT`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Case {
+    /// A short, unique name for the case.
+    pub name: &'static str,
+    /// The gate's [release time][NoiseGate::release_time], in frames.
+    pub release_time: usize,
+    /// What the gate does with `NaN`s.
+    pub non_finite: NonFinite,
+    /// How the gate measures a stereo frame's level.
+    pub detection: Detection,
+    /// The transmissions the gate should produce, in order.
+    pub expected: &'static [Transmission],
+}
+
+/// A single transmission, as seen by a [`Sink`][crate::Sink].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Transmission {
+    /// The index of the frame which opened the gate (the position given to
+    /// [`Sink::transmission_started()`][crate::Sink::transmission_started]).
+    pub opened_at: u64,
+    /// The index of the frame which closed the gate, which is the first
+    /// frame *not* recorded, or `None` if the gate was still open at the
+    /// end of the input.
+    pub closed_at: Option<u64>,
+    /// The number of frames recorded.
+    pub frames: usize,
+}
+
+const fn transmission(
+    opened_at: u64,
+    closed_at: Option<u64>,
+    frames: usize,
+) -> Transmission {
+    Transmission {
+        opened_at,
+        closed_at,
+        frames,
+    }
+}
+
+/// Every combination of release time, [`NonFinite`] and [`Detection`].
+pub const CASES: &[Case] = &[
+    Case {
+        name: "any-channel/nan-loud/release-0",
+        release_time: 0,
+        non_finite: NonFinite::Loud,
+        detection: Detection::AnyChannel,
+        expected: &[
+            transmission(1, Some(3), 2),
+            transmission(4, Some(8), 4),
+            transmission(12, Some(14), 2),
+        ],
+    },
+    Case {
+        name: "any-channel/nan-silent/release-0",
+        release_time: 0,
+        non_finite: NonFinite::Silent,
+        detection: Detection::AnyChannel,
+        expected: &[
+            transmission(1, Some(3), 2),
+            transmission(4, Some(6), 2),
+            transmission(12, Some(14), 2),
+        ],
+    },
+    Case {
+        name: "downmix/nan-loud/release-0",
+        release_time: 0,
+        non_finite: NonFinite::Loud,
+        detection: Detection::Downmix,
+        expected: &[transmission(4, Some(8), 4), transmission(12, Some(14), 2)],
+    },
+    Case {
+        name: "downmix/nan-silent/release-0",
+        release_time: 0,
+        non_finite: NonFinite::Silent,
+        detection: Detection::Downmix,
+        expected: &[transmission(4, Some(6), 2), transmission(12, Some(14), 2)],
+    },
+    Case {
+        name: "any-channel/nan-loud/release-2",
+        release_time: 2,
+        non_finite: NonFinite::Loud,
+        detection: Detection::AnyChannel,
+        expected: &[transmission(1, Some(10), 9), transmission(12, None, 4)],
+    },
+    Case {
+        name: "any-channel/nan-silent/release-2",
+        release_time: 2,
+        non_finite: NonFinite::Silent,
+        detection: Detection::AnyChannel,
+        expected: &[transmission(1, Some(8), 7), transmission(12, None, 4)],
+    },
+    Case {
+        name: "downmix/nan-loud/release-2",
+        release_time: 2,
+        non_finite: NonFinite::Loud,
+        detection: Detection::Downmix,
+        expected: &[transmission(4, Some(10), 6), transmission(12, None, 4)],
+    },
+    Case {
+        name: "downmix/nan-silent/release-2",
+        release_time: 2,
+        non_finite: NonFinite::Silent,
+        detection: Detection::Downmix,
+        expected: &[transmission(4, Some(8), 4), transmission(12, None, 4)],
+    },
+];
+
+/// Run a [`Case`] through this crate's [`NoiseGate`], giving the reference
+/// results.
+pub fn run(case: &Case) -> Vec<Transmission> {
+    let mut gate = NoiseGate::new(THRESHOLD, case.release_time)
+        .with_non_finite(case.non_finite)
+        .with_detection(case.detection);
+    let mut recorder = Recorder::default();
+
+    gate.process_interleaved(INPUT, CHANNELS, &mut recorder);
+
+    recorder.finished
+}
+
+/// Turns the sink callbacks into [`Transmission`]s.
+#[derive(Debug, Default)]
+struct Recorder {
+    finished: Vec<Transmission>,
+}
+
+impl InterleavedSink<f32> for Recorder {
+    fn record_interleaved(&mut self, samples: &[f32], channels: usize) {
+        let current = self.finished.last_mut().expect("Recorded while closed");
+        current.frames += samples.len() / channels;
+    }
+
+    fn end_of_transmission(&mut self) {
+        if let Some(current) = self.finished.last_mut() {
+            current.closed_at = Some(current.opened_at + current.frames as u64);
+        }
+    }
+
+    fn transmission_started(&mut self, position: u64) {
+        self.finished.push(transmission(position, None, 0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sink;
+
+    #[test]
+    fn names_are_unique() {
+        let mut names: Vec<_> = CASES.iter().map(|case| case.name).collect();
+        names.sort_unstable();
+        names.dedup();
+
+        assert_eq!(names.len(), CASES.len());
+    }
+
+    /// The interleaved and frame-based APIs should agree too.
+    #[test]
+    fn frames_give_the_same_transmissions() {
+        #[derive(Default)]
+        struct Frames(Vec<Transmission>);
+
+        impl Sink<[f32; 2]> for Frames {
+            fn record(&mut self, _frame: [f32; 2]) {
+                self.0.last_mut().unwrap().frames += 1;
+            }
+
+            fn end_of_transmission(&mut self) {
+                let current = self.0.last_mut().unwrap();
+                current.closed_at =
+                    Some(current.opened_at + current.frames as u64);
+            }
+
+            fn transmission_started(&mut self, position: u64) {
+                self.0.push(transmission(position, None, 0));
+            }
+        }
+
+        let frames: Vec<[f32; 2]> =
+            INPUT.chunks(CHANNELS).map(|s| [s[0], s[1]]).collect();
+
+        for case in CASES {
+            let mut gate = NoiseGate::new(THRESHOLD, case.release_time)
+                .with_non_finite(case.non_finite)
+                .with_detection(case.detection);
+            let mut sink = Frames::default();
+            gate.process_frames(&frames, &mut sink);
+
+            assert_eq!(sink.0, case.expected, "{}", case.name);
+        }
+    }
+}
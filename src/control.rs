@@ -0,0 +1,303 @@
+//! Adjusting a gate's parameters from another thread while it's running.
+
+use crate::{NoiseGate, Sink};
+use dasp::{sample::Duplex, Frame, Sample};
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc,
+};
+
+/// How many frames are processed between each step while the threshold is
+/// being smoothed.
+const SMOOTHING_BLOCK: usize = 64;
+
+/// Split a [`NoiseGate`] into a [`GateHandle`] which can be used to tweak its
+/// parameters from a UI or control thread, and a [`GateProcessor`] which
+/// does the actual processing on the audio thread.
+///
+/// Changes to the threshold are ramped in over `smoothing_time` frames to
+/// avoid the gate chattering when someone drags a slider around.
+///
+/// ```rust
+/// use noise_gate::{NoiseGate, Sink};
+///
+/// # #[derive(Default)]
+/// # struct Recording(Vec<[i16; 1]>);
+/// # impl Sink<[i16; 1]> for Recording {
+/// #     fn record(&mut self, frame: [i16; 1]) { self.0.push(frame); }
+/// #     fn end_of_transmission(&mut self) {}
+/// # }
+/// let gate = NoiseGate::new(100_i16, 480);
+/// let (handle, mut processor) = noise_gate::control::split(gate, 0);
+///
+/// // on the control thread
+/// handle.set_open_threshold(250);
+///
+/// // on the audio thread
+/// let mut sink = Recording::default();
+/// processor.process_frames(&[[200_i16]], &mut sink);
+///
+/// assert!(processor.gate().is_closed());
+/// ```
+pub fn split<S>(
+    gate: NoiseGate<S>,
+    smoothing_time: usize,
+) -> (GateHandle<S>, GateProcessor<S>)
+where
+    S: Sample + Duplex<f64>,
+{
+    let shared = Arc::new(Shared {
+        open_threshold: AtomicU64::new(
+            gate.open_threshold.to_sample::<f64>().to_bits(),
+        ),
+        release_time: AtomicUsize::new(gate.release_time),
+    });
+
+    let handle = GateHandle {
+        shared: Arc::clone(&shared),
+        _sample: std::marker::PhantomData,
+    };
+    let processor = GateProcessor {
+        current_threshold: gate.open_threshold.to_sample(),
+        gate,
+        shared,
+        smoothing_time,
+        ramp: None,
+    };
+
+    (handle, processor)
+}
+
+/// The parameters which are shared between a [`GateHandle`] and its
+/// [`GateProcessor`].
+#[derive(Debug)]
+struct Shared {
+    /// The bits of an `f64`, because there's no `AtomicF64`.
+    open_threshold: AtomicU64,
+    release_time: AtomicUsize,
+}
+
+/// The control side of a [`NoiseGate`] created with [`split()`].
+///
+/// Updates are lock-free, so the audio thread is never blocked waiting for
+/// the control thread.
+#[derive(Debug)]
+pub struct GateHandle<S> {
+    shared: Arc<Shared>,
+    _sample: std::marker::PhantomData<fn(S)>,
+}
+
+impl<S: Sample + Duplex<f64>> GateHandle<S> {
+    /// Change the level at which the gate opens.
+    pub fn set_open_threshold(&self, open_threshold: S) {
+        let bits = open_threshold.to_sample::<f64>().to_bits();
+        self.shared.open_threshold.store(bits, Ordering::Relaxed);
+    }
+
+    /// The most recently requested open threshold.
+    pub fn open_threshold(&self) -> S {
+        let bits = self.shared.open_threshold.load(Ordering::Relaxed);
+        f64::from_bits(bits).to_sample()
+    }
+
+    /// Change how long (in frames) the gate takes to close.
+    pub fn set_release_time(&self, release_time: usize) {
+        self.shared
+            .release_time
+            .store(release_time, Ordering::Relaxed);
+    }
+
+    /// The most recently requested release time.
+    pub fn release_time(&self) -> usize {
+        self.shared.release_time.load(Ordering::Relaxed)
+    }
+}
+
+impl<S> Clone for GateHandle<S> {
+    fn clone(&self) -> Self {
+        GateHandle {
+            shared: Arc::clone(&self.shared),
+            _sample: std::marker::PhantomData,
+        }
+    }
+}
+
+/// The audio side of a [`NoiseGate`] created with [`split()`].
+///
+/// Like the [`NoiseGate`] itself, processing never allocates or blocks.
+#[derive(Debug)]
+pub struct GateProcessor<S> {
+    gate: NoiseGate<S>,
+    shared: Arc<Shared>,
+    smoothing_time: usize,
+    /// The threshold being used right now, which may lag behind the target
+    /// while smoothing.
+    current_threshold: f64,
+    ramp: Option<Ramp>,
+}
+
+impl<S: Sample + Duplex<f64>> GateProcessor<S> {
+    /// Process a batch of frames, picking up any changes made through the
+    /// [`GateHandle`].
+    pub fn process_frames<K, F>(&mut self, frames: &[F], sink: &mut K)
+    where
+        F: Frame<Sample = S>,
+        K: Sink<F>,
+    {
+        self.gate.release_time =
+            self.shared.release_time.load(Ordering::Relaxed);
+
+        let target =
+            f64::from_bits(self.shared.open_threshold.load(Ordering::Relaxed));
+        self.update_ramp(target);
+
+        let mut remaining = frames;
+
+        // The threshold only changes between blocks while we're smoothing,
+        // so we can go back to processing everything at once when done
+        while self.ramp.is_some() && !remaining.is_empty() {
+            let (block, rest) =
+                remaining.split_at(remaining.len().min(SMOOTHING_BLOCK));
+            self.gate.process_frames(block, sink);
+            self.advance_ramp(block.len());
+            remaining = rest;
+        }
+
+        self.gate.process_frames(remaining, sink);
+    }
+
+    /// The underlying [`NoiseGate`].
+    pub fn gate(&self) -> &NoiseGate<S> { &self.gate }
+
+    /// How many frames it takes for a new threshold to be fully applied.
+    pub fn smoothing_time(&self) -> usize { self.smoothing_time }
+
+    fn update_ramp(&mut self, target: f64) {
+        let current_target = match self.ramp {
+            Some(ramp) => ramp.to,
+            None => self.current_threshold,
+        };
+
+        if target.to_bits() == current_target.to_bits() {
+            return;
+        }
+
+        if self.smoothing_time == 0 {
+            self.set_threshold(target);
+        } else {
+            self.ramp = Some(Ramp {
+                from: self.current_threshold,
+                to: target,
+                elapsed: 0,
+            });
+        }
+    }
+
+    fn advance_ramp(&mut self, frames: usize) {
+        if let Some(mut ramp) = self.ramp.take() {
+            ramp.elapsed += frames;
+
+            if ramp.elapsed >= self.smoothing_time {
+                self.set_threshold(ramp.to);
+            } else {
+                let t = ramp.elapsed as f64 / self.smoothing_time as f64;
+                self.set_threshold(ramp.from + (ramp.to - ramp.from) * t);
+                self.ramp = Some(ramp);
+            }
+        }
+    }
+
+    fn set_threshold(&mut self, threshold: f64) {
+        self.current_threshold = threshold;
+        self.gate.open_threshold = threshold.to_sample();
+    }
+}
+
+/// A linear ramp from one threshold to another.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Ramp {
+    from: f64,
+    to: f64,
+    elapsed: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct Counter {
+        frames: usize,
+        clips: usize,
+    }
+
+    impl<F> Sink<F> for Counter {
+        fn record(&mut self, _: F) { self.frames += 1; }
+
+        fn end_of_transmission(&mut self) { self.clips += 1; }
+    }
+
+    #[test]
+    fn changes_are_applied_immediately_without_smoothing() {
+        let (handle, mut processor) = split(NoiseGate::new(100_i16, 5), 0);
+
+        handle.set_open_threshold(300);
+        handle.set_release_time(42);
+        processor.process_frames::<_, [i16; 1]>(&[], &mut Counter::default());
+
+        assert_eq!(processor.gate().open_threshold, 300);
+        assert_eq!(processor.gate().release_time, 42);
+    }
+
+    #[test]
+    fn threshold_is_ramped_in() {
+        let smoothing_time = 4 * SMOOTHING_BLOCK;
+        let (handle, mut processor) =
+            split(NoiseGate::new(100_i16, 5), smoothing_time);
+        let silence = vec![[0_i16]; SMOOTHING_BLOCK];
+        let mut sink = Counter::default();
+
+        handle.set_open_threshold(500);
+        let mut thresholds = Vec::new();
+        for _ in 0..5 {
+            processor.process_frames(&silence, &mut sink);
+            thresholds.push(processor.gate().open_threshold);
+        }
+
+        assert_eq!(thresholds, vec![200, 300, 400, 500, 500]);
+    }
+
+    #[test]
+    fn retargeting_mid_ramp_starts_from_the_current_threshold() {
+        let smoothing_time = 2 * SMOOTHING_BLOCK;
+        let (handle, mut processor) =
+            split(NoiseGate::new(0.0_f32, 5), smoothing_time);
+        let silence = vec![[0.0_f32]; SMOOTHING_BLOCK];
+        let mut sink = Counter::default();
+
+        handle.set_open_threshold(1.0);
+        processor.process_frames(&silence, &mut sink);
+        assert_eq!(processor.gate().open_threshold, 0.5);
+
+        handle.set_open_threshold(0.0);
+        processor.process_frames(&silence, &mut sink);
+        assert_eq!(processor.gate().open_threshold, 0.25);
+        processor.process_frames(&silence, &mut sink);
+        assert_eq!(processor.gate().open_threshold, 0.0);
+    }
+
+    #[test]
+    fn the_gate_keeps_its_state_when_parameters_change() {
+        let (handle, mut processor) = split(NoiseGate::new(100_i16, 5), 0);
+        let mut sink = Counter::default();
+
+        processor.process_frames(&[[200_i16]], &mut sink);
+        assert!(processor.gate().is_open());
+
+        handle.set_open_threshold(50);
+        processor.process_frames(&[[75_i16]], &mut sink);
+
+        assert!(processor.gate().is_open());
+        assert_eq!(sink.frames, 2);
+    }
+}
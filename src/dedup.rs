@@ -0,0 +1,398 @@
+//! Spotting segments which are near-duplicates of something recorded
+//! recently, like an automated announcement a channel repeats every few
+//! minutes.
+//!
+//! Each segment is reduced to a coarse [`Fingerprint`] of how its spectrum
+//! changes over time, which survives changes in level and background noise.
+//! A [`Deduplicator`] is a [`Classifier`] which remembers the fingerprints
+//! of recent segments, so it can be plugged straight into
+//! [`Classify`][crate::sinks::Classify] to drop (or label) the repeats.
+//!
+//! ```rust
+//! use noise_gate::{dedup::Deduplicator, sinks::{Classify, Verdict}, Sink};
+//! use std::f64::consts::PI;
+//!
+//! let sample_rate = 8000;
+//! // a made-up announcement, which sweeps through a few tones
+//! let announcement = |level: f64| -> Vec<[f64; 1]> {
+//!     (0..4000)
+//!         .map(|i| {
+//!             let frequency = 400.0 + 200.0 * (i / 400) as f64;
+//!             let t = i as f64 / f64::from(sample_rate);
+//!             [level * (2.0 * PI * frequency * t).sin()]
+//!         })
+//!         .collect()
+//! };
+//!
+//! let mut sink = Classify::new(Vec::new(), Deduplicator::new(sample_rate));
+//! sink.record_frames(&announcement(0.5));
+//! sink.end_of_transmission();
+//! // the same announcement again, only quieter
+//! sink.record_frames(&announcement(0.2));
+//! sink.end_of_transmission();
+//!
+//! let classifications = sink.take_classifications();
+//! assert_eq!(classifications[0].verdict, Verdict::Keep);
+//! assert_eq!(classifications[1].verdict, Verdict::Drop);
+//! assert_eq!(sink.inner().len(), 4000);
+//! ```
+
+use crate::{
+    sinks::{Classifier, Verdict},
+    tone::Goertzel,
+};
+use dasp::{sample::Duplex, Frame};
+use std::collections::VecDeque;
+
+/// The number of frequency bands, giving one bit per band.
+const BANDS: usize = 16;
+/// The range of frequencies the bands cover, in Hz, which is where most of
+/// the energy in speech (and announcements) is.
+const LOWEST_BAND: f64 = 300.0;
+const HIGHEST_BAND: f64 = 3000.0;
+/// How long each block of the fingerprint is, in milliseconds.
+const BLOCK_MS: usize = 32;
+/// How many blocks two fingerprints can be shifted by when comparing them,
+/// because the gate won't open at exactly the same point every time.
+const MAX_SHIFT: usize = 4;
+
+/// A coarse spectral hash of a segment, with 16 bits for every 32 ms of
+/// audio.
+///
+/// The spectrum is split into 16 log-spaced bands between 300 Hz and
+/// 3 kHz, and each bit records whether a band was louder than its average
+/// over the whole segment. Only the shape of the spectrum and how it changes
+/// over time matter, so a recording played back quieter or over a noisier
+/// channel gives a similar fingerprint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint {
+    blocks: Vec<u16>,
+}
+
+impl Fingerprint {
+    /// Fingerprint a segment, averaging multi-channel frames down to mono.
+    pub fn new<F>(frames: &[F], sample_rate: u32) -> Self
+    where
+        F: Frame,
+        F::Sample: Duplex<f64>,
+    {
+        let block_size = (sample_rate as usize * BLOCK_MS / 1000).max(1);
+        let mut bins = filter_bank(sample_rate, block_size);
+
+        // the energy in each band, for every block
+        let mut energies: Vec<[f64; BANDS]> = Vec::new();
+        let mut current = [0.0; BANDS];
+        for &frame in frames {
+            let mut finished = false;
+            for (band, bin) in &mut bins {
+                if let Some(magnitude) = bin.process(frame) {
+                    current[*band] += magnitude * magnitude;
+                    finished = true;
+                }
+            }
+            if finished {
+                energies.push(std::mem::replace(&mut current, [0.0; BANDS]));
+            }
+        }
+
+        let mut average = [0.0; BANDS];
+        for block in &energies {
+            for (average, energy) in average.iter_mut().zip(block) {
+                *average += energy / energies.len() as f64;
+            }
+        }
+
+        let blocks = energies
+            .iter()
+            .map(|block| {
+                block
+                    .iter()
+                    .zip(&average)
+                    .enumerate()
+                    .filter(|(_, (energy, average))| energy > average)
+                    .fold(0, |bits, (band, _)| bits | 1 << band)
+            })
+            .collect();
+
+        Fingerprint { blocks }
+    }
+
+    /// The number of 16-bit blocks in the fingerprint.
+    pub fn len(&self) -> usize { self.blocks.len() }
+
+    /// Is the fingerprint empty (e.g. because the segment was too short)?
+    pub fn is_empty(&self) -> bool { self.blocks.is_empty() }
+
+    /// The fraction of bits which differ between two fingerprints, when
+    /// lined up as well as possible.
+    ///
+    /// Unrelated audio usually scores above `0.3`, and repeats of the same
+    /// audio well below `0.2`. Returns `None` if there's nothing to
+    /// compare.
+    pub fn bit_error_rate(&self, other: &Fingerprint) -> Option<f64> {
+        let (short, long) = if self.len() <= other.len() {
+            (&self.blocks, &other.blocks)
+        } else {
+            (&other.blocks, &self.blocks)
+        };
+        if short.is_empty() {
+            return None;
+        }
+
+        let max_shift = MAX_SHIFT.min(short.len() / 2);
+        let bits = |blocks: usize| (blocks * BANDS) as f64;
+        let mut best: Option<f64> = None;
+
+        for shift in 0..=max_shift {
+            for (a, b) in &[(short, long), (long, short)] {
+                // slide `b` along by `shift` blocks
+                let overlap = a.len().min(b.len().saturating_sub(shift));
+                if overlap == 0 {
+                    continue;
+                }
+                let errors: u32 = a[..overlap]
+                    .iter()
+                    .zip(&b[shift..shift + overlap])
+                    .map(|(x, y)| (x ^ y).count_ones())
+                    .sum();
+                let rate = f64::from(errors) / bits(overlap);
+                best = Some(best.map_or(rate, |best| best.min(rate)));
+            }
+        }
+
+        best
+    }
+}
+
+/// One [`Goertzel`] filter for every frequency bin (`sample_rate /
+/// block_size` Hz apart) in the range being fingerprinted, along with the
+/// band it belongs to.
+fn filter_bank(sample_rate: u32, block_size: usize) -> Vec<(usize, Goertzel)> {
+    let spacing = f64::from(sample_rate) / block_size as f64;
+    let highest = HIGHEST_BAND.min(f64::from(sample_rate) * 0.45);
+    let ratio = (highest / LOWEST_BAND).max(1.0);
+    let edge =
+        |band: usize| LOWEST_BAND * ratio.powf(band as f64 / BANDS as f64);
+
+    let mut bins = Vec::new();
+    for band in 0..BANDS {
+        let (low, high) = (edge(band), edge(band + 1));
+        let first = (low / spacing).ceil() as usize;
+        let last = ((high / spacing).ceil() as usize).max(first + 1);
+
+        // narrow bands still get at least one bin
+        for bin in first..last {
+            let frequency = bin as f64 * spacing;
+            bins.push((
+                band,
+                Goertzel::new(frequency, sample_rate, block_size),
+            ));
+        }
+    }
+
+    bins
+}
+
+/// The label a [`Deduplicator`] gives a segment it has heard before.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Duplicate {
+    /// The segment this one is a repeat of, counting from `0`.
+    pub of: usize,
+    /// How different the two segments' fingerprints were (see
+    /// [`Fingerprint::bit_error_rate()`]).
+    pub bit_error_rate: f64,
+}
+
+/// A [`Classifier`] which drops segments that are near-duplicates of a
+/// recent one.
+///
+/// Only segments which were kept are remembered, so a long run of repeats
+/// is compared against the original rather than drifting from one repeat
+/// to the next.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deduplicator {
+    sample_rate: u32,
+    max_bit_error_rate: f64,
+    max_length_ratio: f64,
+    memory: usize,
+    keep_duplicates: bool,
+    seen: VecDeque<(usize, Fingerprint)>,
+    next_segment: usize,
+}
+
+impl Deduplicator {
+    /// Create a [`Deduplicator`] for audio at a particular sample rate,
+    /// which remembers the last 32 segments.
+    pub fn new(sample_rate: u32) -> Self {
+        Deduplicator {
+            sample_rate,
+            max_bit_error_rate: 0.2,
+            max_length_ratio: 1.25,
+            memory: 32,
+            keep_duplicates: false,
+            seen: VecDeque::new(),
+            next_segment: 0,
+        }
+    }
+
+    /// Treat anything with a [`Fingerprint::bit_error_rate()`] at or below
+    /// `max_bit_error_rate` as a duplicate (`0.2` by default).
+    pub fn with_threshold(mut self, max_bit_error_rate: f64) -> Self {
+        self.max_bit_error_rate = max_bit_error_rate;
+        self
+    }
+
+    /// Remember the fingerprints of the last `memory` segments.
+    pub fn with_memory(mut self, memory: usize) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    /// Pass duplicates through, labelling them with [`Verdict::Relabel`]
+    /// instead of dropping them.
+    pub fn keep_duplicates(mut self) -> Self {
+        self.keep_duplicates = true;
+        self
+    }
+
+    /// Forget every segment heard so far.
+    pub fn clear(&mut self) { self.seen.clear(); }
+
+    fn find(&self, fingerprint: &Fingerprint) -> Option<Duplicate> {
+        let similar_length = |seen: &Fingerprint| {
+            let (short, long) = if seen.len() < fingerprint.len() {
+                (seen.len(), fingerprint.len())
+            } else {
+                (fingerprint.len(), seen.len())
+            };
+            long as f64 <= short as f64 * self.max_length_ratio
+        };
+
+        self.seen
+            .iter()
+            .filter(|(_, seen)| similar_length(seen))
+            .filter_map(|(of, seen)| {
+                let bit_error_rate = fingerprint.bit_error_rate(seen)?;
+                Some(Duplicate {
+                    of: *of,
+                    bit_error_rate,
+                })
+            })
+            .filter(|d| d.bit_error_rate <= self.max_bit_error_rate)
+            .min_by(|a, b| a.bit_error_rate.total_cmp(&b.bit_error_rate))
+    }
+}
+
+impl<F> Classifier<F> for Deduplicator
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    type Label = Duplicate;
+
+    fn classify(&mut self, segment: &[F]) -> Verdict<Duplicate> {
+        let index = self.next_segment;
+        self.next_segment += 1;
+        let fingerprint = Fingerprint::new(segment, self.sample_rate);
+
+        match self.find(&fingerprint) {
+            Some(duplicate) if self.keep_duplicates => {
+                Verdict::Relabel(duplicate)
+            },
+            Some(_) => Verdict::Drop,
+            None => {
+                if self.memory > 0 && !fingerprint.is_empty() {
+                    if self.seen.len() >= self.memory {
+                        self.seen.pop_front();
+                    }
+                    self.seen.push_back((index, fingerprint));
+                }
+                Verdict::Keep
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    const SAMPLE_RATE: u32 = 8000;
+
+    /// Something vaguely speech-like, with a few harmonics which move
+    /// around according to `melody`, plus some noise.
+    fn announcement(melody: &[f64], level: f64, seed: u32) -> Vec<[f32; 1]> {
+        let mut seed = seed.max(1);
+        let step = SAMPLE_RATE as usize / 8;
+
+        (0..melody.len() * step)
+            .map(|i| {
+                let t = i as f64 / f64::from(SAMPLE_RATE);
+                let pitch = melody[i / step];
+                let voice: f64 = (1..=4)
+                    .map(|h| (2.0 * PI * pitch * h as f64 * t).sin() / h as f64)
+                    .sum();
+
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                let noise = seed as f64 / u32::MAX as f64 - 0.5;
+
+                [(level * voice * 0.4 + 0.02 * noise) as f32]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn repeats_match_and_other_audio_doesnt() {
+        let melody = [300.0, 450.0, 380.0, 520.0, 600.0, 340.0, 410.0, 700.0];
+        let other = [650.0, 320.0, 540.0, 300.0, 480.0, 720.0, 360.0, 500.0];
+        let original = announcement(&melody, 0.8, 1);
+
+        let fingerprint =
+            |frames: &[[f32; 1]]| Fingerprint::new(frames, SAMPLE_RATE);
+        let a = fingerprint(&original);
+        // a quieter repeat with different noise which starts a bit late
+        let b = fingerprint(&announcement(&melody, 0.3, 7)[100..]);
+        let c = fingerprint(&announcement(&other, 0.8, 1));
+
+        assert_eq!(a.len(), 31);
+        let repeat = a.bit_error_rate(&b).unwrap();
+        let different = a.bit_error_rate(&c).unwrap();
+        assert!(repeat < 0.2, "{}", repeat);
+        assert!(different > 0.3, "{}", different);
+    }
+
+    #[test]
+    fn duplicates_can_be_labelled_instead_of_dropped() {
+        let melody = [300.0, 450.0, 380.0, 520.0, 600.0, 340.0, 410.0, 700.0];
+        let other = [650.0, 320.0, 540.0, 300.0, 480.0, 720.0, 360.0, 500.0];
+        let mut dedup = Deduplicator::new(SAMPLE_RATE).keep_duplicates();
+
+        let verdicts: Vec<_> = [
+            announcement(&melody, 0.8, 1),
+            announcement(&other, 0.8, 2),
+            // too short to fingerprint
+            announcement(&melody[..1], 0.8, 3)[..200].to_vec(),
+            announcement(&melody, 0.5, 4),
+            announcement(&other, 0.6, 5),
+        ]
+        .iter()
+        .map(|segment| dedup.classify(segment))
+        .collect();
+
+        assert_eq!(
+            verdicts[..3],
+            [Verdict::Keep, Verdict::Keep, Verdict::Keep]
+        );
+        assert!(matches!(
+            verdicts[3],
+            Verdict::Relabel(Duplicate { of: 0, .. })
+        ));
+        assert!(matches!(
+            verdicts[4],
+            Verdict::Relabel(Duplicate { of: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,431 @@
+//! Starting, stopping, and tagging recordings with DTMF sequences sent over
+//! the audio, like a repeater controller or IVR monitor would.
+//!
+//! ```rust
+//! use noise_gate::{
+//!     dtmf::{self, Command, DtmfControl},
+//!     NoiseGate,
+//! };
+//!
+//! let sample_rate = 8000;
+//! let mut control = DtmfControl::new(NoiseGate::new(0.05, 800), sample_rate)
+//!     .with_command("*0", Command::Stop)
+//!     .with_command("*1", Command::Start)
+//!     .with_command("#7", Command::Tag("net control"));
+//!
+//! let mut audio = Vec::new();
+//! audio.extend(dtmf::tones("#7", sample_rate));
+//! audio.extend(dtmf::tones("*0", sample_rate));
+//! // nobody wants this bit recorded
+//! audio.extend(vec![[0.5_f32]; 4000]);
+//! audio.extend(dtmf::tones("*1", sample_rate));
+//!
+//! let mut recorded = Vec::new();
+//! control.process_frames(&audio, &mut recorded);
+//!
+//! assert!(control.is_recording());
+//! assert_eq!(control.tags().len(), 1);
+//! assert_eq!(control.tags()[0].label, "net control");
+//! assert!(recorded.len() < audio.len() - 4000);
+//! ```
+
+use crate::{tone::Goertzel, NoiseGate, Sink};
+use dasp::{sample::Duplex, Frame, Sample};
+use std::f64::consts::PI;
+
+/// The low (row) frequencies, in Hz.
+const ROWS: [f64; 4] = [697.0, 770.0, 852.0, 941.0];
+/// The high (column) frequencies, in Hz.
+const COLUMNS: [f64; 4] = [1209.0, 1336.0, 1477.0, 1633.0];
+const KEYPAD: [[char; 4]; 4] = [
+    ['1', '2', '3', 'A'],
+    ['4', '5', '6', 'B'],
+    ['7', '8', '9', 'C'],
+    ['*', '0', '#', 'D'],
+];
+
+/// The tones need to be at least this loud (where a full scale sine is
+/// `1.0`) to count.
+const MIN_AMPLITUDE: f64 = 0.01;
+/// The strongest tone in each group must be this many times louder than the
+/// other tones in its group.
+const MIN_DOMINANCE: f64 = 2.0;
+/// The row and column tones must be within this ratio of each other.
+const MAX_TWIST: f64 = 2.5;
+
+/// Decodes DTMF digits from a stream of audio.
+///
+/// Each digit is reported once, when it has been heard for two blocks in a
+/// row (about 50 ms), so tones need to last a bit longer than that to be
+/// reliably picked up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DtmfDecoder {
+    rows: [Goertzel; 4],
+    columns: [Goertzel; 4],
+    previous: Option<char>,
+    held: Option<char>,
+}
+
+impl DtmfDecoder {
+    /// Create a decoder for audio at a particular sample rate.
+    pub fn new(sample_rate: u32) -> Self {
+        // the classic 205 samples at 8 kHz, which separates adjacent tones
+        let block_size = (205 * sample_rate as usize / 8000).max(1);
+        let detector = |f: f64| Goertzel::new(f, sample_rate, block_size);
+
+        DtmfDecoder {
+            rows: ROWS.map(detector),
+            columns: COLUMNS.map(detector),
+            previous: None,
+            held: None,
+        }
+    }
+
+    /// The number of frames in each detection block.
+    pub fn block_size(&self) -> usize { self.rows[0].block_size() }
+
+    /// Add a frame, returning a digit if one was just pressed.
+    pub fn process<F>(&mut self, frame: F) -> Option<char>
+    where
+        F: Frame,
+        F::Sample: Duplex<f64>,
+    {
+        let mut finished = false;
+        for detector in self.rows.iter_mut().chain(&mut self.columns) {
+            finished |= detector.process(frame).is_some();
+        }
+        if !finished {
+            return None;
+        }
+
+        let digit = self.classify();
+        let pressed = if digit == self.previous && digit != self.held {
+            self.held = digit;
+            digit
+        } else {
+            None
+        };
+        if digit != self.previous {
+            self.held = None;
+        }
+        self.previous = digit;
+
+        pressed
+    }
+
+    /// Decode every digit in a batch of frames.
+    pub fn digits<F>(&mut self, frames: &[F]) -> String
+    where
+        F: Frame,
+        F::Sample: Duplex<f64>,
+    {
+        frames.iter().filter_map(|&f| self.process(f)).collect()
+    }
+
+    fn classify(&self) -> Option<char> {
+        let (row, row_level) = dominant(&self.rows)?;
+        let (column, column_level) = dominant(&self.columns)?;
+        let twist = row_level / column_level;
+
+        if (1.0 / MAX_TWIST..=MAX_TWIST).contains(&twist) {
+            Some(KEYPAD[row][column])
+        } else {
+            None
+        }
+    }
+}
+
+/// The loudest detector in a group, if it stands out from the others.
+fn dominant(detectors: &[Goertzel; 4]) -> Option<(usize, f64)> {
+    let (index, level) = detectors
+        .iter()
+        .map(Goertzel::magnitude)
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+
+    let stands_out = detectors
+        .iter()
+        .enumerate()
+        .all(|(i, d)| i == index || d.magnitude() * MIN_DOMINANCE <= level);
+
+    if level >= MIN_AMPLITUDE && stands_out {
+        Some((index, level))
+    } else {
+        None
+    }
+}
+
+/// Generate a sequence of DTMF digits (100 ms of tone, then 100 ms of
+/// silence each), e.g. for testing.
+///
+/// Characters which aren't on the keypad are skipped.
+pub fn tones(digits: &str, sample_rate: u32) -> Vec<[f32; 1]> {
+    let tone_length = sample_rate as usize / 10;
+    let mut frames = Vec::new();
+
+    for digit in digits.chars() {
+        let position = KEYPAD.iter().enumerate().find_map(|(r, row)| {
+            row.iter().position(|&d| d == digit).map(|c| (r, c))
+        });
+        let (row, column) = match position {
+            Some(position) => position,
+            None => continue,
+        };
+
+        frames.extend((0..tone_length).map(|i| {
+            let t = i as f64 / f64::from(sample_rate);
+            let sample = 0.25 * (2.0 * PI * ROWS[row] * t).sin()
+                + 0.25 * (2.0 * PI * COLUMNS[column] * t).sin();
+            [sample as f32]
+        }));
+        frames.extend(std::iter::repeat_n([0.0], tone_length));
+    }
+
+    frames
+}
+
+/// What to do when a DTMF sequence is heard.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command<L> {
+    /// Start passing audio through the gate.
+    Start,
+    /// Stop recording, ending the current transmission.
+    Stop,
+    /// Remember the current position with a label, e.g. to mark which
+    /// segment something happened in.
+    Tag(L),
+}
+
+/// A labelled position in the stream, added by [`Command::Tag`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tag<L> {
+    /// The frame just after the sequence finished.
+    pub position: u64,
+    /// The command's label.
+    pub label: L,
+}
+
+/// A [`NoiseGate`] which can be started, stopped, and tagged by DTMF
+/// sequences in the audio it's listening to.
+///
+/// The decoder listens to every frame, even when recording is stopped.
+/// Commands take effect at the end of the block where their last digit was
+/// recognised, and the digits typed so far are forgotten if nothing is
+/// pressed for a few seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DtmfControl<S, L> {
+    gate: NoiseGate<S>,
+    decoder: DtmfDecoder,
+    commands: Vec<(String, Command<L>)>,
+    digits: String,
+    last_digit: u64,
+    timeout: u64,
+    recording: bool,
+    position: u64,
+    tags: Vec<Tag<L>>,
+}
+
+impl<S, L> DtmfControl<S, L> {
+    /// Wrap a [`NoiseGate`], starting with recording enabled.
+    pub fn new(gate: NoiseGate<S>, sample_rate: u32) -> Self {
+        DtmfControl {
+            gate,
+            decoder: DtmfDecoder::new(sample_rate),
+            commands: Vec::new(),
+            digits: String::new(),
+            last_digit: 0,
+            timeout: 3 * u64::from(sample_rate),
+            recording: true,
+            position: 0,
+            tags: Vec::new(),
+        }
+    }
+
+    /// Run a [`Command`] whenever a sequence of digits is heard.
+    pub fn with_command(mut self, sequence: &str, command: Command<L>) -> Self {
+        self.commands.push((sequence.to_string(), command));
+        self
+    }
+
+    /// Should recording start off enabled or disabled?
+    pub fn with_recording(self, recording: bool) -> Self {
+        DtmfControl { recording, ..self }
+    }
+
+    /// Is audio currently being passed through the gate?
+    pub fn is_recording(&self) -> bool { self.recording }
+
+    /// The digits which have been heard since the last command.
+    pub fn pending_digits(&self) -> &str { &self.digits }
+
+    /// The tags added so far.
+    pub fn tags(&self) -> &[Tag<L>] { &self.tags }
+
+    /// Remove and return the tags added so far.
+    pub fn take_tags(&mut self) -> Vec<Tag<L>> {
+        std::mem::take(&mut self.tags)
+    }
+
+    /// The number of frames processed so far.
+    pub fn position(&self) -> u64 { self.position }
+
+    /// Get a reference to the [`NoiseGate`].
+    pub fn gate(&self) -> &NoiseGate<S> { &self.gate }
+
+    /// Get a mutable reference to the [`NoiseGate`].
+    pub fn gate_mut(&mut self) -> &mut NoiseGate<S> { &mut self.gate }
+}
+
+impl<S: Sample, L: Clone> DtmfControl<S, L> {
+    /// Listen for commands in a batch of frames, passing them through the
+    /// gate to the `sink` while recording is enabled.
+    pub fn process_frames<K, F>(&mut self, frames: &[F], sink: &mut K)
+    where
+        F: Frame<Sample = S>,
+        F::Sample: Duplex<f64>,
+        K: Sink<F>,
+    {
+        let mut start = 0;
+
+        for (i, &frame) in frames.iter().enumerate() {
+            let position = self.position + i as u64 + 1;
+            let command = match self.decoder.process(frame) {
+                Some(digit) => self.push_digit(digit, position),
+                None => None,
+            };
+
+            if let Some(command) = command {
+                self.forward(&frames[start..=i], sink);
+                start = i + 1;
+                self.run(command, position, sink);
+            }
+        }
+
+        self.forward(&frames[start..], sink);
+        self.position += frames.len() as u64;
+    }
+
+    fn forward<K, F>(&mut self, frames: &[F], sink: &mut K)
+    where
+        F: Frame<Sample = S>,
+        K: Sink<F>,
+    {
+        if self.recording {
+            self.gate.process_frames(frames, sink);
+        }
+    }
+
+    fn push_digit(&mut self, digit: char, position: u64) -> Option<Command<L>> {
+        if position.saturating_sub(self.last_digit) > self.timeout {
+            self.digits.clear();
+        }
+        self.last_digit = position;
+        self.digits.push(digit);
+
+        let (_, command) = self
+            .commands
+            .iter()
+            .find(|(sequence, _)| self.digits.ends_with(sequence.as_str()))?;
+        let command = command.clone();
+        self.digits.clear();
+
+        Some(command)
+    }
+
+    fn run<K, F>(&mut self, command: Command<L>, position: u64, sink: &mut K)
+    where
+        K: Sink<F>,
+    {
+        match command {
+            Command::Start => self.recording = true,
+            Command::Stop => {
+                self.recording = false;
+                self.gate.force_close(sink);
+            },
+            Command::Tag(label) => self.tags.push(Tag { position, label }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_every_digit() {
+        let digits = "0123456789*#ABCD";
+        let mut decoder = DtmfDecoder::new(8000);
+
+        let got = decoder.digits(&tones(digits, 8000));
+
+        assert_eq!(got, digits);
+    }
+
+    #[test]
+    fn works_at_other_sample_rates() {
+        let mut decoder = DtmfDecoder::new(48_000);
+
+        assert_eq!(decoder.block_size(), 1230);
+        assert_eq!(decoder.digits(&tones("911", 48_000)), "911");
+    }
+
+    #[test]
+    fn single_tones_and_noise_arent_digits() {
+        let single: Vec<[f32; 1]> = (0..8000)
+            .map(|i| {
+                [(0.5 * (2.0 * PI * 697.0 * i as f64 / 8000.0).sin()) as f32]
+            })
+            .collect();
+        let noise: Vec<[f32; 1]> = (0..8000_u32)
+            .map(|i| {
+                [(i.wrapping_mul(2_654_435_761) >> 16) as f32 / 65536.0 - 0.5]
+            })
+            .collect();
+        let mut decoder = DtmfDecoder::new(8000);
+
+        assert_eq!(decoder.digits(&single), "");
+        assert_eq!(decoder.digits(&noise), "");
+    }
+
+    #[derive(Debug, Default)]
+    struct Clips(Vec<usize>, usize);
+
+    impl Sink<[f32; 1]> for Clips {
+        fn record(&mut self, _: [f32; 1]) { self.1 += 1; }
+
+        fn end_of_transmission(&mut self) {
+            self.0.push(std::mem::take(&mut self.1));
+        }
+    }
+
+    #[test]
+    fn stopping_closes_the_current_transmission() {
+        let mut control = DtmfControl::new(NoiseGate::new(0.05, 8000), 8000)
+            .with_command("*0", Command::<()>::Stop);
+        let mut clips = Clips::default();
+
+        control.process_frames(&tones("1*0", 8000), &mut clips);
+
+        assert!(!control.is_recording());
+        assert_eq!(clips.0.len(), 1);
+        // everything up to the end of the "0" was recorded
+        assert!(clips.0[0] > 3200 + 205 && clips.0[0] < 3200 + 800);
+        assert_eq!(control.pending_digits(), "");
+    }
+
+    #[test]
+    fn stale_digits_are_forgotten() {
+        let mut control = DtmfControl::new(NoiseGate::new(0.05, 0), 8000)
+            .with_command("12", Command::Tag(1))
+            .with_recording(false);
+        let mut audio = tones("1", 8000);
+        audio.extend(vec![[0.0]; 4 * 8000]);
+        audio.extend(tones("2", 8000));
+        audio.extend(tones("12", 8000));
+
+        control.process_frames(&audio, &mut Clips::default());
+
+        assert_eq!(control.tags().len(), 1);
+        assert!(control.tags()[0].position > audio.len() as u64 - 1600);
+    }
+}
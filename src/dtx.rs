@@ -0,0 +1,218 @@
+//! Deciding which packets in a VoIP stream are worth forwarding, like
+//! discontinuous transmission (DTX) but done server-side (e.g. in an SFU,
+//! after the Opus packets have been decoded).
+
+use crate::{InterleavedSink, NoiseGate, Sink};
+use dasp::{Frame, Sample};
+
+/// What to do with a packet of audio.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PacketDecision {
+    /// Someone is talking, forward the packet as normal.
+    Send,
+    /// Nothing worth hearing, the packet can be dropped.
+    Suppress,
+    /// Nothing worth hearing, but send a comfort noise (SID) update so the
+    /// receiver keeps its background noise going and knows the stream is
+    /// still alive.
+    ComfortNoise,
+}
+
+/// A [`NoiseGate`] which looks at a stream one packet (typically 20 ms) at a
+/// time and decides whether each packet needs to be sent.
+///
+/// A packet is sent if the gate lets any of it through, so the gate's
+/// release time acts as the hangover which stops the ends of words being
+/// clipped. While the gate is closed packets are suppressed, except for a
+/// comfort noise update when the talker goes quiet and then every
+/// [`PacketGate::comfort_noise_interval`] packets after that.
+///
+/// The gate remembers its state between packets, so packets must be passed
+/// in the order they were captured.
+///
+/// ```rust
+/// use noise_gate::{
+///     dtx::{PacketDecision, PacketGate},
+///     NoiseGate,
+/// };
+///
+/// // 20 ms packets of 48 kHz mono audio, with a 50 ms hangover
+/// let mut gate = PacketGate::new(NoiseGate::new(0.1, 2400));
+/// let speech = [[0.5_f32]; 960];
+/// let silence = [[0.0_f32]; 960];
+///
+/// assert_eq!(gate.decide(&silence), PacketDecision::ComfortNoise);
+/// assert_eq!(gate.decide(&silence), PacketDecision::Suppress);
+/// assert_eq!(gate.decide(&speech), PacketDecision::Send);
+///
+/// // the hangover runs into the next 3 packets
+/// for _ in 0..3 {
+///     assert_eq!(gate.decide(&silence), PacketDecision::Send);
+/// }
+///
+/// assert_eq!(gate.decide(&silence), PacketDecision::ComfortNoise);
+/// assert_eq!(gate.decide(&silence), PacketDecision::Suppress);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PacketGate<S> {
+    gate: NoiseGate<S>,
+    /// How many packets to wait between comfort noise updates while the
+    /// gate is closed (the default is `20`, or 400 ms of 20 ms packets), or
+    /// `None` to never send them.
+    pub comfort_noise_interval: Option<usize>,
+    /// The number of packets suppressed since the last comfort noise
+    /// update, or `None` if there hasn't been one since the gate closed.
+    since_comfort_noise: Option<usize>,
+}
+
+impl<S> PacketGate<S> {
+    /// Decide which packets to send using a [`NoiseGate`].
+    pub fn new(gate: NoiseGate<S>) -> Self {
+        PacketGate {
+            gate,
+            comfort_noise_interval: Some(20),
+            since_comfort_noise: None,
+        }
+    }
+
+    /// Set [`PacketGate::comfort_noise_interval`].
+    pub fn with_comfort_noise_interval(self, interval: Option<usize>) -> Self {
+        PacketGate {
+            comfort_noise_interval: interval,
+            ..self
+        }
+    }
+
+    /// Get a reference to the underlying [`NoiseGate`].
+    pub fn gate(&self) -> &NoiseGate<S> { &self.gate }
+
+    /// Get a mutable reference to the underlying [`NoiseGate`].
+    pub fn gate_mut(&mut self) -> &mut NoiseGate<S> { &mut self.gate }
+
+    /// Start again from scratch (e.g. when a new participant joins on the
+    /// same stream).
+    pub fn reset(&mut self) {
+        self.gate.reset();
+        self.since_comfort_noise = None;
+    }
+
+    fn decision(&mut self, heard: bool) -> PacketDecision {
+        if heard {
+            self.since_comfort_noise = None;
+            return PacketDecision::Send;
+        }
+
+        let interval = match self.comfort_noise_interval {
+            Some(interval) => interval.max(1),
+            None => return PacketDecision::Suppress,
+        };
+
+        match self.since_comfort_noise {
+            Some(suppressed) if suppressed + 1 < interval => {
+                self.since_comfort_noise = Some(suppressed + 1);
+                PacketDecision::Suppress
+            },
+            _ => {
+                self.since_comfort_noise = Some(0);
+                PacketDecision::ComfortNoise
+            },
+        }
+    }
+}
+
+impl<S: Sample> PacketGate<S> {
+    /// Decide what to do with the next packet.
+    pub fn decide<F>(&mut self, packet: &[F]) -> PacketDecision
+    where
+        F: Frame<Sample = S>,
+    {
+        let mut heard = Heard(false);
+        self.gate.process_frames(packet, &mut heard);
+        self.decision(heard.0)
+    }
+
+    /// Decide what to do with the next packet, where the decoder gives
+    /// interleaved samples and the number of channels is only known at
+    /// runtime.
+    pub fn decide_interleaved(
+        &mut self,
+        samples: &[S],
+        channels: usize,
+    ) -> PacketDecision {
+        let mut heard = Heard(false);
+        self.gate.process_interleaved(samples, channels, &mut heard);
+        self.decision(heard.0)
+    }
+}
+
+/// A sink which only notes whether the gate let anything through.
+#[derive(Debug)]
+struct Heard(bool);
+
+impl<F> Sink<F> for Heard {
+    fn record(&mut self, _frame: F) { self.0 = true; }
+
+    fn record_frames(&mut self, frames: &[F])
+    where
+        F: Copy,
+    {
+        self.0 |= !frames.is_empty();
+    }
+
+    fn end_of_transmission(&mut self) {}
+}
+
+impl<S> InterleavedSink<S> for Heard {
+    fn record_interleaved(&mut self, samples: &[S], _channels: usize) {
+        self.0 |= !samples.is_empty();
+    }
+
+    fn end_of_transmission(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comfort_noise_is_sent_periodically_while_closed() {
+        let mut gate = PacketGate::new(NoiseGate::new(100_i16, 0))
+            .with_comfort_noise_interval(Some(3));
+        let mut packet = vec![0_i16; 2 * 160];
+        packet[41] = 500;
+        let silence = vec![0_i16; 2 * 160];
+
+        let mut got = vec![gate.decide_interleaved(&packet, 2)];
+        for _ in 0..7 {
+            got.push(gate.decide_interleaved(&silence, 2));
+        }
+
+        use PacketDecision::*;
+        assert_eq!(
+            got,
+            vec![
+                Send,
+                ComfortNoise,
+                Suppress,
+                Suppress,
+                ComfortNoise,
+                Suppress,
+                Suppress,
+                ComfortNoise,
+            ]
+        );
+    }
+
+    #[test]
+    fn comfort_noise_can_be_turned_off() {
+        let mut gate = PacketGate::new(NoiseGate::new(100_i16, 0))
+            .with_comfort_noise_interval(None);
+        let mut speech = [[0_i16]; 160];
+        speech[0] = [500];
+        let silence = [[0_i16]; 160];
+
+        assert_eq!(gate.decide(&silence), PacketDecision::Suppress);
+        assert_eq!(gate.decide(&speech), PacketDecision::Send);
+        assert_eq!(gate.decide(&silence), PacketDecision::Suppress);
+    }
+}
@@ -0,0 +1,403 @@
+//! Comparing the segments a gate detected against a hand-labelled reference,
+//! so parameters can be tuned (and detector changes validated) objectively.
+//!
+//! ```rust
+//! use noise_gate::eval;
+//!
+//! let reference = eval::parse_audacity_labels("0.5\t1.5\tspeech\n3\t4\t\n")?;
+//! let reference: Vec<_> = reference.iter().map(|l| l.to_frames(10)).collect();
+//! let detected = vec![6..15, 20..22];
+//!
+//! let evaluation = eval::evaluate(&detected, &reference);
+//!
+//! assert_eq!(evaluation.segments.true_positives, 1);
+//! assert_eq!(evaluation.segments.false_positives, 1);
+//! assert_eq!(evaluation.segments.false_negatives, 1);
+//! assert_eq!(evaluation.frames.true_positives, 9);
+//! assert_eq!(evaluation.starts.mean, 1.0);
+//! # Ok::<(), eval::LabelError>(())
+//! ```
+
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    ops::Range,
+};
+
+/// A labelled region, as exported from Audacity's *Export Labels* menu.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    /// When the region starts, in seconds.
+    pub start: f64,
+    /// When the region ends, in seconds.
+    pub end: f64,
+    /// The label's text (possibly empty).
+    pub text: String,
+}
+
+impl Label {
+    /// The frames covered by this label, at a particular sample rate.
+    pub fn to_frames(&self, sample_rate: u32) -> Range<usize> {
+        let to_frames = |seconds: f64| {
+            (seconds.max(0.0) * f64::from(sample_rate)).round() as usize
+        };
+        let start = to_frames(self.start);
+
+        start..to_frames(self.end).max(start)
+    }
+}
+
+/// Why a label file couldn't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelError {
+    /// The line the error was on, starting from `1`.
+    pub line: usize,
+    /// What was wrong with it.
+    pub message: String,
+}
+
+impl Display for LabelError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for LabelError {}
+
+/// Parse an Audacity label track, where each line is a tab-separated start
+/// time, end time, and label text.
+///
+/// Blank lines are skipped, as are the `\` lines Audacity uses for the
+/// frequency range of spectral selections.
+pub fn parse_audacity_labels(src: &str) -> Result<Vec<Label>, LabelError> {
+    let mut labels = Vec::new();
+
+    for (i, line) in src.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('\\') {
+            continue;
+        }
+
+        let error = |message: String| LabelError {
+            line: i + 1,
+            message,
+        };
+        let mut fields = line.splitn(3, '\t');
+        let mut time = |name: &str| {
+            let field = fields.next().unwrap_or("").trim();
+            field
+                .parse::<f64>()
+                .ok()
+                .filter(|t| t.is_finite())
+                .ok_or_else(|| {
+                    error(format!("Invalid {} time, \"{}\"", name, field))
+                })
+        };
+
+        let start = time("start")?;
+        let end = time("end")?;
+        let text = fields.next().unwrap_or("").trim_end().to_string();
+
+        if end < start {
+            return Err(error(String::from("The label ends before it starts")));
+        }
+
+        labels.push(Label { start, end, text });
+    }
+
+    Ok(labels)
+}
+
+/// Counts of agreement between the detected and reference segments.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Scores {
+    /// Things which were detected and are in the reference.
+    pub true_positives: usize,
+    /// Things which were detected, but aren't in the reference.
+    pub false_positives: usize,
+    /// Things in the reference which weren't detected.
+    pub false_negatives: usize,
+}
+
+impl Scores {
+    /// The fraction of detections which were correct, or `1.0` if there
+    /// weren't any.
+    pub fn precision(&self) -> f64 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_positives,
+        )
+    }
+
+    /// The fraction of the reference which was detected, or `1.0` if the
+    /// reference is empty.
+    pub fn recall(&self) -> f64 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_negatives,
+        )
+    }
+
+    /// The harmonic mean of [`Scores::precision()`] and
+    /// [`Scores::recall()`].
+    pub fn f1(&self) -> f64 {
+        let (precision, recall) = (self.precision(), self.recall());
+
+        if precision + recall == 0.0 {
+            0.0
+        } else {
+            2.0 * precision * recall / (precision + recall)
+        }
+    }
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        1.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+/// Statistics about how far detected boundaries were from the reference, in
+/// frames.
+///
+/// Errors are `detected - reference`, so a positive mean start error means
+/// segments tend to start late.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct BoundaryErrors {
+    /// How many boundaries were compared.
+    pub count: usize,
+    /// The mean signed error.
+    pub mean: f64,
+    /// The mean absolute error.
+    pub mean_absolute: f64,
+    /// The largest absolute error.
+    pub max_absolute: usize,
+}
+
+impl BoundaryErrors {
+    fn from_errors(errors: &[i64]) -> Self {
+        if errors.is_empty() {
+            return BoundaryErrors::default();
+        }
+
+        let count = errors.len();
+        let sum: i64 = errors.iter().sum();
+        let absolute: u64 = errors.iter().map(|e| e.unsigned_abs()).sum();
+        let max_absolute =
+            errors.iter().map(|e| e.unsigned_abs()).max().unwrap_or(0);
+
+        BoundaryErrors {
+            count,
+            mean: sum as f64 / count as f64,
+            mean_absolute: absolute as f64 / count as f64,
+            max_absolute: max_absolute as usize,
+        }
+    }
+}
+
+/// The result of comparing detected segments with a reference.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Evaluation {
+    /// Segment-level scores, where a detected segment is correct if it
+    /// overlaps a reference segment which hasn't already been matched.
+    pub segments: Scores,
+    /// Frame-level scores, counting individual frames.
+    pub frames: Scores,
+    /// How far the start of each matched segment was from the reference.
+    pub starts: BoundaryErrors,
+    /// How far the end of each matched segment was from the reference.
+    pub ends: BoundaryErrors,
+    /// Each matched pair of `(detected, reference)` indices.
+    pub matches: Vec<(usize, usize)>,
+}
+
+/// Compare detected segments with a reference, where both are sorted,
+/// non-overlapping ranges of frames.
+///
+/// Each detected segment is matched with at most one reference segment (the
+/// first one it overlaps which hasn't been matched yet), so one long
+/// detection spanning two reference segments counts as one hit and one
+/// miss.
+pub fn evaluate(
+    detected: &[Range<usize>],
+    reference: &[Range<usize>],
+) -> Evaluation {
+    let mut matches = Vec::new();
+    let mut next_reference = 0;
+
+    for (d, segment) in detected.iter().enumerate() {
+        // skip anything which ends before this segment starts
+        while next_reference < reference.len()
+            && reference[next_reference].end <= segment.start
+        {
+            next_reference += 1;
+        }
+
+        if let Some(r) = reference.get(next_reference) {
+            if overlap(segment, r) > 0 {
+                matches.push((d, next_reference));
+                next_reference += 1;
+            }
+        }
+    }
+
+    let segments = Scores {
+        true_positives: matches.len(),
+        false_positives: detected.len() - matches.len(),
+        false_negatives: reference.len() - matches.len(),
+    };
+
+    let overlapping = overlapping_frames(detected, reference);
+    let frames = Scores {
+        true_positives: overlapping,
+        false_positives: total_frames(detected) - overlapping,
+        false_negatives: total_frames(reference) - overlapping,
+    };
+
+    let error = |a: usize, b: usize| a as i64 - b as i64;
+    let starts: Vec<i64> = matches
+        .iter()
+        .map(|&(d, r)| error(detected[d].start, reference[r].start))
+        .collect();
+    let ends: Vec<i64> = matches
+        .iter()
+        .map(|&(d, r)| error(detected[d].end, reference[r].end))
+        .collect();
+
+    Evaluation {
+        segments,
+        frames,
+        starts: BoundaryErrors::from_errors(&starts),
+        ends: BoundaryErrors::from_errors(&ends),
+        matches,
+    }
+}
+
+fn overlap(a: &Range<usize>, b: &Range<usize>) -> usize {
+    a.end.min(b.end).saturating_sub(a.start.max(b.start))
+}
+
+fn total_frames(segments: &[Range<usize>]) -> usize {
+    segments.iter().map(|s| s.end.saturating_sub(s.start)).sum()
+}
+
+/// The number of frames covered by both sets of segments.
+fn overlapping_frames(a: &[Range<usize>], b: &[Range<usize>]) -> usize {
+    let (mut i, mut j) = (0, 0);
+    let mut total = 0;
+
+    while i < a.len() && j < b.len() {
+        total += overlap(&a[i], &b[j]);
+
+        if a[i].end < b[j].end {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_labels() {
+        let src = "1.5\t2.25\tHello, World\n\\\t100\t200\n\n3\t3\t\n";
+
+        let got = parse_audacity_labels(src).unwrap();
+
+        assert_eq!(
+            got,
+            vec![
+                Label {
+                    start: 1.5,
+                    end: 2.25,
+                    text: String::from("Hello, World"),
+                },
+                Label {
+                    start: 3.0,
+                    end: 3.0,
+                    text: String::new(),
+                },
+            ]
+        );
+        assert_eq!(got[0].to_frames(8000), 12000..18000);
+    }
+
+    #[test]
+    fn invalid_labels_say_which_line_they_were_on() {
+        let err = parse_audacity_labels("1\t2\tok\n2\tabc\n").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert_eq!(err.to_string(), "Line 2: Invalid end time, \"abc\"");
+
+        let err = parse_audacity_labels("5\t4\n").unwrap_err();
+        assert_eq!(err.message, "The label ends before it starts");
+    }
+
+    #[test]
+    fn perfect_detection() {
+        let segments = vec![0..10, 20..30];
+
+        let got = evaluate(&segments, &segments);
+
+        assert_eq!(got.segments.f1(), 1.0);
+        assert_eq!(got.frames.f1(), 1.0);
+        assert_eq!(got.starts.max_absolute, 0);
+        assert_eq!(got.matches, vec![(0, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn one_detection_spanning_two_references() {
+        let detected = vec![Range { start: 0, end: 30 }];
+        let reference = vec![0..10, 20..30];
+
+        let got = evaluate(&detected, &reference);
+
+        assert_eq!(
+            got.segments,
+            Scores {
+                true_positives: 1,
+                false_positives: 0,
+                false_negatives: 1,
+            }
+        );
+        assert_eq!(
+            got.frames,
+            Scores {
+                true_positives: 20,
+                false_positives: 10,
+                false_negatives: 0,
+            }
+        );
+        assert_eq!(got.ends.mean, 20.0);
+    }
+
+    #[test]
+    fn boundary_errors_are_signed() {
+        let detected = vec![2..10, 18..33];
+        let reference = vec![0..10, 20..30];
+
+        let got = evaluate(&detected, &reference);
+
+        assert_eq!(got.starts.mean, 0.0);
+        assert_eq!(got.starts.mean_absolute, 2.0);
+        assert_eq!(got.ends.mean, 1.5);
+        assert_eq!(got.ends.max_absolute, 3);
+    }
+
+    #[test]
+    fn empty_inputs() {
+        let got = evaluate(&[], &[]);
+        assert_eq!(got.segments.precision(), 1.0);
+        assert_eq!(got.segments.recall(), 1.0);
+
+        let detected = [0..5, 10..15];
+        let got = evaluate(&detected, &[]);
+        assert_eq!(got.segments.precision(), 0.0);
+        assert_eq!(got.segments.f1(), 0.0);
+    }
+}
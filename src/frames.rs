@@ -0,0 +1,187 @@
+//! Grouping a stream of interleaved samples (e.g. from a decoder like
+//! [`hound`][hound]) into [`Frame`]s the gate can process.
+//!
+//! ```rust,no_run
+//! use noise_gate::{frames, NoiseGate};
+//!
+//! let reader = hound::WavReader::open("recording.wav")?;
+//! assert_eq!(reader.spec().channels, 2);
+//!
+//! let recording: Vec<[i16; 2]> =
+//!     frames::try_from_samples(reader.into_samples::<i16>())
+//!         .collect::<Result<_, _>>()?;
+//!
+//! let mut clips = Vec::new();
+//! NoiseGate::new(300, 4800).process_frames(&recording, &mut clips);
+//! # Ok::<(), hound::Error>(())
+//! ```
+//!
+//! If there aren't enough samples left to fill the last frame, it's
+//! dropped.
+//!
+//! [hound]: https://docs.rs/hound
+
+use dasp::{Frame, Sample};
+use std::marker::PhantomData;
+
+/// Group interleaved samples into frames of `F::CHANNELS` samples each.
+///
+/// ```rust
+/// use noise_gate::frames;
+///
+/// let samples = vec![1_i16, 2, 3, 4, 5];
+///
+/// let stereo: Vec<[i16; 2]> = frames::from_samples(samples).collect();
+///
+/// assert_eq!(stereo, vec![[1, 2], [3, 4]]);
+/// ```
+pub fn from_samples<F, I>(samples: I) -> FromSamples<F, I::IntoIter>
+where
+    F: Frame,
+    I: IntoIterator<Item = F::Sample>,
+{
+    FromSamples {
+        samples: samples.into_iter(),
+        _frame: PhantomData,
+    }
+}
+
+/// Group interleaved samples into frames, where reading each sample may
+/// fail (e.g. `hound::WavReader::into_samples()`).
+///
+/// If any sample in a frame can't be read, the error is returned instead of
+/// that frame.
+pub fn try_from_samples<F, I, E>(
+    samples: I,
+) -> TryFromSamples<F, I::IntoIter>
+where
+    F: Frame,
+    I: IntoIterator<Item = Result<F::Sample, E>>,
+{
+    TryFromSamples {
+        samples: samples.into_iter(),
+        _frame: PhantomData,
+    }
+}
+
+/// The iterator returned by [`from_samples()`].
+#[derive(Debug, Clone)]
+pub struct FromSamples<F, I> {
+    samples: I,
+    _frame: PhantomData<F>,
+}
+
+impl<F, I> FromSamples<F, I> {
+    /// Consume the adapter, returning the samples which haven't been read
+    /// yet.
+    pub fn into_inner(self) -> I { self.samples }
+}
+
+impl<F, I> Iterator for FromSamples<F, I>
+where
+    F: Frame,
+    I: Iterator<Item = F::Sample>,
+{
+    type Item = F;
+
+    fn next(&mut self) -> Option<F> {
+        let mut complete = true;
+
+        let frame = F::from_fn(|_| match self.samples.next() {
+            Some(sample) => sample,
+            None => {
+                complete = false;
+                F::Sample::EQUILIBRIUM
+            },
+        });
+
+        if complete {
+            Some(frame)
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.samples.size_hint();
+        (lower / F::CHANNELS, upper.map(|upper| upper / F::CHANNELS))
+    }
+}
+
+/// The iterator returned by [`try_from_samples()`].
+#[derive(Debug, Clone)]
+pub struct TryFromSamples<F, I> {
+    samples: I,
+    _frame: PhantomData<F>,
+}
+
+impl<F, I> TryFromSamples<F, I> {
+    /// Consume the adapter, returning the samples which haven't been read
+    /// yet.
+    pub fn into_inner(self) -> I { self.samples }
+}
+
+impl<F, I, E> Iterator for TryFromSamples<F, I>
+where
+    F: Frame,
+    I: Iterator<Item = Result<F::Sample, E>>,
+{
+    type Item = Result<F, E>;
+
+    fn next(&mut self) -> Option<Result<F, E>> {
+        let mut error = None;
+        let mut complete = true;
+
+        // Note: keep reading after an error so the next frame still starts
+        // on a frame boundary
+        let frame = F::from_fn(|_| match self.samples.next() {
+            Some(Ok(sample)) => sample,
+            Some(Err(e)) => {
+                error.get_or_insert(e);
+                F::Sample::EQUILIBRIUM
+            },
+            None => {
+                complete = false;
+                F::Sample::EQUILIBRIUM
+            },
+        });
+
+        match error {
+            Some(e) => Some(Err(e)),
+            None if complete => Some(Ok(frame)),
+            None => None,
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.samples.size_hint();
+        // an error in the last, incomplete frame is still returned
+        (
+            lower / F::CHANNELS,
+            upper.map(|upper| upper.div_ceil(F::CHANNELS)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_are_returned_in_place_of_the_frame() {
+        let samples = vec![Ok(1_i16), Err("oops"), Ok(3), Ok(4), Ok(5)];
+
+        let frames: Vec<Result<[i16; 2], _>> =
+            try_from_samples(samples).collect();
+
+        assert_eq!(frames, vec![Err("oops"), Ok([3, 4])]);
+    }
+
+    #[test]
+    fn mono_frames_wrap_each_sample() {
+        let frames = from_samples::<[f32; 1], _>(vec![0.5, -0.5]);
+
+        assert_eq!(frames.size_hint(), (2, Some(2)));
+        assert_eq!(frames.collect::<Vec<_>>(), vec![[0.5], [-0.5]]);
+    }
+}
@@ -0,0 +1,464 @@
+//! Streaming gated segments to a speech pipeline over gRPC, using
+//! [`tonic`].
+//!
+//! The gate does exactly the "endpointing" streaming speech-to-text services
+//! want, so every segment from a [`SegmentStream`] is sent as a run of
+//! [`AudioChunk`]s on a single bidirectional stream. Results come back as
+//! [`Transcript`]s. The service is described in `proto/noise_gate.proto`,
+//! which servers (or a proxy in front of a cloud API) can implement.
+//!
+//! The audio thread never touches the network. It hands chunks to a
+//! [`ChunkSender`] without blocking, and the async side passes the matching
+//! [`ChunkStream`] to [`SegmentClient::stream_segments()`].
+//!
+//! ```rust,no_run
+//! use noise_gate::{
+//!     grpc::{self, SegmentClient},
+//!     sinks::SegmentStream,
+//!     NoiseGate,
+//! };
+//!
+//! # struct Discard;
+//! # impl noise_gate::Sink<[i16; 1]> for Discard {
+//! #     fn record(&mut self, _: [i16; 1]) {}
+//! #     fn end_of_transmission(&mut self) {}
+//! # }
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let (mut sender, chunks) = grpc::channel(64, 16_000, 1);
+//!
+//! // on the audio thread
+//! let mut sink = SegmentStream::new(Discard, 1600, move |e| sender.send(e));
+//! let mut gate = NoiseGate::new(300_i16, 4_000);
+//! # let frames = [[0_i16]; 16];
+//! gate.process_frames(&frames, &mut sink);
+//!
+//! // somewhere async
+//! let mut client = SegmentClient::connect("http://localhost:50051").await?;
+//! let mut transcripts =
+//!     client.stream_segments(chunks).await?.into_inner();
+//!
+//! while let Some(transcript) = transcripts.message().await? {
+//!     println!("{}: {}", transcript.segment, transcript.text);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::sinks::SegmentEvent;
+use futures_core::Stream;
+use std::{
+    convert::TryInto,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+use tokio::sync::mpsc;
+use tonic::{
+    client::Grpc,
+    codec::ProstCodec,
+    codegen::{http::uri::PathAndQuery, StdError},
+    transport::{Channel, Endpoint},
+    Request, Response, Status, Streaming,
+};
+
+#[cfg(doc)]
+use crate::sinks::SegmentStream;
+
+/// Part of a segment, as sent to the `SegmentStreaming` service.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AudioChunk {
+    /// Which segment this chunk belongs to, starting from `0`.
+    #[prost(uint64, tag = "1")]
+    pub segment: u64,
+    /// What happened.
+    #[prost(oneof = "audio_chunk::Event", tags = "2, 3, 4, 5")]
+    pub event: Option<audio_chunk::Event>,
+}
+
+/// Types nested inside an [`AudioChunk`].
+pub mod audio_chunk {
+    /// What an [`AudioChunk`][super::AudioChunk] is telling the server.
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Event {
+        /// A new segment has started.
+        #[prost(message, tag = "2")]
+        Start(super::SegmentStart),
+        /// Interleaved `LINEAR16` samples, little-endian.
+        #[prost(bytes = "vec", tag = "3")]
+        Audio(Vec<u8>),
+        /// The segment is complete.
+        #[prost(message, tag = "4")]
+        End(super::SegmentEnd),
+        /// Chunks from the segment were dropped, so it should be thrown
+        /// away (see [`SegmentCancel`][super::SegmentCancel]).
+        #[prost(message, tag = "5")]
+        Cancel(super::SegmentCancel),
+    }
+}
+
+/// The start of a segment.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SegmentStart {
+    /// Where the segment started in the input, in frames.
+    #[prost(uint64, optional, tag = "1")]
+    pub position: Option<u64>,
+    /// The audio's sample rate.
+    #[prost(uint32, tag = "2")]
+    pub sample_rate: u32,
+    /// The number of interleaved channels.
+    #[prost(uint32, tag = "3")]
+    pub channels: u32,
+}
+
+/// The end of a segment.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SegmentEnd {
+    /// The segment's total length in frames.
+    #[prost(uint64, tag = "1")]
+    pub frames: u64,
+}
+
+/// Tells the server that chunks from a segment were dropped, so whatever
+/// arrived for it (and for any earlier segment which hasn't ended) should be
+/// thrown away.
+///
+/// Nothing else is sent for the segment.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SegmentCancel {}
+
+/// A result sent back by the `SegmentStreaming` service.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Transcript {
+    /// The segment this result is for.
+    #[prost(uint64, tag = "1")]
+    pub segment: u64,
+    /// What was said.
+    #[prost(string, tag = "2")]
+    pub text: String,
+    /// Will this segment's text change any more?
+    #[prost(bool, tag = "3")]
+    pub is_final: bool,
+}
+
+/// Create a [`ChunkSender`] for the audio thread and the [`ChunkStream`]
+/// it feeds, buffering up to `capacity` chunks.
+pub fn channel(
+    capacity: usize,
+    sample_rate: u32,
+    channels: usize,
+) -> (ChunkSender, ChunkStream) {
+    let (tx, rx) = mpsc::channel(capacity.max(1));
+    let sender = ChunkSender {
+        tx,
+        sample_rate,
+        channels: channels as u32,
+        dropped: Arc::new(AtomicUsize::new(0)),
+        dropping: None,
+        cancel: None,
+    };
+
+    (sender, ChunkStream { rx })
+}
+
+/// Converts [`SegmentEvent`]s into [`AudioChunk`]s and queues them for the
+/// [`ChunkStream`], without ever blocking.
+///
+/// If the network can't keep up and the queue fills, chunks are dropped and
+/// counted (see [`ChunkSender::dropped()`]) instead of stalling the audio
+/// thread. A segment with a gap in it is no use to anyone, so once one of
+/// its chunks is dropped the rest of the segment is dropped too, and a
+/// [`SegmentCancel`] is sent as soon as there's room.
+#[derive(Debug, Clone)]
+pub struct ChunkSender {
+    tx: mpsc::Sender<AudioChunk>,
+    sample_rate: u32,
+    channels: u32,
+    dropped: Arc<AtomicUsize>,
+    /// The segment whose chunks are being thrown away.
+    dropping: Option<u64>,
+    /// The latest segment which still needs a [`SegmentCancel`].
+    cancel: Option<u64>,
+}
+
+impl ChunkSender {
+    /// Queue an event to be sent.
+    pub fn send(&mut self, event: SegmentEvent) {
+        let segment = event.segment() as u64;
+
+        if self.dropping == Some(segment) {
+            self.drop_chunk();
+            self.send_cancel();
+            return;
+        }
+        self.dropping = None;
+
+        // the server never heard about a segment whose start was dropped
+        let needs_cancel = !matches!(event, SegmentEvent::Start { .. });
+        // the cancel has to go out before anything from a later segment
+        let sent =
+            self.send_cancel() && self.tx.try_send(self.chunk(event)).is_ok();
+
+        if !sent {
+            self.drop_chunk();
+            self.dropping = Some(segment);
+            if needs_cancel {
+                self.cancel = Some(segment);
+            }
+        }
+    }
+
+    /// How many chunks were dropped because the queue was full or the
+    /// stream was closed.
+    pub fn dropped(&self) -> usize { self.dropped.load(Ordering::Relaxed) }
+
+    fn drop_chunk(&self) { self.dropped.fetch_add(1, Ordering::Relaxed); }
+
+    /// Try to send any outstanding [`SegmentCancel`], returning `true` if
+    /// there's nothing left to cancel.
+    fn send_cancel(&mut self) -> bool {
+        let segment = match self.cancel {
+            Some(segment) => segment,
+            None => return true,
+        };
+        let chunk = AudioChunk {
+            segment,
+            event: Some(audio_chunk::Event::Cancel(SegmentCancel {})),
+        };
+
+        if self.tx.try_send(chunk).is_ok() {
+            self.cancel = None;
+        }
+
+        self.cancel.is_none()
+    }
+
+    fn chunk(&self, event: SegmentEvent) -> AudioChunk {
+        let segment = event.segment() as u64;
+        let event = match event {
+            SegmentEvent::Start { position, .. } => {
+                audio_chunk::Event::Start(SegmentStart {
+                    position,
+                    sample_rate: self.sample_rate,
+                    channels: self.channels,
+                })
+            },
+            SegmentEvent::Audio { samples, .. } => audio_chunk::Event::Audio(
+                samples.iter().flat_map(|s| s.to_le_bytes()).collect(),
+            ),
+            SegmentEvent::End { frames, .. } => {
+                audio_chunk::Event::End(SegmentEnd {
+                    frames: frames as u64,
+                })
+            },
+        };
+
+        AudioChunk {
+            segment,
+            event: Some(event),
+        }
+    }
+}
+
+/// The [`AudioChunk`]s queued by a [`ChunkSender`], as a [`Stream`] which
+/// can be used as a streaming gRPC request.
+///
+/// The stream ends once every [`ChunkSender`] has been dropped.
+#[derive(Debug)]
+pub struct ChunkStream {
+    rx: mpsc::Receiver<AudioChunk>,
+}
+
+impl Stream for ChunkStream {
+    type Item = AudioChunk;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<AudioChunk>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+/// A client for the `noise_gate.v1.SegmentStreaming` service.
+#[derive(Debug, Clone)]
+pub struct SegmentClient {
+    inner: Grpc<Channel>,
+}
+
+impl SegmentClient {
+    /// Connect to a server (e.g. `"http://localhost:50051"`).
+    pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+    where
+        D: TryInto<Endpoint>,
+        D::Error: Into<StdError>,
+    {
+        let channel = Endpoint::new(dst)?.connect().await?;
+        Ok(SegmentClient::new(channel))
+    }
+
+    /// Create a client which uses an existing [`Channel`] (e.g. one set up
+    /// with TLS or authentication).
+    pub fn new(channel: Channel) -> Self {
+        SegmentClient {
+            inner: Grpc::new(channel),
+        }
+    }
+
+    /// Stream segments to the server, getting back a stream of
+    /// [`Transcript`]s.
+    pub async fn stream_segments<S>(
+        &mut self,
+        chunks: S,
+    ) -> Result<Response<Streaming<Transcript>>, Status>
+    where
+        S: Stream<Item = AudioChunk> + Send + 'static,
+    {
+        self.inner.ready().await.map_err(|e| {
+            Status::unavailable(format!("Service was not ready: {}", e))
+        })?;
+
+        let path = PathAndQuery::from_static(
+            "/noise_gate.v1.SegmentStreaming/StreamSegments",
+        );
+
+        self.inner
+            .streaming(Request::new(chunks), path, ProstCodec::default())
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+    use std::task::Waker;
+
+    fn next(stream: &mut ChunkStream) -> Poll<Option<AudioChunk>> {
+        let mut cx = Context::from_waker(Waker::noop());
+        Pin::new(stream).poll_next(&mut cx)
+    }
+
+    #[test]
+    fn events_become_chunks() {
+        let (mut sender, mut chunks) = channel(8, 16_000, 2);
+
+        sender.send(SegmentEvent::Start {
+            segment: 3,
+            position: Some(42),
+        });
+        sender.send(SegmentEvent::Audio {
+            segment: 3,
+            samples: vec![1, -2],
+        });
+        sender.send(SegmentEvent::End {
+            segment: 3,
+            frames: 1,
+        });
+        drop(sender);
+
+        let got: Vec<_> = std::iter::from_fn(|| match next(&mut chunks) {
+            Poll::Ready(chunk) => chunk,
+            Poll::Pending => panic!("The stream should never block"),
+        })
+        .collect();
+
+        assert_eq!(
+            got,
+            vec![
+                AudioChunk {
+                    segment: 3,
+                    event: Some(audio_chunk::Event::Start(SegmentStart {
+                        position: Some(42),
+                        sample_rate: 16_000,
+                        channels: 2,
+                    })),
+                },
+                AudioChunk {
+                    segment: 3,
+                    event: Some(audio_chunk::Event::Audio(vec![
+                        1, 0, 0xFE, 0xFF
+                    ])),
+                },
+                AudioChunk {
+                    segment: 3,
+                    event: Some(audio_chunk::Event::End(SegmentEnd {
+                        frames: 1
+                    })),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn chunks_are_dropped_instead_of_blocking() {
+        let (mut sender, mut chunks) = channel(1, 8000, 1);
+
+        for _ in 0..3 {
+            sender.send(SegmentEvent::End {
+                segment: 0,
+                frames: 0,
+            });
+        }
+
+        assert_eq!(sender.dropped(), 2);
+        assert!(matches!(next(&mut chunks), Poll::Ready(Some(_))));
+        assert!(matches!(next(&mut chunks), Poll::Pending));
+    }
+
+    #[test]
+    fn segments_straddling_the_queue_limit_are_cancelled() {
+        let (mut sender, mut chunks) = channel(2, 8000, 1);
+        let audio = |segment| SegmentEvent::Audio {
+            segment,
+            samples: vec![1],
+        };
+
+        sender.send(SegmentEvent::Start {
+            segment: 0,
+            position: None,
+        });
+        sender.send(audio(0));
+        // the queue is full
+        sender.send(audio(0));
+        assert!(matches!(next(&mut chunks), Poll::Ready(Some(_))));
+        // there's room again, but the segment already has a gap in it
+        sender.send(audio(0));
+        sender.send(SegmentEvent::End {
+            segment: 0,
+            frames: 3,
+        });
+        assert!(matches!(next(&mut chunks), Poll::Ready(Some(_))));
+        sender.send(SegmentEvent::Start {
+            segment: 1,
+            position: None,
+        });
+
+        let events: Vec<_> = std::iter::from_fn(|| match next(&mut chunks) {
+            Poll::Ready(Some(chunk)) => Some((chunk.segment, chunk.event?)),
+            _ => None,
+        })
+        .collect();
+        assert!(matches!(
+            events[..],
+            [
+                (0, audio_chunk::Event::Cancel(_)),
+                (1, audio_chunk::Event::Start(_)),
+            ]
+        ));
+        assert_eq!(sender.dropped(), 3);
+    }
+
+    #[test]
+    fn wire_format_matches_the_proto() {
+        let chunk = AudioChunk {
+            segment: 1,
+            event: Some(audio_chunk::Event::Audio(vec![0xAB])),
+        };
+
+        // field 1 (varint) = 1, field 3 (bytes) = [0xAB]
+        assert_eq!(chunk.encode_to_vec(), vec![0x08, 1, 0x1A, 1, 0xAB]);
+    }
+}
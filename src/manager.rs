@@ -0,0 +1,291 @@
+//! Gating many independent sources at once (e.g. one per SIP call or per
+//! radio channel), keeping track of which gate and [`Sink`] belongs to each.
+
+use crate::{
+    low_level::State,
+    metrics::{Instrumented, Metrics},
+    NoiseGate, Sink,
+};
+use dasp::{Frame, Sample};
+use noise_gate_core::__private;
+use std::{borrow::Borrow, collections::HashMap, hash::Hash};
+
+/// One source's gate, and where its audio goes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Source<S, K> {
+    /// The gate for this source, which also measures what it's doing.
+    pub gate: Instrumented<S>,
+    /// Where this source's audio is sent.
+    pub sink: K,
+}
+
+/// Statistics for every source in a [`GateManager`].
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Stats {
+    /// The number of sources being gated.
+    pub sources: usize,
+    /// How many of those sources currently have an open gate.
+    pub open_sources: usize,
+    /// Every source's [`Metrics`] added together, where the
+    /// [`Metrics::peak_level`] and [`Metrics::envelope`] are the loudest
+    /// source's and the [`Metrics::position`] is the furthest any source
+    /// has got.
+    pub metrics: Metrics,
+}
+
+/// A set of independent [`NoiseGate`]s keyed by a source id, which all share
+/// the same configuration.
+///
+/// The first time audio arrives for a new id, a gate is created from the
+/// shared configuration and `new_sink` is asked for the [`Sink`] its audio
+/// should go to.
+///
+/// ```rust
+/// use noise_gate::{manager::GateManager, NoiseGate};
+///
+/// let mut manager =
+///     GateManager::new(NoiseGate::new(100_i16, 0), |_: &&str| Vec::new());
+///
+/// manager.process(&"call-1", &[[0], [500], [0], [0]]);
+/// manager.process(&"call-2", &[[0], [0], [0], [900]]);
+///
+/// assert_eq!(manager.source(&"call-1").unwrap().sink, vec![[500], [0]]);
+/// assert_eq!(manager.source(&"call-2").unwrap().sink, vec![[900]]);
+///
+/// let stats = manager.stats();
+/// assert_eq!(stats.sources, 2);
+/// assert_eq!(stats.open_sources, 1);
+/// assert_eq!(stats.metrics.frames, 8);
+/// assert_eq!(stats.metrics.transmissions, 1);
+/// ```
+#[derive(Debug, Clone)]
+pub struct GateManager<Id, S, K, N> {
+    config: NoiseGate<S>,
+    new_sink: N,
+    sources: HashMap<Id, Source<S, K>>,
+}
+
+impl<Id, S, K, N> GateManager<Id, S, K, N>
+where
+    Id: Eq + Hash,
+    N: FnMut(&Id) -> K,
+{
+    /// Create an empty [`GateManager`], where each source's gate is a copy of
+    /// `config`.
+    ///
+    /// The gate's current state is ignored, so new sources always start off
+    /// closed.
+    pub fn new(config: NoiseGate<S>, new_sink: N) -> Self {
+        GateManager {
+            config,
+            new_sink,
+            sources: HashMap::new(),
+        }
+    }
+}
+
+impl<Id, S, K, N> GateManager<Id, S, K, N>
+where
+    Id: Eq + Hash,
+{
+    /// The configuration shared by every source's gate.
+    pub fn config(&self) -> &NoiseGate<S> { &self.config }
+
+    /// The number of sources being gated.
+    pub fn len(&self) -> usize { self.sources.len() }
+
+    /// Are there no sources?
+    pub fn is_empty(&self) -> bool { self.sources.is_empty() }
+
+    /// Look up a source.
+    pub fn source<Q>(&self, id: &Q) -> Option<&Source<S, K>>
+    where
+        Id: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.sources.get(id)
+    }
+
+    /// Get mutable access to a source (e.g. to give it a different
+    /// threshold).
+    pub fn source_mut<Q>(&mut self, id: &Q) -> Option<&mut Source<S, K>>
+    where
+        Id: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.sources.get_mut(id)
+    }
+
+    /// Iterate over every source, in no particular order.
+    pub fn sources(&self) -> impl Iterator<Item = (&Id, &Source<S, K>)> {
+        self.sources.iter()
+    }
+
+    /// Aggregate statistics for every source.
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats::default();
+
+        for source in self.sources.values() {
+            stats.sources += 1;
+            if source.gate.gate().is_open() {
+                stats.open_sources += 1;
+            }
+            add(&mut stats.metrics, source.gate.metrics());
+        }
+
+        stats
+    }
+
+    /// Stop gating a source (e.g. because the call hung up), ending its
+    /// transmission if the gate was open.
+    pub fn remove<Q, F>(&mut self, id: &Q) -> Option<Source<S, K>>
+    where
+        Id: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+        K: Sink<F>,
+    {
+        let mut source = self.sources.remove(id)?;
+        source.gate.gate_mut().force_close(&mut source.sink);
+        Some(source)
+    }
+
+    /// Let a source's gate know some of its input was lost (see
+    /// [`NoiseGate::frames_dropped()`]).
+    pub fn frames_dropped<Q, F>(&mut self, id: &Q, count: u64)
+    where
+        Id: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+        K: Sink<F>,
+    {
+        if let Some(source) = self.sources.get_mut(id) {
+            source.gate.frames_dropped(count, &mut source.sink);
+        }
+    }
+
+    /// Consume the manager, returning every source.
+    pub fn into_sources(self) -> HashMap<Id, Source<S, K>> { self.sources }
+}
+
+impl<Id, S, K, N> GateManager<Id, S, K, N>
+where
+    Id: Eq + Hash,
+    S: Clone,
+{
+    /// Change the shared configuration, updating every existing source's
+    /// threshold, release time, and detection settings without closing any
+    /// of their gates.
+    pub fn set_config(&mut self, config: NoiseGate<S>) {
+        for source in self.sources.values_mut() {
+            let gate = source.gate.gate_mut();
+            gate.open_threshold = config.open_threshold.clone();
+            gate.release_time = config.release_time;
+            gate.non_finite = config.non_finite;
+            gate.detection = config.detection;
+        }
+
+        self.config = config;
+    }
+}
+
+impl<Id, S, K, N> GateManager<Id, S, K, N>
+where
+    Id: Eq + Hash + Clone,
+    S: Sample,
+    N: FnMut(&Id) -> K,
+{
+    /// Pass a batch of frames from one source through its gate, adding the
+    /// source if this is the first time it has been seen.
+    ///
+    /// Like [`NoiseGate::process_frames()`], each gate remembers its state
+    /// between calls.
+    pub fn process<F>(&mut self, id: &Id, frames: &[F])
+    where
+        F: Frame<Sample = S>,
+        K: Sink<F>,
+    {
+        if !self.sources.contains_key(id) {
+            let mut gate = self.config.clone();
+            __private::set_state(&mut gate, State::Closed);
+            let source = Source {
+                gate: Instrumented::new(gate),
+                sink: (self.new_sink)(id),
+            };
+            self.sources.insert(id.clone(), source);
+        }
+
+        if let Some(source) = self.sources.get_mut(id) {
+            source.gate.process_frames(frames, &mut source.sink);
+        }
+    }
+}
+
+fn add(total: &mut Metrics, metrics: &Metrics) {
+    total.frames += metrics.frames;
+    total.open_frames += metrics.open_frames;
+    total.closing_frames += metrics.closing_frames;
+    total.closed_frames += metrics.closed_frames;
+    total.transmissions += metrics.transmissions;
+    total.position = total.position.max(metrics.position);
+    total.dropped_frames += metrics.dropped_frames;
+    total.peak_level = match (total.peak_level, metrics.peak_level) {
+        (Some(total), Some(level)) => Some(total.max(level)),
+        (total, level) => total.or(level),
+    };
+    total.envelope = match (total.envelope, metrics.envelope) {
+        (Some(total), Some(level)) => Some(total.max(level)),
+        (total, level) => total.or(level),
+    };
+    total.sink_errors += metrics.sink_errors;
+    total.processing_time += metrics.processing_time;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sources_are_gated_independently() {
+        let mut created = Vec::new();
+        let mut manager = GateManager::new(NoiseGate::new(100_i16, 1), |id| {
+            created.push(*id);
+            Vec::new()
+        });
+
+        for i in 0..30_i16 {
+            let channel = u32::from(i as u16 % 3);
+            let level = if i == 4 || i == 10 { 500 } else { i };
+            manager.process(&channel, &[[level]]);
+        }
+        let quiet = manager.remove(&0).unwrap();
+        let busy = manager.remove(&1).unwrap();
+
+        // frames 4 and 10 both belong to the second source
+        assert_eq!(quiet.sink, Vec::<[i16; 1]>::new());
+        assert_eq!(busy.sink, vec![[500], [7], [500], [13], [16]]);
+        assert_eq!(busy.gate.metrics().transmissions, 1);
+        assert_eq!(manager.len(), 1);
+        drop(manager);
+        assert_eq!(created, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn changing_the_config_keeps_gates_open() {
+        let mut manager =
+            GateManager::new(NoiseGate::new(100_i16, 0), |_: &u8| Vec::new());
+        manager.process(&7, &[[500_i16]]);
+
+        manager.set_config(NoiseGate::new(1000, 5));
+        manager.process(&7, &[[0], [0]]);
+        manager.process(&8, &[[500]]);
+        manager.frames_dropped(&8, 10);
+
+        let stats = manager.stats();
+        assert_eq!(stats.sources, 2);
+        assert_eq!(stats.open_sources, 1);
+        assert_eq!(stats.metrics.dropped_frames, 10);
+        assert_eq!(stats.metrics.position, 11);
+        assert_eq!(manager.source(&7).unwrap().sink, vec![[500], [0], [0]]);
+        assert!(manager.source(&8).unwrap().sink.is_empty());
+        assert_eq!(manager.config().release_time, 5);
+    }
+}
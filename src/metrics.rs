@@ -0,0 +1,696 @@
+//! Measuring how well the gate keeps up with real-time, and publishing what
+//! it's doing to monitoring systems like [Prometheus][prometheus].
+//!
+//! [`Metrics::to_prometheus()`] formats everything for a `/metrics` endpoint
+//! directly. With the `metrics` feature enabled, an [`Instrumented`] gate can
+//! also publish to whichever [`metrics`][facade] recorder the service has
+//! installed (see `Instrumented::with_metrics()`).
+//!
+//! [prometheus]: https://prometheus.io/
+//! [facade]: https://crates.io/crates/metrics
+
+use crate::{low_level::State, NoiseGate, Sink};
+use dasp::{Frame, Sample};
+use noise_gate_core::__private;
+use std::{
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+/// A [`NoiseGate`] which keeps track of how much audio it has processed and
+/// how long that took.
+///
+/// ```rust
+/// use noise_gate::{metrics::Instrumented, NoiseGate, Sink};
+///
+/// # struct Ignore;
+/// # impl Sink<[i16; 1]> for Ignore {
+/// #     fn record(&mut self, _: [i16; 1]) {}
+/// #     fn end_of_transmission(&mut self) {}
+/// # }
+/// let mut gate = Instrumented::new(NoiseGate::new(100_i16, 2));
+///
+/// gate.process_frames(&[[0], [500], [0], [0], [0], [0]], &mut Ignore);
+///
+/// let metrics = gate.metrics();
+/// assert_eq!(metrics.frames, 6);
+/// assert_eq!(metrics.open_frames, 2);
+/// assert_eq!(metrics.closing_frames, 3);
+/// assert_eq!(metrics.closed_frames, 1);
+/// assert_eq!(metrics.transmissions, 1);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instrumented<S> {
+    gate: NoiseGate<S>,
+    metrics: Metrics,
+    track_peak_level: bool,
+    track_envelope: bool,
+    envelope: f64,
+    #[cfg(feature = "metrics")]
+    published: Option<Published>,
+}
+
+impl<S> Instrumented<S> {
+    /// Start measuring a [`NoiseGate`].
+    pub fn new(gate: NoiseGate<S>) -> Self {
+        Instrumented {
+            gate,
+            metrics: Metrics::default(),
+            track_peak_level: false,
+            track_envelope: false,
+            envelope: 0.0,
+            #[cfg(feature = "metrics")]
+            published: None,
+        }
+    }
+
+    /// Also measure [`Metrics::peak_level`].
+    ///
+    /// This is off by default because it means looking at every sample a
+    /// second time.
+    pub fn with_peak_level(self) -> Self {
+        Instrumented {
+            track_peak_level: true,
+            ..self
+        }
+    }
+
+    /// Also measure [`Metrics::envelope`].
+    ///
+    /// Like [`Instrumented::with_peak_level()`], this means looking at every
+    /// sample a second time.
+    pub fn with_envelope(self) -> Self {
+        Instrumented {
+            track_envelope: true,
+            ..self
+        }
+    }
+
+    /// Publish the metrics using the [`metrics`][facade] crate after every
+    /// batch of frames, with the same names and `labels` as
+    /// [`Metrics::to_prometheus()`].
+    ///
+    /// The `current_envelope` gauge is part of the basic set of metrics, so
+    /// this also turns on [`Instrumented::with_envelope()`].
+    ///
+    /// [facade]: https://crates.io/crates/metrics
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(self, prefix: &str, labels: &[(&str, &str)]) -> Self {
+        Instrumented {
+            published: Some(Published::register(prefix, labels)),
+            track_envelope: true,
+            ..self
+        }
+    }
+
+    /// Everything measured so far.
+    pub fn metrics(&self) -> &Metrics { &self.metrics }
+
+    /// Start measuring from scratch.
+    pub fn reset_metrics(&mut self) { self.metrics = Metrics::default(); }
+
+    /// Get a reference to the underlying [`NoiseGate`].
+    pub fn gate(&self) -> &NoiseGate<S> { &self.gate }
+
+    /// Get a mutable reference to the underlying [`NoiseGate`] (e.g. to
+    /// change its threshold).
+    pub fn gate_mut(&mut self) -> &mut NoiseGate<S> { &mut self.gate }
+
+    /// Let the gate know some input was lost, exactly like
+    /// [`NoiseGate::frames_dropped()`].
+    pub fn frames_dropped<F, K>(&mut self, count: u64, sink: &mut K)
+    where
+        K: Sink<F>,
+    {
+        if count > 0 && self.gate.is_open() {
+            self.metrics.transmissions += 1;
+        }
+
+        self.gate.frames_dropped(count, sink);
+        self.metrics.dropped_frames += count;
+        self.metrics.position = self.gate.position();
+        self.metrics.sink_errors = sink.errors();
+        self.publish();
+    }
+
+    #[cfg(feature = "metrics")]
+    fn publish(&self) {
+        if let Some(published) = &self.published {
+            published.update(&self.metrics);
+        }
+    }
+
+    #[cfg(not(feature = "metrics"))]
+    fn publish(&self) {}
+
+    /// Stop measuring, returning the [`NoiseGate`].
+    pub fn into_inner(self) -> NoiseGate<S> { self.gate }
+}
+
+impl<S: Sample> Instrumented<S> {
+    /// Process a batch of frames, exactly like
+    /// [`NoiseGate::process_frames()`].
+    pub fn process_frames<K, F>(&mut self, frames: &[F], sink: &mut K)
+    where
+        F: Frame<Sample = S>,
+        K: Sink<F>,
+    {
+        let metrics = &mut self.metrics;
+        let started = Instant::now();
+
+        __private::process_runs(&mut self.gate, frames, sink, |state, run| {
+            match state {
+                State::Open => metrics.open_frames += run.len,
+                State::Closing { .. } => metrics.closing_frames += run.len,
+                State::Closed => metrics.closed_frames += run.len,
+            }
+
+            if run.closed {
+                metrics.transmissions += 1;
+            }
+        });
+
+        metrics.frames += frames.len();
+        metrics.position = self.gate.position();
+        metrics.sink_errors = sink.errors();
+        if self.track_peak_level {
+            metrics.peak_level = Some(peak_level(frames));
+        }
+        if self.track_envelope {
+            let decay = decay(self.gate.release_time);
+            for &frame in frames {
+                let level = frame_level(frame);
+                self.envelope = follow(self.envelope, level, decay);
+            }
+            metrics.envelope = Some(self.envelope);
+        }
+        metrics.processing_time += started.elapsed();
+        self.publish();
+    }
+}
+
+/// The largest absolute sample value, where `1.0` is full scale.
+fn peak_level<F: Frame>(frames: &[F]) -> f64 {
+    frames.iter().map(|&frame| frame_level(frame)).fold(0.0, f64::max)
+}
+
+/// The loudest channel in a frame, where `1.0` is full scale, ignoring any
+/// non-finite samples.
+fn frame_level<F: Frame>(frame: F) -> f64 {
+    frame
+        .channels()
+        .map(|sample| sample.to_float_sample().to_sample::<f64>().abs())
+        .filter(|level| level.is_finite())
+        .fold(0.0, f64::max)
+}
+
+/// How much the envelope decays by each frame, so it falls over roughly the
+/// gate's release time.
+fn decay(release_time: usize) -> f64 {
+    if release_time == 0 {
+        0.0
+    } else {
+        (-1.0 / release_time as f64).exp()
+    }
+}
+
+/// Jump up to louder frames straight away, and decay towards quieter ones.
+fn follow(envelope: f64, level: f64, decay: f64) -> f64 {
+    if level >= envelope {
+        level
+    } else {
+        level + (envelope - level) * decay
+    }
+}
+
+fn frames_to_duration(frames: usize, sample_rate: u32) -> Duration {
+    if sample_rate == 0 {
+        Duration::ZERO
+    } else {
+        Duration::from_secs_f64(frames as f64 / f64::from(sample_rate))
+    }
+}
+
+/// Handles for publishing [`Metrics`] with the `metrics` crate.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone)]
+struct Published {
+    segments: ::metrics::Counter,
+    frames: ::metrics::Counter,
+    open_duty_cycle: ::metrics::Gauge,
+    current_envelope: ::metrics::Gauge,
+    peak_level: ::metrics::Gauge,
+    sink_errors: ::metrics::Counter,
+    dropped_frames: ::metrics::Counter,
+}
+
+#[cfg(feature = "metrics")]
+impl Published {
+    fn register(prefix: &str, labels: &[(&str, &str)]) -> Self {
+        use ::metrics::{
+            counter, describe_counter, describe_gauge, gauge, Label,
+        };
+
+        let labels: Vec<Label> = labels
+            .iter()
+            .map(|&(name, value)| {
+                Label::new(name.to_string(), value.to_string())
+            })
+            .collect();
+        let name = |metric: &str| format!("{}_{}", prefix, metric);
+        let help = |metric: &str| {
+            DESCRIPTIONS
+                .iter()
+                .find(|(name, _)| *name == metric)
+                .map(|(_, help)| *help)
+                .unwrap_or_default()
+        };
+
+        for metric in &[
+            "segments_total",
+            "frames_total",
+            "sink_errors_total",
+            "dropped_frames_total",
+        ] {
+            describe_counter!(name(metric), help(metric));
+        }
+        for metric in &["open_duty_cycle", "current_envelope", "peak_level"] {
+            describe_gauge!(name(metric), help(metric));
+        }
+
+        Published {
+            segments: counter!(name("segments_total"), labels.clone()),
+            frames: counter!(name("frames_total"), labels.clone()),
+            open_duty_cycle: gauge!(name("open_duty_cycle"), labels.clone()),
+            current_envelope: gauge!(
+                name("current_envelope"),
+                labels.clone()
+            ),
+            peak_level: gauge!(name("peak_level"), labels.clone()),
+            sink_errors: counter!(name("sink_errors_total"), labels.clone()),
+            dropped_frames: counter!(name("dropped_frames_total"), labels),
+        }
+    }
+
+    fn update(&self, metrics: &Metrics) {
+        self.segments.absolute(metrics.transmissions as u64);
+        self.frames.absolute(metrics.frames as u64);
+        self.open_duty_cycle.set(metrics.duty_cycle());
+        if let Some(envelope) = metrics.envelope {
+            self.current_envelope.set(envelope);
+        }
+        if let Some(peak_level) = metrics.peak_level {
+            self.peak_level.set(peak_level);
+        }
+        self.sink_errors.absolute(metrics.sink_errors as u64);
+        self.dropped_frames.absolute(metrics.dropped_frames);
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl PartialEq for Published {
+    // the handles only point at the recorder, so there's nothing to compare
+    fn eq(&self, _other: &Published) -> bool { true }
+}
+
+/// The help text for each metric.
+const DESCRIPTIONS: &[(&str, &str)] = &[
+    ("segments_total", "The number of segments the gate has produced."),
+    ("frames_total", "The number of frames the gate has processed."),
+    (
+        "open_duty_cycle",
+        "The fraction of frames where the gate was open.",
+    ),
+    (
+        "current_envelope",
+        "The level of the signal's envelope after the most recent batch of \
+         frames, relative to full scale.",
+    ),
+    (
+        "peak_level",
+        "The peak level of the most recent batch of frames, relative to \
+         full scale.",
+    ),
+    ("sink_errors_total", "The number of errors reported by the sink."),
+    (
+        "dropped_frames_total",
+        "The number of frames which were lost before reaching the gate.",
+    ),
+];
+
+/// Statistics gathered by an [`Instrumented`] gate.
+///
+/// Any frame which makes the gate open or start closing is counted as open.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Metrics {
+    /// The total number of frames processed.
+    pub frames: usize,
+    /// Frames seen while the gate was fully open.
+    pub open_frames: usize,
+    /// Frames seen while the gate was waiting to close.
+    pub closing_frames: usize,
+    /// Frames seen while the gate was closed.
+    pub closed_frames: usize,
+    /// The number of times the gate closed.
+    pub transmissions: usize,
+    /// The gate's [position][NoiseGate::position] after the most recent
+    /// batch of frames.
+    pub position: u64,
+    /// Frames which were lost before they reached the gate (see
+    /// [`Instrumented::frames_dropped()`]).
+    pub dropped_frames: u64,
+    /// The peak level of the most recent batch of frames, where `1.0` is
+    /// full scale, if it's being measured (see
+    /// [`Instrumented::with_peak_level()`]).
+    pub peak_level: Option<f64>,
+    /// The level of a peak envelope which jumps up to each louder frame and
+    /// decays over the gate's release time, as of the end of the most recent
+    /// batch of frames, where `1.0` is full scale, if it's being measured
+    /// (see [`Instrumented::with_envelope()`]).
+    pub envelope: Option<f64>,
+    /// The number of errors reported by the [`Sink`] (see
+    /// [`Sink::errors()`]).
+    pub sink_errors: usize,
+    /// How much time was spent processing frames, including any time spent
+    /// in the [`Sink`].
+    pub processing_time: Duration,
+}
+
+impl Metrics {
+    /// How many frames were processed per second of processing time, or
+    /// `0.0` if no processing time has been measured yet.
+    pub fn frames_per_second(&self) -> f64 {
+        if self.processing_time == Duration::ZERO {
+            0.0
+        } else {
+            self.frames as f64 / self.processing_time.as_secs_f64()
+        }
+    }
+
+    /// How many times faster than real-time the gate is running, for audio
+    /// at the provided sample rate.
+    ///
+    /// Anything below `1.0` means the gate can't keep up.
+    pub fn real_time_factor(&self, sample_rate: u32) -> f64 {
+        self.frames_per_second() / f64::from(sample_rate)
+    }
+
+    /// How much audio was heard while the gate was fully open, for audio at
+    /// the provided sample rate.
+    pub fn time_open(&self, sample_rate: u32) -> Duration {
+        frames_to_duration(self.open_frames, sample_rate)
+    }
+
+    /// How much audio was heard while the gate was waiting to close.
+    pub fn time_closing(&self, sample_rate: u32) -> Duration {
+        frames_to_duration(self.closing_frames, sample_rate)
+    }
+
+    /// How much audio was heard while the gate was closed.
+    pub fn time_closed(&self, sample_rate: u32) -> Duration {
+        frames_to_duration(self.closed_frames, sample_rate)
+    }
+
+    /// The fraction of frames where the gate was open or closing.
+    pub fn duty_cycle(&self) -> f64 {
+        if self.frames == 0 {
+            0.0
+        } else {
+            (self.open_frames + self.closing_frames) as f64 / self.frames as f64
+        }
+    }
+
+    /// Format the metrics using Prometheus' [text exposition format][fmt],
+    /// ready to be served from a `/metrics` endpoint.
+    ///
+    /// Each metric's name starts with `prefix` (e.g. `"noise_gate"` gives
+    /// `noise_gate_segments_total`), and `labels` are attached to every
+    /// sample so several gates can share an endpoint.
+    ///
+    /// ```rust
+    /// use noise_gate::metrics::Metrics;
+    ///
+    /// let metrics = Metrics {
+    ///     frames: 100,
+    ///     open_frames: 20,
+    ///     closing_frames: 5,
+    ///     transmissions: 2,
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let text = metrics.to_prometheus("noise_gate", &[("mic", "1")]);
+    ///
+    /// assert!(text.contains("noise_gate_segments_total{mic=\"1\"} 2\n"));
+    /// assert!(text.contains("noise_gate_open_duty_cycle{mic=\"1\"} 0.25\n"));
+    /// ```
+    ///
+    /// [fmt]: https://prometheus.io/docs/instrumenting/exposition_formats/
+    pub fn to_prometheus(
+        &self,
+        prefix: &str,
+        labels: &[(&str, &str)],
+    ) -> String {
+        let labels = format_labels(labels);
+        let metrics = [
+            ("segments_total", "counter", Some(self.transmissions as f64)),
+            ("frames_total", "counter", Some(self.frames as f64)),
+            ("open_duty_cycle", "gauge", Some(self.duty_cycle())),
+            ("current_envelope", "gauge", self.envelope),
+            ("peak_level", "gauge", self.peak_level),
+            ("sink_errors_total", "counter", Some(self.sink_errors as f64)),
+            (
+                "dropped_frames_total",
+                "counter",
+                Some(self.dropped_frames as f64),
+            ),
+        ];
+
+        let mut text = String::new();
+
+        for (name, kind, value) in &metrics {
+            let value = match value {
+                Some(value) => value,
+                None => continue,
+            };
+            let help = DESCRIPTIONS
+                .iter()
+                .find(|(metric, _)| metric == name)
+                .map(|(_, help)| *help)
+                .unwrap_or_default();
+
+            let _ = writeln!(text, "# HELP {}_{} {}", prefix, name, help);
+            let _ = writeln!(text, "# TYPE {}_{} {}", prefix, name, kind);
+            let _ = writeln!(text, "{}_{}{} {}", prefix, name, labels, value);
+        }
+
+        text
+    }
+}
+
+/// Format a set of Prometheus labels (e.g. `{mic="1"}`), escaping any
+/// special characters in their values.
+fn format_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect();
+
+    format!("{{{}}}", labels.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct Counter {
+        frames: usize,
+        clips: usize,
+    }
+
+    impl<F> Sink<F> for Counter {
+        fn record(&mut self, _: F) { self.frames += 1; }
+
+        fn end_of_transmission(&mut self) { self.clips += 1; }
+    }
+
+    #[test]
+    fn states_account_for_every_frame() {
+        let frames: Vec<[i16; 1]> = (0..1000)
+            .map(|i| if i % 100 < 20 { [500] } else { [0] })
+            .collect();
+        let mut gate = Instrumented::new(NoiseGate::new(100, 30));
+        let mut sink = Counter::default();
+
+        for chunk in frames.chunks(64) {
+            gate.process_frames(chunk, &mut sink);
+        }
+
+        let metrics = gate.metrics();
+        assert_eq!(metrics.frames, 1000);
+        assert_eq!(
+            metrics.open_frames
+                + metrics.closing_frames
+                + metrics.closed_frames,
+            1000
+        );
+        assert_eq!(metrics.closing_frames, 10 * 31);
+        assert_eq!(metrics.transmissions, sink.clips);
+        assert_eq!(metrics.peak_level, None);
+    }
+
+    #[test]
+    fn dropped_frames_are_counted() {
+        let mut gate = Instrumented::new(NoiseGate::new(100_i16, 10));
+        let mut sink = Counter::default();
+
+        gate.process_frames(&[[0], [500]], &mut sink);
+        gate.frames_dropped::<[i16; 1], _>(256, &mut sink);
+        gate.frames_dropped::<[i16; 1], _>(256, &mut sink);
+        gate.process_frames(&[[0]], &mut sink);
+
+        let metrics = gate.metrics();
+        assert_eq!(metrics.dropped_frames, 512);
+        assert_eq!(metrics.frames, 3);
+        assert_eq!(metrics.position, 515);
+        assert_eq!(metrics.transmissions, 1);
+        assert_eq!(sink.clips, 1);
+    }
+
+    #[test]
+    fn peak_level_follows_the_latest_batch() {
+        let mut gate =
+            Instrumented::new(NoiseGate::new(0.1_f32, 0)).with_peak_level();
+        let mut sink = Counter::default();
+
+        gate.process_frames(&[[0.5_f32, -0.75], [0.25, 0.0]], &mut sink);
+        assert_eq!(gate.metrics().peak_level, Some(0.75));
+
+        gate.process_frames(&[[0.0, f32::NAN], [0.125, 0.0]], &mut sink);
+        assert_eq!(gate.metrics().peak_level, Some(0.125));
+    }
+
+    #[test]
+    fn envelope_decays_over_the_release_time() {
+        let mut gate =
+            Instrumented::new(NoiseGate::new(0.1_f32, 100)).with_envelope();
+        let mut sink = Counter::default();
+
+        gate.process_frames(&[[0.0_f32, 0.5]], &mut sink);
+        assert_eq!(gate.metrics().envelope, Some(0.5));
+
+        gate.process_frames(&[[0.0, f32::NAN]; 100], &mut sink);
+        let envelope = gate.metrics().envelope.unwrap();
+        assert!((envelope - 0.5 / std::f64::consts::E).abs() < 1e-9);
+        assert_eq!(gate.metrics().peak_level, None);
+    }
+
+    /// Fails to save every transmission.
+    #[derive(Debug, Default)]
+    struct Broken {
+        errors: usize,
+    }
+
+    impl<F> Sink<F> for Broken {
+        fn record(&mut self, _: F) {}
+
+        fn end_of_transmission(&mut self) { self.errors += 1; }
+
+        fn errors(&self) -> usize { self.errors }
+    }
+
+    #[test]
+    fn sink_errors_are_picked_up_automatically() {
+        let mut gate = Instrumented::new(NoiseGate::new(100_i16, 0));
+        let mut sink = Broken::default();
+
+        gate.process_frames(&[[500], [0], [0], [500], [0], [0]], &mut sink);
+
+        assert_eq!(gate.metrics().sink_errors, 2);
+    }
+
+    #[test]
+    fn prometheus_exposition() {
+        let metrics = Metrics {
+            frames: 8,
+            open_frames: 2,
+            closing_frames: 2,
+            transmissions: 1,
+            envelope: Some(0.25),
+            peak_level: Some(0.5),
+            sink_errors: 3,
+            dropped_frames: 64,
+            ..Default::default()
+        };
+
+        let text = metrics.to_prometheus("gate", &[]);
+
+        let samples: Vec<&str> =
+            text.lines().filter(|line| !line.starts_with('#')).collect();
+        assert_eq!(
+            samples,
+            vec![
+                "gate_segments_total 1",
+                "gate_frames_total 8",
+                "gate_open_duty_cycle 0.5",
+                "gate_current_envelope 0.25",
+                "gate_peak_level 0.5",
+                "gate_sink_errors_total 3",
+                "gate_dropped_frames_total 64",
+            ]
+        );
+        assert!(text.starts_with(
+            "# HELP gate_segments_total The number of segments the gate has \
+             produced.\n# TYPE gate_segments_total counter\n"
+        ));
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        assert_eq!(format_labels(&[]), "");
+        assert_eq!(
+            format_labels(&[("a", "x"), ("b", "say \"hi\"\\\n")]),
+            "{a=\"x\",b=\"say \\\"hi\\\"\\\\\\n\"}"
+        );
+    }
+
+    #[test]
+    fn real_time_factor() {
+        let metrics = Metrics {
+            frames: 96_000,
+            processing_time: Duration::from_millis(500),
+            ..Default::default()
+        };
+
+        assert_eq!(metrics.frames_per_second(), 192_000.0);
+        assert_eq!(metrics.real_time_factor(48_000), 4.0);
+        assert_eq!(Metrics::default().frames_per_second(), 0.0);
+        assert_eq!(Metrics::default().real_time_factor(48_000), 0.0);
+    }
+
+    #[test]
+    fn time_spent_in_each_state() {
+        let metrics = Metrics {
+            frames: 24_000,
+            open_frames: 12_000,
+            closing_frames: 4_000,
+            closed_frames: 8_000,
+            ..Default::default()
+        };
+
+        assert_eq!(metrics.time_open(16_000), Duration::from_millis(750));
+        assert_eq!(metrics.time_closing(16_000), Duration::from_millis(250));
+        assert_eq!(metrics.time_closed(16_000), Duration::from_millis(500));
+        assert_eq!(metrics.time_closed(0), Duration::ZERO);
+    }
+}
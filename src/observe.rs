@@ -0,0 +1,503 @@
+//! Hooks for watching what a gate does, so its behaviour inside a larger
+//! service can be reported through whatever logging or tracing framework
+//! the service already uses.
+//!
+//! An [`Observer`] gets told about each event along with the index of the
+//! sample it happened at, and can forward it on to anything. Enable the
+//! `tracing` feature to use the `Tracing` observer, which turns each
+//! segment into a [`tracing`][tracing] span.
+//!
+//! Errors are picked up from [`Sink::errors()`] after every batch of
+//! frames, so adapters which can fail (e.g. [`EventLog`]) are reported
+//! without any extra work.
+//!
+//! [tracing]: https://crates.io/crates/tracing
+//! [`EventLog`]: crate::sinks::EventLog
+
+use crate::{NoiseGate, Sink};
+use dasp::{Frame, Sample};
+use noise_gate_core::__private;
+
+/// Something which wants to know what an [`Observed`] gate is doing.
+///
+/// Every method has an empty default, so only the interesting events need
+/// to be implemented. Sample indices count frames from the first one the
+/// gate was given.
+pub trait Observer<S> {
+    /// A segment started at this sample.
+    fn segment_started(&mut self, _sample: u64) {}
+
+    /// The segment ended, just before this sample, after `frames` frames
+    /// were recorded.
+    fn segment_ended(&mut self, _sample: u64, _frames: usize) {}
+
+    /// The gate's threshold or release time were changed, taking effect at
+    /// this sample.
+    fn parameters_changed(
+        &mut self,
+        _sample: u64,
+        _open_threshold: S,
+        _release_time: usize,
+    ) {
+    }
+
+    /// The sink ran into `count` more errors while processing the frames
+    /// before this sample.
+    fn sink_errors(&mut self, _sample: u64, _count: usize) {}
+}
+
+impl<S, O: Observer<S> + ?Sized> Observer<S> for &mut O {
+    fn segment_started(&mut self, sample: u64) {
+        (**self).segment_started(sample);
+    }
+
+    fn segment_ended(&mut self, sample: u64, frames: usize) {
+        (**self).segment_ended(sample, frames);
+    }
+
+    fn parameters_changed(
+        &mut self,
+        sample: u64,
+        open_threshold: S,
+        release_time: usize,
+    ) {
+        (**self).parameters_changed(sample, open_threshold, release_time);
+    }
+
+    fn sink_errors(&mut self, sample: u64, count: usize) {
+        (**self).sink_errors(sample, count);
+    }
+}
+
+/// A [`NoiseGate`] which tells an [`Observer`] whenever a segment starts or
+/// ends, or its parameters change.
+///
+/// ```rust
+/// use noise_gate::{
+///     observe::{Observed, Observer},
+///     NoiseGate,
+/// };
+///
+/// #[derive(Default)]
+/// struct Segments(Vec<(u64, usize)>);
+///
+/// impl Observer<i16> for Segments {
+///     fn segment_ended(&mut self, sample: u64, frames: usize) {
+///         self.0.push((sample, frames));
+///     }
+/// }
+///
+/// let mut gate = Observed::new(NoiseGate::new(100_i16, 0), Segments::default());
+/// let mut recorded = Vec::new();
+///
+/// gate.process_frames(&[[0], [500], [600], [0], [0]], &mut recorded);
+///
+/// assert_eq!(gate.observer().0, vec![(4, 3)]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observed<S, O> {
+    gate: NoiseGate<S>,
+    observer: O,
+    /// Where the current segment started, if there is one.
+    segment_start: Option<u64>,
+    /// The threshold and release time last time frames were processed.
+    parameters: (S, usize),
+    /// How many errors the sink had reported last time we checked.
+    sink_errors: usize,
+}
+
+impl<S: Copy, O> Observed<S, O> {
+    /// Start observing a [`NoiseGate`].
+    pub fn new(gate: NoiseGate<S>, observer: O) -> Self {
+        Observed {
+            parameters: (gate.open_threshold, gate.release_time),
+            gate,
+            observer,
+            segment_start: None,
+            sink_errors: 0,
+        }
+    }
+}
+
+impl<S, O> Observed<S, O> {
+    /// The index of the next sample to be processed (see
+    /// [`NoiseGate::position()`]).
+    pub fn position(&self) -> u64 { self.gate.position() }
+
+    /// Get a reference to the [`Observer`].
+    pub fn observer(&self) -> &O { &self.observer }
+
+    /// Get a mutable reference to the [`Observer`].
+    pub fn observer_mut(&mut self) -> &mut O { &mut self.observer }
+
+    /// Get a reference to the underlying [`NoiseGate`].
+    pub fn gate(&self) -> &NoiseGate<S> { &self.gate }
+
+    /// Get a mutable reference to the underlying [`NoiseGate`] (e.g. to
+    /// change its threshold).
+    ///
+    /// Changes are reported to the [`Observer`] the next time frames are
+    /// processed.
+    pub fn gate_mut(&mut self) -> &mut NoiseGate<S> { &mut self.gate }
+
+    /// Stop observing, returning the [`NoiseGate`] and [`Observer`].
+    pub fn into_inner(self) -> (NoiseGate<S>, O) { (self.gate, self.observer) }
+}
+
+impl<S, O> Observed<S, O>
+where
+    S: Sample,
+    O: Observer<S>,
+{
+    /// Process a batch of frames, exactly like
+    /// [`NoiseGate::process_frames()`].
+    pub fn process_frames<K, F>(&mut self, frames: &[F], sink: &mut K)
+    where
+        F: Frame<Sample = S>,
+        K: Sink<F>,
+    {
+        let parameters = (self.gate.open_threshold, self.gate.release_time);
+        if parameters != self.parameters {
+            self.parameters = parameters;
+            self.observer.parameters_changed(
+                self.gate.position(),
+                parameters.0,
+                parameters.1,
+            );
+        }
+
+        let Observed {
+            gate,
+            observer,
+            segment_start,
+            ..
+        } = self;
+        let mut position = gate.position();
+
+        __private::process_runs(gate, frames, sink, |_, run| {
+            if run.recorded > 0 && segment_start.is_none() {
+                *segment_start = Some(position);
+                observer.segment_started(position);
+            }
+
+            if run.closed {
+                let end = position + run.recorded as u64;
+                let start = segment_start.take().unwrap_or(end);
+                observer.segment_ended(end, (end - start) as usize);
+            }
+
+            position += run.len as u64;
+        });
+
+        self.check_sink_errors(sink);
+    }
+
+    /// Let the gate know some input was lost, exactly like
+    /// [`NoiseGate::frames_dropped()`].
+    ///
+    /// If this ends a segment, the [`Observer`] is told it ended where the
+    /// gap started.
+    pub fn frames_dropped<F, K>(&mut self, count: u64, sink: &mut K)
+    where
+        K: Sink<F>,
+    {
+        if count == 0 {
+            return;
+        }
+
+        let end = self.gate.position();
+        if let Some(start) = self.segment_start.take() {
+            self.observer.segment_ended(end, (end - start) as usize);
+        }
+
+        self.gate.frames_dropped(count, sink);
+        self.check_sink_errors(sink);
+    }
+
+    fn check_sink_errors<F, K: Sink<F>>(&mut self, sink: &K) {
+        let errors = sink.errors();
+
+        if errors > self.sink_errors {
+            self.observer
+                .sink_errors(self.gate.position(), errors - self.sink_errors);
+        }
+        self.sink_errors = errors;
+    }
+}
+
+/// An [`Observer`] which reports everything using [`tracing`].
+///
+/// Each segment gets its own `"segment"` span (with a `start` field), which
+/// is closed when the segment ends, so subscribers can see how long it
+/// lasted. Segment boundaries are logged at `INFO`, parameter changes at
+/// `DEBUG`, and sink errors at `WARN`, each with a `sample` field.
+///
+/// ```rust
+/// use noise_gate::{
+///     observe::{Observed, Tracing},
+///     NoiseGate,
+/// };
+///
+/// let mut gate = Observed::new(NoiseGate::new(100_i16, 0), Tracing::new());
+/// let mut recorded = Vec::new();
+///
+/// gate.process_frames(&[[0], [500], [600], [0], [0]], &mut recorded);
+/// ```
+#[cfg(feature = "tracing")]
+#[derive(Debug, Default, Clone)]
+pub struct Tracing {
+    segment: Option<tracing::Span>,
+}
+
+#[cfg(feature = "tracing")]
+impl Tracing {
+    /// Create a new [`Tracing`] observer.
+    pub fn new() -> Self { Tracing::default() }
+}
+
+#[cfg(feature = "tracing")]
+impl<S: std::fmt::Debug> Observer<S> for Tracing {
+    fn segment_started(&mut self, sample: u64) {
+        let span = tracing::info_span!("segment", start = sample);
+        tracing::info!(parent: &span, sample, "segment started");
+        self.segment = Some(span);
+    }
+
+    fn segment_ended(&mut self, sample: u64, frames: usize) {
+        match self.segment.take() {
+            Some(span) => {
+                tracing::info!(parent: &span, sample, frames, "segment ended")
+            },
+            None => tracing::info!(sample, frames, "segment ended"),
+        }
+    }
+
+    fn parameters_changed(
+        &mut self,
+        sample: u64,
+        open_threshold: S,
+        release_time: usize,
+    ) {
+        tracing::debug!(
+            sample,
+            ?open_threshold,
+            release_time,
+            "parameters changed"
+        );
+    }
+
+    fn sink_errors(&mut self, sample: u64, count: usize) {
+        tracing::warn!(sample, count, "the sink reported errors");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Events(Vec<String>);
+
+    impl Observer<i16> for Events {
+        fn segment_started(&mut self, sample: u64) {
+            self.0.push(format!("start {}", sample));
+        }
+
+        fn segment_ended(&mut self, sample: u64, frames: usize) {
+            self.0.push(format!("end {} ({})", sample, frames));
+        }
+
+        fn parameters_changed(
+            &mut self,
+            sample: u64,
+            open_threshold: i16,
+            release_time: usize,
+        ) {
+            self.0.push(format!(
+                "parameters {} {} {}",
+                sample, open_threshold, release_time
+            ));
+        }
+
+        fn sink_errors(&mut self, sample: u64, count: usize) {
+            self.0.push(format!("errors {} {}", sample, count));
+        }
+    }
+
+    /// A sink which fails to save its second transmission.
+    #[derive(Debug, Default)]
+    struct Flaky {
+        transmissions: usize,
+        errors: usize,
+    }
+
+    impl Sink<[i16; 1]> for Flaky {
+        fn record(&mut self, _: [i16; 1]) {}
+
+        fn end_of_transmission(&mut self) {
+            self.transmissions += 1;
+            if self.transmissions == 2 {
+                self.errors += 1;
+            }
+        }
+
+        fn errors(&self) -> usize { self.errors }
+    }
+
+    #[test]
+    fn events_use_sample_indices_across_buffers() {
+        let mut gate = Observed::new(NoiseGate::new(100, 1), Events::default());
+        let mut recorded = Vec::new();
+
+        gate.process_frames(&[[0], [0], [500], [600]], &mut recorded);
+        gate.process_frames(&[[0], [0], [0], [700]], &mut recorded);
+        gate.gate_mut().open_threshold = 800;
+        gate.process_frames(&[[0], [0], [900], [0], [0], [0]], &mut recorded);
+
+        assert_eq!(
+            gate.observer().0,
+            vec![
+                "start 2",
+                "end 6 (4)",
+                "start 7",
+                "parameters 8 800 1",
+                "end 13 (6)",
+            ]
+        );
+        assert_eq!(gate.position(), 14);
+        assert_eq!(recorded.len(), 10);
+    }
+
+    #[test]
+    fn dropped_frames_end_the_segment() {
+        let mut gate = Observed::new(NoiseGate::new(100, 5), Events::default());
+        let mut recorded = Vec::new();
+
+        gate.process_frames(&[[0], [500], [600]], &mut recorded);
+        gate.frames_dropped(100, &mut recorded);
+        gate.process_frames(&[[0], [700]], &mut recorded);
+
+        assert_eq!(
+            gate.observer().0,
+            vec!["start 1", "end 3 (2)", "start 104"]
+        );
+        assert_eq!(gate.position(), 105);
+    }
+
+    #[test]
+    fn sink_errors_are_picked_up_automatically() {
+        let mut gate = Observed::new(NoiseGate::new(100, 0), Events::default());
+        let mut sink = Flaky::default();
+        let segment = [[500], [0], [0]];
+
+        gate.process_frames(&segment, &mut sink);
+        gate.process_frames(&segment, &mut sink);
+        gate.process_frames(&segment, &mut sink);
+
+        assert_eq!(
+            gate.observer().0,
+            vec![
+                "start 0",
+                "end 2 (2)",
+                "start 3",
+                "end 5 (2)",
+                "errors 6 1",
+                "start 6",
+                "end 8 (2)",
+            ]
+        );
+    }
+
+    #[cfg(feature = "tracing")]
+    mod tracing_observer {
+        use super::*;
+        use std::{
+            fmt::Debug,
+            sync::{
+                atomic::{AtomicU64, Ordering},
+                Arc, Mutex,
+            },
+        };
+        use tracing::{
+            field::{Field, Visit},
+            span, Event, Metadata, Subscriber,
+        };
+
+        /// A [`Subscriber`] which writes down each span and event.
+        #[derive(Debug, Default, Clone)]
+        struct Log {
+            lines: Arc<Mutex<Vec<String>>>,
+            next_id: Arc<AtomicU64>,
+        }
+
+        struct Fields(String);
+
+        impl Visit for Fields {
+            fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+                self.0.push_str(&format!(" {}={:?}", field.name(), value));
+            }
+        }
+
+        impl Subscriber for Log {
+            fn enabled(&self, _: &Metadata<'_>) -> bool { true }
+
+            fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+                let mut fields =
+                    Fields(format!("span {}", span.metadata().name()));
+                span.record(&mut fields);
+                self.lines.lock().unwrap().push(fields.0);
+
+                span::Id::from_u64(
+                    self.next_id.fetch_add(1, Ordering::Relaxed) + 1,
+                )
+            }
+
+            fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = Fields(event.metadata().level().to_string());
+                event.record(&mut fields);
+                if event.parent().is_some() {
+                    fields.0.push_str(" (in span)");
+                }
+                self.lines.lock().unwrap().push(fields.0);
+            }
+
+            fn enter(&self, _: &span::Id) {}
+
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        #[test]
+        fn segments_become_spans() {
+            let log = Log::default();
+            let lines = Arc::clone(&log.lines);
+            let mut gate =
+                Observed::new(NoiseGate::new(100, 0), Tracing::new());
+            let mut sink = Flaky::default();
+
+            tracing::subscriber::with_default(log, || {
+                gate.process_frames(&[[0], [500], [0], [0]], &mut sink);
+                gate.gate_mut().release_time = 1;
+                gate.process_frames(&[[500], [0], [0], [0]], &mut sink);
+            });
+
+            assert_eq!(
+                *lines.lock().unwrap(),
+                vec![
+                    "span segment start=1",
+                    "INFO message=segment started sample=1 (in span)",
+                    "INFO message=segment ended sample=3 frames=2 (in span)",
+                    "DEBUG message=parameters changed sample=4 \
+                     open_threshold=100 release_time=1",
+                    "span segment start=4",
+                    "INFO message=segment started sample=4 (in span)",
+                    "INFO message=segment ended sample=7 frames=3 (in span)",
+                    "WARN message=the sink reported errors sample=8 count=1",
+                ]
+            );
+        }
+    }
+}
@@ -0,0 +1,312 @@
+//! Broadcasting the gate's transitions as [OSC][osc] messages, so lighting
+//! and show-control systems can react to voice activity.
+//!
+//! [osc]: https://opensoundcontrol.stanford.edu/spec-1_0.html
+
+use crate::Sink;
+use rosc::{OscMessage, OscPacket, OscTime, OscType};
+use std::{
+    convert::TryFrom,
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::SystemTime,
+};
+
+/// A [`Sink`] adapter which sends an OSC message over UDP whenever a
+/// transmission starts or stops.
+///
+/// When a transmission starts, `{prefix}/open` is sent with a time tag
+/// saying when it happened. When it ends, `{prefix}/close` is sent with a
+/// time tag and the transmission's length in frames (as an `int32`). If
+/// the transmission is thrown away (see [`Sink::discard_transmission()`]),
+/// `{prefix}/discard` is sent with the same arguments instead. The default
+/// prefix is `/noise-gate`.
+///
+/// When the gate reports where a transmission started (see
+/// [`Sink::transmission_started()`]), each message also ends with an `int64`
+/// giving the position of its first (for `open`) or one-past-the-last (for
+/// `close` and `discard`) frame.
+///
+/// Sending a UDP packet is a syscall, so this shouldn't be used directly from
+/// a real-time audio thread. Errors are counted rather than interrupting the
+/// recording (see [`OscTransitions::send_errors()`]).
+///
+/// ```rust,no_run
+/// use noise_gate::{osc::OscTransitions, Sink};
+/// use std::net::UdpSocket;
+///
+/// # struct Discard;
+/// # impl Sink<[f32; 1]> for Discard {
+/// #     fn record(&mut self, _: [f32; 1]) {}
+/// #     fn end_of_transmission(&mut self) {}
+/// # }
+/// let socket = UdpSocket::bind("0.0.0.0:0")?;
+/// socket.set_broadcast(true)?;
+/// let mut sink = OscTransitions::new(Discard, socket, "255.255.255.255:9000")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct OscTransitions<K> {
+    inner: K,
+    socket: UdpSocket,
+    target: SocketAddr,
+    prefix: String,
+    /// How many frames are in the current transmission, if there is one.
+    current: Option<usize>,
+    start_position: Option<u64>,
+    send_errors: usize,
+}
+
+impl<K> OscTransitions<K> {
+    /// Wrap a [`Sink`], sending messages to `target` using `socket`.
+    pub fn new<A: ToSocketAddrs>(
+        inner: K,
+        socket: UdpSocket,
+        target: A,
+    ) -> io::Result<Self> {
+        let target = target.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "No address given")
+        })?;
+
+        Ok(OscTransitions {
+            inner,
+            socket,
+            target,
+            prefix: String::from("/noise-gate"),
+            current: None,
+            start_position: None,
+            send_errors: 0,
+        })
+    }
+
+    /// Use a different address prefix (e.g. `"/studio/mic-1"`).
+    pub fn with_prefix<S: Into<String>>(self, prefix: S) -> Self {
+        OscTransitions {
+            prefix: prefix.into(),
+            ..self
+        }
+    }
+
+    /// How many messages couldn't be sent.
+    pub fn send_errors(&self) -> usize { self.send_errors }
+
+    /// Get a reference to the inner [`Sink`].
+    pub fn inner(&self) -> &K { &self.inner }
+
+    /// Get a mutable reference to the inner [`Sink`].
+    pub fn inner_mut(&mut self) -> &mut K { &mut self.inner }
+
+    /// Consume the adapter, returning the inner [`Sink`].
+    pub fn into_inner(self) -> K { self.inner }
+
+    fn started(&mut self, frames: usize) {
+        match self.current.as_mut() {
+            Some(current) => *current += frames,
+            None => {
+                self.current = Some(frames);
+                self.send("open", None, self.start_position);
+            },
+        }
+    }
+
+    /// Announce the end of the current transmission, if there is one.
+    fn finished(&mut self, event: &str) {
+        if let Some(length) = self.current.take() {
+            let end = self.start_position.take().map(|p| p + length as u64);
+            self.send(event, Some(length), end);
+        }
+    }
+
+    fn send(
+        &mut self,
+        event: &str,
+        length: Option<usize>,
+        position: Option<u64>,
+    ) {
+        let mut args = Vec::with_capacity(3);
+        // times before 1900 or after 2036 can't be represented
+        if let Ok(now) = OscTime::try_from(SystemTime::now()) {
+            args.push(OscType::Time(now));
+        }
+        if let Some(length) = length {
+            args.push(OscType::Int(i32::try_from(length).unwrap_or(i32::MAX)));
+        }
+        if let Some(position) = position {
+            let position = i64::try_from(position).unwrap_or(i64::MAX);
+            args.push(OscType::Long(position));
+        }
+
+        let packet = OscPacket::Message(OscMessage {
+            addr: format!("{}/{}", self.prefix, event),
+            args,
+        });
+        let sent = rosc::encoder::encode(&packet)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            .and_then(|bytes| self.socket.send_to(&bytes, self.target));
+
+        if sent.is_err() {
+            self.send_errors += 1;
+        }
+    }
+}
+
+impl<F, K> Sink<F> for OscTransitions<K>
+where
+    F: Copy,
+    K: Sink<F>,
+{
+    fn record(&mut self, frame: F) {
+        self.started(1);
+        self.inner.record(frame);
+    }
+
+    fn record_frames(&mut self, frames: &[F]) {
+        if !frames.is_empty() {
+            self.started(frames.len());
+            self.inner.record_frames(frames);
+        }
+    }
+
+    fn end_of_transmission(&mut self) {
+        self.finished("close");
+        self.inner.end_of_transmission();
+    }
+
+    fn discard_transmission(&mut self) {
+        self.finished("discard");
+        self.inner.discard_transmission();
+    }
+
+    fn transmission_started(&mut self, position: u64) {
+        if self.current.is_none() {
+            self.start_position = Some(position);
+        }
+        self.inner.transmission_started(position);
+    }
+
+    fn latency_samples(&self) -> usize { self.inner.latency_samples() }
+
+    fn errors(&self) -> usize { self.send_errors + self.inner.errors() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    struct Discard;
+
+    impl Sink<[i16; 1]> for Discard {
+        fn record(&mut self, _: [i16; 1]) {}
+
+        fn end_of_transmission(&mut self) {}
+    }
+
+    /// Remembers how the last transmission ended.
+    #[derive(Debug, Default)]
+    struct Ending {
+        ended: bool,
+        discarded: bool,
+    }
+
+    impl Sink<[i16; 1]> for Ending {
+        fn record(&mut self, _: [i16; 1]) {}
+
+        fn end_of_transmission(&mut self) { self.ended = true; }
+
+        fn discard_transmission(&mut self) { self.discarded = true; }
+    }
+
+    fn receiver() -> UdpSocket {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        receiver
+    }
+
+    fn receive(socket: &UdpSocket) -> OscMessage {
+        let mut buffer = [0; 64];
+        let len = socket.recv(&mut buffer).unwrap();
+
+        match rosc::decoder::decode_udp(&buffer[..len]).unwrap() {
+            (_, OscPacket::Message(message)) => message,
+            (_, other) => panic!("Expected a message, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn transitions_are_sent_over_udp() {
+        let receiver = receiver();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut sink = OscTransitions::new(
+            Discard,
+            socket,
+            receiver.local_addr().unwrap(),
+        )
+        .unwrap()
+        .with_prefix("/mic");
+
+        sink.record_frames(&[[1000_i16]; 5]);
+        sink.record([1000]);
+        sink.end_of_transmission();
+
+        let open = receive(&receiver);
+        assert_eq!(open.addr, "/mic/open");
+        assert!(matches!(open.args[..], [OscType::Time(_)]));
+
+        let close = receive(&receiver);
+        assert_eq!(close.addr, "/mic/close");
+        assert!(matches!(close.args[..], [OscType::Time(_), OscType::Int(6)]));
+        assert_eq!(sink.send_errors(), 0);
+    }
+
+    #[test]
+    fn discarded_transmissions_are_cancelled() {
+        let receiver = receiver();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut sink = OscTransitions::new(
+            Ending::default(),
+            socket,
+            receiver.local_addr().unwrap(),
+        )
+        .unwrap();
+
+        sink.record_frames(&[[1000_i16]; 3]);
+        sink.discard_transmission();
+
+        assert_eq!(receive(&receiver).addr, "/noise-gate/open");
+        let discard = receive(&receiver);
+        assert_eq!(discard.addr, "/noise-gate/discard");
+        assert!(matches!(
+            discard.args[..],
+            [OscType::Time(_), OscType::Int(3)]
+        ));
+        assert!(sink.inner().discarded);
+        assert!(!sink.inner().ended);
+    }
+
+    #[test]
+    fn messages_include_the_position_when_known() {
+        let receiver = receiver();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut sink =
+            OscTransitions::new(Discard, socket, receiver.local_addr().unwrap())
+                .unwrap();
+
+        sink.transmission_started(1000);
+        sink.record_frames(&[[1000_i16]; 4]);
+        sink.end_of_transmission();
+
+        let open = receive(&receiver);
+        assert!(matches!(
+            open.args[..],
+            [OscType::Time(_), OscType::Long(1000)]
+        ));
+        let close = receive(&receiver);
+        assert!(matches!(
+            close.args[..],
+            [OscType::Time(_), OscType::Int(4), OscType::Long(1004)]
+        ));
+    }
+}
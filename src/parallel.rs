@@ -0,0 +1,263 @@
+//! Splitting huge in-memory recordings across several threads.
+//!
+//! The buffer is cut into one partition per thread and each partition is run
+//! through its own [`NoiseGate`]. A partition's state only depends on what
+//! came before it up until its first loud frame (which always leaves the gate
+//! open), so the threads can start from there and the leading silence gets
+//! stitched onto the previous partition's result afterwards.
+
+use crate::{first_above_threshold, low_level::State, NoiseGate};
+use dasp::Frame;
+use noise_gate_core::__private;
+use std::{ops::Range, thread};
+
+/// Find the spans of noise in `frames`, spreading the work across `threads`
+/// threads.
+///
+/// This gives the same segments as [`NoiseGate::segments()`] would for a
+/// freshly created gate, except a segment which is still open at the end of
+/// the buffer is treated as finishing there.
+///
+/// ```rust
+/// let frames: Vec<[i16; 1]> = (0..10_000)
+///     .map(|i| if i % 1000 < 100 { [500] } else { [0] })
+///     .collect();
+///
+/// let segments = noise_gate::parallel::find_segments(&frames, 100, 50, 4);
+///
+/// assert_eq!(segments.len(), 10);
+/// assert_eq!(segments[0], 0..151);
+/// ```
+pub fn find_segments<F>(
+    frames: &[F],
+    open_threshold: F::Sample,
+    release_time: usize,
+    threads: usize,
+) -> Vec<Range<usize>>
+where
+    F: Frame + Sync,
+    F::Sample: Send,
+{
+    let partition_length = match frames.len() / threads.max(1) {
+        0 => frames.len().max(1),
+        n => n + 1,
+    };
+
+    let partitions: Vec<Partition> = thread::scope(|scope| {
+        let handles: Vec<_> = frames
+            .chunks(partition_length)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let offset = i * partition_length;
+                scope.spawn(move || {
+                    Partition::detect(
+                        chunk,
+                        offset,
+                        open_threshold,
+                        release_time,
+                    )
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("The worker thread panicked"))
+            .collect()
+    });
+
+    stitch(frames, partitions, open_threshold, release_time)
+}
+
+/// The result of running a single partition through a gate, starting from
+/// its first loud frame.
+#[derive(Debug, Clone, PartialEq)]
+struct Partition {
+    /// Where the partition starts in the overall buffer.
+    start: usize,
+    /// Where the partition's first loud frame is, if it has one.
+    first_loud: Option<usize>,
+    /// Every segment which started and finished inside the partition.
+    segments: Vec<Range<usize>>,
+    /// The start of a segment which was still open at the end of the
+    /// partition.
+    open: Option<usize>,
+    /// The gate's state at the end of the partition.
+    state: State,
+}
+
+impl Partition {
+    fn detect<F: Frame>(
+        frames: &[F],
+        start: usize,
+        open_threshold: F::Sample,
+        release_time: usize,
+    ) -> Self {
+        let mut gate = NoiseGate::new(open_threshold, release_time);
+        let mut segments = Vec::new();
+        let mut open = None;
+        let first_loud = first_above_threshold(frames, open_threshold);
+
+        if let Some(first_loud) = first_loud {
+            collect(
+                &mut gate,
+                &frames[first_loud..],
+                start + first_loud,
+                &mut open,
+                &mut segments,
+            );
+        }
+
+        Partition {
+            start,
+            first_loud: first_loud.map(|i| start + i),
+            segments,
+            open,
+            state: gate.state(),
+        }
+    }
+}
+
+/// Join the partitions back together, carrying the gate's state over from
+/// one partition into the leading silence of the next.
+fn stitch<F: Frame>(
+    frames: &[F],
+    partitions: Vec<Partition>,
+    open_threshold: F::Sample,
+    release_time: usize,
+) -> Vec<Range<usize>> {
+    let mut gate = NoiseGate::new(open_threshold, release_time);
+    let mut segments = Vec::new();
+    let mut open = None;
+
+    for (i, partition) in partitions.iter().enumerate() {
+        let end = partitions.get(i + 1).map_or(frames.len(), |p| p.start);
+        let silence_end = partition.first_loud.unwrap_or(end);
+
+        // run the previous partition's state through the leading silence
+        collect(
+            &mut gate,
+            &frames[partition.start..silence_end],
+            partition.start,
+            &mut open,
+            &mut segments,
+        );
+
+        if partition.first_loud.is_none() {
+            continue;
+        }
+
+        // The partition assumed the gate opened at its first loud frame, so
+        // if the previous segment is still going they join up
+        let mut carried = open.take();
+
+        for segment in &partition.segments {
+            let start = carried.take().unwrap_or(segment.start);
+            segments.push(start..segment.end);
+        }
+
+        open = partition.open.map(|start| carried.take().unwrap_or(start));
+        __private::set_state(&mut gate, partition.state);
+    }
+
+    if let Some(start) = open {
+        segments.push(start..frames.len());
+    }
+
+    segments
+}
+
+/// Run `frames` through the `gate`, adding any segments which finish to
+/// `segments` and keeping track of where the currently open segment started.
+fn collect<F: Frame>(
+    gate: &mut NoiseGate<F::Sample>,
+    frames: &[F],
+    offset: usize,
+    open: &mut Option<usize>,
+    segments: &mut Vec<Range<usize>>,
+) {
+    let mut position = 0;
+
+    while position < frames.len() {
+        let run = __private::next_run(gate, &frames[position..]);
+
+        if run.recorded > 0 && open.is_none() {
+            *open = Some(offset + position);
+        }
+
+        if run.closed {
+            let start = open.take().unwrap_or(offset + position);
+            segments.push(start..offset + position + run.recorded);
+        }
+
+        position += run.len;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sequential(
+        frames: &[[i16; 1]],
+        release_time: usize,
+    ) -> Vec<Range<usize>> {
+        let mut gate = NoiseGate::new(100, release_time);
+        let mut segments: Vec<_> =
+            gate.segments(frames).map(|(range, _)| range).collect();
+
+        if gate.is_open() {
+            if let Some(last) = segments.last_mut() {
+                last.end = frames.len();
+            }
+        }
+
+        segments
+    }
+
+    fn signal() -> Vec<[i16; 1]> {
+        let mut seed = 0xdead_beef_u32;
+
+        (0..5000)
+            .map(|i| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+
+                // bursts of varying density, so segments regularly straddle
+                // partition boundaries
+                if (i / 250) % 2 == 0 && seed.is_multiple_of(7) {
+                    [500]
+                } else {
+                    [(seed % 50) as i16]
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_segments_as_processing_sequentially() {
+        let frames = signal();
+
+        for &release_time in &[0, 3, 40, 600, 10_000] {
+            let expected = sequential(&frames, release_time);
+
+            for &threads in &[1, 2, 3, 7, 16, 5000, 6000] {
+                let got = find_segments(&frames, 100, release_time, threads);
+
+                assert_eq!(
+                    got, expected,
+                    "release time: {}, threads: {}",
+                    release_time, threads
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn empty_and_silent_buffers_have_no_segments() {
+        assert!(find_segments::<[i16; 1]>(&[], 100, 10, 4).is_empty());
+        assert!(find_segments(&[[0_i16]; 100], 100, 10, 4).is_empty());
+        assert!(find_segments(&[[0_i16]; 100], 100, 10, 0).is_empty());
+    }
+}
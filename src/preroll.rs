@@ -0,0 +1,177 @@
+//! Starting each transmission a little before the gate opened.
+
+use crate::{HistoryBuffer, NoiseGate, Sink};
+use dasp::Frame;
+use noise_gate_core::__private;
+
+/// A [`NoiseGate`] which remembers the last few frames it skipped and replays
+/// them at the start of each transmission, so quiet attacks (e.g. the first
+/// consonant of a word) aren't cut off.
+///
+/// Skipped frames go into a [`HistoryBuffer`] which is allocated up front,
+/// so processing never allocates and this can be used from an audio thread.
+/// When the gate opens, [`Sink::transmission_started()`] is given the
+/// position of the first replayed frame and then the history is drained into
+/// the [`Sink`].
+///
+/// ```rust
+/// use noise_gate::{preroll::PreRoll, NoiseGate};
+///
+/// let frames = [[0_i16], [1], [2], [3], [500], [0]];
+/// let mut gate = PreRoll::new(NoiseGate::new(100, 0), 2);
+/// let mut recorded = Vec::new();
+///
+/// gate.process_frames(&frames, &mut recorded);
+///
+/// assert_eq!(recorded, vec![[2], [3], [500], [0]]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreRoll<F: Frame> {
+    gate: NoiseGate<F::Sample>,
+    history: HistoryBuffer<F>,
+}
+
+impl<F: Frame> PreRoll<F> {
+    /// Wrap a [`NoiseGate`], replaying up to `frames` frames from before
+    /// each transmission.
+    pub fn new(gate: NoiseGate<F::Sample>, frames: usize) -> Self {
+        PreRoll {
+            gate,
+            history: HistoryBuffer::new(frames),
+        }
+    }
+
+    /// The most frames that will be replayed before a transmission.
+    pub fn pre_roll(&self) -> usize { self.history.capacity() }
+
+    /// Get a reference to the underlying [`NoiseGate`].
+    pub fn gate(&self) -> &NoiseGate<F::Sample> { &self.gate }
+
+    /// Get a mutable reference to the underlying [`NoiseGate`].
+    pub fn gate_mut(&mut self) -> &mut NoiseGate<F::Sample> { &mut self.gate }
+
+    /// Start again from scratch, forgetting the history.
+    pub fn reset(&mut self) {
+        self.gate.reset();
+        self.history.clear();
+    }
+
+    /// Tell the gate that `count` frames were lost (see
+    /// [`NoiseGate::frames_dropped()`]).
+    ///
+    /// The history from before the gap isn't contiguous with what comes
+    /// after it, so it's thrown away.
+    pub fn frames_dropped<K>(&mut self, count: u64, sink: &mut K)
+    where
+        K: Sink<F>,
+    {
+        if count > 0 {
+            self.history.clear();
+            self.gate.frames_dropped(count, sink);
+        }
+    }
+
+    /// Process a batch of frames, passing each transmission (and the frames
+    /// from just before it) through to the `sink`.
+    pub fn process_frames<K>(&mut self, frames: &[F], sink: &mut K)
+    where
+        K: Sink<F>,
+    {
+        let mut remaining = frames;
+
+        while !remaining.is_empty() {
+            let run = __private::next_run(&mut self.gate, remaining);
+            let (recorded, rest) = remaining.split_at(run.recorded);
+            let (skipped, rest) = rest.split_at(run.len - run.recorded);
+
+            if !recorded.is_empty() {
+                sink.record_frames(recorded);
+            }
+            if run.closed {
+                sink.end_of_transmission();
+            }
+
+            self.history.extend(skipped.iter().copied());
+
+            if run.opened {
+                let start = self.gate.position() - self.history.len() as u64;
+                sink.transmission_started(start);
+                self.history.drain_into(sink);
+            }
+
+            remaining = rest;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct Clips {
+        clips: Vec<(u64, Vec<[i16; 1]>)>,
+    }
+
+    impl Sink<[i16; 1]> for Clips {
+        fn record(&mut self, frame: [i16; 1]) {
+            self.clips.last_mut().unwrap().1.push(frame);
+        }
+
+        fn end_of_transmission(&mut self) {}
+
+        fn transmission_started(&mut self, position: u64) {
+            self.clips.push((position, Vec::new()));
+        }
+    }
+
+    #[test]
+    fn clips_start_with_the_pre_roll() {
+        let frames = [[1], [2], [3], [500], [0], [4], [5], [600], [0]];
+        let mut gate = PreRoll::new(NoiseGate::new(100, 0), 2);
+        let mut sink = Clips::default();
+
+        gate.process_frames(&frames, &mut sink);
+
+        assert_eq!(
+            sink.clips,
+            vec![
+                (1, vec![[2], [3], [500], [0]]),
+                (5, vec![[4], [5], [600], [0]]),
+            ]
+        );
+    }
+
+    #[test]
+    fn chunk_size_doesnt_matter() {
+        let frames: Vec<[i16; 1]> = (0..200)
+            .map(|i| [if i % 50 == 40 { 500 } else { i % 50 }])
+            .collect();
+        let mut expected = Clips::default();
+        PreRoll::new(NoiseGate::new(100, 3), 5)
+            .process_frames(&frames, &mut expected);
+
+        for chunk_size in [1, 3, 7, 64] {
+            let mut gate = PreRoll::new(NoiseGate::new(100, 3), 5);
+            let mut sink = Clips::default();
+
+            for chunk in frames.chunks(chunk_size) {
+                gate.process_frames(chunk, &mut sink);
+            }
+
+            assert_eq!(sink.clips, expected.clips, "{}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn dropped_frames_clear_the_history() {
+        let mut gate = PreRoll::new(NoiseGate::new(100, 0), 4);
+        let mut sink = Clips::default();
+
+        gate.process_frames(&[[1], [2], [3]], &mut sink);
+        gate.frames_dropped(10, &mut sink);
+        gate.process_frames(&[[4], [500]], &mut sink);
+
+        assert_eq!(sink.clips, vec![(13, vec![[4], [500]])]);
+    }
+}
@@ -0,0 +1,132 @@
+//! Starting points for common kinds of recordings.
+//!
+//! Picking a threshold and release time from scratch is mostly guesswork, so
+//! these are tuned for a handful of common situations. Each threshold is
+//! given in dBFS so it means the same thing regardless of the sample format.
+//!
+//! ```rust
+//! use noise_gate::{presets, NoiseGate};
+//!
+//! let gate: NoiseGate<i16> = presets::podcast(44_100);
+//!
+//! assert_eq!(gate.open_threshold, 327);
+//! assert_eq!(gate.release_time, 11_025);
+//! ```
+
+use crate::NoiseGate;
+use dasp::{sample::FromSample, Sample};
+use std::time::Duration;
+
+/// A set of tuned parameters for a [`NoiseGate`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Preset {
+    /// The level the gate opens at, in dB relative to full scale.
+    pub threshold_dbfs: f64,
+    /// How long the signal needs to stay quiet before the gate closes.
+    pub release_time: Duration,
+    /// How long to fade the start and end of each clip for (see
+    /// [`FadeEdges`][crate::sinks::FadeEdges]) to avoid clicks.
+    pub fade_time: Duration,
+}
+
+impl Preset {
+    /// Quiet sources with long natural decays (birdsong, ambience, etc.).
+    pub const FIELD_RECORDING: Preset = Preset {
+        threshold_dbfs: -50.0,
+        release_time: Duration::from_millis(2000),
+        fade_time: Duration::from_millis(20),
+    };
+    /// Radio receivers, where squelch tails and keyed-up carriers need a
+    /// longer release.
+    pub const HAM_RADIO: Preset = Preset {
+        threshold_dbfs: -36.0,
+        release_time: Duration::from_millis(1000),
+        fade_time: Duration::from_millis(5),
+    };
+    /// Voice recordings made in a reasonably quiet room, closing quickly
+    /// between sentences so clips stay tight.
+    pub const PODCAST: Preset = Preset {
+        threshold_dbfs: -40.0,
+        release_time: Duration::from_millis(250),
+        fade_time: Duration::from_millis(5),
+    };
+
+    /// The threshold as a sample value.
+    pub fn threshold<S>(&self) -> S
+    where
+        S: Sample + FromSample<f64>,
+    {
+        crate::analysis::from_dbfs(self.threshold_dbfs).to_sample()
+    }
+
+    /// The release time as a number of frames.
+    pub fn release_frames(&self, sample_rate: u32) -> usize {
+        to_frames(self.release_time, sample_rate)
+    }
+
+    /// The fade time as a number of frames.
+    pub fn fade_frames(&self, sample_rate: u32) -> usize {
+        to_frames(self.fade_time, sample_rate)
+    }
+
+    /// Create a [`NoiseGate`] using these parameters.
+    pub fn gate<S>(&self, sample_rate: u32) -> NoiseGate<S>
+    where
+        S: Sample + FromSample<f64>,
+    {
+        NoiseGate::new(self.threshold(), self.release_frames(sample_rate))
+    }
+}
+
+/// A [`NoiseGate`] for [`Preset::PODCAST`].
+pub fn podcast<S>(sample_rate: u32) -> NoiseGate<S>
+where
+    S: Sample + FromSample<f64>,
+{
+    Preset::PODCAST.gate(sample_rate)
+}
+
+/// A [`NoiseGate`] for [`Preset::HAM_RADIO`].
+pub fn ham_radio<S>(sample_rate: u32) -> NoiseGate<S>
+where
+    S: Sample + FromSample<f64>,
+{
+    Preset::HAM_RADIO.gate(sample_rate)
+}
+
+/// A [`NoiseGate`] for [`Preset::FIELD_RECORDING`].
+pub fn field_recording<S>(sample_rate: u32) -> NoiseGate<S>
+where
+    S: Sample + FromSample<f64>,
+{
+    Preset::FIELD_RECORDING.gate(sample_rate)
+}
+
+fn to_frames(duration: Duration, sample_rate: u32) -> usize {
+    (duration.as_secs_f64() * f64::from(sample_rate)).round() as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dasp::sample::I24;
+
+    #[test]
+    fn thresholds_are_the_same_for_every_format() {
+        let preset = Preset::HAM_RADIO;
+
+        assert_eq!(preset.threshold::<i16>(), 519);
+        assert_eq!(preset.threshold::<i32>() >> 16, 519);
+        assert_eq!(preset.threshold::<I24>().inner() >> 8, 519);
+        assert_eq!(preset.threshold::<u8>(), 130);
+        assert!((preset.threshold::<f32>() - 0.015_849).abs() < 1e-6);
+    }
+
+    #[test]
+    fn durations_scale_with_the_sample_rate() {
+        let gate: NoiseGate<f32> = field_recording(48_000);
+        assert_eq!(gate.release_time, 96_000);
+
+        assert_eq!(Preset::FIELD_RECORDING.fade_frames(8_000), 160);
+    }
+}
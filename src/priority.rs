@@ -0,0 +1,207 @@
+//! Mixing a priority source over a secondary one, like a paging mic which
+//! cuts through background music or a dispatcher talking over a repeater.
+
+use crate::{NoiseGate, Sink};
+use dasp::{sample::Duplex, Frame, Sample};
+use noise_gate_core::__private;
+
+/// Two gated inputs mixed into one stream, where the secondary input is
+/// ducked (or muted entirely) whenever the priority input's gate is open.
+///
+/// Each input has its own [`NoiseGate`], so they can use different
+/// thresholds and release times. The [`Sink`] sees one transmission for as
+/// long as either gate is open, with the inputs mixed together.
+///
+/// ```rust
+/// use noise_gate::{priority::PriorityMixer, NoiseGate};
+///
+/// let paging = [[0.0_f32], [0.0], [0.8], [0.0], [0.0], [0.0]];
+/// let music = [[0.3_f32], [0.3], [0.3], [0.3], [0.3], [0.3]];
+///
+/// let mut mixer =
+///     PriorityMixer::new(NoiseGate::new(0.5, 1), NoiseGate::new(0.1, 0));
+/// let mut mixed = Vec::new();
+/// mixer.process(&paging, &music, &mut mixed);
+///
+/// // the music drops out while the page is going
+/// assert_eq!(mixed, vec![[0.3], [0.3], [0.8], [0.0], [0.0], [0.3]]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriorityMixer<S> {
+    priority: NoiseGate<S>,
+    secondary: NoiseGate<S>,
+    /// The gain applied to the secondary input while the priority input is
+    /// active, where `0.0` (the default) mutes it completely.
+    pub duck_gain: f64,
+    position: u64,
+    transmitting: bool,
+    priority_open: Vec<bool>,
+    secondary_open: Vec<bool>,
+}
+
+impl<S> PriorityMixer<S> {
+    /// Create a new [`PriorityMixer`] from the gates for each input.
+    pub fn new(priority: NoiseGate<S>, secondary: NoiseGate<S>) -> Self {
+        PriorityMixer {
+            priority,
+            secondary,
+            duck_gain: 0.0,
+            position: 0,
+            transmitting: false,
+            priority_open: Vec::new(),
+            secondary_open: Vec::new(),
+        }
+    }
+
+    /// Duck the secondary input to this gain instead of muting it.
+    pub fn with_duck_gain(self, duck_gain: f64) -> Self {
+        PriorityMixer { duck_gain, ..self }
+    }
+
+    /// Get a reference to the priority input's [`NoiseGate`].
+    pub fn priority_gate(&self) -> &NoiseGate<S> { &self.priority }
+
+    /// Get a mutable reference to the priority input's [`NoiseGate`].
+    pub fn priority_gate_mut(&mut self) -> &mut NoiseGate<S> {
+        &mut self.priority
+    }
+
+    /// Get a reference to the secondary input's [`NoiseGate`].
+    pub fn secondary_gate(&self) -> &NoiseGate<S> { &self.secondary }
+
+    /// Get a mutable reference to the secondary input's [`NoiseGate`].
+    pub fn secondary_gate_mut(&mut self) -> &mut NoiseGate<S> {
+        &mut self.secondary
+    }
+
+    /// Is the priority input currently ducking the secondary one?
+    pub fn is_ducking(&self) -> bool { self.priority.is_open() }
+
+    /// Is the mixer currently passing anything through to the [`Sink`]?
+    pub fn is_transmitting(&self) -> bool { self.transmitting }
+}
+
+impl<S: Sample + Duplex<f64>> PriorityMixer<S> {
+    /// Gate both inputs and pass the mix through to the `sink`.
+    ///
+    /// The `i`'th priority frame is mixed with the `i`'th secondary frame,
+    /// and if one buffer is longer than the other the extra frames are
+    /// ignored. Like the [`NoiseGate`], the mixer remembers its state
+    /// between calls.
+    pub fn process<F, K>(
+        &mut self,
+        priority: &[F],
+        secondary: &[F],
+        sink: &mut K,
+    ) where
+        F: Frame<Sample = S>,
+        K: Sink<F>,
+    {
+        let len = priority.len().min(secondary.len());
+        let priority = &priority[..len];
+        let secondary = &secondary[..len];
+
+        open_frames(&mut self.priority, priority, &mut self.priority_open);
+        open_frames(&mut self.secondary, secondary, &mut self.secondary_open);
+
+        for i in 0..len {
+            let priority_open = self.priority_open[i];
+            let secondary_open = self.secondary_open[i];
+
+            if !priority_open && !secondary_open {
+                if self.transmitting {
+                    self.transmitting = false;
+                    sink.end_of_transmission();
+                }
+                continue;
+            }
+
+            if !self.transmitting {
+                self.transmitting = true;
+                sink.transmission_started(self.position + i as u64);
+            }
+
+            let priority_gain = if priority_open { 1.0 } else { 0.0 };
+            let secondary_gain = match (priority_open, secondary_open) {
+                (_, false) => 0.0,
+                (true, true) => self.duck_gain,
+                (false, true) => 1.0,
+            };
+            let mixed = priority[i].zip_map(secondary[i], |p, s| {
+                let p = p.to_sample::<f64>() * priority_gain;
+                let s = s.to_sample::<f64>() * secondary_gain;
+                (p + s).to_sample::<S>()
+            });
+
+            sink.record(mixed);
+        }
+
+        self.position += len as u64;
+    }
+}
+
+/// Run `frames` through a gate, noting whether each one was recorded.
+fn open_frames<S, F>(
+    gate: &mut NoiseGate<S>,
+    frames: &[F],
+    open: &mut Vec<bool>,
+) where
+    S: Sample,
+    F: Frame<Sample = S>,
+{
+    open.clear();
+    let mut remaining = frames;
+
+    while !remaining.is_empty() {
+        let run = __private::next_run(gate, remaining);
+        open.resize(open.len() + run.recorded, true);
+        open.resize(open.len() + run.len - run.recorded, false);
+        remaining = &remaining[run.len..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Transmissions(Vec<(u64, Vec<[f64; 1]>)>);
+
+    impl Sink<[f64; 1]> for Transmissions {
+        fn record(&mut self, frame: [f64; 1]) {
+            self.0.last_mut().unwrap().1.push(frame);
+        }
+
+        fn end_of_transmission(&mut self) {}
+
+        fn transmission_started(&mut self, position: u64) {
+            self.0.push((position, Vec::new()));
+        }
+    }
+
+    #[test]
+    fn the_secondary_input_is_ducked_while_the_priority_is_open() {
+        let mut priority = [[0.0]; 12];
+        priority[2] = [0.5];
+        priority[9] = [0.5];
+        let mut secondary = [[0.0]; 12];
+        secondary[1..5].iter_mut().for_each(|f| *f = [0.2]);
+
+        let mut mixer =
+            PriorityMixer::new(NoiseGate::new(0.5, 0), NoiseGate::new(0.1, 0))
+                .with_duck_gain(0.5);
+        let mut got = Transmissions::default();
+
+        // streaming in chunks is the same as doing it all at once
+        for (p, s) in priority.chunks(5).zip(secondary.chunks(5)) {
+            mixer.process(p, s, &mut got);
+        }
+
+        let expected = vec![
+            (1, vec![[0.2], [0.6], [0.1], [0.2], [0.0]]),
+            (9, vec![[0.5], [0.0]]),
+        ];
+        assert_eq!(got.0, expected);
+        assert!(!mixer.is_transmitting());
+    }
+}
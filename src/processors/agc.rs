@@ -0,0 +1,140 @@
+use crate::{analysis::frame_peak, processors::Processor};
+use dasp::{sample::Duplex, Frame, Sample};
+
+/// Anything quieter than this is flushed to zero so the envelope never
+/// decays into denormal numbers.
+const DENORMAL_LIMIT: f64 = 1e-30;
+
+/// Automatic gain control, which raises or lowers the volume so the signal's
+/// peaks sit around a target level.
+///
+/// Wildly varying input levels (e.g. a hand-held microphone) make any fixed
+/// threshold unusable, so running an [`Agc`] first gives the gate something
+/// more consistent to work with.
+///
+/// The gain never goes above `max_gain`, otherwise the background noise
+/// would be amplified until it sounds like a transmission. The envelope
+/// rises over roughly `attack_time` frames and falls over `release_time`
+/// frames.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Agc {
+    /// The level peaks are brought to, where `1.0` is full scale.
+    pub target_level: f64,
+    /// The most the signal will ever be amplified by.
+    pub max_gain: f64,
+    attack_time: usize,
+    release_time: usize,
+    envelope: f64,
+}
+
+impl Agc {
+    /// Create a new [`Agc`].
+    pub fn new(
+        target_level: f64,
+        max_gain: f64,
+        attack_time: usize,
+        release_time: usize,
+    ) -> Self {
+        Agc {
+            target_level,
+            max_gain,
+            attack_time: attack_time.max(1),
+            release_time: release_time.max(1),
+            // start at unity gain rather than blasting the first few frames
+            envelope: target_level,
+        }
+    }
+
+    /// The gain currently being applied.
+    pub fn gain(&self) -> f64 {
+        (self.target_level / self.envelope).min(self.max_gain)
+    }
+
+    fn update_envelope(&mut self, level: f64) {
+        // skip NaN and infinity so they can't poison the envelope forever
+        if !level.is_finite() {
+            return;
+        }
+
+        let time = if level > self.envelope {
+            self.attack_time
+        } else {
+            self.release_time
+        };
+        self.envelope += (level - self.envelope) / time as f64;
+
+        if self.envelope < DENORMAL_LIMIT {
+            self.envelope = 0.0;
+        }
+    }
+}
+
+impl<F> Processor<F> for Agc
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn process(&mut self, frames: &mut [F]) {
+        for frame in frames {
+            self.update_envelope(frame_peak(*frame));
+            let gain = self.gain();
+
+            *frame = frame.map(|sample| {
+                let sample = sample.to_sample::<f64>() * gain;
+                sample.clamp(-1.0, 1.0).to_sample()
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(amplitude: f64, len: usize) -> Vec<[f64; 1]> {
+        (0..len)
+            .map(|i| [amplitude * (i as f64 * 0.05).sin()])
+            .collect()
+    }
+
+    fn peak(frames: &[[f64; 1]]) -> f64 {
+        frames.iter().map(|f| f[0].abs()).fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn quiet_and_loud_signals_end_up_at_the_target() {
+        for &amplitude in &[0.05, 0.9] {
+            let mut agc = Agc::new(0.5, 20.0, 10, 1000);
+            let mut frames = sine(amplitude, 20_000);
+
+            agc.process(&mut frames);
+
+            let settled = peak(&frames[10_000..]);
+            assert!((settled - 0.5).abs() < 0.1, "{}: {}", amplitude, settled);
+        }
+    }
+
+    #[test]
+    fn background_noise_is_only_amplified_up_to_max_gain() {
+        let mut agc = Agc::new(0.5, 4.0, 10, 100);
+        let mut frames = sine(0.001, 10_000);
+
+        agc.process(&mut frames);
+
+        assert_eq!(agc.gain(), 4.0);
+        assert!(peak(&frames[5000..]) <= 0.004 + 1e-9);
+    }
+
+    #[test]
+    fn integer_samples_never_wrap_around() {
+        let mut agc = Agc::new(0.9, 100.0, 1, 1);
+        let mut frames = vec![[100_i16], [-100], [i16::MIN], [i16::MAX]];
+
+        agc.process(&mut frames);
+
+        assert!(frames[0][0] > 100);
+        assert!(frames[1][0] < -100);
+        assert!(frames[2][0] < 0);
+        assert!(frames[3][0] > 0);
+    }
+}
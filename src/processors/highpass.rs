@@ -0,0 +1,184 @@
+use crate::processors::Processor;
+use dasp::{sample::Duplex, Frame, Sample};
+use std::{f64::consts::PI, marker::PhantomData};
+
+/// A second-order (biquad) high-pass filter, for removing wind noise,
+/// handling noise, and other rumble.
+///
+/// Low frequency noise often carries a lot of energy, so it can easily be
+/// enough to trigger the gate on its own. The filter can be used on the audio
+/// itself (it implements [`Processor`]), or just in the detection path by
+/// filtering a copy and passing it to
+/// [`NoiseGate::process_sidechain()`][crate::NoiseGate::process_sidechain].
+///
+/// ```rust
+/// use noise_gate::{processors::HighPass, NoiseGate, Sink};
+///
+/// # struct Discard;
+/// # impl Sink<[i16; 2]> for Discard {
+/// #     fn record(&mut self, _: [i16; 2]) {}
+/// #     fn end_of_transmission(&mut self) {}
+/// # }
+/// let mut filter = HighPass::new(80.0, 48_000.0);
+/// let mut gate = NoiseGate::new(500, 12_000);
+///
+/// let frames = vec![[0_i16, 0]; 1024];
+/// let mut sidechain = Vec::with_capacity(frames.len());
+///
+/// sidechain.extend(frames.iter().map(|&frame| filter.filter(frame)));
+/// gate.process_sidechain(&frames, &sidechain, &mut Discard);
+/// ```
+pub struct HighPass<F> {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    /// The filter's state (`[z1, z2]`) for each channel.
+    state: Vec<[f64; 2]>,
+    _frame: PhantomData<fn(F) -> F>,
+}
+
+impl<F: Frame> HighPass<F> {
+    /// Create a Butterworth high-pass filter which starts rolling off at
+    /// `cutoff` Hz.
+    pub fn new(cutoff: f64, sample_rate: f64) -> Self {
+        HighPass::with_q(cutoff, sample_rate, std::f64::consts::FRAC_1_SQRT_2)
+    }
+
+    /// Create a high-pass filter with a particular `q` (resonance). Higher
+    /// values give a sharper cutoff, but with a bump just above it.
+    pub fn with_q(cutoff: f64, sample_rate: f64, q: f64) -> Self {
+        // See the "Audio EQ Cookbook" by Robert Bristow-Johnson
+        let w0 = 2.0 * PI * cutoff / sample_rate;
+        let cos = w0.cos();
+        let alpha = w0.sin() / (2.0 * q);
+        let a0 = 1.0 + alpha;
+
+        HighPass {
+            b0: (1.0 + cos) / 2.0 / a0,
+            b1: -(1.0 + cos) / a0,
+            b2: (1.0 + cos) / 2.0 / a0,
+            a1: -2.0 * cos / a0,
+            a2: (1.0 - alpha) / a0,
+            state: vec![[0.0; 2]; F::CHANNELS],
+            _frame: PhantomData,
+        }
+    }
+
+    /// Forget about any previous frames.
+    pub fn reset(&mut self) {
+        for state in &mut self.state {
+            *state = [0.0; 2];
+        }
+    }
+}
+
+impl<F> HighPass<F>
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    /// Filter a single frame.
+    pub fn filter(&mut self, frame: F) -> F {
+        let (b0, b1, b2, a1, a2) =
+            (self.b0, self.b1, self.b2, self.a1, self.a2);
+        let mut channels = self.state.iter_mut();
+
+        frame.map(|sample| {
+            let state = channels.next().expect("One state per channel");
+            let x = sample.to_sample::<f64>();
+
+            // transposed direct form II
+            let y = b0 * x + state[0];
+            state[0] = b1 * x - a1 * y + state[1];
+            state[1] = b2 * x - a2 * y;
+
+            y.clamp(-1.0, 1.0).to_sample()
+        })
+    }
+}
+
+impl<F> Processor<F> for HighPass<F>
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    fn process(&mut self, frames: &mut [F]) {
+        for frame in frames {
+            *frame = self.filter(*frame);
+        }
+    }
+}
+
+impl<F> std::fmt::Debug for HighPass<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HighPass")
+            .field("b0", &self.b0)
+            .field("b1", &self.b1)
+            .field("b2", &self.b2)
+            .field("a1", &self.a1)
+            .field("a2", &self.a2)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+impl<F> Clone for HighPass<F> {
+    fn clone(&self) -> Self {
+        HighPass {
+            b0: self.b0,
+            b1: self.b1,
+            b2: self.b2,
+            a1: self.a1,
+            a2: self.a2,
+            state: self.state.clone(),
+            _frame: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peak_after_filtering(frequency: f64) -> f64 {
+        let sample_rate = 48_000.0;
+        let mut filter = HighPass::new(80.0, sample_rate);
+        let mut frames: Vec<[f64; 2]> = (0..48_000)
+            .map(|i| {
+                let t = i as f64 / sample_rate;
+                let s = 0.5 * (2.0 * PI * frequency * t).sin();
+                [s, -s]
+            })
+            .collect();
+
+        filter.process(&mut frames);
+
+        // skip the first half while the filter settles
+        frames[24_000..]
+            .iter()
+            .map(|f| f[0].abs().max(f[1].abs()))
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn rumble_is_removed() {
+        assert!(peak_after_filtering(10.0) < 0.01);
+        assert!(peak_after_filtering(20.0) < 0.04);
+    }
+
+    #[test]
+    fn speech_passes_through() {
+        let peak = peak_after_filtering(1000.0);
+
+        assert!((peak - 0.5).abs() < 0.01, "{}", peak);
+    }
+
+    #[test]
+    fn the_cutoff_is_3db_down() {
+        let peak = peak_after_filtering(80.0);
+
+        assert!((peak - 0.5 / 2.0_f64.sqrt()).abs() < 0.01, "{}", peak);
+    }
+}
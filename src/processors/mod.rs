@@ -0,0 +1,83 @@
+//! Stages which can be run over audio before it reaches the gate.
+//!
+//! ```rust
+//! use noise_gate::{processors::{Agc, Processor}, NoiseGate, Sink};
+//!
+//! # struct Discard;
+//! # impl Sink<[f32; 1]> for Discard {
+//! #     fn record(&mut self, _: [f32; 1]) {}
+//! #     fn end_of_transmission(&mut self) {}
+//! # }
+//! let mut agc = Agc::new(0.5, 10.0, 10, 4800);
+//! let mut gate = NoiseGate::new(0.1, 4800);
+//!
+//! let mut frames = vec![[0.05_f32]; 1024];
+//! agc.process(&mut frames);
+//! gate.process_frames(&frames, &mut Discard);
+//! ```
+
+mod agc;
+mod highpass;
+
+pub use agc::Agc;
+pub use highpass::HighPass;
+
+/// Something which modifies frames in place.
+///
+/// Processors can be chained by putting them in a tuple, in which case
+/// they're run from left to right.
+pub trait Processor<F> {
+    /// Process a batch of frames.
+    ///
+    /// Like the [`NoiseGate`][crate::NoiseGate], processors should remember
+    /// their state between calls so a stream can be processed in chunks of
+    /// any size.
+    fn process(&mut self, frames: &mut [F]);
+
+    /// How many frames of delay this processor adds, so hosts can
+    /// compensate for it.
+    fn latency_samples(&self) -> usize { 0 }
+}
+
+impl<F, P> Processor<F> for &mut P
+where
+    P: Processor<F> + ?Sized,
+{
+    fn process(&mut self, frames: &mut [F]) { (**self).process(frames); }
+
+    fn latency_samples(&self) -> usize { (**self).latency_samples() }
+}
+
+impl<F, A, B> Processor<F> for (A, B)
+where
+    A: Processor<F>,
+    B: Processor<F>,
+{
+    fn process(&mut self, frames: &mut [F]) {
+        self.0.process(frames);
+        self.1.process(frames);
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.0.latency_samples() + self.1.latency_samples()
+    }
+}
+
+impl<F, A, B, C> Processor<F> for (A, B, C)
+where
+    A: Processor<F>,
+    B: Processor<F>,
+    C: Processor<F>,
+{
+    fn process(&mut self, frames: &mut [F]) {
+        self.0.process(frames);
+        self.1.process(frames);
+        self.2.process(frames);
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.0.latency_samples()
+            + self.1.latency_samples()
+            + self.2.latency_samples()
+    }
+}
@@ -0,0 +1,313 @@
+//! Gating on how different the audio sounds from a recording of the
+//! background noise, rather than how loud it is.
+//!
+//! A fixed threshold struggles when the noise is tonal (e.g. mains hum or a
+//! fan), because the hum alone can be louder than quiet speech. A
+//! [`NoiseProfile`] learns the noise's spectrum, then scores each block of
+//! audio by how far it rises above that spectrum. The scores can be used as
+//! a sidechain so the gate opens on anything which *isn't* the usual noise.
+//!
+//! ```rust
+//! use noise_gate::{profile::NoiseProfile, NoiseGate};
+//! use std::f64::consts::PI;
+//!
+//! let hum = |i: usize| 0.3 * (2.0 * PI * i as f64 / 64.0).sin();
+//! let noise: Vec<[f64; 1]> = (0..4096).map(|i| [hum(i)]).collect();
+//! let profile = NoiseProfile::learn_noise(&noise, 256, 16).unwrap();
+//!
+//! // the same hum, with a quieter whistle in the middle
+//! let frames: Vec<[f64; 1]> = (0..4096)
+//!     .map(|i| {
+//!         let whistle = if (1024..2048).contains(&i) {
+//!             0.1 * (2.0 * PI * i as f64 / 5.0).sin()
+//!         } else {
+//!             0.0
+//!         };
+//!         [hum(i) + whistle]
+//!     })
+//!     .collect();
+//!
+//! // a level-based gate opens on every peak of the hum
+//! let mut by_level = NoiseGate::new(0.2, 0);
+//! assert!(by_level.segments(&frames).count() > 100);
+//!
+//! // but the hum doesn't deviate from the profile, so it's ignored
+//! let sidechain = profile.sidechain(&frames);
+//! let mut matched = NoiseGate::new(3.0, 0);
+//! let segments: Vec<_> = matched
+//!     .segments(&sidechain)
+//!     .map(|(range, _)| range)
+//!     .collect();
+//! assert_eq!(segments, vec![1024..2049]);
+//!
+//! // the sidechain lines up with the original frames
+//! let mut recorded = Vec::new();
+//! NoiseGate::new(3.0, 0).process_sidechain(&frames, &sidechain, &mut recorded);
+//! assert_eq!(recorded, &frames[1024..2049]);
+//! ```
+
+use dasp::{sample::Duplex, Frame, Sample};
+use std::f64::consts::PI;
+
+/// Added to every band's power so silence doesn't produce infinite ratios.
+const POWER_FLOOR: f64 = 1e-12;
+
+/// The spectrum of a recording's background noise, split into equally wide
+/// frequency bands.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseProfile {
+    block_size: usize,
+    bands: Vec<f64>,
+}
+
+impl NoiseProfile {
+    /// Learn the average spectrum of some background noise, analysing it in
+    /// blocks of `block_size` frames (rounded up to a power of two) and
+    /// grouping the frequencies into `bands` bands.
+    ///
+    /// Multi-channel frames are averaged down to mono first. Returns `None`
+    /// if there isn't at least one whole block of noise.
+    pub fn learn_noise<F>(
+        frames: &[F],
+        block_size: usize,
+        bands: usize,
+    ) -> Option<Self>
+    where
+        F: Frame,
+        F::Sample: Duplex<f64>,
+    {
+        let block_size = block_size.max(2).next_power_of_two();
+        let bands = bands.clamp(1, block_size / 2);
+        let mut analyser = Analyser::new(block_size, bands);
+        let mut total = vec![0.0; bands];
+        let mut blocks = 0;
+
+        for block in frames.chunks_exact(block_size) {
+            for (sum, power) in total.iter_mut().zip(analyser.bands(block)) {
+                *sum += power;
+            }
+            blocks += 1;
+        }
+
+        if blocks == 0 {
+            return None;
+        }
+
+        Some(NoiseProfile {
+            block_size,
+            bands: total.into_iter().map(|sum| sum / blocks as f64).collect(),
+        })
+    }
+
+    /// The number of frames analysed at a time.
+    pub fn block_size(&self) -> usize { self.block_size }
+
+    /// The noise's average power in each band, from lowest to highest
+    /// frequency.
+    pub fn bands(&self) -> &[f64] { &self.bands }
+
+    /// Work out how far each block of `frames` deviates from the noise,
+    /// returning one score per frame so it can be passed to
+    /// [`NoiseGate::process_sidechain()`][crate::NoiseGate::process_sidechain].
+    ///
+    /// The score is the average number of decibels each band rises above
+    /// the noise (bands which are quieter than the noise count as zero), so
+    /// a gate threshold of a few dB is a good place to start. Every frame in
+    /// a block gets the same score, and a partial block at the end is padded
+    /// with silence.
+    ///
+    /// Blocks start at the beginning of `frames`, so when streaming, pass
+    /// chunks which are a multiple of [`NoiseProfile::block_size()`].
+    pub fn sidechain<F>(&self, frames: &[F]) -> Vec<[f64; 1]>
+    where
+        F: Frame,
+        F::Sample: Duplex<f64>,
+    {
+        let mut analyser = Analyser::new(self.block_size, self.bands.len());
+        let mut sidechain = Vec::with_capacity(frames.len());
+
+        for block in frames.chunks(self.block_size) {
+            let score = self.score(analyser.bands(block));
+            sidechain.extend(std::iter::repeat_n([score], block.len()));
+        }
+
+        sidechain
+    }
+
+    /// How far a block of frames deviates from the noise, in decibels (see
+    /// [`NoiseProfile::sidechain()`]).
+    pub fn deviation<F>(&self, block: &[F]) -> f64
+    where
+        F: Frame,
+        F::Sample: Duplex<f64>,
+    {
+        let mut analyser = Analyser::new(self.block_size, self.bands.len());
+        self.score(analyser.bands(block))
+    }
+
+    fn score(&self, bands: &[f64]) -> f64 {
+        let total: f64 = bands
+            .iter()
+            .zip(&self.bands)
+            .map(|(&power, &noise)| {
+                let ratio = (power + POWER_FLOOR) / (noise + POWER_FLOOR);
+                (10.0 * ratio.log10()).max(0.0)
+            })
+            .sum();
+
+        total / self.bands.len() as f64
+    }
+}
+
+/// Scratch space for turning blocks of audio into band powers.
+struct Analyser {
+    window: Vec<f64>,
+    re: Vec<f64>,
+    im: Vec<f64>,
+    bands: Vec<f64>,
+}
+
+impl Analyser {
+    fn new(block_size: usize, bands: usize) -> Self {
+        let window = (0..block_size)
+            .map(|i| {
+                0.5 - 0.5 * (2.0 * PI * i as f64 / block_size as f64).cos()
+            })
+            .collect();
+
+        Analyser {
+            window,
+            re: vec![0.0; block_size],
+            im: vec![0.0; block_size],
+            bands: vec![0.0; bands],
+        }
+    }
+
+    /// The (Hann windowed) power in each band, ignoring DC.
+    fn bands<F>(&mut self, block: &[F]) -> &[f64]
+    where
+        F: Frame,
+        F::Sample: Duplex<f64>,
+    {
+        let len = self.window.len();
+
+        for i in 0..len {
+            let sample = block.get(i).map_or(0.0, |&frame| mono(frame));
+            // non-finite samples would poison the whole block
+            let sample = if sample.is_finite() { sample } else { 0.0 };
+            self.re[i] = sample * self.window[i];
+            self.im[i] = 0.0;
+        }
+        fft(&mut self.re, &mut self.im);
+
+        // bins 1 to len/2 (inclusive) are shared out as evenly as possible
+        let bins = len / 2;
+        let count = self.bands.len();
+        let (re, im) = (&self.re, &self.im);
+        for (band, power) in self.bands.iter_mut().enumerate() {
+            let start = 1 + band * bins / count;
+            let end = 1 + (band + 1) * bins / count;
+            *power = (start..end)
+                .map(|bin| re[bin].powi(2) + im[bin].powi(2))
+                .sum::<f64>()
+                / len as f64;
+        }
+
+        &self.bands
+    }
+}
+
+fn mono<F>(frame: F) -> f64
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    let sum: f64 = frame.channels().map(|s| s.to_sample::<f64>()).sum();
+    sum / F::CHANNELS as f64
+}
+
+/// An in-place radix-2 FFT, where the buffers' length is a power of two.
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+    let bits = n.trailing_zeros();
+
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if j > i {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut size = 2;
+    while size <= n {
+        let angle = -2.0 * PI / size as f64;
+        for start in (0..n).step_by(size) {
+            for k in 0..size / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let (a, b) = (start + k, start + k + size / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        size *= 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fft_finds_a_pure_tone() {
+        let n = 64;
+        let mut re: Vec<f64> = (0..n)
+            .map(|i| (2.0 * PI * 5.0 * i as f64 / n as f64).cos())
+            .collect();
+        let mut im = vec![0.0; n];
+
+        fft(&mut re, &mut im);
+
+        let magnitudes: Vec<f64> =
+            re.iter().zip(&im).map(|(r, i)| r.hypot(*i)).collect();
+        assert!((magnitudes[5] - 32.0).abs() < 1e-9);
+        assert!((magnitudes[59] - 32.0).abs() < 1e-9);
+        assert!(magnitudes
+            .iter()
+            .enumerate()
+            .all(|(bin, &m)| bin == 5 || bin == 59 || m < 1e-9));
+    }
+
+    #[test]
+    fn the_noise_itself_doesnt_deviate() {
+        let noise: Vec<[f32; 2]> = (0..8192_u32)
+            .map(|i| {
+                let hiss = (i.wrapping_mul(2_654_435_761) >> 16) as f32
+                    / 65536.0
+                    - 0.5;
+                [0.01 * hiss, 0.01 * hiss]
+            })
+            .collect();
+        let profile = NoiseProfile::learn_noise(&noise, 500, 8).unwrap();
+
+        assert_eq!(profile.block_size(), 512);
+        assert_eq!(profile.bands().len(), 8);
+        assert!(profile.deviation(&noise[..512]) < 3.0);
+        let loud: Vec<[f32; 2]> = noise[..512]
+            .iter()
+            .map(|&[l, r]| [l * 100.0, r * 100.0])
+            .collect();
+        assert!(profile.deviation(&loud) > 30.0);
+    }
+
+    #[test]
+    fn not_enough_noise_to_learn_from() {
+        let noise = vec![[0.0_f32]; 100];
+
+        assert!(NoiseProfile::learn_noise(&noise, 256, 4).is_none());
+    }
+}
@@ -0,0 +1,281 @@
+//! Changing the sample rate of the detection path.
+//!
+//! Some detectors (e.g. voice activity detectors or CTCSS decoders) only
+//! work at a particular sample rate. A [`Resampler`] can convert a copy of
+//! the audio to whatever rate the detector needs, and
+//! [`Resampler::to_input_frames()`] maps the detector's results back onto
+//! the original recording.
+
+use dasp::{sample::Duplex, Frame, Sample};
+use rubato::{FftFixedIn, Resampler as _};
+use std::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+};
+
+/// How many input frames are resampled at a time.
+const CHUNK_SIZE: usize = 1024;
+
+/// A streaming resampler, built on [`rubato`]'s FFT resampler.
+///
+/// Audio is resampled in fixed-size chunks, so the output lags behind the
+/// input (see [`Resampler::latency_samples()`]). The resampler's own delay
+/// is removed, so the first output frame always lines up with the first
+/// input frame, and [`Resampler::flush()`] pushes out whatever is left at
+/// the end of a stream.
+///
+/// ```rust
+/// use noise_gate::resample::Resampler;
+///
+/// // a detector which wants 16 kHz audio
+/// let mut resampler = Resampler::new(48_000, 16_000);
+/// let frames = vec![[0.5_f32, -0.5]; 4800];
+/// let mut detector_input = Vec::new();
+///
+/// resampler.process(&frames, &mut detector_input);
+/// resampler.flush(&mut detector_input);
+///
+/// assert_eq!(detector_input.len(), 1600);
+/// // something the detector found at 16 kHz happened here in the original
+/// assert_eq!(resampler.to_input_frames(800), 2400);
+/// ```
+pub struct Resampler<F> {
+    input_rate: u32,
+    output_rate: u32,
+    resampler: FftFixedIn<f64>,
+    /// Audio which hasn't been resampled yet, one buffer per channel.
+    input: Vec<Vec<f64>>,
+    /// Scratch space for the resampled audio, one buffer per channel.
+    output: Vec<Vec<f64>>,
+    /// How many more output frames to throw away to make up for the
+    /// resampler's delay.
+    delay_remaining: usize,
+    frames_in: u64,
+    frames_out: u64,
+    _frame: PhantomData<fn(F) -> F>,
+}
+
+impl<F: Frame> Resampler<F> {
+    /// Create a [`Resampler`] which converts from `input_rate` to
+    /// `output_rate`.
+    pub fn new(input_rate: u32, output_rate: u32) -> Self {
+        assert!(
+            input_rate > 0 && output_rate > 0,
+            "Sample rates must be positive"
+        );
+
+        let resampler = FftFixedIn::new(
+            input_rate as usize,
+            output_rate as usize,
+            CHUNK_SIZE,
+            2,
+            F::CHANNELS,
+        )
+        .expect("The sample rates were already checked");
+        let output = resampler.output_buffer_allocate(true);
+
+        Resampler {
+            input_rate,
+            output_rate,
+            delay_remaining: resampler.output_delay(),
+            resampler,
+            input: vec![Vec::with_capacity(CHUNK_SIZE); F::CHANNELS],
+            output,
+            frames_in: 0,
+            frames_out: 0,
+            _frame: PhantomData,
+        }
+    }
+
+    /// The sample rate being converted from.
+    pub fn input_rate(&self) -> u32 { self.input_rate }
+
+    /// The sample rate being converted to.
+    pub fn output_rate(&self) -> u32 { self.output_rate }
+
+    /// Convert a position in the resampled stream back to the matching frame
+    /// in the original.
+    pub fn to_input_frames(&self, output_frames: usize) -> usize {
+        (output_frames as f64 * f64::from(self.input_rate)
+            / f64::from(self.output_rate))
+        .round() as usize
+    }
+
+    /// How many input frames the output can lag behind by.
+    ///
+    /// Input is buffered until there's a whole chunk to resample, and then
+    /// the resampler's filter adds a little more delay on top.
+    pub fn latency_samples(&self) -> usize {
+        self.resampler.input_frames_max()
+            + self.to_input_frames(self.resampler.output_delay())
+    }
+
+    /// Forget about any previous frames.
+    pub fn reset(&mut self) {
+        self.resampler.reset();
+        self.input.iter_mut().for_each(Vec::clear);
+        self.delay_remaining = self.resampler.output_delay();
+        self.frames_in = 0;
+        self.frames_out = 0;
+    }
+}
+
+impl<F> Resampler<F>
+where
+    F: Frame,
+    F::Sample: Duplex<f64>,
+{
+    /// Resample a batch of frames, appending the result to `output`.
+    ///
+    /// The resampler remembers where it was, so a stream can be processed in
+    /// chunks of any size.
+    pub fn process(&mut self, input: &[F], output: &mut Vec<F>) {
+        for frame in input {
+            for (buffer, sample) in self.input.iter_mut().zip(frame.channels())
+            {
+                buffer.push(sample.to_sample());
+            }
+        }
+        self.frames_in += input.len() as u64;
+
+        while self.input[0].len() >= self.resampler.input_frames_next() {
+            let (used, written) = self
+                .resampler
+                .process_into_buffer(&self.input, &mut self.output, None)
+                .expect("The buffers are always big enough");
+
+            for buffer in &mut self.input {
+                buffer.drain(..used);
+            }
+            self.emit(written, u64::MAX, output);
+        }
+    }
+
+    /// Resample whatever is left at the end of the stream, appending it to
+    /// `output`, and get ready to start a new stream.
+    pub fn flush(&mut self, output: &mut Vec<F>) {
+        let expected = (self.frames_in as f64 * f64::from(self.output_rate)
+            / f64::from(self.input_rate))
+        .round() as u64;
+        let mut remaining = Some(std::mem::take(&mut self.input));
+
+        while self.frames_out < expected {
+            let (_, written) = self
+                .resampler
+                .process_partial_into_buffer(
+                    remaining.as_deref(),
+                    &mut self.output,
+                    None,
+                )
+                .expect("The buffers are always big enough");
+
+            if let Some(input) = remaining.take() {
+                self.input = input;
+            }
+            self.emit(written, expected, output);
+        }
+
+        self.reset();
+    }
+
+    /// Pass the first `written` frames of resampled audio on to `output`,
+    /// skipping the resampler's delay and stopping at `limit` frames.
+    fn emit(&mut self, written: usize, limit: u64, output: &mut Vec<F>) {
+        let skipped = written.min(self.delay_remaining);
+        self.delay_remaining -= skipped;
+
+        for i in skipped..written {
+            if self.frames_out >= limit {
+                break;
+            }
+
+            output.push(F::from_fn(|channel| {
+                self.output[channel][i].to_sample()
+            }));
+            self.frames_out += 1;
+        }
+    }
+}
+
+impl<F> Debug for Resampler<F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Resampler")
+            .field("input_rate", &self.input_rate)
+            .field("output_rate", &self.output_rate)
+            .field("buffered", &self.input.first().map_or(0, Vec::len))
+            .field("delay_remaining", &self.delay_remaining)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn tone(frequency: f64, sample_rate: u32, len: usize) -> Vec<[f64; 1]> {
+        (0..len)
+            .map(|i| {
+                let t = i as f64 / f64::from(sample_rate);
+                [(2.0 * PI * frequency * t).sin() * 0.5]
+            })
+            .collect()
+    }
+
+    fn resample(input: &[[f64; 1]], from: u32, to: u32) -> Vec<[f64; 1]> {
+        let mut resampler = Resampler::new(from, to);
+        let mut output = Vec::new();
+        resampler.process(input, &mut output);
+        resampler.flush(&mut output);
+        output
+    }
+
+    #[test]
+    fn the_output_lines_up_with_the_input() {
+        let input = tone(440.0, 48_000, 48_000);
+
+        let output = resample(&input, 48_000, 16_000);
+
+        assert_eq!(output.len(), 16_000);
+        let expected = tone(440.0, 16_000, 16_000);
+        // ignore the edges, where the filter doesn't have a full window, and
+        // allow for the part of the delay which is less than a whole frame
+        for (got, want) in output.iter().zip(&expected).skip(100).take(15_800)
+        {
+            assert!((got[0] - want[0]).abs() < 0.05, "{:?}", (got, want));
+        }
+    }
+
+    #[test]
+    fn frequencies_above_nyquist_are_filtered_out() {
+        // 7 kHz can't be represented at 8 kHz and would alias to 1 kHz
+        let input = tone(7_000.0, 48_000, 48_000);
+
+        let output = resample(&input, 48_000, 8_000);
+
+        let peak = output[100..7_900]
+            .iter()
+            .map(|frame| frame[0].abs())
+            .fold(0.0, f64::max);
+        assert!(peak < 0.01, "{}", peak);
+    }
+
+    #[test]
+    fn chunk_size_doesnt_matter() {
+        let input = tone(1000.0, 44_100, 5000);
+        let expected = resample(&input, 44_100, 16_000);
+        assert_eq!(expected.len(), 1814);
+
+        for &chunk_size in &[1, 7, 128, 4999] {
+            let mut resampler = Resampler::new(44_100, 16_000);
+            let mut got = Vec::new();
+
+            for chunk in input.chunks(chunk_size) {
+                resampler.process(chunk, &mut got);
+            }
+            resampler.flush(&mut got);
+
+            assert_eq!(got, expected, "{}", chunk_size);
+        }
+    }
+}
@@ -0,0 +1,384 @@
+//! Archiving clips to S3-compatible object storage.
+//!
+//! This plugs an [`S3Uploader`] into the [`UploadSink`] from the
+//! [`upload`](crate::upload) module, so clips are spooled to disk and
+//! uploaded from a background thread without ever blocking the audio thread.
+//! Anything which couldn't be uploaded stays in the spool directory until the
+//! bucket is reachable again.
+//!
+//! ```rust,no_run
+//! use noise_gate::{
+//!     s3::{Credentials, S3Uploader},
+//!     upload::{RetryPolicy, UploadSink},
+//!     NoiseGate,
+//! };
+//!
+//! let credentials = Credentials::new("AKIDEXAMPLE", "secret");
+//! let uploader = S3Uploader::new(
+//!     "https://s3.us-east-1.amazonaws.com",
+//!     "recordings",
+//!     "us-east-1",
+//!     credentials,
+//! )?
+//! .with_key_template("radio/{date}/{time}-{index}.wav");
+//! let mut sink = UploadSink::with_spool_dir(
+//!     uploader,
+//!     16_000,
+//!     RetryPolicy::default(),
+//!     "/var/spool/noise-gate",
+//! )?;
+//! let mut gate = NoiseGate::new(300_i16, 4_000);
+//!
+//! # let frames = [[0_i16]; 16];
+//! gate.process_frames(&frames, &mut sink);
+//! let report = sink.finish();
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use crate::upload::{
+    unix_seconds, utc, Clip, Endpoint, UploadError, Uploader,
+};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt::{self, Debug, Formatter, Write},
+    time::{Duration, SystemTime},
+};
+
+#[cfg(doc)]
+use crate::upload::UploadSink;
+
+/// The access key used to sign requests.
+#[derive(Clone, PartialEq)]
+pub struct Credentials {
+    access_key_id: String,
+    secret_access_key: String,
+}
+
+impl Credentials {
+    /// Create a new set of [`Credentials`].
+    pub fn new<A, S>(access_key_id: A, secret_access_key: S) -> Self
+    where
+        A: Into<String>,
+        S: Into<String>,
+    {
+        Credentials {
+            access_key_id: access_key_id.into(),
+            secret_access_key: secret_access_key.into(),
+        }
+    }
+}
+
+impl Debug for Credentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // don't leak the secret into logs
+        f.debug_struct("Credentials")
+            .field("access_key_id", &self.access_key_id)
+            .field("secret_access_key", &"...")
+            .finish()
+    }
+}
+
+/// An [`Uploader`] which `PUT`s each clip into an S3 bucket.
+///
+/// Requests use path-style addressing (`{endpoint}/{bucket}/{key}`) and are
+/// signed with AWS Signature Version 4, so this works with AWS itself as
+/// well as MinIO, Ceph, and friends. Use a `https://` endpoint for anything
+/// which isn't on the local network.
+///
+/// Object keys are generated from a template, where the following
+/// placeholders are replaced with details from the [`Clip`]:
+///
+/// | Placeholder     | Value                                        |
+/// | --------------- | -------------------------------------------- |
+/// | `{index}`       | The transmission number                      |
+/// | `{frames}`      | The clip's length in frames                  |
+/// | `{sample_rate}` | The sample rate                              |
+/// | `{timestamp}`   | Seconds since the Unix epoch when it ended   |
+/// | `{date}`        | The UTC date it ended, as `YYYY-MM-DD`       |
+/// | `{time}`        | The UTC time it ended, as `HHMMSS`           |
+#[derive(Debug, Clone, PartialEq)]
+pub struct S3Uploader {
+    endpoint: Endpoint,
+    bucket: String,
+    region: String,
+    credentials: Credentials,
+    key_template: String,
+    timeout: Duration,
+}
+
+impl S3Uploader {
+    /// The key template used when none is provided.
+    pub const DEFAULT_KEY_TEMPLATE: &'static str = "{date}/{time}-{index}.wav";
+
+    /// Create a new [`S3Uploader`].
+    pub fn new<B, R>(
+        endpoint: &str,
+        bucket: B,
+        region: R,
+        credentials: Credentials,
+    ) -> Result<Self, UploadError>
+    where
+        B: Into<String>,
+        R: Into<String>,
+    {
+        Ok(S3Uploader {
+            endpoint: Endpoint::parse(endpoint)?,
+            bucket: bucket.into(),
+            region: region.into(),
+            credentials,
+            key_template: String::from(S3Uploader::DEFAULT_KEY_TEMPLATE),
+            timeout: Duration::from_secs(30),
+        })
+    }
+
+    /// Set the template used to generate each clip's object key.
+    pub fn with_key_template<T: Into<String>>(self, key_template: T) -> Self {
+        S3Uploader {
+            key_template: key_template.into(),
+            ..self
+        }
+    }
+
+    /// How long to wait for the server before giving up on an attempt.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        S3Uploader { timeout, ..self }
+    }
+
+    /// Get the object key a clip will be stored under.
+    pub fn key(&self, clip: &Clip) -> String {
+        clip.fill_template(&self.key_template)
+    }
+
+    fn put(
+        &self,
+        key: &str,
+        body: &[u8],
+        now: SystemTime,
+    ) -> Result<(), UploadError> {
+        let path = format!(
+            "{}/{}/{}",
+            self.endpoint.path.trim_end_matches('/'),
+            uri_encode(&self.bucket),
+            uri_encode(key.trim_start_matches('/')),
+        );
+        let headers = self.signed_headers("PUT", &path, body, now);
+
+        self.endpoint
+            .send("PUT", &path, &headers, body, self.timeout)
+    }
+
+    /// Get the headers needed for a Signature Version 4 request.
+    fn signed_headers(
+        &self,
+        method: &str,
+        path: &str,
+        body: &[u8],
+        now: SystemTime,
+    ) -> Vec<(String, String)> {
+        let (date, time) = utc(unix_seconds(now));
+        let date = date.replace('-', "");
+        let amz_date = format!("{}T{}Z", date, time);
+        let payload_hash = hex(&sha256(body));
+        let host = self.endpoint.authority();
+
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method, path, host, payload_hash, amz_date, SIGNED_HEADERS,
+            payload_hash,
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(&sha256(canonical_request.as_bytes())),
+        );
+        let key = signing_key(
+            &self.credentials.secret_access_key,
+            &date,
+            &self.region,
+            "s3",
+        );
+        let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+
+        vec![
+            (String::from("Content-Type"), String::from("audio/wav")),
+            (String::from("X-Amz-Content-Sha256"), payload_hash),
+            (String::from("X-Amz-Date"), amz_date),
+            (
+                String::from("Authorization"),
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, \
+                     Signature={}",
+                    self.credentials.access_key_id,
+                    scope,
+                    SIGNED_HEADERS,
+                    signature
+                ),
+            ),
+        ]
+    }
+}
+
+impl Uploader for S3Uploader {
+    fn upload(&mut self, clip: &Clip) -> Result<(), UploadError> {
+        self.put(&self.key(clip), &clip.wav, SystemTime::now())
+    }
+}
+
+const SIGNED_HEADERS: &str = "host;x-amz-content-sha256;x-amz-date";
+
+/// Percent-encode everything except unreserved characters and `/`.
+fn uri_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => {
+                encoded.push(byte as char)
+            },
+            b'-' | b'.' | b'_' | b'~' | b'/' => encoded.push(byte as char),
+            _ => write!(encoded, "%{:02X}", byte).unwrap(),
+        }
+    }
+
+    encoded
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, b| {
+        write!(s, "{:02x}", b).unwrap();
+        s
+    })
+}
+
+fn signing_key(
+    secret: &str,
+    date: &str,
+    region: &str,
+    service: &str,
+) -> [u8; 32] {
+    let key =
+        hmac_sha256(format!("AWS4{}", secret).as_bytes(), date.as_bytes());
+    let key = hmac_sha256(&key, region.as_bytes());
+    let key = hmac_sha256(&key, service.as_bytes());
+    hmac_sha256(&key, b"aws4_request")
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key)
+        .expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+fn sha256(data: &[u8]) -> [u8; 32] { Sha256::digest(data).into() }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Read, Write as _},
+        net::TcpListener,
+        thread,
+        time::UNIX_EPOCH,
+    };
+
+    #[test]
+    fn aws_signing_key_example() {
+        // from the AWS docs for deriving a signing key
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+
+        assert_eq!(
+            hex(&key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    fn clip() -> Clip {
+        Clip {
+            index: 7,
+            frames: 1600,
+            channels: 1,
+            sample_rate: 16_000,
+            finished: UNIX_EPOCH + Duration::from_secs(1_329_264_000 + 3_723),
+            wav: b"RIFF....".to_vec(),
+        }
+    }
+
+    #[test]
+    fn fill_in_key_templates() {
+        let uploader = S3Uploader::new(
+            "http://localhost:9000",
+            "bucket",
+            "us-east-1",
+            Credentials::new("id", "secret"),
+        )
+        .unwrap();
+
+        assert_eq!(uploader.key(&clip()), "2012-02-15/010203-7.wav");
+
+        let uploader = uploader
+            .with_key_template("{sample_rate}/{timestamp}_{frames}.wav");
+        assert_eq!(uploader.key(&clip()), "16000/1329267723_1600.wav");
+    }
+
+    #[test]
+    fn keys_are_percent_encoded() {
+        assert_eq!(uri_encode("a b/c+d~.wav"), "a%20b/c%2Bd~.wav");
+    }
+
+    #[test]
+    fn put_a_signed_object() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = Vec::new();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                head.push(line.trim().to_string());
+                line.clear();
+            }
+            let mut body = [0; 8];
+            reader.read_exact(&mut body).unwrap();
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\n\r\n")
+                .unwrap();
+            (head, body)
+        });
+
+        let mut uploader = S3Uploader::new(
+            &endpoint,
+            "recordings",
+            "us-east-1",
+            Credentials::new("AKIDEXAMPLE", "secret"),
+        )
+        .unwrap()
+        .with_key_template("radio/{index}.wav");
+        uploader.upload(&clip()).unwrap();
+
+        let (head, body) = server.join().unwrap();
+        assert_eq!(head[0], "PUT /recordings/radio/7.wav HTTP/1.1");
+        assert_eq!(&body, b"RIFF....");
+        let authorization = head
+            .iter()
+            .find(|h| h.starts_with("Authorization: "))
+            .unwrap();
+        assert!(authorization.starts_with(
+            "Authorization: AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/"
+        ));
+        assert!(authorization.contains(
+            "/us-east-1/s3/aws4_request, SignedHeaders=host;\
+             x-amz-content-sha256;x-amz-date, Signature="
+        ));
+    }
+}
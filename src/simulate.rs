@@ -0,0 +1,226 @@
+//! Running several gate configurations over the same audio at once, so
+//! different settings can be compared side by side.
+
+use crate::{NoiseGate, Sink};
+use dasp::{Frame, Sample};
+use noise_gate_core::__private;
+use std::ops::Range;
+
+/// How many frames each gate processes before moving on to the next, chosen
+/// so a block of audio stays in the CPU's cache while every gate looks at
+/// it.
+const BLOCK_SIZE: usize = 4096;
+
+/// Several [`NoiseGate`]s being run over the same stream of audio, recording
+/// where each one would have produced a segment.
+///
+/// This makes A/B comparisons cheap, because the audio only needs to be
+/// decoded and walked through once no matter how many configurations are
+/// being compared. Frames can be added in chunks of any size.
+///
+/// ```rust
+/// use noise_gate::{simulate::Simulation, NoiseGate};
+///
+/// let frames = [[0_i16], [500], [0], [0], [0], [600], [0], [0], [0]];
+/// let mut simulation = Simulation::new(vec![
+///     NoiseGate::new(100, 0),
+///     NoiseGate::new(100, 5),
+///     NoiseGate::new(550, 0),
+/// ]);
+///
+/// simulation.process_frames(&frames[..4]);
+/// simulation.process_frames(&frames[4..]);
+///
+/// assert_eq!(
+///     simulation.finish(),
+///     vec![vec![1..3, 5..7], vec![1..9], vec![5..7]]
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Simulation<S> {
+    trials: Vec<Trial<S>>,
+    position: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Trial<S> {
+    gate: NoiseGate<S>,
+    /// The range of the segment currently being recorded, if there is one.
+    current: Option<Range<usize>>,
+    segments: Vec<Range<usize>>,
+}
+
+impl<S> Simulation<S> {
+    /// Start simulating a set of [`NoiseGate`]s.
+    pub fn new<I>(gates: I) -> Self
+    where
+        I: IntoIterator<Item = NoiseGate<S>>,
+    {
+        let trials = gates
+            .into_iter()
+            .map(|gate| Trial {
+                gate,
+                current: None,
+                segments: Vec::new(),
+            })
+            .collect();
+
+        Simulation {
+            trials,
+            position: 0,
+        }
+    }
+
+    /// The number of frames processed so far.
+    pub fn position(&self) -> usize { self.position }
+
+    /// The gates being simulated, in the order they were provided.
+    pub fn gates(&self) -> impl Iterator<Item = &NoiseGate<S>> + '_ {
+        self.trials.iter().map(|trial| &trial.gate)
+    }
+
+    /// The segments each gate has finished so far, in the order the gates
+    /// were provided.
+    ///
+    /// Segments which are still being recorded aren't included.
+    pub fn segments(&self) -> impl Iterator<Item = &[Range<usize>]> + '_ {
+        self.trials.iter().map(|trial| trial.segments.as_slice())
+    }
+
+    /// Stop simulating, returning every gate's segments in the order the
+    /// gates were provided.
+    ///
+    /// A segment which is still open is treated as finishing at the last
+    /// frame it recorded.
+    pub fn finish(self) -> Vec<Vec<Range<usize>>> {
+        self.trials
+            .into_iter()
+            .map(|mut trial| {
+                trial.segments.extend(trial.current);
+                trial.segments
+            })
+            .collect()
+    }
+}
+
+impl<S: Sample> Simulation<S> {
+    /// Run every gate over the next chunk of frames.
+    pub fn process_frames<F>(&mut self, frames: &[F])
+    where
+        F: Frame<Sample = S>,
+    {
+        for (i, block) in frames.chunks(BLOCK_SIZE).enumerate() {
+            let offset = self.position + i * BLOCK_SIZE;
+
+            for trial in &mut self.trials {
+                trial.process(block, offset);
+            }
+        }
+
+        self.position += frames.len();
+    }
+}
+
+impl<S: Sample> Trial<S> {
+    fn process<F>(&mut self, frames: &[F], offset: usize)
+    where
+        F: Frame<Sample = S>,
+    {
+        let Trial {
+            gate,
+            current,
+            segments,
+        } = self;
+        let mut position = offset;
+
+        __private::process_runs(gate, frames, &mut Discard, |_, run| {
+            if run.recorded > 0 {
+                let end = position + run.recorded;
+                match current {
+                    Some(segment) => segment.end = end,
+                    None => *current = Some(position..end),
+                }
+            }
+
+            if run.closed {
+                segments.extend(current.take());
+            }
+
+            position += run.len;
+        });
+    }
+}
+
+/// Segments are tracked using the runs, so the frames themselves aren't
+/// needed.
+struct Discard;
+
+impl<F> Sink<F> for Discard {
+    fn record(&mut self, _: F) {}
+
+    fn end_of_transmission(&mut self) {}
+}
+
+/// Run several [`NoiseGate`]s over a recording which is already in memory,
+/// returning each one's segments.
+///
+/// See [`Simulation`] for details.
+pub fn simulate<F, I>(frames: &[F], gates: I) -> Vec<Vec<Range<usize>>>
+where
+    F: Frame,
+    I: IntoIterator<Item = NoiseGate<F::Sample>>,
+{
+    let mut simulation = Simulation::new(gates);
+    simulation.process_frames(frames);
+    simulation.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_segments_as_running_each_gate_separately() {
+        let frames: Vec<[i16; 1]> = (0..20_000)
+            .map(|i| {
+                if i % 1234 < 300 || i % 777 == 0 {
+                    [500]
+                } else {
+                    [0]
+                }
+            })
+            .collect();
+        let gates: Vec<_> = [(100, 0), (100, 50), (100, 2000), (600, 10)]
+            .iter()
+            .map(|&(threshold, release)| NoiseGate::new(threshold, release))
+            .collect();
+
+        let mut simulation = Simulation::new(gates.clone());
+        for chunk in frames.chunks(1000) {
+            simulation.process_frames(chunk);
+        }
+        let got = simulation.finish();
+
+        let expected: Vec<Vec<Range<usize>>> = gates
+            .into_iter()
+            .map(|mut gate| {
+                gate.segments(&frames).map(|(range, _)| range).collect()
+            })
+            .collect();
+        assert_eq!(got, expected);
+        assert!(got[3].is_empty());
+    }
+
+    #[test]
+    fn only_finished_segments_are_reported_while_running() {
+        let mut simulation = Simulation::new(vec![NoiseGate::new(0.1, 1)]);
+
+        simulation.process_frames(&[[0.5_f32], [0.0], [0.0], [0.0], [0.5]]);
+
+        let segments: Vec<_> = simulation.segments().collect();
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].to_vec(), vec![0..3]);
+        assert_eq!(simulation.position(), 5);
+        assert_eq!(simulation.finish(), vec![vec![0..3, 4..5]]);
+    }
+}
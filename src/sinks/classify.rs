@@ -0,0 +1,254 @@
+use crate::Sink;
+
+/// What should happen to a segment once it has been classified.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict<L> {
+    /// Pass the segment through to the inner sink.
+    Keep,
+    /// Throw the segment away.
+    Drop,
+    /// Pass the segment through, but remember it was given a label.
+    Relabel(L),
+}
+
+/// Something which decides what to do with each segment the gate finds.
+///
+/// This is implemented for any closure which takes the segment's frames and
+/// returns a [`Verdict`].
+pub trait Classifier<F> {
+    /// The label a segment can be given.
+    type Label;
+
+    /// Look at a complete segment and decide what to do with it.
+    fn classify(&mut self, segment: &[F]) -> Verdict<Self::Label>;
+}
+
+impl<F, L, C> Classifier<F> for C
+where
+    C: FnMut(&[F]) -> Verdict<L>,
+{
+    type Label = L;
+
+    fn classify(&mut self, segment: &[F]) -> Verdict<L> { self(segment) }
+}
+
+/// The outcome of classifying a single segment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Classification<L> {
+    /// Which segment this was, starting from `0`.
+    pub segment: usize,
+    /// The segment's length in frames.
+    pub frames: usize,
+    /// What the [`Classifier`] decided.
+    pub verdict: Verdict<L>,
+}
+
+/// A [`Sink`] adapter which passes each completed segment to a
+/// [`Classifier`] before it is committed to the inner sink, so things like
+/// DTMF bursts or carrier noise can be filtered out.
+///
+/// Every segment is held in memory until the gate closes, so the inner sink
+/// lags behind the gate by a whole segment. The outcome for each segment is
+/// logged and can be retrieved with
+/// [`Classify::take_classifications()`].
+///
+/// ```rust
+/// use noise_gate::{
+///     sinks::{Classify, Verdict},
+///     NoiseGate,
+/// };
+///
+/// # #[derive(Default)]
+/// # struct Counter { frames: usize }
+/// # impl noise_gate::Sink<[i16; 1]> for Counter {
+/// #     fn record(&mut self, _: [i16; 1]) { self.frames += 1; }
+/// #     fn end_of_transmission(&mut self) {}
+/// # }
+/// // anything shorter than 3 frames is just a click
+/// let classifier = |segment: &[[i16; 1]]| {
+///     if segment.len() < 3 {
+///         Verdict::Drop
+///     } else {
+///         Verdict::Relabel("speech")
+///     }
+/// };
+/// let mut sink = Classify::new(Counter::default(), classifier);
+/// let mut gate = NoiseGate::new(100_i16, 0);
+///
+/// gate.process_frames(&[[500], [0], [0], [600], [700], [800], [0], [0]], &mut sink);
+///
+/// let classifications = sink.take_classifications();
+/// assert_eq!(classifications[0].verdict, Verdict::Drop);
+/// assert_eq!(classifications[1].verdict, Verdict::Relabel("speech"));
+/// assert_eq!(sink.inner().frames, 4);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Classify<F, K, C, L> {
+    inner: K,
+    classifier: C,
+    segment: Vec<F>,
+    next_segment: usize,
+    classifications: Vec<Classification<L>>,
+}
+
+impl<F, K, C> Classify<F, K, C, C::Label>
+where
+    C: Classifier<F>,
+{
+    /// Wrap a [`Sink`], only committing the segments `classifier` keeps.
+    pub fn new(inner: K, classifier: C) -> Self {
+        Classify {
+            inner,
+            classifier,
+            segment: Vec::new(),
+            next_segment: 0,
+            classifications: Vec::new(),
+        }
+    }
+}
+
+impl<F, K, C, L> Classify<F, K, C, L> {
+    /// The outcome for each segment classified so far.
+    pub fn classifications(&self) -> &[Classification<L>] {
+        &self.classifications
+    }
+
+    /// Remove and return the outcome for each segment classified so far.
+    pub fn take_classifications(&mut self) -> Vec<Classification<L>> {
+        std::mem::take(&mut self.classifications)
+    }
+
+    /// Get a reference to the inner [`Sink`].
+    pub fn inner(&self) -> &K { &self.inner }
+
+    /// Get a mutable reference to the inner [`Sink`].
+    pub fn inner_mut(&mut self) -> &mut K { &mut self.inner }
+
+    /// Consume the adapter, returning the inner [`Sink`].
+    ///
+    /// Any segment which is still in progress will be discarded.
+    pub fn into_inner(self) -> K { self.inner }
+}
+
+impl<F, K, C> Sink<F> for Classify<F, K, C, C::Label>
+where
+    F: Copy,
+    K: Sink<F>,
+    C: Classifier<F>,
+{
+    fn record(&mut self, frame: F) { self.segment.push(frame); }
+
+    fn record_frames(&mut self, frames: &[F]) {
+        self.segment.extend_from_slice(frames);
+    }
+
+    fn end_of_transmission(&mut self) {
+        if self.segment.is_empty() {
+            self.inner.end_of_transmission();
+            return;
+        }
+
+        let verdict = self.classifier.classify(&self.segment);
+
+        if !matches!(verdict, Verdict::Drop) {
+            self.inner.record_frames(&self.segment);
+            self.inner.end_of_transmission();
+        }
+
+        self.classifications.push(Classification {
+            segment: self.next_segment,
+            frames: self.segment.len(),
+            verdict,
+        });
+        self.next_segment += 1;
+        self.segment.clear();
+    }
+
+    fn discard_transmission(&mut self) {
+        // the segment never gets classified, so it doesn't get a number
+        self.segment.clear();
+        self.inner.discard_transmission();
+    }
+
+    fn transmission_started(&mut self, position: u64) {
+        self.inner.transmission_started(position);
+    }
+
+    fn latency_samples(&self) -> usize { self.inner.latency_samples() }
+
+    fn errors(&self) -> usize { self.inner.errors() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct Clips(Vec<Vec<[f32; 1]>>, Vec<[f32; 1]>);
+
+    impl Sink<[f32; 1]> for Clips {
+        fn record(&mut self, frame: [f32; 1]) { self.1.push(frame); }
+
+        fn end_of_transmission(&mut self) {
+            if !self.1.is_empty() {
+                self.0.push(std::mem::take(&mut self.1));
+            }
+        }
+    }
+
+    /// Pretend anything which is perfectly constant is a tone.
+    struct ToneDetector;
+
+    impl Classifier<[f32; 1]> for ToneDetector {
+        type Label = ();
+
+        fn classify(&mut self, segment: &[[f32; 1]]) -> Verdict<()> {
+            if segment.windows(2).all(|pair| pair[0] == pair[1]) {
+                Verdict::Drop
+            } else {
+                Verdict::Keep
+            }
+        }
+    }
+
+    #[test]
+    fn only_kept_segments_reach_the_inner_sink() {
+        let mut sink = Classify::new(Clips::default(), ToneDetector);
+
+        sink.record_frames(&[[0.5], [0.5], [0.5]]);
+        sink.end_of_transmission();
+        sink.record_frames(&[[0.5], [0.25]]);
+        sink.end_of_transmission();
+
+        assert_eq!(sink.inner().0, vec![vec![[0.5], [0.25]]]);
+        assert_eq!(
+            sink.classifications(),
+            &[
+                Classification {
+                    segment: 0,
+                    frames: 3,
+                    verdict: Verdict::Drop
+                },
+                Classification {
+                    segment: 1,
+                    frames: 2,
+                    verdict: Verdict::Keep
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn segments_are_held_back_until_the_gate_closes() {
+        let mut sink = Classify::new(Clips::default(), ToneDetector);
+
+        sink.record([0.1]);
+        sink.record([0.2]);
+        assert!(sink.inner().1.is_empty());
+
+        sink.end_of_transmission();
+        assert_eq!(sink.inner().0.len(), 1);
+        assert_eq!(sink.take_classifications().len(), 1);
+        assert!(sink.classifications().is_empty());
+    }
+}
@@ -0,0 +1,266 @@
+use crate::{
+    clock::{self, Clock, SystemClock},
+    Sink,
+};
+use std::{io::Write, time::SystemTime};
+
+/// A [`Sink`] adapter which writes a line of JSON to a log whenever a
+/// transmission starts or ends, for monitoring deployments where the audio
+/// itself is thrown away.
+///
+/// Each line looks like one of these:
+///
+/// ```text
+/// {"event":"open","transmission":0,"time":"2012-02-15T01:02:03.450Z"}
+/// {"event":"close","transmission":0,"time":"2012-02-15T01:02:05.000Z","frames":68600,"duration":1.550}
+/// ```
+///
+/// The `duration` is the wall-clock time between the two events, in
+/// seconds. When the transmission comes straight from a
+/// [`NoiseGate`][crate::NoiseGate], each event also has a `"sample"` field
+/// with the [position][crate::NoiseGate::position] of the first frame (for
+/// `"open"`) or the frame after the last one (for `"close"`).
+///
+/// Writing to the log may block, so this shouldn't be used directly from a
+/// real-time audio thread. Errors are counted rather than interrupting the
+/// recording (see [`EventLog::write_errors()`]).
+///
+/// ```rust
+/// use noise_gate::{sinks::EventLog, Sink};
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let mut log = Vec::new();
+/// let clock = || UNIX_EPOCH + Duration::from_secs(60);
+/// let mut sink = EventLog::with_clock(Vec::new(), &mut log, clock);
+///
+/// sink.record([0.5_f32]);
+/// sink.end_of_transmission();
+///
+/// let log = String::from_utf8(log).unwrap();
+/// assert_eq!(log.lines().count(), 2);
+/// assert!(log.starts_with(r#"{"event":"open","transmission":0,"time":"1970-01-01T00:01:00.000Z"}"#));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventLog<K, W, C = SystemClock> {
+    inner: K,
+    writer: W,
+    clock: C,
+    transmissions: usize,
+    /// When the current transmission started and how many frames it has
+    /// had so far, if there is one.
+    current: Option<(SystemTime, usize)>,
+    /// Where the gate said the current (or next) transmission starts.
+    start_position: Option<u64>,
+    write_errors: usize,
+}
+
+impl<K, W> EventLog<K, W> {
+    /// Wrap a [`Sink`], logging events to `writer` using the system time.
+    pub fn new(inner: K, writer: W) -> Self {
+        EventLog::with_clock(inner, writer, SystemClock)
+    }
+}
+
+impl<K, W, C> EventLog<K, W, C> {
+    /// Wrap a [`Sink`], logging events to `writer` using timestamps from a
+    /// custom [`Clock`].
+    pub fn with_clock(inner: K, writer: W, clock: C) -> Self {
+        EventLog {
+            inner,
+            writer,
+            clock,
+            transmissions: 0,
+            current: None,
+            start_position: None,
+            write_errors: 0,
+        }
+    }
+
+    /// How many events couldn't be written to the log.
+    pub fn write_errors(&self) -> usize { self.write_errors }
+
+    /// The number of transmissions which have been started so far.
+    pub fn transmissions(&self) -> usize { self.transmissions }
+
+    /// Get a reference to the inner [`Sink`].
+    pub fn inner(&self) -> &K { &self.inner }
+
+    /// Get a mutable reference to the inner [`Sink`].
+    pub fn inner_mut(&mut self) -> &mut K { &mut self.inner }
+
+    /// Get a reference to the log being written to.
+    pub fn writer(&self) -> &W { &self.writer }
+
+    /// Consume the adapter, returning the inner [`Sink`] and the log.
+    pub fn into_inner(self) -> (K, W) { (self.inner, self.writer) }
+}
+
+impl<K, W, C> EventLog<K, W, C>
+where
+    W: Write,
+    C: Clock,
+{
+    fn started(&mut self, frames: usize) {
+        match self.current.as_mut() {
+            Some((_, current)) => *current += frames,
+            None => {
+                let now = self.clock.now();
+                self.current = Some((now, frames));
+                let line = format!(
+                    r#"{{"event":"open","transmission":{}{},"time":"{}"}}"#,
+                    self.transmissions,
+                    sample_field(self.start_position),
+                    clock::rfc3339(now)
+                );
+                self.write_line(&line);
+            },
+        }
+    }
+
+    fn finished(&mut self) {
+        if let Some((started, frames)) = self.current.take() {
+            let now = self.clock.now();
+            let duration = now.duration_since(started).unwrap_or_default();
+            let end = self.start_position.take().map(|p| p + frames as u64);
+            let line = format!(
+                r#"{{"event":"close","transmission":{}{},"time":"{}","frames":{},"duration":{:.3}}}"#,
+                self.transmissions,
+                sample_field(end),
+                clock::rfc3339(now),
+                frames,
+                duration.as_secs_f64()
+            );
+            self.write_line(&line);
+            self.transmissions += 1;
+        }
+    }
+
+    fn write_line(&mut self, line: &str) {
+        let result =
+            writeln!(self.writer, "{}", line).and_then(|_| self.writer.flush());
+
+        if result.is_err() {
+            self.write_errors += 1;
+        }
+    }
+}
+
+impl<F, K, W, C> Sink<F> for EventLog<K, W, C>
+where
+    F: Copy,
+    K: Sink<F>,
+    W: Write,
+    C: Clock,
+{
+    fn record(&mut self, frame: F) {
+        self.started(1);
+        self.inner.record(frame);
+    }
+
+    fn record_frames(&mut self, frames: &[F]) {
+        if !frames.is_empty() {
+            self.started(frames.len());
+            self.inner.record_frames(frames);
+        }
+    }
+
+    fn end_of_transmission(&mut self) {
+        self.finished();
+        self.inner.end_of_transmission();
+    }
+
+    fn discard_transmission(&mut self) {
+        self.finished();
+        self.inner.discard_transmission();
+    }
+
+    fn transmission_started(&mut self, position: u64) {
+        if self.current.is_none() {
+            self.start_position = Some(position);
+        }
+        self.inner.transmission_started(position);
+    }
+
+    fn latency_samples(&self) -> usize { self.inner.latency_samples() }
+
+    fn errors(&self) -> usize { self.write_errors + self.inner.errors() }
+}
+
+fn sample_field(position: Option<u64>) -> String {
+    position.map_or_else(String::new, |p| format!(r#","sample":{}"#, p))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    struct Broken;
+
+    impl Write for Broken {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("broken"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    #[test]
+    fn open_and_close_events_are_logged() {
+        let mut millis = 1_329_267_723_000;
+        let clock = move || {
+            millis += 250;
+            UNIX_EPOCH + Duration::from_millis(millis)
+        };
+        let mut sink = EventLog::with_clock(Vec::new(), Vec::new(), clock);
+
+        sink.record_frames(&[[1_i16]; 5]);
+        sink.record([1]);
+        sink.end_of_transmission();
+        sink.end_of_transmission();
+        sink.record([2]);
+        sink.end_of_transmission();
+
+        let (frames, log) = sink.into_inner();
+        assert_eq!(frames.len(), 7);
+        let log = String::from_utf8(log).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                r#"{"event":"open","transmission":0,"time":"2012-02-15T01:02:03.250Z"}"#,
+                r#"{"event":"close","transmission":0,"time":"2012-02-15T01:02:03.500Z","frames":6,"duration":0.250}"#,
+                r#"{"event":"open","transmission":1,"time":"2012-02-15T01:02:03.750Z"}"#,
+                r#"{"event":"close","transmission":1,"time":"2012-02-15T01:02:04.000Z","frames":1,"duration":0.250}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn events_from_a_gate_include_the_sample() {
+        let clock = || UNIX_EPOCH;
+        let mut sink = EventLog::with_clock(Vec::new(), Vec::new(), clock);
+        let mut gate = crate::NoiseGate::new(100_i16, 0);
+
+        gate.process_frames(&[[0], [0], [500], [600], [0], [0]], &mut sink);
+
+        let log = String::from_utf8(sink.into_inner().1).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert!(lines[0].contains(r#""transmission":0,"sample":2,"#));
+        assert!(lines[1].contains(r#""transmission":0,"sample":5,"#));
+    }
+
+    #[test]
+    fn write_errors_are_counted() {
+        let mut sink = EventLog::new(Vec::new(), Broken);
+
+        sink.record([0.5_f32]);
+        sink.end_of_transmission();
+
+        assert_eq!(sink.write_errors(), 2);
+        assert_eq!(sink.transmissions(), 1);
+    }
+}
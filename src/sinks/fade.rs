@@ -0,0 +1,209 @@
+use crate::Sink;
+use dasp::{Frame, Sample};
+use std::collections::VecDeque;
+
+/// A [`Sink`] adapter which applies a short linear fade-in and fade-out to
+/// each clip, removing the click you get when audio starts or stops abruptly.
+///
+/// The fade-out is applied by holding back the last `fade_length` frames of
+/// each clip until [`Sink::end_of_transmission()`] is called, so the inner
+/// sink will lag behind the gate by that many frames.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FadeEdges<F, K> {
+    inner: K,
+    fade_length: usize,
+    frames_recorded: usize,
+    pending: VecDeque<F>,
+}
+
+impl<F, K> FadeEdges<F, K> {
+    /// Wrap a [`Sink`], fading clips in and out over `fade_length` frames.
+    pub fn new(inner: K, fade_length: usize) -> Self {
+        FadeEdges {
+            inner,
+            fade_length,
+            frames_recorded: 0,
+            pending: VecDeque::with_capacity(fade_length + 1),
+        }
+    }
+
+    /// The number of frames each fade lasts for.
+    pub fn fade_length(&self) -> usize { self.fade_length }
+
+    /// Get a reference to the inner [`Sink`].
+    pub fn inner(&self) -> &K { &self.inner }
+
+    /// Get a mutable reference to the inner [`Sink`].
+    pub fn inner_mut(&mut self) -> &mut K { &mut self.inner }
+
+    /// Consume the adapter, returning the inner [`Sink`].
+    ///
+    /// Any frames which are still being held back for the fade-out will be
+    /// lost, so make sure the current clip has ended first.
+    pub fn into_inner(self) -> K { self.inner }
+}
+
+impl<F, K> Sink<F> for FadeEdges<F, K>
+where
+    F: Frame,
+    K: Sink<F>,
+{
+    fn record(&mut self, frame: F) {
+        let frame = if self.frames_recorded < self.fade_length {
+            frame.scale_amp(gain::<F>(self.frames_recorded, self.fade_length))
+        } else {
+            frame
+        };
+        self.frames_recorded += 1;
+
+        self.pending.push_back(frame);
+
+        if self.pending.len() > self.fade_length {
+            if let Some(frame) = self.pending.pop_front() {
+                self.inner.record(frame);
+            }
+        }
+    }
+
+    fn end_of_transmission(&mut self) {
+        // Note: a clip shorter than the fade length won't fill the buffer,
+        // so we fade out from wherever it ended up.
+        let remaining = self.pending.len();
+
+        for (i, frame) in self.pending.drain(..).enumerate() {
+            let gain = gain::<F>(remaining - 1 - i, remaining);
+            self.inner.record(frame.scale_amp(gain));
+        }
+
+        self.frames_recorded = 0;
+        self.inner.end_of_transmission();
+    }
+
+    fn discard_transmission(&mut self) {
+        self.pending.clear();
+        self.frames_recorded = 0;
+        self.inner.discard_transmission();
+    }
+
+    fn transmission_started(&mut self, position: u64) {
+        self.inner.transmission_started(position);
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.fade_length + self.inner.latency_samples()
+    }
+
+    fn errors(&self) -> usize { self.inner.errors() }
+}
+
+/// The gain to use for the `n`'th frame of a linear ramp `length` frames
+/// long.
+fn gain<F: Frame>(n: usize, length: usize) -> <F::Sample as Sample>::Float {
+    let ratio = (n + 1) as f64 / (length + 1) as f64;
+    ratio.to_sample()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct Recorder {
+        clips: Vec<Vec<[f32; 1]>>,
+        current: Vec<[f32; 1]>,
+    }
+
+    impl Sink<[f32; 1]> for Recorder {
+        fn record(&mut self, frame: [f32; 1]) { self.current.push(frame); }
+
+        fn end_of_transmission(&mut self) {
+            self.clips.push(std::mem::take(&mut self.current));
+        }
+
+        fn discard_transmission(&mut self) { self.current.clear(); }
+    }
+
+    #[test]
+    fn latency_includes_the_inner_sink() {
+        let sink = FadeEdges::new(FadeEdges::new(Recorder::default(), 3), 5);
+
+        assert_eq!(Sink::<[f32; 1]>::latency_samples(&sink), 8);
+    }
+
+    #[test]
+    fn ramps_are_applied_to_both_ends() {
+        let mut sink = FadeEdges::new(Recorder::default(), 3);
+
+        for _ in 0..10 {
+            sink.record([1.0]);
+        }
+        sink.end_of_transmission();
+
+        let clips = sink.into_inner().clips;
+        let got: Vec<f32> = clips[0].iter().map(|f| f[0]).collect();
+        assert_eq!(
+            got,
+            vec![0.25, 0.5, 0.75, 1.0, 1.0, 1.0, 1.0, 0.75, 0.5, 0.25]
+        );
+    }
+
+    #[test]
+    fn frames_are_held_back_for_the_fade_out() {
+        let mut sink = FadeEdges::new(Recorder::default(), 2);
+
+        for _ in 0..5 {
+            sink.record([1.0]);
+        }
+
+        assert_eq!(sink.inner().current.len(), 3);
+    }
+
+    #[test]
+    fn zero_length_fade_is_a_passthrough() {
+        let mut sink = FadeEdges::new(Recorder::default(), 0);
+
+        sink.record([0.5]);
+        assert_eq!(sink.inner().current, vec![[0.5]]);
+        sink.end_of_transmission();
+
+        assert_eq!(sink.into_inner().clips, vec![vec![[0.5]]]);
+    }
+
+    #[test]
+    fn each_clip_gets_its_own_fade_in() {
+        let mut sink = FadeEdges::new(Recorder::default(), 1);
+
+        sink.record([1.0]);
+        sink.record([1.0]);
+        sink.end_of_transmission();
+        sink.record([1.0]);
+        sink.record([1.0]);
+        sink.end_of_transmission();
+
+        let clips = sink.into_inner().clips;
+        assert_eq!(clips[0], clips[1]);
+        assert_eq!(clips[0], vec![[0.5], [0.5]]);
+    }
+
+    #[test]
+    fn discarding_drops_the_held_back_frames() {
+        let mut sink = FadeEdges::new(Recorder::default(), 2);
+
+        for _ in 0..5 {
+            sink.record([1.0]);
+        }
+        sink.discard_transmission();
+        sink.record([1.0]);
+        sink.record([1.0]);
+        sink.end_of_transmission();
+
+        // the next clip is faded as if nothing had happened
+        let mut expected = FadeEdges::new(Recorder::default(), 2);
+        expected.record([1.0]);
+        expected.record([1.0]);
+        expected.end_of_transmission();
+        let recorder = sink.into_inner();
+        assert!(recorder.current.is_empty());
+        assert_eq!(recorder.clips, expected.into_inner().clips);
+    }
+}
@@ -0,0 +1,345 @@
+use crate::Sink;
+use dasp::{Frame, Sample};
+use std::collections::VecDeque;
+
+/// A brick-wall [`Sink`] adapter which turns frames down on their way to the
+/// inner sink so no sample ever goes past the `ceiling`.
+///
+/// Put this last, just before the sink that writes to disk, so any gain or
+/// normalisation stages earlier in the chain can't make the output clip.
+///
+/// The limiter looks `lookahead` frames ahead so it can ramp the gain down
+/// before a peak arrives instead of distorting it, then lets the gain
+/// recover over `release` frames. Like [`FadeEdges`][super::FadeEdges], this
+/// means the inner sink lags behind the gate by `lookahead` frames until the
+/// transmission ends.
+///
+/// ```rust
+/// use noise_gate::{sinks::Limiter, Sink};
+///
+/// let mut written = Vec::new();
+/// let mut sink = Limiter::new(&mut written, 0.5, 2, 4);
+///
+/// sink.record_frames(&[[0.25_f32], [0.25], [1.0], [0.25]]);
+/// sink.end_of_transmission();
+///
+/// // the gain ramps down ahead of the peak and recovers afterwards
+/// assert_eq!(sink.limited(), 4);
+/// drop(sink);
+/// assert!(written.iter().all(|[sample]| sample.abs() <= 0.5));
+/// assert_eq!(written[2], [0.5]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limiter<F, K> {
+    inner: K,
+    ceiling: f64,
+    lookahead: usize,
+    release: usize,
+    true_peak: bool,
+    /// Frames waiting to be written, and the most each one can be amplified
+    /// by without going over the ceiling.
+    pending: VecDeque<(F, f64)>,
+    /// The last few frames, for estimating the peaks between samples.
+    history: VecDeque<F>,
+    gain: f64,
+    limited: usize,
+}
+
+impl<F, K> Limiter<F, K> {
+    /// Wrap a [`Sink`], keeping every sample at or below `ceiling` (where
+    /// `1.0` is full scale).
+    pub fn new(
+        inner: K,
+        ceiling: f64,
+        lookahead: usize,
+        release: usize,
+    ) -> Self {
+        Limiter {
+            inner,
+            ceiling: ceiling.abs(),
+            lookahead,
+            release: release.max(1),
+            true_peak: false,
+            pending: VecDeque::with_capacity(lookahead + 1),
+            history: VecDeque::with_capacity(4),
+            gain: 1.0,
+            limited: 0,
+        }
+    }
+
+    /// Create a [`Limiter`] which also estimates the peaks between samples,
+    /// so the output stays below `ceiling` after it has been converted back
+    /// to an analogue signal (or resampled).
+    ///
+    /// Inter-sample peaks are estimated by interpolating between frames,
+    /// which needs at least 2 frames of lookahead.
+    pub fn true_peak(
+        inner: K,
+        ceiling: f64,
+        lookahead: usize,
+        release: usize,
+    ) -> Self {
+        let mut limiter =
+            Limiter::new(inner, ceiling, lookahead.max(2), release);
+        limiter.true_peak = true;
+        limiter
+    }
+
+    /// The loudest a sample can be.
+    pub fn ceiling(&self) -> f64 { self.ceiling }
+
+    /// How many frames the limiter looks ahead.
+    pub fn lookahead(&self) -> usize { self.lookahead }
+
+    /// Are the peaks between samples being limited too?
+    pub fn is_true_peak(&self) -> bool { self.true_peak }
+
+    /// The gain applied to the last frame which was written.
+    pub fn gain(&self) -> f64 { self.gain }
+
+    /// How many frames have been turned down so far.
+    pub fn limited(&self) -> usize { self.limited }
+
+    /// Get a reference to the inner [`Sink`].
+    pub fn inner(&self) -> &K { &self.inner }
+
+    /// Get a mutable reference to the inner [`Sink`].
+    pub fn inner_mut(&mut self) -> &mut K { &mut self.inner }
+
+    /// Consume the adapter, returning the inner [`Sink`].
+    ///
+    /// Any frames which are still being held back will be lost, so make
+    /// sure the current clip has ended first.
+    pub fn into_inner(self) -> K { self.inner }
+
+    /// The most a frame with this `peak` can be amplified by.
+    fn max_gain(&self, peak: f64) -> f64 { (self.ceiling / peak).min(1.0) }
+}
+
+impl<F, K> Limiter<F, K>
+where
+    F: Frame,
+    K: Sink<F>,
+{
+    /// Write the oldest pending frame, ramping the gain down early for any
+    /// peaks coming up.
+    fn write_next(&mut self) {
+        let released = (self.gain + 1.0 / self.release as f64).min(1.0);
+        let ramp = (self.lookahead + 1) as f64;
+        let target = self.pending.iter().enumerate().fold(
+            released,
+            |gain, (distance, &(_, max))| {
+                gain.min(max + (1.0 - max) * distance as f64 / ramp)
+            },
+        );
+
+        if let Some((frame, _)) = self.pending.pop_front() {
+            self.gain = target;
+            if target < 1.0 {
+                self.limited += 1;
+                self.inner.record(frame.scale_amp(target.to_sample()));
+            } else {
+                self.inner.record(frame);
+            }
+        }
+    }
+
+    /// Limit the segment between the two middle frames in the history, so
+    /// both ends are turned down enough to keep the interpolated peak under
+    /// the ceiling.
+    fn limit_between_samples(&mut self) {
+        let max = match self.history.len() {
+            4 => self.max_gain(inter_sample_peak(
+                self.history[0],
+                self.history[1],
+                self.history[2],
+                self.history[3],
+            )),
+            _ => return,
+        };
+
+        // the segment ends 1 frame before the newest frame
+        let len = self.pending.len();
+        for (_, gain) in self.pending.range_mut(len.saturating_sub(3)..len - 1)
+        {
+            *gain = gain.min(max);
+        }
+    }
+}
+
+impl<F, K> Sink<F> for Limiter<F, K>
+where
+    F: Frame,
+    K: Sink<F>,
+{
+    fn record(&mut self, frame: F) {
+        let max = self.max_gain(peak(frame));
+        self.pending.push_back((frame, max));
+
+        if self.true_peak {
+            if self.history.is_empty() {
+                // pretend the clip was preceded by its first frame
+                self.history.push_back(frame);
+            }
+            self.history.push_back(frame);
+            if self.history.len() > 4 {
+                self.history.pop_front();
+            }
+            self.limit_between_samples();
+        }
+
+        if self.pending.len() > self.lookahead {
+            self.write_next();
+        }
+    }
+
+    fn end_of_transmission(&mut self) {
+        if self.true_peak {
+            // and followed by its last one
+            if let Some(&last) = self.history.back() {
+                self.history.push_back(last);
+                if self.history.len() > 4 {
+                    self.history.pop_front();
+                }
+                // the duplicated frame isn't pending, so pretend it is
+                self.pending.push_back((last, 1.0));
+                self.limit_between_samples();
+                self.pending.pop_back();
+            }
+        }
+
+        while !self.pending.is_empty() {
+            self.write_next();
+        }
+
+        self.history.clear();
+        self.gain = 1.0;
+        self.inner.end_of_transmission();
+    }
+
+    fn discard_transmission(&mut self) {
+        self.pending.clear();
+        self.history.clear();
+        self.gain = 1.0;
+        self.inner.discard_transmission();
+    }
+
+    fn transmission_started(&mut self, position: u64) {
+        self.inner.transmission_started(position);
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.lookahead + self.inner.latency_samples()
+    }
+
+    fn errors(&self) -> usize { self.inner.errors() }
+}
+
+fn value<S: Sample>(sample: S) -> f64 {
+    sample.to_float_sample().to_sample::<f64>()
+}
+
+/// The loudest sample in a frame.
+fn peak<F: Frame>(frame: F) -> f64 {
+    frame.channels().map(|s| value(s).abs()).fold(0.0, f64::max)
+}
+
+/// Estimate the loudest point between `b` and `c` by oversampling a
+/// Catmull-Rom spline through all 4 frames.
+fn inter_sample_peak<F: Frame>(a: F, b: F, c: F, d: F) -> f64 {
+    const OVERSAMPLING: usize = 4;
+    let mut loudest: f64 = 0.0;
+
+    let channels = a.channels().zip(b.channels()).zip(c.channels());
+    for (((a, b), c), d) in channels.zip(d.channels()) {
+        let [a, b, c, d] = [a, b, c, d].map(value);
+
+        for step in 0..=OVERSAMPLING {
+            let t = step as f64 / OVERSAMPLING as f64;
+            let y = 0.5
+                * (2.0 * b
+                    + (c - a) * t
+                    + (2.0 * a - 5.0 * b + 4.0 * c - d) * t * t
+                    + (3.0 * (b - c) + d - a) * t * t * t);
+            loudest = loudest.max(y.abs());
+        }
+    }
+
+    loudest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_goes_over_the_ceiling() {
+        let frames: Vec<[f32; 2]> = (0..500)
+            .map(|i| {
+                let t = i as f32 / 10.0;
+                let loud = if i % 100 < 20 { 3.0 } else { 0.3 };
+                [t.sin() * loud, -t.cos() * loud]
+            })
+            .collect();
+
+        for lookahead in [0, 1, 5, 32] {
+            let mut written = Vec::new();
+            let mut sink = Limiter::new(&mut written, 0.9, lookahead, 50);
+            for chunk in frames.chunks(7) {
+                sink.record_frames(chunk);
+            }
+            sink.end_of_transmission();
+            drop(sink);
+
+            assert_eq!(written.len(), frames.len());
+            for frame in &written {
+                assert!(peak(*frame) <= 0.9 + 1e-6, "{:?}", frame);
+            }
+        }
+    }
+
+    #[test]
+    fn quiet_audio_is_untouched() {
+        let frames = [[100_i16], [-2000], [3000], [0]];
+        let mut written = Vec::new();
+        let mut sink = Limiter::true_peak(&mut written, 0.5, 3, 10);
+
+        sink.record_frames(&frames);
+        sink.end_of_transmission();
+
+        assert_eq!(sink.limited(), 0);
+        assert_eq!(Sink::<[i16; 1]>::latency_samples(&sink), 3);
+        drop(sink);
+        assert_eq!(written, frames);
+    }
+
+    #[test]
+    fn true_peaks_between_samples_are_limited() {
+        // a sine at a quarter of the sample rate, sampled 45 degrees out of
+        // phase, so every sample is 0.707 but the real peaks reach 1.0
+        let frames: Vec<[f64; 1]> = (0..64)
+            .map(|i| {
+                let phase = std::f64::consts::FRAC_PI_4
+                    + i as f64 * std::f64::consts::FRAC_PI_2;
+                [phase.sin()]
+            })
+            .collect();
+
+        let mut sample_peak = Limiter::new(Vec::new(), 0.8, 4, 10);
+        sample_peak.record_frames(&frames);
+        sample_peak.end_of_transmission();
+        let mut true_peak = Limiter::true_peak(Vec::new(), 0.8, 4, 10);
+        true_peak.record_frames(&frames);
+        true_peak.end_of_transmission();
+
+        // the samples themselves are already under the ceiling
+        assert_eq!(sample_peak.limited(), 0);
+        assert!(true_peak.limited() > 0);
+        let written = true_peak.into_inner();
+        for window in written.windows(4).skip(8).take(40) {
+            let estimate =
+                inter_sample_peak(window[0], window[1], window[2], window[3]);
+            assert!(estimate <= 0.8 + 1e-6, "{}", estimate);
+        }
+    }
+}
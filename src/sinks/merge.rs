@@ -0,0 +1,244 @@
+use crate::{NoiseGate, Sink};
+use dasp::Frame;
+
+/// Wraps a [`Sink`] so segments separated by a short gap end up in the same
+/// transmission, with the real audio from the gap in between.
+///
+/// The gate's release time already bridges short pauses, but only by
+/// staying open. For conversational recordings it often sounds better to
+/// keep the gate's release short and merge nearby segments afterwards, so a
+/// single clip covers a whole exchange without a long tail of silence at the
+/// end.
+///
+/// The sink never sees the frames the gate skips, so a [`MergeGaps`] drives
+/// the [`NoiseGate`] itself and holds on to the most recent `max_gap` frames
+/// of silence in case the next segment starts soon.
+///
+/// ```rust
+/// use noise_gate::{sinks::MergeGaps, NoiseGate};
+///
+/// let frames = [[500_i16], [0], [1], [2], [600], [0], [0], [0], [0], [700]];
+/// let mut gate = NoiseGate::new(100, 0);
+/// let mut sink = MergeGaps::new(Vec::new(), 3);
+///
+/// sink.process_frames(&mut gate, &frames);
+/// sink.finish();
+///
+/// // the 2 frame gap was merged, but the 3 frame gap wasn't
+/// assert_eq!(sink.merged(), 1);
+/// assert_eq!(
+///     sink.into_inner(),
+///     vec![[500], [0], [1], [2], [600], [0], [700]]
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeGaps<F, K> {
+    inner: K,
+    max_gap: usize,
+    state: Merging,
+    /// The silence since the last segment ended, while waiting to see
+    /// whether another one starts.
+    gap: Vec<F>,
+    merged: usize,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Merging {
+    Idle,
+    Recording,
+    Waiting,
+}
+
+impl<F, K> MergeGaps<F, K> {
+    /// Wrap a [`Sink`], merging segments separated by fewer than `max_gap`
+    /// frames.
+    pub fn new(inner: K, max_gap: usize) -> Self {
+        MergeGaps {
+            inner,
+            max_gap,
+            state: Merging::Idle,
+            gap: Vec::new(),
+            merged: 0,
+        }
+    }
+
+    /// The longest gap which can be merged, in frames.
+    pub fn max_gap(&self) -> usize { self.max_gap }
+
+    /// How many gaps have been merged.
+    pub fn merged(&self) -> usize { self.merged }
+
+    /// Is the last segment being held open in case another one starts?
+    pub fn is_waiting(&self) -> bool { self.state == Merging::Waiting }
+
+    /// Get a reference to the inner [`Sink`].
+    pub fn inner(&self) -> &K { &self.inner }
+
+    /// Get a mutable reference to the inner [`Sink`].
+    pub fn inner_mut(&mut self) -> &mut K { &mut self.inner }
+
+    /// Consume the adapter, returning the inner [`Sink`].
+    ///
+    /// Make sure to call [`MergeGaps::finish()`] first, otherwise the last
+    /// transmission may not have ended.
+    pub fn into_inner(self) -> K { self.inner }
+}
+
+impl<F, K> MergeGaps<F, K>
+where
+    F: Frame,
+    K: Sink<F>,
+{
+    /// Pass a batch of frames through the `gate`, sending whatever it lets
+    /// through (and any short gaps) to the inner [`Sink`].
+    ///
+    /// Like [`NoiseGate::process_frames()`], the gate and adapter remember
+    /// their state between calls.
+    pub fn process_frames(
+        &mut self,
+        gate: &mut NoiseGate<F::Sample>,
+        frames: &[F],
+    ) {
+        let was_open = gate.is_open();
+        let start = gate.position();
+        if was_open && self.state != Merging::Recording {
+            // someone else was using the gate, pick up where it left off
+            self.inner.transmission_started(start);
+            self.state = Merging::Recording;
+        }
+
+        let mut cursor = 0;
+
+        for (range, _) in gate.segments(frames) {
+            if self.state == Merging::Recording && range.start > cursor {
+                // the gate closed since the last frame we recorded
+                self.segment_ended();
+            }
+            self.skipped(&frames[cursor..range.start]);
+
+            match self.state {
+                Merging::Idle => {
+                    self.inner.transmission_started(start + range.start as u64);
+                },
+                Merging::Waiting => {
+                    self.inner.record_frames(&self.gap);
+                    self.gap.clear();
+                    self.merged += 1;
+                },
+                Merging::Recording => {},
+            }
+            self.inner.record_frames(&frames[range.clone()]);
+            self.state = Merging::Recording;
+            cursor = range.end;
+        }
+
+        if self.state == Merging::Recording && !gate.is_open() {
+            self.segment_ended();
+        }
+        self.skipped(&frames[cursor..]);
+    }
+
+    /// End the current transmission, if there is one, without waiting for
+    /// the next segment (e.g. at the end of a recording).
+    pub fn finish(&mut self) {
+        if self.state != Merging::Idle {
+            self.gap.clear();
+            self.state = Merging::Idle;
+            self.inner.end_of_transmission();
+        }
+    }
+
+    fn segment_ended(&mut self) {
+        self.state = Merging::Waiting;
+        if self.max_gap == 0 {
+            self.finish();
+        }
+    }
+
+    fn skipped(&mut self, frames: &[F]) {
+        if self.state != Merging::Waiting {
+            return;
+        }
+
+        if self.gap.len() + frames.len() >= self.max_gap {
+            self.finish();
+        } else {
+            self.gap.extend_from_slice(frames);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct Clips {
+        clips: Vec<(u64, Vec<[i16; 1]>)>,
+        current: Option<(u64, Vec<[i16; 1]>)>,
+    }
+
+    impl Sink<[i16; 1]> for Clips {
+        fn record(&mut self, frame: [i16; 1]) {
+            self.current.as_mut().unwrap().1.push(frame);
+        }
+
+        fn end_of_transmission(&mut self) {
+            self.clips.extend(self.current.take());
+        }
+
+        fn transmission_started(&mut self, position: u64) {
+            assert!(self.current.is_none());
+            self.current = Some((position, Vec::new()));
+        }
+    }
+
+    fn signal() -> Vec<[i16; 1]> {
+        // bursts separated by gaps of 2, 4, 6, ... frames
+        let mut frames = Vec::new();
+        for gap in (2..20).step_by(2) {
+            frames.push([500]);
+            frames.extend((0..gap).map(|i| [i]));
+        }
+        frames
+    }
+
+    #[test]
+    fn gaps_are_merged_across_buffers() {
+        let frames = signal();
+        let mut expected = MergeGaps::new(Clips::default(), 7);
+        expected.process_frames(&mut NoiseGate::new(100, 0), &frames);
+        expected.finish();
+        let expected = expected.into_inner().clips;
+
+        // gaps of 2, 4, and 6 are merged, minus the frame the gate records
+        // while closing
+        assert_eq!(expected.len(), 6);
+        assert_eq!(expected[0].0, 0);
+        assert_eq!(expected[0].1.len(), 3 + 5 + 7 + 1 + 1);
+        assert_eq!(&expected[0].1[..4], &[[500], [0], [1], [500]]);
+
+        for chunk_size in [1, 3, 17, 40] {
+            let mut gate = NoiseGate::new(100, 0);
+            let mut sink = MergeGaps::new(Clips::default(), 7);
+            for chunk in frames.chunks(chunk_size) {
+                sink.process_frames(&mut gate, chunk);
+            }
+            sink.finish();
+
+            assert_eq!(sink.into_inner().clips, expected, "{}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn no_gap_means_no_merging() {
+        let frames = signal();
+        let mut sink = MergeGaps::new(Clips::default(), 0);
+
+        sink.process_frames(&mut NoiseGate::new(100, 0), &frames);
+        sink.finish();
+
+        assert_eq!(sink.merged(), 0);
+        assert_eq!(sink.into_inner().clips.len(), 9);
+    }
+}
@@ -0,0 +1,200 @@
+use crate::Sink;
+#[cfg(feature = "midi")]
+use std::error::Error;
+
+/// A [`Sink`] adapter which sends a MIDI note-on when a transmission starts
+/// and a note-off when it ends, so the gate can trigger samplers, tally
+/// lights, or recording automation.
+///
+/// Messages are passed to a callback as raw MIDI bytes, so this works with
+/// any MIDI library. With the `midi` feature, `midi_output()` connects to
+/// a hardware or virtual port using [`midir`][midir]:
+///
+/// ```rust,ignore
+/// use noise_gate::sinks::{midi_output, MidiTrigger};
+///
+/// let sink = MidiTrigger::new(sink, midi_output("Launchpad")?);
+/// ```
+///
+/// Frames are always passed through to the inner sink untouched.
+///
+/// ```rust
+/// use noise_gate::{sinks::MidiTrigger, Sink};
+///
+/// # struct Discard;
+/// # impl Sink<[f32; 1]> for Discard {
+/// #     fn record(&mut self, _: [f32; 1]) {}
+/// #     fn end_of_transmission(&mut self) {}
+/// # }
+/// let mut messages = Vec::new();
+/// let send = |msg: &[u8]| messages.push(msg.to_vec());
+/// let mut sink = MidiTrigger::new(Discard, send).with_note(9, 36, 100);
+///
+/// sink.record([0.5_f32]);
+/// sink.record([0.4]);
+/// sink.end_of_transmission();
+///
+/// assert_eq!(messages, vec![vec![0x99, 36, 100], vec![0x89, 36, 0]]);
+/// ```
+///
+/// [midir]: https://crates.io/crates/midir
+#[derive(Debug, Clone, PartialEq)]
+pub struct MidiTrigger<K, M> {
+    inner: K,
+    send: M,
+    channel: u8,
+    note: u8,
+    velocity: u8,
+    playing: bool,
+}
+
+impl<K, M> MidiTrigger<K, M>
+where
+    M: FnMut(&[u8]),
+{
+    /// Wrap a [`Sink`], sending middle C on channel 1 with full velocity.
+    pub fn new(inner: K, send: M) -> Self {
+        MidiTrigger {
+            inner,
+            send,
+            channel: 0,
+            note: 60,
+            velocity: 127,
+            playing: false,
+        }
+    }
+
+    /// Use a different note.
+    ///
+    /// The `channel` is zero-based (i.e. `9` is what most software calls
+    /// channel 10), and everything is masked to its valid range.
+    pub fn with_note(self, channel: u8, note: u8, velocity: u8) -> Self {
+        MidiTrigger {
+            channel: channel & 0x0F,
+            note: note & 0x7F,
+            velocity: velocity & 0x7F,
+            ..self
+        }
+    }
+
+    /// Is the note currently playing (i.e. are we in the middle of a
+    /// transmission)?
+    pub fn is_playing(&self) -> bool { self.playing }
+
+    /// Get a reference to the inner [`Sink`].
+    pub fn inner(&self) -> &K { &self.inner }
+
+    /// Get a mutable reference to the inner [`Sink`].
+    pub fn inner_mut(&mut self) -> &mut K { &mut self.inner }
+
+    /// Consume the adapter, returning the inner [`Sink`].
+    pub fn into_inner(self) -> K { self.inner }
+
+    fn note_on(&mut self) {
+        if !self.playing {
+            self.playing = true;
+            (self.send)(&[0x90 | self.channel, self.note, self.velocity]);
+        }
+    }
+
+    fn note_off(&mut self) {
+        if self.playing {
+            self.playing = false;
+            (self.send)(&[0x80 | self.channel, self.note, 0]);
+        }
+    }
+}
+
+impl<F, K, M> Sink<F> for MidiTrigger<K, M>
+where
+    F: Copy,
+    K: Sink<F>,
+    M: FnMut(&[u8]),
+{
+    fn record(&mut self, frame: F) {
+        self.note_on();
+        self.inner.record(frame);
+    }
+
+    fn record_frames(&mut self, frames: &[F]) {
+        if !frames.is_empty() {
+            self.note_on();
+            self.inner.record_frames(frames);
+        }
+    }
+
+    fn end_of_transmission(&mut self) {
+        self.note_off();
+        self.inner.end_of_transmission();
+    }
+
+    fn discard_transmission(&mut self) {
+        self.note_off();
+        self.inner.discard_transmission();
+    }
+
+    fn transmission_started(&mut self, position: u64) {
+        self.inner.transmission_started(position);
+    }
+
+    fn latency_samples(&self) -> usize { self.inner.latency_samples() }
+
+    fn errors(&self) -> usize { self.inner.errors() }
+}
+
+/// Connect to the first MIDI output port whose name contains `port`,
+/// returning a callback which can be passed to [`MidiTrigger::new()`].
+///
+/// Errors while sending (e.g. because the device was unplugged) are ignored.
+#[cfg(feature = "midi")]
+pub fn midi_output(
+    port: &str,
+) -> Result<impl FnMut(&[u8]), Box<dyn Error + Send + Sync>> {
+    let output = midir::MidiOutput::new("noise-gate")?;
+
+    let found = output
+        .ports()
+        .into_iter()
+        .find(|p| output.port_name(p).is_ok_and(|name| name.contains(port)))
+        .ok_or_else(|| format!("No MIDI output port matching \"{}\"", port))?;
+
+    let mut connection = output
+        .connect(&found, "noise-gate")
+        .map_err(|e| e.to_string())?;
+
+    Ok(move |msg: &[u8]| {
+        let _ = connection.send(msg);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NoiseGate;
+
+    struct Discard;
+
+    impl Sink<[i16; 1]> for Discard {
+        fn record(&mut self, _: [i16; 1]) {}
+
+        fn end_of_transmission(&mut self) {}
+    }
+
+    #[test]
+    fn one_note_per_transmission() {
+        let frames = [[0_i16], [500], [600], [0], [0], [0], [700], [0], [0]];
+        let mut gate = NoiseGate::new(100, 1);
+        let mut messages = Vec::new();
+        let mut sink = MidiTrigger::new(Discard, |msg: &[u8]| {
+            messages.push([msg[0], msg[1], msg[2]])
+        });
+
+        gate.process_frames(&frames, &mut sink);
+        assert!(sink.is_playing());
+        sink.end_of_transmission();
+
+        let on = [0x90, 60, 127];
+        let off = [0x80, 60, 0];
+        assert_eq!(messages, vec![on, off, on, off]);
+    }
+}
@@ -0,0 +1,34 @@
+//! Adapters which can be wrapped around an existing [`Sink`] to alter what
+//! gets recorded.
+//!
+//! [`Sink`]: crate::Sink
+
+mod classify;
+mod event_log;
+mod fade;
+mod limit;
+mod merge;
+mod midi;
+mod pad;
+mod quantize;
+mod roger;
+mod rotate;
+mod stream;
+mod trim;
+mod watchdog;
+
+pub use classify::{Classification, Classifier, Classify, Verdict};
+pub use event_log::EventLog;
+pub use fade::FadeEdges;
+pub use limit::Limiter;
+pub use merge::MergeGaps;
+#[cfg(feature = "midi")]
+pub use midi::midi_output;
+pub use midi::MidiTrigger;
+pub use pad::PadGaps;
+pub use quantize::Quantize;
+pub use roger::RogerBeep;
+pub use rotate::RotateFiles;
+pub use stream::{SegmentEvent, SegmentStream};
+pub use trim::TrimSilence;
+pub use watchdog::{Timeout, Watchdog};
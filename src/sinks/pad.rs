@@ -0,0 +1,194 @@
+use crate::Sink;
+use dasp::{Frame, Sample};
+
+/// A [`Sink`] adapter which inserts a separator between transmissions, so
+/// someone listening to the condensed audio can tell where the cuts were.
+///
+/// This is intended for sinks which write everything to a single continuous
+/// output (e.g. a `Vec<F>` or one long WAV file). The separator is written
+/// immediately before the first frame of each transmission after the first,
+/// so there is never any padding at the very start or end of the output.
+///
+/// # Examples
+///
+/// ```rust
+/// use noise_gate::{sinks::PadGaps, NoiseGate};
+///
+/// let frames = [[500_i16], [0], [0], [0], [700], [0], [0]];
+/// let mut condensed = Vec::new();
+///
+/// let mut gate = NoiseGate::new(100, 0);
+/// gate.process_frames(&frames, &mut PadGaps::silence(&mut condensed, 2));
+///
+/// assert_eq!(condensed, vec![[500], [0], [0], [0], [700], [0]]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PadGaps<F, K> {
+    inner: K,
+    separator: Vec<F>,
+    in_transmission: bool,
+    transmissions: usize,
+}
+
+impl<F, K> PadGaps<F, K> {
+    /// Wrap a [`Sink`], writing `separator` between consecutive
+    /// transmissions.
+    pub fn new(inner: K, separator: Vec<F>) -> Self {
+        PadGaps {
+            inner,
+            separator,
+            in_transmission: false,
+            transmissions: 0,
+        }
+    }
+
+    /// Separate transmissions with `length` frames of silence.
+    pub fn silence(inner: K, length: usize) -> Self
+    where
+        F: Frame,
+    {
+        PadGaps::new(inner, vec![F::EQUILIBRIUM; length])
+    }
+
+    /// Separate transmissions with a `length` frame sine wave beep which
+    /// repeats every `period` frames, with a peak `amplitude` between `0.0`
+    /// and `1.0` relative to full scale.
+    pub fn beep(inner: K, length: usize, period: usize, amplitude: f64) -> Self
+    where
+        F: Frame,
+    {
+        PadGaps::new(inner, sine(length, period.max(1) as f64, amplitude))
+    }
+
+    /// The frames written between transmissions.
+    pub fn separator(&self) -> &[F] { &self.separator }
+
+    /// The number of transmissions which have been started so far.
+    pub fn transmissions(&self) -> usize { self.transmissions }
+
+    /// Get a reference to the inner [`Sink`].
+    pub fn inner(&self) -> &K { &self.inner }
+
+    /// Get a mutable reference to the inner [`Sink`].
+    pub fn inner_mut(&mut self) -> &mut K { &mut self.inner }
+
+    /// Consume the adapter, returning the inner [`Sink`].
+    pub fn into_inner(self) -> K { self.inner }
+}
+
+impl<F, K> PadGaps<F, K>
+where
+    F: Copy,
+    K: Sink<F>,
+{
+    fn start_transmission(&mut self) {
+        if self.in_transmission {
+            return;
+        }
+
+        if self.transmissions > 0 {
+            self.inner.record_frames(&self.separator);
+        }
+
+        self.in_transmission = true;
+        self.transmissions += 1;
+    }
+}
+
+impl<F, K> Sink<F> for PadGaps<F, K>
+where
+    F: Copy,
+    K: Sink<F>,
+{
+    fn record(&mut self, frame: F) {
+        self.start_transmission();
+        self.inner.record(frame);
+    }
+
+    fn record_frames(&mut self, frames: &[F])
+    where
+        F: Copy,
+    {
+        if frames.is_empty() {
+            return;
+        }
+
+        self.start_transmission();
+        self.inner.record_frames(frames);
+    }
+
+    fn end_of_transmission(&mut self) {
+        self.in_transmission = false;
+        self.inner.end_of_transmission();
+    }
+
+    fn discard_transmission(&mut self) {
+        self.in_transmission = false;
+        self.inner.discard_transmission();
+    }
+
+    fn transmission_started(&mut self, position: u64) {
+        self.inner.transmission_started(position);
+    }
+
+    fn latency_samples(&self) -> usize { self.inner.latency_samples() }
+
+    fn errors(&self) -> usize { self.inner.errors() }
+}
+
+/// A `length` frame sine wave which repeats every `period` frames, with a
+/// peak `amplitude` between `0.0` and `1.0` relative to full scale.
+pub(super) fn sine<F: Frame>(
+    length: usize,
+    period: f64,
+    amplitude: f64,
+) -> Vec<F> {
+    let amplitude = amplitude.clamp(0.0, 1.0);
+
+    (0..length)
+        .map(|i| {
+            let phase = 2.0 * std::f64::consts::PI * i as f64 / period;
+            let value: <F::Sample as Sample>::Float =
+                (amplitude * phase.sin()).to_sample();
+            let sample: F::Sample = value.to_sample();
+            F::from_fn(|_| sample)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn separators_only_go_between_transmissions() {
+        let mut sink = PadGaps::new(Vec::new(), vec![[9_i16], [9]]);
+
+        sink.record([1]);
+        sink.record([2]);
+        sink.end_of_transmission();
+        sink.record_frames(&[[3], [4]]);
+        sink.end_of_transmission();
+        sink.end_of_transmission();
+        sink.record([5]);
+        sink.end_of_transmission();
+
+        assert_eq!(sink.transmissions(), 3);
+        assert_eq!(
+            sink.into_inner(),
+            vec![[1], [2], [9], [9], [3], [4], [9], [9], [5]]
+        );
+    }
+
+    #[test]
+    fn beeps_follow_a_sine_wave() {
+        let sink: PadGaps<[f32; 2], Vec<[f32; 2]>> =
+            PadGaps::beep(Vec::new(), 4, 4, 0.5);
+
+        let separator = sink.separator();
+        assert_eq!(separator.len(), 4);
+        assert_eq!(separator[0], [0.0, 0.0]);
+        assert_eq!(separator[1], [0.5, 0.5]);
+        assert!((separator[3][0] + 0.5).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,200 @@
+use crate::Sink;
+use dasp::{
+    sample::{Duplex, FromSample, ToSample},
+    Frame, Sample,
+};
+use std::marker::PhantomData;
+
+/// A [`Sink`] adapter which converts frames to another sample type (e.g.
+/// float processing to 16-bit output), optionally adding TPDF dither so the
+/// rounding doesn't turn into audible distortion.
+///
+/// Every gain change (fades, limiting, filters) leaves samples between the
+/// steps an integer format can represent. Truncating them correlates the
+/// error with the signal, which sounds like distortion on quiet passages.
+/// Adding triangular noise of ±1 step before rounding turns that error into
+/// a constant, much less noticeable, noise floor.
+///
+/// When converting to a floating point format there is nothing to round, so
+/// frames are converted as-is.
+///
+/// ```rust
+/// use noise_gate::{sinks::Quantize, Sink};
+///
+/// let mut written = Vec::new();
+/// // the output format can't be inferred from a Vec, so spell it out
+/// let mut sink = Quantize::<[i16; 1], _>::dithered(&mut written);
+///
+/// sink.record_frames(&[[0.5_f32]; 4]);
+///
+/// drop(sink);
+/// assert!(written.iter().all(|&[s]| (s - 16384).abs() <= 1));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantize<G, K> {
+    inner: K,
+    dither: bool,
+    /// The size of one step in the output format, where `1.0` is full
+    /// scale, or `0.0` if it isn't an integer format.
+    step: f64,
+    seed: u32,
+    _output: PhantomData<fn(G)>,
+}
+
+impl<G, K> Quantize<G, K>
+where
+    G: Frame,
+    G::Sample: Duplex<f64>,
+{
+    /// Wrap a [`Sink`], rounding each sample to the nearest value the
+    /// output format can represent.
+    pub fn new(inner: K) -> Self {
+        Quantize {
+            inner,
+            dither: false,
+            step: step::<G::Sample>(),
+            seed: 0x2545_f491,
+            _output: PhantomData,
+        }
+    }
+
+    /// Wrap a [`Sink`], adding TPDF dither before rounding.
+    pub fn dithered(inner: K) -> Self {
+        let mut quantize = Quantize::new(inner);
+        quantize.dither = true;
+        quantize
+    }
+}
+
+impl<G, K> Quantize<G, K> {
+    /// Is dither being added before rounding?
+    pub fn is_dithered(&self) -> bool { self.dither }
+
+    /// Get a reference to the inner [`Sink`].
+    pub fn inner(&self) -> &K { &self.inner }
+
+    /// Get a mutable reference to the inner [`Sink`].
+    pub fn inner_mut(&mut self) -> &mut K { &mut self.inner }
+
+    /// Consume the adapter, returning the inner [`Sink`].
+    pub fn into_inner(self) -> K { self.inner }
+
+    fn quantize<S: FromSample<f64>>(&mut self, sample: f64) -> S {
+        if self.step == 0.0 {
+            return sample.to_sample();
+        }
+
+        let noise = if self.dither {
+            // the difference of two uniform values has a triangular
+            // distribution between -1 and 1
+            self.uniform() - self.uniform()
+        } else {
+            0.0
+        };
+        let steps = (sample / self.step + noise).round();
+
+        // stay in range, so the conversion doesn't overflow at full scale
+        (steps * self.step).clamp(-1.0, 1.0 - self.step).to_sample()
+    }
+
+    fn uniform(&mut self) -> f64 {
+        // xorshift, which is plenty random enough for dither
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        self.seed as f64 / u32::MAX as f64
+    }
+}
+
+impl<F, G, K> Sink<F> for Quantize<G, K>
+where
+    F: Frame,
+    F::Sample: ToSample<f64>,
+    G: Frame<NumChannels = F::NumChannels>,
+    G::Sample: FromSample<f64>,
+    K: Sink<G>,
+{
+    fn record(&mut self, frame: F) {
+        let frame: G = frame.map(|sample| self.quantize(sample.to_sample()));
+        self.inner.record(frame);
+    }
+
+    fn end_of_transmission(&mut self) { self.inner.end_of_transmission(); }
+
+    fn discard_transmission(&mut self) { self.inner.discard_transmission(); }
+
+    fn transmission_started(&mut self, position: u64) {
+        self.inner.transmission_started(position);
+    }
+
+    fn latency_samples(&self) -> usize { self.inner.latency_samples() }
+
+    fn errors(&self) -> usize { self.inner.errors() }
+}
+
+/// Find the smallest step a sample type can represent by converting
+/// smaller and smaller powers of two until they round to silence.
+fn step<S: Sample + Duplex<f64>>() -> f64 {
+    let mut step = 0.0;
+
+    for bits in 0..=32 {
+        let candidate = 0.5_f64.powi(bits);
+        let roundtrip = candidate.to_sample::<S>().to_sample::<f64>()
+            - S::EQUILIBRIUM.to_sample::<f64>();
+        if roundtrip == 0.0 {
+            return step;
+        }
+        step = candidate;
+    }
+
+    // still not silent after 32 bits, so it must be floating point
+    0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dasp::sample::I24;
+
+    #[test]
+    fn steps_match_the_bit_depth() {
+        assert_eq!(step::<i8>(), 1.0 / 128.0);
+        assert_eq!(step::<u8>(), 1.0 / 128.0);
+        assert_eq!(step::<i16>(), 1.0 / 32768.0);
+        assert_eq!(step::<u16>(), 1.0 / 32768.0);
+        assert_eq!(step::<I24>(), 1.0 / 8_388_608.0);
+        assert_eq!(step::<i32>(), 1.0 / 2_147_483_648.0);
+        assert_eq!(step::<f32>(), 0.0);
+    }
+
+    #[test]
+    fn dither_preserves_detail_below_one_step() {
+        // 0.3 of a step, which plain rounding always turns into silence
+        let level = 0.3 / 32768.0;
+        let frames = vec![[level]; 10_000];
+
+        let mut rounded = Quantize::<[i16; 1], _>::new(Vec::new());
+        rounded.record_frames(&frames);
+        let mut dithered = Quantize::<[i16; 1], _>::dithered(Vec::new());
+        dithered.record_frames(&frames);
+
+        assert!(rounded.into_inner().iter().all(|&[s]| s == 0));
+        let dithered = dithered.into_inner();
+        assert!(dithered.iter().all(|&[s]| (-1..=1).contains(&s)));
+        let mean = dithered.iter().map(|&[s]| f64::from(s)).sum::<f64>()
+            / dithered.len() as f64;
+        assert!((mean - 0.3).abs() < 0.05, "{}", mean);
+    }
+
+    #[test]
+    fn full_scale_doesnt_overflow() {
+        let mut sink = Quantize::<[I24; 2], _>::dithered(Vec::new());
+
+        sink.record_frames(&[[1.0_f64, -1.0], [2.0, -2.0]]);
+
+        for [left, right] in sink.into_inner() {
+            assert!(left.inner() >= 8_388_606, "{:?}", left);
+            assert!(right.inner() <= -8_388_606, "{:?}", right);
+        }
+    }
+}
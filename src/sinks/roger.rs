@@ -0,0 +1,152 @@
+use crate::Sink;
+use dasp::Frame;
+
+/// A [`Sink`] adapter which appends a marker (traditionally a short "roger
+/// beep") to the end of every transmission, as is conventional in radio
+/// logging.
+///
+/// The marker is written just before the inner sink is told the
+/// transmission ended, so each clip ends with its own marker. To mark the
+/// boundaries in one long condensed recording, use
+/// [`PadGaps`][super::PadGaps] instead.
+///
+/// ```rust
+/// use noise_gate::{sinks::RogerBeep, NoiseGate};
+///
+/// let frames = [[500_i16], [0], [0], [0], [700], [0], [0]];
+/// let mut recorded = Vec::new();
+/// let mut sink = RogerBeep::new(&mut recorded, vec![[9], [9]]);
+///
+/// let mut gate = NoiseGate::new(100, 0);
+/// gate.process_frames(&frames, &mut sink);
+///
+/// assert_eq!(sink.markers(), 2);
+/// drop(sink);
+/// assert_eq!(recorded, vec![[500], [0], [9], [9], [700], [0], [9], [9]]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RogerBeep<F, K> {
+    inner: K,
+    marker: Vec<F>,
+    in_transmission: bool,
+    markers: usize,
+}
+
+impl<F, K> RogerBeep<F, K> {
+    /// Wrap a [`Sink`], appending `marker` to every transmission.
+    pub fn new(inner: K, marker: Vec<F>) -> Self {
+        RogerBeep {
+            inner,
+            marker,
+            in_transmission: false,
+            markers: 0,
+        }
+    }
+
+    /// Append a `length` frame tone at `frequency` Hz, with a peak
+    /// `amplitude` between `0.0` and `1.0` relative to full scale.
+    pub fn tone(
+        inner: K,
+        frequency: f64,
+        sample_rate: u32,
+        length: usize,
+        amplitude: f64,
+    ) -> Self
+    where
+        F: Frame,
+    {
+        let period = f64::from(sample_rate) / frequency.max(f64::MIN_POSITIVE);
+        RogerBeep::new(inner, super::pad::sine(length, period, amplitude))
+    }
+
+    /// The frames appended to each transmission.
+    pub fn marker(&self) -> &[F] { &self.marker }
+
+    /// The number of markers which have been written so far.
+    pub fn markers(&self) -> usize { self.markers }
+
+    /// Get a reference to the inner [`Sink`].
+    pub fn inner(&self) -> &K { &self.inner }
+
+    /// Get a mutable reference to the inner [`Sink`].
+    pub fn inner_mut(&mut self) -> &mut K { &mut self.inner }
+
+    /// Consume the adapter, returning the inner [`Sink`].
+    pub fn into_inner(self) -> K { self.inner }
+}
+
+impl<F, K> Sink<F> for RogerBeep<F, K>
+where
+    F: Copy,
+    K: Sink<F>,
+{
+    fn record(&mut self, frame: F) {
+        self.in_transmission = true;
+        self.inner.record(frame);
+    }
+
+    fn record_frames(&mut self, frames: &[F]) {
+        if frames.is_empty() {
+            return;
+        }
+
+        self.in_transmission = true;
+        self.inner.record_frames(frames);
+    }
+
+    fn end_of_transmission(&mut self) {
+        // don't turn an empty transmission into a clip containing just the
+        // marker
+        if self.in_transmission && !self.marker.is_empty() {
+            self.inner.record_frames(&self.marker);
+            self.markers += 1;
+        }
+
+        self.in_transmission = false;
+        self.inner.end_of_transmission();
+    }
+
+    fn discard_transmission(&mut self) {
+        self.in_transmission = false;
+        self.inner.discard_transmission();
+    }
+
+    fn transmission_started(&mut self, position: u64) {
+        self.inner.transmission_started(position);
+    }
+
+    fn latency_samples(&self) -> usize { self.inner.latency_samples() }
+
+    fn errors(&self) -> usize { self.inner.errors() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tones_have_the_right_pitch() {
+        let sink: RogerBeep<[f32; 1], Vec<[f32; 1]>> =
+            RogerBeep::tone(Vec::new(), 1000.0, 8000, 80, 0.5);
+
+        let marker = sink.marker();
+        assert_eq!(marker.len(), 80);
+        assert_eq!(marker[0], [0.0]);
+        assert!((marker[2][0] - 0.5).abs() < 1e-6);
+        assert!((marker[6][0] + 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn empty_transmissions_dont_get_a_marker() {
+        let mut sink = RogerBeep::new(Vec::new(), vec![[9_u8]]);
+
+        sink.end_of_transmission();
+        sink.record_frames(&[]);
+        sink.end_of_transmission();
+        sink.record([1]);
+        sink.end_of_transmission();
+
+        assert_eq!(sink.markers(), 1);
+        assert_eq!(sink.into_inner(), vec![[1], [9]]);
+    }
+}
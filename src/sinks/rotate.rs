@@ -0,0 +1,231 @@
+use crate::Sink;
+
+/// A [`Sink`] adapter which limits how long each file can get, ending the
+/// current transmission and starting a new one when the limit is reached.
+///
+/// This is useful for long continuous transmissions, where you don't want a
+/// single huge file (e.g. because of filesystem limits on an embedded
+/// recorder). The segment carries on seamlessly in the next file, so
+/// nothing is lost when rolling over. If the transmission's start was
+/// reported (see [`Sink::transmission_started()`]), each continuation is
+/// started at the position of its first frame.
+///
+/// ```rust
+/// use noise_gate::{sinks::RotateFiles, Sink};
+///
+/// # #[derive(Default)]
+/// # struct Files(Vec<usize>, usize);
+/// # impl Sink<[i16; 2]> for Files {
+/// #     fn record(&mut self, _: [i16; 2]) { self.1 += 1; }
+/// #     fn end_of_transmission(&mut self) {
+/// #         self.0.push(std::mem::take(&mut self.1));
+/// #     }
+/// # }
+/// // 16-bit stereo, with room for a 44-byte WAV header
+/// let mut sink = RotateFiles::by_size(Files::default(), 1044, 44, 4);
+/// assert_eq!(sink.max_frames(), 250);
+///
+/// sink.record_frames(&[[0, 0]; 600]);
+/// sink.end_of_transmission();
+///
+/// assert_eq!(sink.inner().0, vec![250, 250, 100]);
+/// assert_eq!(sink.rollovers(), 2);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RotateFiles<K> {
+    inner: K,
+    max_frames: usize,
+    frames_in_file: usize,
+    rollovers: usize,
+    /// The position of the next frame, if we know where the transmission
+    /// started.
+    position: Option<u64>,
+}
+
+impl<K> RotateFiles<K> {
+    /// Wrap a [`Sink`], starting a new file after every `max_frames` frames.
+    ///
+    /// Use [`RotateFiles::by_size()`] to limit the number of bytes instead.
+    pub fn new(inner: K, max_frames: usize) -> Self {
+        RotateFiles {
+            inner,
+            max_frames: max_frames.max(1),
+            frames_in_file: 0,
+            rollovers: 0,
+            position: None,
+        }
+    }
+
+    /// Wrap a [`Sink`], making sure no file is more than `max_bytes` long.
+    ///
+    /// The file's header (44 bytes for a typical WAV file) counts towards
+    /// the limit.
+    pub fn by_size(
+        inner: K,
+        max_bytes: usize,
+        header_bytes: usize,
+        bytes_per_frame: usize,
+    ) -> Self {
+        let frames =
+            max_bytes.saturating_sub(header_bytes) / bytes_per_frame.max(1);
+        RotateFiles::new(inner, frames)
+    }
+
+    /// The most frames a single file can contain.
+    pub fn max_frames(&self) -> usize { self.max_frames }
+
+    /// How many times a transmission has been split across files.
+    pub fn rollovers(&self) -> usize { self.rollovers }
+
+    /// Get a reference to the inner [`Sink`].
+    pub fn inner(&self) -> &K { &self.inner }
+
+    /// Get a mutable reference to the inner [`Sink`].
+    pub fn inner_mut(&mut self) -> &mut K { &mut self.inner }
+
+    /// Consume the adapter, returning the inner [`Sink`].
+    pub fn into_inner(self) -> K { self.inner }
+
+    fn roll_over_if_full<F>(&mut self)
+    where
+        K: Sink<F>,
+    {
+        if self.frames_in_file >= self.max_frames {
+            self.inner.end_of_transmission();
+            self.frames_in_file = 0;
+            self.rollovers += 1;
+
+            if let Some(position) = self.position {
+                self.inner.transmission_started(position);
+            }
+        }
+    }
+
+    fn recorded(&mut self, frames: usize) {
+        self.frames_in_file += frames;
+        if let Some(position) = self.position.as_mut() {
+            *position += frames as u64;
+        }
+    }
+}
+
+impl<F, K> Sink<F> for RotateFiles<K>
+where
+    F: Copy,
+    K: Sink<F>,
+{
+    fn record(&mut self, frame: F) {
+        self.roll_over_if_full();
+        self.inner.record(frame);
+        self.recorded(1);
+    }
+
+    fn record_frames(&mut self, mut frames: &[F]) {
+        while !frames.is_empty() {
+            self.roll_over_if_full();
+
+            let space = self.max_frames - self.frames_in_file;
+            let (head, tail) = frames.split_at(space.min(frames.len()));
+            self.inner.record_frames(head);
+            self.recorded(head.len());
+            frames = tail;
+        }
+    }
+
+    fn end_of_transmission(&mut self) {
+        self.frames_in_file = 0;
+        self.position = None;
+        self.inner.end_of_transmission();
+    }
+
+    fn discard_transmission(&mut self) {
+        self.frames_in_file = 0;
+        self.position = None;
+        self.inner.discard_transmission();
+    }
+
+    fn transmission_started(&mut self, position: u64) {
+        if self.frames_in_file == 0 {
+            self.position = Some(position);
+        }
+        self.inner.transmission_started(position);
+    }
+
+    fn latency_samples(&self) -> usize { self.inner.latency_samples() }
+
+    fn errors(&self) -> usize { self.inner.errors() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct Files {
+        files: Vec<Vec<u32>>,
+        current: Vec<u32>,
+        starts: Vec<u64>,
+    }
+
+    impl Sink<u32> for Files {
+        fn record(&mut self, frame: u32) { self.current.push(frame); }
+
+        fn end_of_transmission(&mut self) {
+            self.files.push(std::mem::take(&mut self.current));
+        }
+
+        fn transmission_started(&mut self, position: u64) {
+            self.starts.push(position);
+        }
+    }
+
+    #[test]
+    fn long_transmissions_are_split_without_losing_anything() {
+        let mut sink = RotateFiles::new(Files::default(), 3);
+
+        for i in 0..4 {
+            sink.record(i);
+        }
+        sink.record_frames(&[4, 5, 6, 7]);
+        sink.end_of_transmission();
+
+        assert_eq!(
+            sink.into_inner().files,
+            vec![vec![0, 1, 2], vec![3, 4, 5], vec![6, 7]]
+        );
+    }
+
+    #[test]
+    fn short_transmissions_are_untouched() {
+        let mut sink = RotateFiles::new(Files::default(), 3);
+
+        sink.record_frames(&[1, 2]);
+        sink.end_of_transmission();
+        sink.record_frames(&[3, 4, 5]);
+        sink.end_of_transmission();
+
+        assert_eq!(sink.rollovers(), 0);
+        assert_eq!(sink.into_inner().files, vec![vec![1, 2], vec![3, 4, 5]]);
+    }
+
+    #[test]
+    fn continuations_start_where_the_last_file_ended() {
+        let mut sink = RotateFiles::new(Files::default(), 3);
+
+        sink.transmission_started(100);
+        sink.record_frames(&[0, 1, 2, 3]);
+        sink.record(4);
+        sink.record_frames(&[5, 6]);
+        sink.end_of_transmission();
+        sink.transmission_started(200);
+        sink.record_frames(&[7, 8]);
+        sink.end_of_transmission();
+
+        let files = sink.into_inner();
+        assert_eq!(files.starts, vec![100, 103, 106, 200]);
+        assert_eq!(
+            files.files,
+            vec![vec![0, 1, 2], vec![3, 4, 5], vec![6], vec![7, 8]]
+        );
+    }
+}
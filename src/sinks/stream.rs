@@ -0,0 +1,278 @@
+use crate::Sink;
+use dasp::{sample::ToSample, Frame, Sample};
+
+/// Something that happened while streaming gated segments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SegmentEvent {
+    /// The gate opened and a new segment has started.
+    Start {
+        /// Which segment this was, starting from `0`.
+        segment: usize,
+        /// Where the segment started, according to the gate's
+        /// [position][crate::NoiseGate::position], if the gate said.
+        position: Option<u64>,
+    },
+    /// A chunk of audio from the current segment, as interleaved 16-bit
+    /// samples.
+    Audio {
+        /// Which segment this audio belongs to.
+        segment: usize,
+        /// The samples.
+        samples: Vec<i16>,
+    },
+    /// The gate closed and the segment is complete.
+    End {
+        /// Which segment just ended.
+        segment: usize,
+        /// The segment's total length in frames.
+        frames: usize,
+    },
+}
+
+impl SegmentEvent {
+    /// The segment this event belongs to.
+    pub fn segment(&self) -> usize {
+        match *self {
+            SegmentEvent::Start { segment, .. }
+            | SegmentEvent::Audio { segment, .. }
+            | SegmentEvent::End { segment, .. } => segment,
+        }
+    }
+}
+
+/// A [`Sink`] adapter which turns each transmission into a stream of
+/// [`SegmentEvent`]s, ready to be forwarded to a streaming speech-to-text
+/// service.
+///
+/// The gate does exactly the "endpointing" these services want, so a
+/// segment maps directly onto a single recognition request: open a stream on
+/// [`SegmentEvent::Start`], send each [`SegmentEvent::Audio`] chunk as
+/// `LINEAR16` audio, and half-close the stream on [`SegmentEvent::End`].
+///
+/// Events are passed to a callback so this works with any transport. Enable
+/// the `grpc` feature for a ready-made [`tonic`][tonic] client, where
+/// `grpc::ChunkSender` forwards events to a bidirectional stream without
+/// blocking the audio thread.
+///
+/// Frames are always passed through to the inner sink untouched.
+///
+/// ```rust
+/// use noise_gate::{
+///     sinks::{SegmentEvent, SegmentStream},
+///     NoiseGate,
+/// };
+///
+/// # struct Discard;
+/// # impl noise_gate::Sink<[i16; 1]> for Discard {
+/// #     fn record(&mut self, _: [i16; 1]) {}
+/// #     fn end_of_transmission(&mut self) {}
+/// # }
+/// let mut events = Vec::new();
+/// let mut sink = SegmentStream::new(Discard, 2, |e| events.push(e));
+/// let mut gate = NoiseGate::new(100_i16, 0);
+///
+/// gate.process_frames(&[[500], [600], [700], [0], [0]], &mut sink);
+/// drop(sink);
+///
+/// assert_eq!(
+///     events,
+///     vec![
+///         SegmentEvent::Start { segment: 0, position: Some(0) },
+///         SegmentEvent::Audio { segment: 0, samples: vec![500, 600] },
+///         SegmentEvent::Audio { segment: 0, samples: vec![700, 0] },
+///         SegmentEvent::End { segment: 0, frames: 4 },
+///     ]
+/// );
+/// ```
+///
+/// [tonic]: https://crates.io/crates/tonic
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentStream<K, S> {
+    inner: K,
+    send: S,
+    chunk_size: usize,
+    buffer: Vec<i16>,
+    buffered_frames: usize,
+    next_segment: usize,
+    /// How many frames are in the current segment, if there is one.
+    current: Option<usize>,
+    /// Where the gate said the next segment starts.
+    start_position: Option<u64>,
+}
+
+impl<K, S> SegmentStream<K, S>
+where
+    S: FnMut(SegmentEvent),
+{
+    /// Wrap a [`Sink`], passing audio to `send` in chunks of `chunk_size`
+    /// frames.
+    pub fn new(inner: K, chunk_size: usize, send: S) -> Self {
+        SegmentStream {
+            inner,
+            send,
+            chunk_size: chunk_size.max(1),
+            buffer: Vec::new(),
+            buffered_frames: 0,
+            next_segment: 0,
+            current: None,
+            start_position: None,
+        }
+    }
+
+    /// Is a segment currently being streamed?
+    pub fn is_streaming(&self) -> bool { self.current.is_some() }
+
+    /// Get a reference to the inner [`Sink`].
+    pub fn inner(&self) -> &K { &self.inner }
+
+    /// Get a mutable reference to the inner [`Sink`].
+    pub fn inner_mut(&mut self) -> &mut K { &mut self.inner }
+
+    /// Consume the adapter, returning the inner [`Sink`].
+    pub fn into_inner(self) -> K { self.inner }
+
+    fn flush(&mut self) {
+        if !self.buffer.is_empty() {
+            let samples = std::mem::take(&mut self.buffer);
+            (self.send)(SegmentEvent::Audio {
+                segment: self.next_segment,
+                samples,
+            });
+            self.buffered_frames = 0;
+        }
+    }
+
+    fn segment_ended(&mut self) {
+        self.flush();
+
+        if let Some(frames) = self.current.take() {
+            (self.send)(SegmentEvent::End {
+                segment: self.next_segment,
+                frames,
+            });
+            self.next_segment += 1;
+        }
+    }
+}
+
+impl<F, K, S> Sink<F> for SegmentStream<K, S>
+where
+    F: Frame,
+    F::Sample: ToSample<i16>,
+    K: Sink<F>,
+    S: FnMut(SegmentEvent),
+{
+    fn record(&mut self, frame: F) {
+        match self.current.as_mut() {
+            Some(frames) => *frames += 1,
+            None => {
+                self.current = Some(1);
+                (self.send)(SegmentEvent::Start {
+                    segment: self.next_segment,
+                    position: self.start_position.take(),
+                });
+            },
+        }
+
+        self.buffer
+            .extend(frame.channels().map(|sample| sample.to_sample::<i16>()));
+        self.buffered_frames += 1;
+
+        if self.buffered_frames >= self.chunk_size {
+            self.flush();
+        }
+
+        self.inner.record(frame);
+    }
+
+    fn end_of_transmission(&mut self) {
+        self.segment_ended();
+        self.inner.end_of_transmission();
+    }
+
+    fn discard_transmission(&mut self) {
+        // the audio has already been streamed, so all we can do is end it
+        self.segment_ended();
+        self.inner.discard_transmission();
+    }
+
+    fn transmission_started(&mut self, position: u64) {
+        self.start_position = Some(position);
+        self.inner.transmission_started(position);
+    }
+
+    fn latency_samples(&self) -> usize { self.inner.latency_samples() }
+
+    fn errors(&self) -> usize { self.inner.errors() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct Counter {
+        frames: usize,
+        transmissions: usize,
+    }
+
+    impl Sink<[f32; 2]> for Counter {
+        fn record(&mut self, _: [f32; 2]) { self.frames += 1; }
+
+        fn end_of_transmission(&mut self) { self.transmissions += 1; }
+    }
+
+    #[test]
+    fn segments_are_numbered_and_passed_through() {
+        let mut events = Vec::new();
+        let mut sink =
+            SegmentStream::new(Counter::default(), 4, |e| events.push(e));
+
+        sink.record([0.5, -0.5]);
+        assert!(sink.is_streaming());
+        sink.end_of_transmission();
+        assert!(!sink.is_streaming());
+        // a spurious end-of-transmission doesn't start a new segment
+        sink.end_of_transmission();
+        sink.transmission_started(42);
+        sink.record([0.25, 0.0]);
+        sink.end_of_transmission();
+
+        let counter = sink.into_inner();
+        assert_eq!(counter.frames, 2);
+        assert_eq!(counter.transmissions, 3);
+        assert_eq!(
+            events,
+            vec![
+                SegmentEvent::Start {
+                    segment: 0,
+                    position: None
+                },
+                SegmentEvent::Audio {
+                    segment: 0,
+                    samples: vec![16384, -16384]
+                },
+                SegmentEvent::End {
+                    segment: 0,
+                    frames: 1
+                },
+                SegmentEvent::Start {
+                    segment: 1,
+                    position: Some(42)
+                },
+                SegmentEvent::Audio {
+                    segment: 1,
+                    samples: vec![8192, 0]
+                },
+                SegmentEvent::End {
+                    segment: 1,
+                    frames: 1
+                },
+            ]
+        );
+        assert!(events
+            .iter()
+            .map(SegmentEvent::segment)
+            .eq([0, 0, 0, 1, 1, 1]));
+    }
+}
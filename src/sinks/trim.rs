@@ -0,0 +1,251 @@
+use crate::{low_level::Level, Sink};
+use dasp::Frame;
+
+/// A [`Sink`] adapter which trims low-level audio from the very start and
+/// end of each segment.
+///
+/// The gate stays open for its release time after the audio drops below
+/// the threshold, so every segment normally ends with a chunk of
+/// near-silence. Anything below the trim threshold at either end of a
+/// segment gets removed, while quiet frames in the middle are left alone.
+///
+/// Quiet frames are held back until we know whether more audio is coming,
+/// so the inner sink may lag behind the gate by up to the release time. Use
+/// [`TrimSilence::with_capacity()`] to allocate room for them up front.
+///
+/// The inner sink is only told a transmission started once its first frame
+/// is kept, with the position moved past whatever was trimmed from the
+/// start. Segments which are trimmed away entirely never reach it at all.
+///
+/// ```rust
+/// use noise_gate::{sinks::TrimSilence, NoiseGate};
+///
+/// # #[derive(Default)]
+/// # struct Collect(Vec<[i16; 1]>);
+/// # impl noise_gate::Sink<[i16; 1]> for Collect {
+/// #     fn record(&mut self, frame: [i16; 1]) { self.0.push(frame); }
+/// #     fn end_of_transmission(&mut self) {}
+/// # }
+/// let mut gate = NoiseGate::new(1000_i16, 4);
+/// let mut sink = TrimSilence::new(Collect::default(), 50);
+///
+/// gate.process_frames(&[[2000], [100], [10], [1500], [20], [10], [0], [0]], &mut sink);
+///
+/// assert_eq!(sink.inner().0, vec![[2000], [100], [10], [1500]]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrimSilence<F, K, S> {
+    inner: K,
+    threshold: S,
+    /// Have we seen anything above the trim threshold in this segment yet?
+    started: bool,
+    /// Where the gate said this segment started, if we haven't passed that
+    /// on yet.
+    start: Option<u64>,
+    /// How many frames have been trimmed from the start of this segment.
+    trimmed: u64,
+    /// Quiet frames which will only be kept if something louder follows.
+    pending: Vec<F>,
+}
+
+impl<F, K, S> TrimSilence<F, K, S> {
+    /// Wrap a [`Sink`], trimming anything quieter than `threshold` from both
+    /// ends of each segment.
+    pub fn new(inner: K, threshold: S) -> Self {
+        TrimSilence {
+            inner,
+            threshold,
+            started: false,
+            start: None,
+            trimmed: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Wrap a [`Sink`], allocating enough space to hold back `capacity`
+    /// quiet frames (normally the gate's release time) without allocating.
+    pub fn with_capacity(inner: K, threshold: S, capacity: usize) -> Self {
+        TrimSilence {
+            pending: Vec::with_capacity(capacity),
+            ..TrimSilence::new(inner, threshold)
+        }
+    }
+
+    /// The level below which audio gets trimmed.
+    pub fn threshold(&self) -> &S { &self.threshold }
+
+    /// Get a reference to the inner [`Sink`].
+    pub fn inner(&self) -> &K { &self.inner }
+
+    /// Get a mutable reference to the inner [`Sink`].
+    pub fn inner_mut(&mut self) -> &mut K { &mut self.inner }
+
+    /// Consume the adapter, returning the inner [`Sink`].
+    ///
+    /// Any quiet frames which are being held back will be lost.
+    pub fn into_inner(self) -> K { self.inner }
+
+    /// Get ready for the next segment, returning whether the inner sink saw
+    /// anything from this one.
+    fn reset(&mut self) -> bool {
+        let started = self.started;
+
+        self.pending.clear();
+        self.started = false;
+        self.start = None;
+        self.trimmed = 0;
+
+        started
+    }
+}
+
+impl<F, K> Sink<F> for TrimSilence<F, K, F::Sample>
+where
+    F: Frame,
+    K: Sink<F>,
+{
+    fn record(&mut self, frame: F) {
+        let quiet = Level::of(frame, self.threshold) == Level::Quiet;
+
+        if !self.started {
+            if quiet {
+                // leading silence
+                self.trimmed += 1;
+                return;
+            }
+            self.started = true;
+
+            if let Some(position) = self.start.take() {
+                self.inner.transmission_started(position + self.trimmed);
+            }
+        }
+
+        if quiet {
+            self.pending.push(frame);
+        } else {
+            if !self.pending.is_empty() {
+                self.inner.record_frames(&self.pending);
+                self.pending.clear();
+            }
+            self.inner.record(frame);
+        }
+    }
+
+    fn end_of_transmission(&mut self) {
+        // whatever is still pending is trailing silence
+        if self.reset() {
+            self.inner.end_of_transmission();
+        }
+    }
+
+    fn discard_transmission(&mut self) {
+        if self.reset() {
+            self.inner.discard_transmission();
+        }
+    }
+
+    fn transmission_started(&mut self, position: u64) {
+        self.start = Some(position);
+        self.trimmed = 0;
+    }
+
+    fn latency_samples(&self) -> usize { self.inner.latency_samples() }
+
+    fn errors(&self) -> usize { self.inner.errors() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NoiseGate;
+
+    #[derive(Debug, Default)]
+    struct Recorder {
+        clips: Vec<Vec<[f32; 2]>>,
+        current: Vec<[f32; 2]>,
+    }
+
+    impl Sink<[f32; 2]> for Recorder {
+        fn record(&mut self, frame: [f32; 2]) { self.current.push(frame); }
+
+        fn end_of_transmission(&mut self) {
+            self.clips.push(std::mem::take(&mut self.current));
+        }
+    }
+
+    #[test]
+    fn both_ends_are_trimmed() {
+        let mut sink = TrimSilence::new(Recorder::default(), 0.1);
+
+        for &frame in &[
+            [0.01, 0.0],
+            [0.5, 0.0],
+            [0.0, 0.05],
+            [0.0, -0.5],
+            [0.05, 0.05],
+            [0.0, 0.0],
+        ] {
+            sink.record(frame);
+        }
+        sink.end_of_transmission();
+
+        assert_eq!(
+            sink.into_inner().clips,
+            vec![vec![[0.5, 0.0], [0.0, 0.05], [0.0, -0.5]]]
+        );
+    }
+
+    #[test]
+    fn each_segment_is_trimmed_separately() {
+        let mut sink = TrimSilence::new(Recorder::default(), 0.1);
+
+        sink.record([0.5, 0.5]);
+        sink.record([0.0, 0.0]);
+        // held back until we know if it's trailing silence
+        assert_eq!(sink.inner().current.len(), 1);
+        sink.end_of_transmission();
+        sink.record([0.0, 0.0]);
+        sink.record([0.2, 0.2]);
+        sink.end_of_transmission();
+
+        assert_eq!(
+            sink.into_inner().clips,
+            vec![vec![[0.5, 0.5]], vec![[0.2, 0.2]]]
+        );
+    }
+
+    /// Keeps track of where each segment started.
+    #[derive(Debug, Default)]
+    struct Starts {
+        starts: Vec<u64>,
+        frames: usize,
+        ends: usize,
+    }
+
+    impl Sink<[i16; 1]> for Starts {
+        fn record(&mut self, _frame: [i16; 1]) { self.frames += 1; }
+
+        fn end_of_transmission(&mut self) { self.ends += 1; }
+
+        fn transmission_started(&mut self, position: u64) {
+            self.starts.push(position);
+        }
+    }
+
+    #[test]
+    fn starts_skip_the_trimmed_lead_in() {
+        let mut gate = NoiseGate::new(100, 2);
+        let mut sink = TrimSilence::new(Starts::default(), 1000);
+
+        let frames = [[500], [0], [0], [0], [0], [200], [2000], [0]];
+
+        // the first segment is trimmed away entirely
+        gate.process_frames(&frames, &mut sink);
+        gate.process_frames(&[[0]; 4], &mut sink);
+
+        let starts = sink.into_inner();
+        assert_eq!(starts.starts, vec![6]);
+        assert_eq!(starts.frames, 1);
+        assert_eq!(starts.ends, 1);
+    }
+}
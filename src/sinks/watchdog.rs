@@ -0,0 +1,239 @@
+use crate::Sink;
+
+/// Details about a transmission which was cut short by a [`Watchdog`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Timeout {
+    /// Which transmission timed out, starting from `0`.
+    pub transmission: usize,
+    /// How many frames were recorded before it was cut off.
+    pub frames: usize,
+}
+
+/// A [`Sink`] adapter which cuts off any transmission that stays open for
+/// longer than `max_frames`, protecting an unattended recorder from a
+/// stuck-open squelch filling the disk.
+///
+/// When the limit is reached the transmission is ended early, the
+/// `on_timeout` callback is told about it, and everything is dropped until
+/// the gate closes by itself. Unlike [`RotateFiles`][super::RotateFiles],
+/// which carries on in a new file, nothing more gets recorded until the
+/// squelch goes quiet again.
+///
+/// ```rust
+/// use noise_gate::{
+///     sinks::{Timeout, Watchdog},
+///     NoiseGate,
+/// };
+///
+/// let mut timeouts = Vec::new();
+/// let mut recorded = Vec::new();
+/// let mut sink = Watchdog::new(&mut recorded, 3, |t| timeouts.push(t));
+///
+/// let mut gate = NoiseGate::new(10, 0);
+/// gate.process_frames(
+///     &[[50_i16], [50], [50], [50], [50], [0], [0], [20], [0], [0]],
+///     &mut sink,
+/// );
+///
+/// assert_eq!(sink.timeouts(), 1);
+/// drop(sink);
+/// assert_eq!(recorded, vec![[50], [50], [50], [20], [0]]);
+/// assert_eq!(
+///     timeouts,
+///     vec![Timeout {
+///         transmission: 0,
+///         frames: 3
+///     }]
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watchdog<K, C> {
+    inner: K,
+    max_frames: usize,
+    on_timeout: C,
+    frames: usize,
+    transmission: usize,
+    tripped: bool,
+    timeouts: usize,
+}
+
+impl<K, C> Watchdog<K, C>
+where
+    C: FnMut(Timeout),
+{
+    /// Wrap a [`Sink`], cutting off transmissions after `max_frames` frames
+    /// and passing each [`Timeout`] to `on_timeout`.
+    pub fn new(inner: K, max_frames: usize, on_timeout: C) -> Self {
+        Watchdog {
+            inner,
+            max_frames: max_frames.max(1),
+            on_timeout,
+            frames: 0,
+            transmission: 0,
+            tripped: false,
+            timeouts: 0,
+        }
+    }
+}
+
+impl<K, C> Watchdog<K, C> {
+    /// The longest a transmission can be, in frames.
+    pub fn max_frames(&self) -> usize { self.max_frames }
+
+    /// How many transmissions have been cut off.
+    pub fn timeouts(&self) -> usize { self.timeouts }
+
+    /// Is the watchdog waiting for a timed out transmission to finish?
+    pub fn is_tripped(&self) -> bool { self.tripped }
+
+    /// Get a reference to the inner [`Sink`].
+    pub fn inner(&self) -> &K { &self.inner }
+
+    /// Get a mutable reference to the inner [`Sink`].
+    pub fn inner_mut(&mut self) -> &mut K { &mut self.inner }
+
+    /// Consume the adapter, returning the inner [`Sink`].
+    pub fn into_inner(self) -> K { self.inner }
+}
+
+impl<K, C> Watchdog<K, C>
+where
+    C: FnMut(Timeout),
+{
+    fn trip<F>(&mut self)
+    where
+        K: Sink<F>,
+    {
+        self.inner.end_of_transmission();
+        self.tripped = true;
+        self.timeouts += 1;
+        (self.on_timeout)(Timeout {
+            transmission: self.transmission,
+            frames: self.frames,
+        });
+    }
+}
+
+impl<F, K, C> Sink<F> for Watchdog<K, C>
+where
+    F: Copy,
+    K: Sink<F>,
+    C: FnMut(Timeout),
+{
+    fn record(&mut self, frame: F) { self.record_frames(&[frame]); }
+
+    fn record_frames(&mut self, frames: &[F]) {
+        if self.tripped {
+            return;
+        }
+
+        let space = self.max_frames - self.frames;
+        if frames.len() <= space {
+            self.inner.record_frames(frames);
+            self.frames += frames.len();
+        } else {
+            self.inner.record_frames(&frames[..space]);
+            self.frames += space;
+            self.trip();
+        }
+    }
+
+    fn end_of_transmission(&mut self) {
+        if !self.tripped && self.frames > 0 {
+            self.inner.end_of_transmission();
+        }
+        if self.tripped || self.frames > 0 {
+            self.transmission += 1;
+        }
+
+        self.frames = 0;
+        self.tripped = false;
+    }
+
+    fn discard_transmission(&mut self) {
+        if !self.tripped && self.frames > 0 {
+            self.inner.discard_transmission();
+        }
+        if self.tripped || self.frames > 0 {
+            self.transmission += 1;
+        }
+
+        self.frames = 0;
+        self.tripped = false;
+    }
+
+    fn transmission_started(&mut self, position: u64) {
+        self.inner.transmission_started(position);
+    }
+
+    fn latency_samples(&self) -> usize { self.inner.latency_samples() }
+
+    fn errors(&self) -> usize { self.inner.errors() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct Files {
+        files: Vec<Vec<u32>>,
+        current: Vec<u32>,
+    }
+
+    impl Sink<u32> for Files {
+        fn record(&mut self, frame: u32) { self.current.push(frame); }
+
+        fn end_of_transmission(&mut self) {
+            self.files.push(std::mem::take(&mut self.current));
+        }
+    }
+
+    #[test]
+    fn stuck_transmissions_are_cut_off_until_the_gate_closes() {
+        let mut timeouts = Vec::new();
+        let mut sink = Watchdog::new(Files::default(), 4, |t| timeouts.push(t));
+
+        sink.record_frames(&[1, 2]);
+        sink.end_of_transmission();
+        for i in 10..20 {
+            sink.record(i);
+        }
+        assert!(sink.is_tripped());
+        sink.end_of_transmission();
+        sink.record_frames(&[30, 31, 32, 33, 34, 35]);
+        sink.end_of_transmission();
+
+        assert!(!sink.is_tripped());
+        assert_eq!(sink.timeouts(), 2);
+        assert_eq!(
+            sink.into_inner().files,
+            vec![vec![1, 2], vec![10, 11, 12, 13], vec![30, 31, 32, 33]]
+        );
+        assert_eq!(
+            timeouts,
+            vec![
+                Timeout {
+                    transmission: 1,
+                    frames: 4
+                },
+                Timeout {
+                    transmission: 2,
+                    frames: 4
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn short_transmissions_pass_through() {
+        let mut sink = Watchdog::new(Files::default(), 4, |_| unreachable!());
+
+        sink.record_frames(&[1, 2, 3, 4]);
+        sink.end_of_transmission();
+        sink.end_of_transmission();
+
+        assert_eq!(sink.timeouts(), 0);
+        assert_eq!(sink.into_inner().files, vec![vec![1, 2, 3, 4]]);
+    }
+}
@@ -0,0 +1,430 @@
+//! Gates where the open/close decision is made by a pluggable [`Strategy`],
+//! for experimenting with custom hysteresis curves or probabilistic
+//! decisions.
+//!
+//! The [`StrategyGate`] takes care of measuring each frame and passing
+//! frames through to a [`Sink`], so a strategy only needs to pick the next
+//! [`State`]. It asks the strategy about the audio one frame at a time, so
+//! it's slower than a [`NoiseGate`] and best suited to research. Otherwise
+//! it behaves like a [`NoiseGate`], keeping the same position clock for
+//! [`Sink::transmission_started()`] and honouring [`EndOfInput`] when
+//! [finished][StrategyGate::finish].
+//!
+//! ```rust
+//! use noise_gate::{
+//!     low_level::State,
+//!     strategy::{Observation, StrategyGate},
+//! };
+//!
+//! // open above 0.5, but don't start closing until we drop below 0.1
+//! let strategy = |observation: &Observation| match observation.state {
+//!     _ if observation.level >= 0.5 => State::Open,
+//!     State::Open if observation.level >= 0.1 => State::Open,
+//!     _ => State::Closed,
+//! };
+//! let mut gate = StrategyGate::new(strategy, 0);
+//! let mut recorded = Vec::new();
+//!
+//! gate.process_frames(&[[0.2_f32], [0.6], [0.2], [0.05], [0.2]], &mut recorded);
+//!
+//! assert_eq!(recorded, vec![[0.6], [0.2]]);
+//! ```
+//!
+//! [`NoiseGate`]: crate::NoiseGate
+
+use crate::{
+    low_level::{self, Level, Params, State},
+    EndOfInput, Sink,
+};
+use dasp::{Frame, Sample};
+
+/// What a [`Strategy`] knows about the current frame.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Observation {
+    /// The loudest channel in this frame, where `1.0` is full scale.
+    ///
+    /// Non-finite samples are treated as infinitely loud.
+    pub level: f64,
+    /// A peak envelope of the frames so far, which jumps up to each louder
+    /// frame and decays over the gate's envelope release time.
+    pub envelope: f64,
+    /// The state the gate was in before this frame.
+    pub state: State,
+}
+
+/// Decides what state the gate should be in after each frame.
+///
+/// The gate passes a frame through whenever the new state
+/// [`is_open()`][State::is_open], and the transmission ends when it goes
+/// from open to [`State::Closed`].
+pub trait Strategy {
+    /// Pick the gate's next state.
+    fn next_state(&mut self, observation: &Observation) -> State;
+}
+
+impl<F> Strategy for F
+where
+    F: FnMut(&Observation) -> State,
+{
+    fn next_state(&mut self, observation: &Observation) -> State {
+        self(observation)
+    }
+}
+
+/// The [`NoiseGate`][crate::NoiseGate]'s own behaviour, opening whenever a
+/// frame reaches `threshold` and closing after `release_time` quiet frames.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Threshold {
+    /// The level (where `1.0` is full scale) which opens the gate.
+    pub threshold: f64,
+    /// How many quiet frames to wait before closing.
+    pub release_time: usize,
+}
+
+impl Strategy for Threshold {
+    fn next_state(&mut self, observation: &Observation) -> State {
+        let level = if observation.level >= self.threshold {
+            Level::Loud
+        } else {
+            Level::Quiet
+        };
+        let params = Params {
+            release_time: self.release_time,
+        };
+
+        low_level::step(observation.state, level, &params)
+    }
+}
+
+/// Separate thresholds for opening and closing, so a signal hovering around
+/// one threshold doesn't make the gate chatter.
+///
+/// The gate opens when the envelope reaches `open`, and starts counting down
+/// the `release_time` once it drops below `close`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Hysteresis {
+    /// The envelope level which opens the gate.
+    pub open: f64,
+    /// The envelope level the gate needs to drop below before it starts
+    /// closing.
+    pub close: f64,
+    /// How many quiet frames to wait before closing.
+    pub release_time: usize,
+}
+
+impl Strategy for Hysteresis {
+    fn next_state(&mut self, observation: &Observation) -> State {
+        let threshold = if observation.state.is_open() {
+            self.close
+        } else {
+            self.open
+        };
+        let level = if observation.envelope >= threshold {
+            Level::Loud
+        } else {
+            Level::Quiet
+        };
+        let params = Params {
+            release_time: self.release_time,
+        };
+
+        low_level::step(observation.state, level, &params)
+    }
+}
+
+/// A gate which asks a [`Strategy`] when to open and close.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrategyGate<St> {
+    /// What [`StrategyGate::finish()`] does with a transmission which is
+    /// still going when the input runs out.
+    pub end_of_input: EndOfInput,
+    strategy: St,
+    state: State,
+    envelope: f64,
+    decay: f64,
+    position: u64,
+}
+
+impl<St: Strategy> StrategyGate<St> {
+    /// Create a new [`StrategyGate`], where the [`Observation::envelope`]
+    /// decays with a time constant of `envelope_release` frames.
+    pub fn new(strategy: St, envelope_release: usize) -> Self {
+        let decay = if envelope_release == 0 {
+            0.0
+        } else {
+            (-1.0 / envelope_release as f64).exp()
+        };
+
+        StrategyGate {
+            end_of_input: EndOfInput::Flush,
+            strategy,
+            state: State::Closed,
+            envelope: 0.0,
+            decay,
+            position: 0,
+        }
+    }
+
+    /// Set [`StrategyGate::end_of_input`].
+    pub fn with_end_of_input(self, end_of_input: EndOfInput) -> Self {
+        StrategyGate {
+            end_of_input,
+            ..self
+        }
+    }
+
+    /// The gate's current [`State`].
+    pub fn state(&self) -> State { self.state }
+
+    /// The current envelope level.
+    pub fn envelope(&self) -> f64 { self.envelope }
+
+    /// The index of the next frame to be processed (see
+    /// [`NoiseGate::position()`][crate::NoiseGate::position]).
+    pub fn position(&self) -> u64 { self.position }
+
+    /// Get a reference to the [`Strategy`].
+    pub fn strategy(&self) -> &St { &self.strategy }
+
+    /// Get a mutable reference to the [`Strategy`].
+    pub fn strategy_mut(&mut self) -> &mut St { &mut self.strategy }
+
+    /// Close the gate straight away, telling the `sink` the transmission has
+    /// ended if the gate was open.
+    pub fn force_close<F, K>(&mut self, sink: &mut K)
+    where
+        K: Sink<F>,
+    {
+        if self.state.is_open() {
+            self.state = State::Closed;
+            sink.end_of_transmission();
+        }
+    }
+
+    /// There's no more input, so deal with any transmission which is still
+    /// in progress according to [`StrategyGate::end_of_input`] (see
+    /// [`NoiseGate::finish()`][crate::NoiseGate::finish]).
+    pub fn finish<F, K>(&mut self, sink: &mut K)
+    where
+        K: Sink<F>,
+    {
+        match self.end_of_input {
+            EndOfInput::Flush => self.force_close(sink),
+            EndOfInput::Discard => {
+                if self.state.is_open() {
+                    self.state = State::Closed;
+                    sink.discard_transmission();
+                }
+            },
+        }
+    }
+
+    /// Let the gate know `count` frames of input were lost (see
+    /// [`NoiseGate::frames_dropped()`][crate::NoiseGate::frames_dropped]).
+    pub fn frames_dropped<F, K>(&mut self, count: u64, sink: &mut K)
+    where
+        K: Sink<F>,
+    {
+        if count > 0 {
+            self.position += count;
+            self.force_close(sink);
+        }
+    }
+
+    /// Process a batch of frames, passing whatever the strategy lets through
+    /// to a `sink`.
+    ///
+    /// Like [`NoiseGate::process_frames()`][crate::NoiseGate::process_frames],
+    /// the gate remembers its state between calls.
+    pub fn process_frames<K, F>(&mut self, frames: &[F], sink: &mut K)
+    where
+        F: Frame,
+        K: Sink<F>,
+    {
+        // where the frames we're currently passing through started
+        let mut recording_from =
+            if self.state.is_open() { Some(0) } else { None };
+
+        for (i, &frame) in frames.iter().enumerate() {
+            let level = level(frame);
+            self.envelope = if level >= self.envelope {
+                level
+            } else {
+                level + (self.envelope - level) * self.decay
+            };
+
+            let observation = Observation {
+                level,
+                envelope: self.envelope,
+                state: self.state,
+            };
+            let next = self.strategy.next_state(&observation);
+
+            match (self.state.is_open(), next.is_open()) {
+                (false, true) => {
+                    sink.transmission_started(self.position);
+                    recording_from = Some(i);
+                },
+                (true, false) => {
+                    if let Some(start) = recording_from.take() {
+                        record(&frames[start..i], sink);
+                    }
+                    sink.end_of_transmission();
+                },
+                _ => {},
+            }
+
+            self.state = next;
+            self.position += 1;
+        }
+
+        if let Some(start) = recording_from {
+            record(&frames[start..], sink);
+        }
+    }
+}
+
+fn record<F: Copy, K: Sink<F>>(frames: &[F], sink: &mut K) {
+    if !frames.is_empty() {
+        sink.record_frames(frames);
+    }
+}
+
+fn level<F: Frame>(frame: F) -> f64 {
+    frame
+        .channels()
+        .map(|sample| {
+            let sample = sample.to_float_sample().to_sample::<f64>();
+            if sample.is_finite() {
+                sample.abs()
+            } else {
+                f64::INFINITY
+            }
+        })
+        .fold(0.0, f64::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NoiseGate;
+
+    #[test]
+    fn threshold_strategy_matches_the_noise_gate() {
+        let frames: Vec<[i16; 2]> = (0..5000_i32)
+            .map(|i| {
+                let left = ((i * 7919) % 2000 - 1000) as i16;
+                let right = if i % 300 < 20 { 16_384 } else { 0 };
+                [left, right]
+            })
+            .collect();
+        let mut expected = Vec::new();
+        NoiseGate::new(16_384, 25).process_frames(&frames, &mut expected);
+        let mut got = Vec::new();
+
+        let strategy = Threshold {
+            threshold: 0.5,
+            release_time: 25,
+        };
+        StrategyGate::new(strategy, 0).process_frames(&frames, &mut got);
+
+        assert_eq!(got, expected);
+        assert!(!got.is_empty());
+    }
+
+    #[test]
+    fn hysteresis_stops_chatter() {
+        // hovers around 0.5 after the initial burst
+        let frames: Vec<[f32; 1]> = (0..100)
+            .map(|i| match i {
+                0..=9 => [0.9],
+                _ if i % 3 == 0 => [0.55],
+                _ => [0.45],
+            })
+            .collect();
+        let transmissions = |strategy| {
+            let mut ends = Ends(0);
+            StrategyGate::new(strategy, 0).process_frames(&frames, &mut ends);
+            ends.0
+        };
+
+        let single = Hysteresis {
+            open: 0.5,
+            close: 0.5,
+            release_time: 0,
+        };
+        let hysteresis = Hysteresis {
+            close: 0.3,
+            ..single
+        };
+
+        assert_eq!(transmissions(single), 30);
+        assert_eq!(transmissions(hysteresis), 0);
+    }
+
+    #[test]
+    fn transmissions_report_where_they_started() {
+        let frames = [[0.0_f32], [0.9], [0.0], [0.0], [0.9], [0.9], [0.0]];
+        let strategy = Threshold {
+            threshold: 0.5,
+            release_time: 0,
+        };
+        let mut gate = StrategyGate::new(strategy, 0);
+        let mut sink = Starts::default();
+
+        gate.process_frames(&frames[..5], &mut sink);
+        gate.frames_dropped(10, &mut sink);
+        gate.process_frames(&frames[5..], &mut sink);
+
+        assert_eq!(sink.starts, vec![1, 4, 15]);
+        assert_eq!(sink.ended, 2);
+        assert_eq!(gate.position(), 17);
+    }
+
+    #[test]
+    fn finishing_honours_end_of_input() {
+        let strategy = Threshold {
+            threshold: 0.5,
+            release_time: 10,
+        };
+        let mut gate = StrategyGate::new(strategy, 0)
+            .with_end_of_input(EndOfInput::Discard);
+        let mut sink = Starts::default();
+
+        gate.process_frames(&[[0.9_f32], [0.0]], &mut sink);
+        gate.finish(&mut sink);
+        gate.finish(&mut sink);
+
+        assert_eq!(sink.discarded, 1);
+        assert_eq!(sink.ended, 0);
+        assert!(!gate.state().is_open());
+    }
+
+    #[derive(Debug, Default)]
+    struct Starts {
+        starts: Vec<u64>,
+        frames: usize,
+        ended: usize,
+        discarded: usize,
+    }
+
+    impl Sink<[f32; 1]> for Starts {
+        fn record(&mut self, _: [f32; 1]) { self.frames += 1; }
+
+        fn end_of_transmission(&mut self) { self.ended += 1; }
+
+        fn discard_transmission(&mut self) { self.discarded += 1; }
+
+        fn transmission_started(&mut self, position: u64) {
+            self.starts.push(position);
+        }
+    }
+
+    struct Ends(usize);
+
+    impl<F> Sink<F> for Ends {
+        fn record(&mut self, _: F) {}
+
+        fn end_of_transmission(&mut self) { self.0 += 1; }
+    }
+}
@@ -0,0 +1,216 @@
+//! Putting clips back where they came from.
+
+use crate::Sink;
+use dasp::{Frame, Sample};
+
+/// Rebuild a recording from a set of clips and the frame each one started
+/// at, filling the gaps between them with silence.
+///
+/// This is the inverse of splitting a recording with a [`NoiseGate`]. The
+/// clips must be in order. Any frames which overlap an earlier clip or go
+/// past `total_frames` are dropped, and [`Sink::end_of_transmission()`] is
+/// called once everything has been written.
+///
+/// ```rust
+/// let clips = vec![(1, vec![[5_i16], [6]]), (4, vec![[7]])];
+/// let mut recording = Vec::new();
+///
+/// noise_gate::timeline::reassemble(clips, 6, &mut recording);
+///
+/// assert_eq!(recording, vec![[0], [5], [6], [0], [7], [0]]);
+/// ```
+///
+/// [`NoiseGate`]: crate::NoiseGate
+pub fn reassemble<F, K, I, C>(clips: I, total_frames: usize, sink: &mut K)
+where
+    F: Frame,
+    K: Sink<F>,
+    I: IntoIterator<Item = (usize, C)>,
+    C: IntoIterator<Item = F>,
+{
+    let mut position = 0;
+
+    for (start, frames) in clips {
+        let start = start.min(total_frames);
+        fill_silence(sink, position, start);
+        // an overlapping clip picks up from wherever the previous one
+        // stopped
+        let skip = position.saturating_sub(start);
+        position = position.max(start);
+
+        for frame in frames.into_iter().skip(skip) {
+            if position >= total_frames {
+                break;
+            }
+            sink.record(frame);
+            position += 1;
+        }
+    }
+
+    fill_silence(sink, position, total_frames);
+    sink.end_of_transmission();
+}
+
+fn fill_silence<F, K>(sink: &mut K, from: usize, to: usize)
+where
+    F: Frame,
+    K: Sink<F>,
+{
+    for _ in from..to {
+        sink.record(F::EQUILIBRIUM);
+    }
+}
+
+/// Join segments end to end, crossfading over `crossfade` frames at each
+/// join instead of cutting hard from one to the next.
+///
+/// This is handy for removing silence, giving a condensed recording which
+/// still sounds natural. Each crossfade is shortened if either segment is
+/// too short, and [`Sink::end_of_transmission()`] is called once the last
+/// segment has been written.
+///
+/// ```rust
+/// let segments = vec![vec![[1.0_f32]; 4], vec![[-1.0]; 4]];
+/// let mut joined = Vec::new();
+///
+/// noise_gate::timeline::concatenate(&segments, 3, &mut joined);
+///
+/// assert_eq!(
+///     joined,
+///     vec![[1.0], [0.5], [0.0], [-0.5], [-1.0]],
+/// );
+/// ```
+pub fn concatenate<F, K, I, C>(segments: I, crossfade: usize, sink: &mut K)
+where
+    F: Frame,
+    K: Sink<F>,
+    I: IntoIterator<Item = C>,
+    C: AsRef<[F]>,
+{
+    // the end of the previous segment, held back so it can be mixed with
+    // the start of the next one
+    let mut tail: Vec<F> = Vec::with_capacity(crossfade);
+
+    for segment in segments {
+        let segment = segment.as_ref();
+        let overlap = tail.len().min(segment.len());
+        let unmixed = tail.len() - overlap;
+
+        sink.record_frames(&tail[..unmixed]);
+        tail.drain(..unmixed);
+
+        for (i, (outgoing, &incoming)) in
+            tail.iter_mut().zip(segment).enumerate()
+        {
+            let ratio = (i + 1) as f64 / (overlap + 1) as f64;
+            let faded_out = outgoing.scale_amp((1.0 - ratio).to_sample());
+            let faded_in = incoming.scale_amp(ratio.to_sample());
+            *outgoing = faded_out.add_amp(faded_in.to_signed_frame());
+        }
+
+        // Hold back the last few frames (which may include the crossfade
+        // when the segment is short) so they can be mixed with the next one
+        let rest = &segment[overlap..];
+        let keep = crossfade.min(tail.len() + rest.len());
+        let from_tail = (tail.len() + rest.len() - keep).min(tail.len());
+        sink.record_frames(&tail[..from_tail]);
+        tail.drain(..from_tail);
+
+        let from_rest = rest.len() - (keep - tail.len());
+        sink.record_frames(&rest[..from_rest]);
+        tail.extend_from_slice(&rest[from_rest..]);
+    }
+
+    sink.record_frames(&tail);
+    sink.end_of_transmission();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct Recording {
+        frames: Vec<[i16; 2]>,
+        finished: bool,
+    }
+
+    impl Sink<[i16; 2]> for Recording {
+        fn record(&mut self, frame: [i16; 2]) { self.frames.push(frame); }
+
+        fn end_of_transmission(&mut self) { self.finished = true; }
+    }
+
+    #[test]
+    fn gaps_are_filled_with_silence() {
+        let clips = vec![(2, vec![[1, 1], [2, 2]]), (5, vec![[3, 3]])];
+        let mut sink = Recording::default();
+
+        reassemble(clips, 7, &mut sink);
+
+        assert_eq!(
+            sink.frames,
+            vec![[0, 0], [0, 0], [1, 1], [2, 2], [0, 0], [3, 3], [0, 0]]
+        );
+        assert!(sink.finished);
+    }
+
+    #[test]
+    fn overlapping_and_overflowing_frames_are_dropped() {
+        let clips = vec![
+            (0, vec![[1, 1], [2, 2]]),
+            (1, vec![[8, 8], [3, 3]]),
+            (3, vec![[4, 4], [5, 5]]),
+        ];
+        let mut sink = Recording::default();
+
+        reassemble(clips, 4, &mut sink);
+
+        assert_eq!(sink.frames, vec![[1, 1], [2, 2], [3, 3], [4, 4]]);
+    }
+
+    #[test]
+    fn short_segments_shorten_the_crossfade() {
+        let segments = vec![
+            vec![[100, 100]; 4],
+            vec![[-100, 100]],
+            vec![[40, 40], [40, 40]],
+        ];
+        let mut sink = Recording::default();
+
+        concatenate(&segments, 2, &mut sink);
+
+        assert_eq!(
+            sink.frames,
+            vec![[100, 100], [100, 100], [100, 100], [20, 70], [40, 40]]
+        );
+        assert!(sink.finished);
+    }
+
+    #[test]
+    fn no_crossfade_is_a_hard_cut() {
+        let segments = [&[[1, 1], [2, 2]][..], &[], &[[3, 3]]];
+        let mut sink = Recording::default();
+
+        concatenate(segments.iter(), 0, &mut sink);
+
+        assert_eq!(sink.frames, vec![[1, 1], [2, 2], [3, 3]]);
+    }
+
+    #[test]
+    fn reassembling_gated_clips_restores_the_timeline() {
+        let frames: Vec<[i16; 2]> = (0..2000)
+            .map(|i| if i % 300 < 50 { [500, -500] } else { [0, 0] })
+            .collect();
+        let mut gate = crate::NoiseGate::new(100, 10);
+        let clips: Vec<_> = gate
+            .segments(&frames)
+            .map(|(range, clip)| (range.start, clip.iter().copied()))
+            .collect();
+        let mut sink = Recording::default();
+
+        reassemble(clips, frames.len(), &mut sink);
+
+        assert_eq!(sink.frames, frames);
+    }
+}
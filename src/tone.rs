@@ -0,0 +1,197 @@
+//! Detecting a single frequency, for keying the gate off a particular tone
+//! (e.g. a CTCSS sub-audible tone, a DTMF digit, or a roger beep) instead of
+//! the overall level.
+
+use dasp::{sample::Duplex, Frame, Sample};
+use std::f64::consts::PI;
+
+/// A [Goertzel filter][wiki], which measures how much of one frequency is
+/// present in each block of audio far more cheaply than a full FFT.
+///
+/// Multi-channel frames are averaged down to mono. Frames can be added in
+/// chunks of any size, and the detector remembers partially filled blocks
+/// between calls.
+///
+/// ```rust
+/// use noise_gate::{tone::Goertzel, NoiseGate};
+/// use std::f64::consts::PI;
+///
+/// // listen for a 1 kHz tone in 10 ms blocks
+/// let sample_rate = 8000;
+/// let mut detector = Goertzel::new(1000.0, sample_rate, 80);
+///
+/// // a loud 300 Hz signal throughout, with the tone only in the middle
+/// let frames: Vec<[f32; 1]> = (0..2400)
+///     .map(|i| {
+///         let t = i as f64 / f64::from(sample_rate);
+///         let voice = 0.5 * (2.0 * PI * 300.0 * t).sin();
+///         let tone = if (800..1600).contains(&i) {
+///             0.2 * (2.0 * PI * 1000.0 * t).sin()
+///         } else {
+///             0.0
+///         };
+///         [(voice + tone) as f32]
+///     })
+///     .collect();
+///
+/// let key = detector.sidechain(&frames);
+/// let mut gate = NoiseGate::new(0.1, 80);
+/// let segments: Vec<_> = gate.segments(&key).map(|(range, _)| range).collect();
+///
+/// // the key lags one block behind the audio
+/// assert_eq!(segments, vec![879..1760]);
+/// ```
+///
+/// [wiki]: https://en.wikipedia.org/wiki/Goertzel_algorithm
+#[derive(Debug, Clone, PartialEq)]
+pub struct Goertzel {
+    frequency: f64,
+    block_size: usize,
+    coefficient: f64,
+    s1: f64,
+    s2: f64,
+    position: usize,
+    magnitude: f64,
+}
+
+impl Goertzel {
+    /// Create a detector for `frequency` (in Hz), which measures blocks of
+    /// `block_size` frames.
+    ///
+    /// Longer blocks can tell apart frequencies which are closer together
+    /// (roughly `sample_rate / block_size` Hz), but react more slowly.
+    pub fn new(frequency: f64, sample_rate: u32, block_size: usize) -> Self {
+        let omega = 2.0 * PI * frequency / f64::from(sample_rate.max(1));
+
+        Goertzel {
+            frequency,
+            block_size: block_size.max(1),
+            coefficient: 2.0 * omega.cos(),
+            s1: 0.0,
+            s2: 0.0,
+            position: 0,
+            magnitude: 0.0,
+        }
+    }
+
+    /// The frequency being detected, in Hz.
+    pub fn frequency(&self) -> f64 { self.frequency }
+
+    /// The number of frames in each block.
+    pub fn block_size(&self) -> usize { self.block_size }
+
+    /// The tone's amplitude in the most recently finished block, where a
+    /// full scale sine wave at exactly the right frequency is `1.0`.
+    pub fn magnitude(&self) -> f64 { self.magnitude }
+
+    /// Add a frame, returning the tone's amplitude if it finished a block.
+    pub fn process<F>(&mut self, frame: F) -> Option<f64>
+    where
+        F: Frame,
+        F::Sample: Duplex<f64>,
+    {
+        let sum: f64 = frame.channels().map(|s| s.to_sample::<f64>()).sum();
+        let sample = sum / F::CHANNELS as f64;
+        // a single NaN would poison the rest of the block
+        let sample = if sample.is_finite() { sample } else { 0.0 };
+
+        let s0 = sample + self.coefficient * self.s1 - self.s2;
+        self.s2 = self.s1;
+        self.s1 = s0;
+        self.position += 1;
+
+        if self.position < self.block_size {
+            return None;
+        }
+
+        let power = self.s1 * self.s1 + self.s2 * self.s2
+            - self.coefficient * self.s1 * self.s2;
+        self.magnitude = 2.0 * power.max(0.0).sqrt() / self.block_size as f64;
+        self.s1 = 0.0;
+        self.s2 = 0.0;
+        self.position = 0;
+
+        Some(self.magnitude)
+    }
+
+    /// Run the detector over a batch of frames, returning the tone's
+    /// amplitude for each frame so it can be used as a sidechain (see
+    /// [`NoiseGate::process_sidechain()`][crate::NoiseGate::process_sidechain]).
+    ///
+    /// Each frame gets the amplitude of the last block to finish (including
+    /// the block it finishes), so the key lags behind the audio by up to one
+    /// block.
+    pub fn sidechain<F>(&mut self, frames: &[F]) -> Vec<[f64; 1]>
+    where
+        F: Frame,
+        F::Sample: Duplex<f64>,
+    {
+        frames
+            .iter()
+            .map(|&frame| {
+                self.process(frame);
+                [self.magnitude]
+            })
+            .collect()
+    }
+
+    /// Forget any partially processed block and the last magnitude.
+    pub fn reset(&mut self) {
+        self.s1 = 0.0;
+        self.s2 = 0.0;
+        self.position = 0;
+        self.magnitude = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency: f64, amplitude: f64, len: usize) -> Vec<[f64; 1]> {
+        (0..len)
+            .map(|i| {
+                [amplitude * (2.0 * PI * frequency * i as f64 / 8000.0).sin()]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn measures_the_amplitude_of_a_matching_tone() {
+        let mut detector = Goertzel::new(1000.0, 8000, 200);
+
+        let magnitudes: Vec<f64> = sine(1000.0, 0.5, 600)
+            .into_iter()
+            .filter_map(|frame| detector.process(frame))
+            .collect();
+
+        assert_eq!(magnitudes.len(), 3);
+        assert!(magnitudes.iter().all(|m| (m - 0.5).abs() < 1e-6));
+    }
+
+    #[test]
+    fn ignores_other_frequencies() {
+        let mut detector = Goertzel::new(1000.0, 8000, 200);
+
+        for frame in sine(1200.0, 1.0, 200) {
+            detector.process(frame);
+        }
+
+        assert!(detector.magnitude() < 0.01);
+    }
+
+    #[test]
+    fn blocks_carry_across_calls() {
+        let frames = sine(697.0, 0.3, 1000);
+        let mut all_at_once = Goertzel::new(697.0, 8000, 205);
+        let mut chunked = all_at_once.clone();
+
+        let expected = all_at_once.sidechain(&frames);
+        let got: Vec<_> = frames
+            .chunks(37)
+            .flat_map(|chunk| chunked.sidechain(chunk))
+            .collect();
+
+        assert_eq!(got, expected);
+    }
+}
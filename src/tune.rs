@@ -0,0 +1,198 @@
+//! Automatically finding the gate parameters which best match a labelled
+//! reference recording.
+//!
+//! Tuning the threshold and release time by ear for every microphone and
+//! room is tedious. Instead, label the speech in a representative recording
+//! (e.g. with Audacity, see [`eval::parse_audacity_labels()`]) and let
+//! [`grid_search()`] try every combination.
+//!
+//! The gate doesn't have a separate hold time, because the release time
+//! already decides how long it stays open after the audio goes quiet.
+
+use crate::{
+    eval::{self, Evaluation},
+    simulate, NoiseGate,
+};
+use dasp::Frame;
+use std::{ops::Range, str::FromStr};
+
+/// What [`grid_search()`] tries to maximise.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Objective {
+    /// The F1 score over individual frames (the default), which rewards
+    /// getting the boundaries right.
+    #[default]
+    FrameF1,
+    /// The F1 score over whole segments, which rewards finding every
+    /// utterance without splitting or merging them.
+    SegmentF1,
+}
+
+impl Objective {
+    /// Score an [`Evaluation`], where higher is better.
+    pub fn score(self, evaluation: &Evaluation) -> f64 {
+        match self {
+            Objective::FrameF1 => evaluation.frames.f1(),
+            Objective::SegmentF1 => evaluation.segments.f1(),
+        }
+    }
+}
+
+impl FromStr for Objective {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "frames" => Ok(Objective::FrameF1),
+            "segments" => Ok(Objective::SegmentF1),
+            other => Err(format!(
+                "Unknown objective \"{}\", expected \"frames\" or \"segments\"",
+                other
+            )),
+        }
+    }
+}
+
+/// The result of trying one set of parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trial<S> {
+    /// The threshold which was used.
+    pub open_threshold: S,
+    /// The release time which was used, in frames.
+    pub release_time: usize,
+    /// How the detected segments compared with the reference.
+    pub evaluation: Evaluation,
+    /// The [`Objective`]'s score for this trial.
+    pub score: f64,
+}
+
+/// Run a fresh [`NoiseGate`] over `frames` for every combination of
+/// `thresholds` and `release_times`, scoring each against the `reference`
+/// segments.
+///
+/// Every combination is simulated in a single pass over the audio (see
+/// [`simulate`]).
+///
+/// Trials are returned best first. Ties are broken in favour of whichever
+/// came first in the grid, so list the parameters you'd prefer (e.g. shorter
+/// release times) first.
+///
+/// ```rust
+/// use noise_gate::tune::{self, Objective};
+///
+/// let frames: Vec<[i16; 1]> = (0..1000)
+///     .map(|i| if i % 250 < 50 { [300] } else { [80] })
+///     .collect();
+/// let reference = vec![0..61, 250..311, 500..561, 750..811];
+///
+/// let trials = tune::grid_search(
+///     &frames,
+///     &reference,
+///     &[50, 100, 500],
+///     &[0, 10, 100],
+///     Objective::FrameF1,
+/// );
+///
+/// let best = &trials[0];
+/// assert_eq!((best.open_threshold, best.release_time), (100, 10));
+/// assert_eq!(best.score, 1.0);
+/// ```
+pub fn grid_search<F>(
+    frames: &[F],
+    reference: &[Range<usize>],
+    thresholds: &[F::Sample],
+    release_times: &[usize],
+    objective: Objective,
+) -> Vec<Trial<F::Sample>>
+where
+    F: Frame,
+{
+    let parameters: Vec<(F::Sample, usize)> = thresholds
+        .iter()
+        .flat_map(|&t| release_times.iter().map(move |&r| (t, r)))
+        .collect();
+    let gates = parameters.iter().map(|&(t, r)| NoiseGate::new(t, r));
+    let detected = simulate::simulate(frames, gates);
+
+    let mut trials: Vec<Trial<F::Sample>> = parameters
+        .into_iter()
+        .zip(detected)
+        .map(|((open_threshold, release_time), detected)| {
+            let evaluation = eval::evaluate(&detected, reference);
+
+            Trial {
+                open_threshold,
+                release_time,
+                score: objective.score(&evaluation),
+                evaluation,
+            }
+        })
+        .collect();
+
+    // NaN scores can't happen, but sort them last just in case
+    trials.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    trials
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_combination_is_tried() {
+        let frames = vec![[0.5_f32]; 10];
+        let reference = vec![Range { start: 0, end: 10 }];
+
+        let trials = grid_search(
+            &frames,
+            &reference,
+            &[0.1, 0.2],
+            &[1, 2, 3],
+            Objective::SegmentF1,
+        );
+
+        assert_eq!(trials.len(), 6);
+        assert!(trials.iter().all(|t| t.score == 1.0));
+        // ties keep the grid's order
+        assert_eq!(trials[0].open_threshold, 0.1);
+        assert_eq!(trials[0].release_time, 1);
+        assert_eq!(trials[5].open_threshold, 0.2);
+        assert_eq!(trials[5].release_time, 3);
+    }
+
+    #[test]
+    fn segment_objective_penalises_splitting() {
+        // two bursts 20 frames apart, labelled as one utterance
+        let frames: Vec<[i16; 1]> = (0..100)
+            .map(|i| match i {
+                10..=29 | 50..=69 => [1000],
+                _ => [0],
+            })
+            .collect();
+        let reference = vec![Range { start: 10, end: 70 }];
+
+        let trials = grid_search(
+            &frames,
+            &reference,
+            &[100],
+            &[5, 25],
+            Objective::SegmentF1,
+        );
+
+        assert_eq!(trials[0].release_time, 25);
+        assert_eq!(trials[0].score, 1.0);
+        assert!(trials[1].score < 1.0);
+    }
+
+    #[test]
+    fn parse_objectives() {
+        assert_eq!("frames".parse(), Ok(Objective::FrameF1));
+        assert_eq!("segments".parse(), Ok(Objective::SegmentF1));
+        assert!("other".parse::<Objective>().is_err());
+    }
+}
@@ -0,0 +1,1093 @@
+//! Uploading finished clips somewhere else (e.g. a transcription service)
+//! from a background thread.
+
+use crate::Sink;
+use dasp::{sample::I24, Frame, Sample};
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// A finished clip, encoded as a WAV file with the same sample format as the
+/// audio it was recorded from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clip {
+    /// Which transmission this was, starting from `0`.
+    pub index: usize,
+    /// The clip's length in frames.
+    pub frames: usize,
+    /// The number of channels.
+    pub channels: usize,
+    /// The sample rate.
+    pub sample_rate: u32,
+    /// When the transmission ended.
+    pub finished: SystemTime,
+    /// The clip, as a complete WAV file.
+    pub wav: Vec<u8>,
+}
+
+/// Something which can send a [`Clip`] somewhere.
+pub trait Uploader: Send + 'static {
+    /// Try to upload a clip.
+    fn upload(&mut self, clip: &Clip) -> Result<(), UploadError>;
+}
+
+impl<U: Uploader + ?Sized> Uploader for Box<U> {
+    fn upload(&mut self, clip: &Clip) -> Result<(), UploadError> {
+        (**self).upload(clip)
+    }
+}
+
+/// Why an upload failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UploadError {
+    /// Something which might work if we try again later (e.g. a network
+    /// error or an overloaded server).
+    Retryable(String),
+    /// Something which will never work (e.g. the server rejected the clip).
+    Permanent(String),
+}
+
+impl Display for UploadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            UploadError::Retryable(msg) => write!(f, "{} (retryable)", msg),
+            UploadError::Permanent(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl Clip {
+    /// Replace the `{index}`, `{frames}`, `{sample_rate}`, `{timestamp}`,
+    /// `{date}`, and `{time}` placeholders in a template with this clip's
+    /// details.
+    #[cfg(feature = "s3")]
+    pub(crate) fn fill_template(&self, template: &str) -> String {
+        let timestamp = unix_seconds(self.finished);
+        let (date, time) = utc(timestamp);
+
+        template
+            .replace("{index}", &self.index.to_string())
+            .replace("{frames}", &self.frames.to_string())
+            .replace("{sample_rate}", &self.sample_rate.to_string())
+            .replace("{timestamp}", &timestamp.to_string())
+            .replace("{date}", &date)
+            .replace("{time}", &time)
+    }
+}
+
+impl Error for UploadError {}
+
+impl From<io::Error> for UploadError {
+    fn from(e: io::Error) -> Self { UploadError::Retryable(e.to_string()) }
+}
+
+/// How hard to try before giving up on a clip.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The most times an upload will be attempted.
+    pub max_attempts: u32,
+    /// How long to wait after the first failure. This doubles after every
+    /// failed attempt.
+    pub initial_backoff: Duration,
+    /// The longest we'll ever wait between attempts.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Keep calling `upload` until it succeeds, fails permanently, or we run
+    /// out of attempts.
+    pub fn run<T, U>(&self, mut upload: U) -> Result<T, UploadError>
+    where
+        U: FnMut() -> Result<T, UploadError>,
+    {
+        let mut backoff = self.initial_backoff;
+        let mut attempt = 1;
+
+        loop {
+            match upload() {
+                Err(UploadError::Retryable(_))
+                    if attempt < self.max_attempts =>
+                {
+                    thread::sleep(backoff);
+                    backoff = (backoff * 2).min(self.max_backoff);
+                    attempt += 1;
+                },
+                other => return other,
+            }
+        }
+    }
+}
+
+/// What happened to every clip sent to an [`UploadSink`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct UploadReport {
+    /// How many clips were uploaded.
+    pub uploaded: usize,
+    /// The clips which couldn't be uploaded, and why.
+    pub failed: Vec<(usize, UploadError)>,
+    /// How many clips are still waiting in the spool directory (see
+    /// [`UploadSink::with_spool_dir()`]) because the uploader couldn't be
+    /// reached. They'll be tried again the next time the directory is used.
+    pub spooled: usize,
+}
+
+/// A [`Sink`] which collects each transmission into a clip and hands it to
+/// an [`Uploader`] on a background thread.
+///
+/// Clips are buffered in memory until the transmission ends, so this
+/// allocates and shouldn't be used directly from a real-time audio thread.
+/// Encoding and uploading happen on the background thread, though. Call
+/// [`UploadSink::finish()`] to wait for any outstanding uploads.
+///
+/// Transmissions longer than [`UploadSink::with_max_frames()`] (five minutes
+/// by default) are split into several clips, so a stuck gate can't use up
+/// all our memory. Transmissions which the gate discards are never uploaded.
+#[derive(Debug)]
+pub struct UploadSink<F> {
+    current: Vec<F>,
+    max_frames: usize,
+    next_index: usize,
+    recordings: Option<Sender<Recording<F>>>,
+    worker: Option<JoinHandle<UploadReport>>,
+}
+
+impl<F> UploadSink<F>
+where
+    F: Frame + Send + 'static,
+    F::Sample: WavSample,
+{
+    /// Start a background thread which uploads clips using `uploader`.
+    pub fn new<U: Uploader>(
+        uploader: U,
+        sample_rate: u32,
+        retry: RetryPolicy,
+    ) -> io::Result<Self> {
+        UploadSink::spawn(uploader, sample_rate, retry, None)
+    }
+
+    /// Start a background thread which uploads clips using `uploader`,
+    /// saving them to `spool_dir` until they've been uploaded.
+    ///
+    /// If the uploader is unreachable, clips stay on disk and are tried
+    /// again after the next successful upload, or by the next [`UploadSink`]
+    /// using the same directory. That way nothing is lost during an outage
+    /// or if the program is restarted.
+    pub fn with_spool_dir<U, P>(
+        uploader: U,
+        sample_rate: u32,
+        retry: RetryPolicy,
+        spool_dir: P,
+    ) -> io::Result<Self>
+    where
+        U: Uploader,
+        P: Into<PathBuf>,
+    {
+        let spool = Spool::open(spool_dir.into())?;
+        UploadSink::spawn(uploader, sample_rate, retry, Some(spool))
+    }
+
+    fn spawn<U: Uploader>(
+        uploader: U,
+        sample_rate: u32,
+        retry: RetryPolicy,
+        spool: Option<Spool>,
+    ) -> io::Result<Self> {
+        let (recordings, rx) = mpsc::channel();
+        let worker = thread::Builder::new()
+            .name(String::from("clip-upload"))
+            .spawn(move || {
+                Worker {
+                    uploader,
+                    retry,
+                    spool,
+                    sample_rate,
+                    report: UploadReport::default(),
+                }
+                .run(rx)
+            })?;
+
+        Ok(UploadSink {
+            current: Vec::new(),
+            max_frames: sample_rate as usize * 300,
+            next_index: 0,
+            recordings: Some(recordings),
+            worker: Some(worker),
+        })
+    }
+
+    /// Split transmissions into clips of at most `max_frames` frames.
+    pub fn with_max_frames(self, max_frames: usize) -> Self {
+        UploadSink {
+            max_frames: max_frames.max(1),
+            ..self
+        }
+    }
+
+    /// Wait for every clip to be uploaded.
+    ///
+    /// Any transmission which is still in progress is discarded.
+    pub fn finish(mut self) -> UploadReport {
+        // closing the channel tells the worker to stop once it's done
+        self.recordings.take();
+
+        self.worker
+            .take()
+            .and_then(|worker| worker.join().ok())
+            .unwrap_or_default()
+    }
+
+    /// Hand the current clip to the worker.
+    fn send_current(&mut self) {
+        if self.current.is_empty() {
+            return;
+        }
+
+        let recording = Recording {
+            index: self.next_index,
+            frames: std::mem::take(&mut self.current),
+            finished: SystemTime::now(),
+        };
+        self.next_index += 1;
+
+        if let Some(recordings) = &self.recordings {
+            // if the worker has died there's nothing we can do anyway
+            let _ = recordings.send(recording);
+        }
+    }
+}
+
+/// The audio for a clip, before it has been encoded.
+#[derive(Debug)]
+struct Recording<F> {
+    index: usize,
+    frames: Vec<F>,
+    finished: SystemTime,
+}
+
+impl<F> Recording<F>
+where
+    F: Frame,
+    F::Sample: WavSample,
+{
+    fn encode(self, sample_rate: u32) -> Clip {
+        Clip {
+            index: self.index,
+            frames: self.frames.len(),
+            channels: F::CHANNELS,
+            sample_rate,
+            finished: self.finished,
+            wav: encode_wav(&self.frames, sample_rate),
+        }
+    }
+}
+
+/// A clip waiting to be uploaded.
+#[derive(Debug)]
+enum Pending {
+    InMemory(Clip),
+    OnDisk(PathBuf),
+}
+
+/// The background thread's state.
+struct Worker<U> {
+    uploader: U,
+    retry: RetryPolicy,
+    spool: Option<Spool>,
+    sample_rate: u32,
+    report: UploadReport,
+}
+
+impl<U: Uploader> Worker<U> {
+    fn run<F>(mut self, recordings: Receiver<Recording<F>>) -> UploadReport
+    where
+        F: Frame,
+        F::Sample: WavSample,
+    {
+        let mut queue = VecDeque::new();
+        // clips which are on disk but couldn't be uploaded
+        let mut backlog = Vec::new();
+
+        if let Some(spool) = &self.spool {
+            queue.extend(spool.pending().into_iter().map(Pending::OnDisk));
+        }
+
+        loop {
+            // get new clips out of memory as soon as possible
+            for recording in recordings.try_iter() {
+                queue.push_back(self.save(recording.encode(self.sample_rate)));
+            }
+
+            let pending = match queue.pop_front() {
+                Some(pending) => pending,
+                None => match recordings.recv() {
+                    Ok(recording) => {
+                        self.save(recording.encode(self.sample_rate))
+                    },
+                    Err(_) => break,
+                },
+            };
+
+            match self.upload(pending) {
+                Some(path) => backlog.push(path),
+                // the uploader is working again, so give the backlog
+                // another go
+                None => queue.extend(backlog.drain(..).map(Pending::OnDisk)),
+            }
+        }
+
+        self.report.spooled = backlog.len();
+        self.report
+    }
+
+    /// Write a clip to the spool directory, if there is one.
+    fn save(&self, clip: Clip) -> Pending {
+        match &self.spool {
+            // if the disk is broken, we can still try to upload it
+            Some(spool) => match spool.save(&clip) {
+                Ok(path) => Pending::OnDisk(path),
+                Err(_) => Pending::InMemory(clip),
+            },
+            None => Pending::InMemory(clip),
+        }
+    }
+
+    /// Try to upload a clip, returning its path if it should stay in the
+    /// spool directory for another attempt.
+    fn upload(&mut self, pending: Pending) -> Option<PathBuf> {
+        let (clip, path) = match pending {
+            Pending::InMemory(clip) => (clip, None),
+            Pending::OnDisk(path) => match Spool::load(&path) {
+                Ok(clip) => (clip, Some(path)),
+                // someone else is messing with the directory, leave it
+                // alone
+                Err(_) => return None,
+            },
+        };
+        let uploader = &mut self.uploader;
+
+        let result = self.retry.run(|| uploader.upload(&clip));
+
+        match result {
+            Ok(()) => self.report.uploaded += 1,
+            Err(UploadError::Retryable(_)) if path.is_some() => return path,
+            Err(e) => self.report.failed.push((clip.index, e)),
+        }
+
+        if let Some(path) = path {
+            let _ = fs::remove_file(path);
+        }
+
+        None
+    }
+}
+
+/// A directory where clips are kept until they've been uploaded.
+///
+/// Each clip is saved as `{milliseconds since the epoch}-{index}.wav`, so
+/// everything needed to upload it again can be recovered from the file.
+#[derive(Debug, Clone, PartialEq)]
+struct Spool {
+    dir: PathBuf,
+}
+
+impl Spool {
+    fn open(dir: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Spool { dir })
+    }
+
+    /// Clips left behind by a previous run, oldest first.
+    fn pending(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension() == Some("wav".as_ref()))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        paths.sort();
+
+        paths
+    }
+
+    fn save(&self, clip: &Clip) -> io::Result<PathBuf> {
+        let mut millis = clip
+            .finished
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let mut path;
+
+        // a previous run may have used the same name, so nudge the timestamp
+        // forward until we find a free one
+        loop {
+            path = self.dir.join(format!("{:013}-{}.wav", millis, clip.index));
+            if !path.exists() {
+                break;
+            }
+            millis += 1;
+        }
+
+        // write to a temporary file first so a crash never leaves half a
+        // clip behind
+        let partial = path.with_extension("part");
+        fs::write(&partial, &clip.wav)?;
+        fs::rename(&partial, &path)?;
+
+        Ok(path)
+    }
+
+    fn load(path: &Path) -> io::Result<Clip> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("\"{}\" isn't a spooled clip", path.display()),
+            )
+        };
+        let (millis, index) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.split_once('-'))
+            .and_then(|(millis, index)| {
+                Some((millis.parse().ok()?, index.parse().ok()?))
+            })
+            .ok_or_else(invalid)?;
+        let wav = fs::read(path)?;
+
+        if wav.len() < 44 || &wav[..4] != b"RIFF" {
+            return Err(invalid());
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([wav[i], wav[i + 1]]);
+        let u32_at = |i: usize| {
+            u32::from_le_bytes([wav[i], wav[i + 1], wav[i + 2], wav[i + 3]])
+        };
+        let block_align = usize::from(u16_at(32)).max(1);
+
+        Ok(Clip {
+            index,
+            frames: u32_at(40) as usize / block_align,
+            channels: usize::from(u16_at(22)),
+            sample_rate: u32_at(24),
+            finished: UNIX_EPOCH + Duration::from_millis(millis),
+            wav,
+        })
+    }
+}
+
+impl<F> Sink<F> for UploadSink<F>
+where
+    F: Frame + Send + 'static,
+    F::Sample: WavSample,
+{
+    fn record(&mut self, frame: F) {
+        self.current.push(frame);
+
+        if self.current.len() >= self.max_frames {
+            self.send_current();
+        }
+    }
+
+    fn record_frames(&mut self, mut frames: &[F]) {
+        while !frames.is_empty() {
+            let room = self.max_frames - self.current.len();
+            let (head, tail) = frames.split_at(room.min(frames.len()));
+            self.current.extend_from_slice(head);
+            frames = tail;
+
+            if self.current.len() >= self.max_frames {
+                self.send_current();
+            }
+        }
+    }
+
+    fn end_of_transmission(&mut self) { self.send_current(); }
+
+    fn discard_transmission(&mut self) {
+        // anything already split off into its own clip has been sent, but
+        // the rest can still be thrown away
+        self.current.clear();
+    }
+}
+
+#[cfg(any(feature = "s3", feature = "webhook"))]
+pub(crate) fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(any(feature = "s3", feature = "webhook"))]
+/// Format a Unix timestamp as a UTC `YYYY-MM-DD` date and `HHMMSS` time.
+pub(crate) fn utc(timestamp: u64) -> (String, String) {
+    let days = (timestamp / 86_400) as i64;
+    let (year, month, day) = crate::clock::civil_from_days(days);
+    let seconds = timestamp % 86_400;
+
+    (
+        format!("{:04}-{:02}-{:02}", year, month, day),
+        format!(
+            "{:02}{:02}{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        ),
+    )
+}
+
+/// A sample type which can be written to a WAV file without losing
+/// precision.
+pub trait WavSample: Sample {
+    /// The WAV format tag (`1` for integer PCM, `3` for floating point).
+    const FORMAT: u16;
+    /// How many bits each sample uses.
+    const BITS: u16;
+
+    /// Append the sample to a WAV file's data.
+    fn write_le(self, wav: &mut Vec<u8>);
+}
+
+macro_rules! wav_sample {
+    ($($sample:ty => $format:expr),*) => {
+        $(
+            impl WavSample for $sample {
+                const FORMAT: u16 = $format;
+                const BITS: u16 = std::mem::size_of::<$sample>() as u16 * 8;
+
+                fn write_le(self, wav: &mut Vec<u8>) {
+                    wav.extend_from_slice(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+wav_sample!(u8 => 1, i16 => 1, i32 => 1, f32 => 3, f64 => 3);
+
+impl WavSample for i8 {
+    const BITS: u16 = 8;
+    const FORMAT: u16 = 1;
+
+    // 8-bit WAV files are unsigned
+    fn write_le(self, wav: &mut Vec<u8>) { wav.push(self as u8 ^ 0x80); }
+}
+
+impl WavSample for I24 {
+    const BITS: u16 = 24;
+    const FORMAT: u16 = 1;
+
+    fn write_le(self, wav: &mut Vec<u8>) {
+        wav.extend_from_slice(&self.inner().to_le_bytes()[..3]);
+    }
+}
+
+/// Encode frames as a WAV file, keeping the sample format.
+pub(crate) fn encode_wav<F>(frames: &[F], sample_rate: u32) -> Vec<u8>
+where
+    F: Frame,
+    F::Sample: WavSample,
+{
+    let channels = F::CHANNELS as u16;
+    let block_align = channels * (F::Sample::BITS / 8);
+    let data_len = frames.len() as u32 * u32::from(block_align);
+    // chunks always take up an even number of bytes
+    let padding = data_len % 2;
+    let mut wav =
+        Vec::with_capacity(44 + data_len as usize + padding as usize);
+
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len + padding).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16_u32.to_le_bytes());
+    wav.extend_from_slice(&F::Sample::FORMAT.to_le_bytes());
+    wav.extend_from_slice(&channels.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(
+        &(sample_rate * u32::from(block_align)).to_le_bytes(),
+    );
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&F::Sample::BITS.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+
+    for sample in frames.iter().flat_map(|frame| frame.channels()) {
+        sample.write_le(&mut wav);
+    }
+    if padding != 0 {
+        wav.push(0);
+    }
+
+    wav
+}
+
+/// An [`Uploader`] which POSTs each clip to a HTTP endpoint.
+///
+/// The body is the WAV file, with the clip's details in `X-Clip-Index`,
+/// `X-Clip-Frames`, `X-Clip-Channels`, and `X-Clip-Sample-Rate` headers. Any
+/// `2xx` response counts as success, `5xx` responses and network errors are
+/// retried, and everything else is treated as a permanent failure.
+///
+/// Both `http://` and `https://` URLs are supported.
+///
+/// ```rust,no_run
+/// use noise_gate::{
+///     upload::{HttpUploader, RetryPolicy, UploadSink},
+///     NoiseGate,
+/// };
+///
+/// let uploader = HttpUploader::new("http://localhost:8000/clips")?
+///     .with_header("Authorization", "Bearer hunter2");
+/// let mut sink = UploadSink::new(uploader, 16_000, RetryPolicy::default())?;
+/// let mut gate = NoiseGate::new(300_i16, 4_000);
+///
+/// # let frames = [[0_i16]; 16];
+/// gate.process_frames(&frames, &mut sink);
+///
+/// let report = sink.finish();
+/// println!("Uploaded {} clips", report.uploaded);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpUploader {
+    endpoint: Endpoint,
+    headers: Vec<(String, String)>,
+    timeout: Duration,
+}
+
+impl HttpUploader {
+    /// Create a [`HttpUploader`] which POSTs to `url`.
+    pub fn new(url: &str) -> Result<Self, UploadError> {
+        Ok(HttpUploader {
+            endpoint: Endpoint::parse(url)?,
+            headers: Vec::new(),
+            timeout: Duration::from_secs(30),
+        })
+    }
+
+    /// Send an extra header with every request (e.g. for authentication).
+    pub fn with_header<N, V>(mut self, name: N, value: V) -> Self
+    where
+        N: Into<String>,
+        V: Into<String>,
+    {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// How long to wait for the server before giving up on an attempt.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        HttpUploader { timeout, ..self }
+    }
+}
+
+impl Uploader for HttpUploader {
+    fn upload(&mut self, clip: &Clip) -> Result<(), UploadError> {
+        let mut headers = vec![
+            (String::from("Content-Type"), String::from("audio/wav")),
+            (String::from("X-Clip-Index"), clip.index.to_string()),
+            (String::from("X-Clip-Frames"), clip.frames.to_string()),
+            (String::from("X-Clip-Channels"), clip.channels.to_string()),
+            (
+                String::from("X-Clip-Sample-Rate"),
+                clip.sample_rate.to_string(),
+            ),
+        ];
+        headers.extend(self.headers.iter().cloned());
+
+        self.endpoint.send(
+            "POST",
+            &self.endpoint.path,
+            &headers,
+            &clip.wav,
+            self.timeout,
+        )
+    }
+}
+
+/// Where a HTTP request should be sent.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Endpoint {
+    pub(crate) secure: bool,
+    pub(crate) host: String,
+    pub(crate) port: u16,
+    pub(crate) path: String,
+}
+
+impl Endpoint {
+    /// Parse a `http[s]://host[:port][/path]` URL.
+    pub(crate) fn parse(url: &str) -> Result<Self, UploadError> {
+        let invalid =
+            || UploadError::Permanent(format!("Invalid URL, {}", url));
+        let (secure, rest) = if let Some(rest) = url.strip_prefix("https://")
+        {
+            (true, rest)
+        } else {
+            (false, url.strip_prefix("http://").ok_or_else(invalid)?)
+        };
+        let default_port = if secure { 443 } else { 80 };
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => rest.split_at(slash),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+            None => (authority, default_port),
+        };
+
+        if host.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(Endpoint {
+            secure,
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    /// The value to use for the `Host` header.
+    pub(crate) fn authority(&self) -> String {
+        let default_port = if self.secure { 443 } else { 80 };
+
+        if self.port == default_port {
+            self.host.clone()
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+
+    /// The full URL for a path on this endpoint.
+    pub(crate) fn url(&self, path: &str) -> String {
+        let scheme = if self.secure { "https" } else { "http" };
+        format!("{}://{}{}", scheme, self.authority(), path)
+    }
+
+    /// Send a request, turning the response's status code into a
+    /// [`UploadError`] if it wasn't successful.
+    pub(crate) fn send(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &[(String, String)],
+        body: &[u8],
+        timeout: Duration,
+    ) -> Result<(), UploadError> {
+        let agent = ureq::AgentBuilder::new().timeout(timeout).build();
+        let mut request = agent.request(method, &self.url(path));
+        for (name, value) in headers {
+            request = request.set(name, value);
+        }
+
+        match request.send_bytes(body) {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(status, response)) => {
+                let msg = format!("{} {}", status, response.status_text());
+
+                if (500..600).contains(&status) {
+                    Err(UploadError::Retryable(msg))
+                } else {
+                    Err(UploadError::Permanent(msg))
+                }
+            },
+            Err(ureq::Error::Transport(e)) => {
+                Err(UploadError::Retryable(e.to_string()))
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NoiseGate;
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::{TcpListener, TcpStream},
+        sync::{Arc, Mutex},
+    };
+
+    #[test]
+    fn wav_header() {
+        let wav = encode_wav(&[[1_i16, -1], [2, -2]], 8000);
+
+        assert_eq!(wav.len(), 44 + 8);
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(&wav[22..24], [2, 0]);
+        assert_eq!(&wav[24..28], 8000_u32.to_le_bytes());
+        assert_eq!(&wav[40..44], 8_u32.to_le_bytes());
+        assert_eq!(&wav[44..], [1, 0, 0xFF, 0xFF, 2, 0, 0xFE, 0xFF]);
+    }
+
+    #[test]
+    fn wav_files_keep_the_sample_format() {
+        let i24 = I24::new(-2).unwrap();
+        let wav = encode_wav(&[[i24]], 48_000);
+        assert_eq!(&wav[20..22], [1, 0]);
+        assert_eq!(&wav[32..36], [3, 0, 24, 0]);
+        // the odd-sized data chunk gets padded
+        assert_eq!(&wav[40..44], 3_u32.to_le_bytes());
+        assert_eq!(&wav[44..], [0xFE, 0xFF, 0xFF, 0]);
+
+        let wav = encode_wav(&[[0.5_f32, -1.0]], 48_000);
+        assert_eq!(&wav[20..22], [3, 0]);
+        assert_eq!(&wav[32..36], [8, 0, 32, 0]);
+        assert_eq!(&wav[44..48], 0.5_f32.to_le_bytes());
+
+        let wav = encode_wav(&[[-128_i8, 0]], 8000);
+        assert_eq!(&wav[44..], [0, 0x80]);
+    }
+
+    #[test]
+    #[cfg(any(feature = "s3", feature = "webhook"))]
+    fn format_utc_timestamps() {
+        assert_eq!(utc(0), ("1970-01-01".into(), "000000".into()));
+        assert_eq!(
+            utc(1_329_264_000 + 3_723),
+            ("2012-02-15".into(), "010203".into())
+        );
+        assert_eq!(utc(951_782_400), ("2000-02-29".into(), "000000".into()));
+    }
+
+    #[test]
+    fn parse_urls() {
+        let got = Endpoint::parse("http://example.com:8080/api/clips").unwrap();
+        assert_eq!(got.host, "example.com");
+        assert_eq!(got.port, 8080);
+        assert_eq!(got.path, "/api/clips");
+        assert_eq!(got.authority(), "example.com:8080");
+
+        let got = Endpoint::parse("http://localhost").unwrap();
+        assert_eq!((got.port, got.path.as_str()), (80, "/"));
+        assert_eq!(got.authority(), "localhost");
+
+        let got = Endpoint::parse("https://example.com/clips").unwrap();
+        assert_eq!(got.port, 443);
+        assert_eq!(got.url(&got.path), "https://example.com/clips");
+
+        assert!(Endpoint::parse("ftp://example.com").is_err());
+        assert!(Endpoint::parse("http://:80").is_err());
+    }
+
+    #[test]
+    fn retries_back_off_until_something_works() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+        };
+        let mut attempts = 0;
+
+        let got = policy.run(|| {
+            attempts += 1;
+            Err::<(), _>(UploadError::Retryable(String::from("Oops")))
+        });
+        assert!(got.is_err());
+        assert_eq!(attempts, 3);
+
+        attempts = 0;
+        let got = policy.run(|| {
+            attempts += 1;
+            Err::<(), _>(UploadError::Permanent(String::from("Nope")))
+        });
+        assert!(got.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    /// Read a request's head and body, using its `Content-Length`.
+    fn read_request(stream: &mut TcpStream) -> Vec<u8> {
+        let mut reader = BufReader::new(stream);
+        let mut request = Vec::new();
+        let mut content_length = 0;
+
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            request.extend_from_slice(line.as_bytes());
+
+            let lowercase = line.to_ascii_lowercase();
+            if let Some(value) = lowercase.strip_prefix("content-length:") {
+                content_length = value.trim().parse().unwrap();
+            }
+            if line == "\r\n" {
+                break;
+            }
+        }
+
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+        request.extend(body);
+
+        request
+    }
+
+    /// Accept `responses.len()` requests, replying with each status in turn
+    /// and returning the requests we received.
+    fn serve(
+        listener: TcpListener,
+        responses: &'static [&'static str],
+    ) -> JoinHandle<Vec<Vec<u8>>> {
+        thread::spawn(move || {
+            responses
+                .iter()
+                .map(|status| {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let request = read_request(&mut stream);
+                    write!(
+                        stream,
+                        "HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n",
+                        status
+                    )
+                    .unwrap();
+                    request
+                })
+                .collect()
+        })
+    }
+
+    #[test]
+    fn clips_are_posted_after_each_transmission() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/clips", listener.local_addr().unwrap());
+        let server = serve(listener, &["503 Busy", "200 OK", "400 Bad"]);
+
+        let uploader =
+            HttpUploader::new(&url).unwrap().with_header("X-Test", "1");
+        let retry = RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        };
+        let mut sink = UploadSink::new(uploader, 8000, retry).unwrap();
+        let mut gate = NoiseGate::new(100_i16, 0);
+
+        gate.process_frames(
+            &[[500], [600], [0], [0], [700], [0], [0]],
+            &mut sink,
+        );
+        let report = sink.finish();
+
+        assert_eq!(report.uploaded, 1);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, 1);
+
+        let requests = server.join().unwrap();
+        let first = String::from_utf8_lossy(&requests[1]);
+        assert!(first.starts_with("POST /clips HTTP/1.1\r\n"));
+        assert!(first.contains("X-Clip-Index: 0\r\n"));
+        assert!(first.contains("X-Clip-Frames: 3\r\n"));
+        assert!(first.contains("X-Test: 1\r\n"));
+        assert!(first.contains("\r\n\r\nRIFF"));
+    }
+
+    /// An [`Uploader`] which can be taken offline.
+    #[derive(Debug, Default, Clone)]
+    struct Flaky {
+        offline: bool,
+        uploaded: Arc<Mutex<Vec<(usize, usize)>>>,
+    }
+
+    impl Uploader for Flaky {
+        fn upload(&mut self, clip: &Clip) -> Result<(), UploadError> {
+            if self.offline {
+                return Err(UploadError::Retryable(String::from("Offline")));
+            }
+
+            self.uploaded.lock().unwrap().push((clip.index, clip.frames));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn spooled_clips_survive_an_outage() {
+        let spool_dir = std::env::temp_dir()
+            .join(format!("noise-gate-spool-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&spool_dir);
+        let retry = RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        };
+        let frames = [[500_i16], [600], [0], [0], [700], [0], [0]];
+
+        let offline = Flaky {
+            offline: true,
+            ..Flaky::default()
+        };
+        let mut sink =
+            UploadSink::with_spool_dir(offline, 8000, retry, &spool_dir)
+                .unwrap();
+        NoiseGate::new(100, 0).process_frames(&frames, &mut sink);
+        let report = sink.finish();
+
+        assert_eq!(report.uploaded, 0);
+        assert!(report.failed.is_empty());
+        assert_eq!(report.spooled, 2);
+        assert_eq!(fs::read_dir(&spool_dir).unwrap().count(), 2);
+
+        // next time around, the clips left on disk are uploaded first
+        let online = Flaky::default();
+        let uploaded = Arc::clone(&online.uploaded);
+        let mut sink =
+            UploadSink::with_spool_dir(online, 8000, retry, &spool_dir)
+                .unwrap();
+        NoiseGate::new(100, 0).process_frames(&frames[..4], &mut sink);
+        let report = sink.finish();
+
+        assert_eq!(report.uploaded, 3);
+        assert_eq!(report.spooled, 0);
+        assert_eq!(*uploaded.lock().unwrap(), vec![(0, 3), (1, 2), (0, 3)]);
+        assert_eq!(fs::read_dir(&spool_dir).unwrap().count(), 0);
+
+        fs::remove_dir_all(&spool_dir).unwrap();
+    }
+
+    #[test]
+    fn discarded_transmissions_are_never_uploaded() {
+        let uploader = Flaky::default();
+        let uploaded = Arc::clone(&uploader.uploaded);
+        let mut sink =
+            UploadSink::new(uploader, 8000, RetryPolicy::default()).unwrap();
+
+        sink.record_frames(&[[1_i16], [2], [3]]);
+        sink.discard_transmission();
+        sink.record_frames(&[[4], [5]]);
+        sink.end_of_transmission();
+        sink.finish();
+
+        assert_eq!(*uploaded.lock().unwrap(), vec![(0, 2)]);
+    }
+
+    #[test]
+    fn long_transmissions_are_split() {
+        let uploader = Flaky::default();
+        let uploaded = Arc::clone(&uploader.uploaded);
+        let mut sink = UploadSink::new(uploader, 8000, RetryPolicy::default())
+            .unwrap()
+            .with_max_frames(4);
+
+        sink.record_frames(&[[1_i16]; 6]);
+        sink.record([7]);
+        sink.record_frames(&[[8], [9], [10]]);
+        sink.end_of_transmission();
+        sink.finish();
+
+        assert_eq!(*uploaded.lock().unwrap(), vec![(0, 4), (1, 4), (2, 2)]);
+    }
+}
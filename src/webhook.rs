@@ -0,0 +1,349 @@
+//! Calling a webhook whenever a clip is finished, so home-automation rules
+//! and alerting systems can react to audio as soon as it's detected.
+//!
+//! The [`Webhook`] only sends a [`Notification`] with the clip's details
+//! (e.g. where it was saved), never the audio itself. Call
+//! [`Webhook::notify()`] once the clip has been written, wrapping it in a
+//! [`RetryPolicy`] if the server might be flaky.
+//!
+//! ```rust,no_run
+//! use noise_gate::{
+//!     upload::RetryPolicy,
+//!     webhook::{Notification, Webhook},
+//! };
+//! use std::time::SystemTime;
+//!
+//! let webhook = Webhook::new(
+//!     "http://homeassistant.local:8123/api/webhook/doorbell?clip={index}",
+//! )?
+//! .with_header("Authorization", "Bearer hunter2");
+//!
+//! // once the clip has been saved
+//! let notification = Notification {
+//!     index: 0,
+//!     path: Some("clips/clip_0.wav".into()),
+//!     start_frame: 48_000,
+//!     frames: 24_000,
+//!     channels: 1,
+//!     sample_rate: 16_000,
+//!     finished: SystemTime::now(),
+//! };
+//! RetryPolicy::default().run(|| webhook.notify(&notification))?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use crate::upload::{unix_seconds, utc, Endpoint, UploadError};
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+#[cfg(doc)]
+use crate::upload::RetryPolicy;
+
+/// The details of a finished clip, as sent by a [`Webhook`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    /// Which transmission this was, starting from `0`.
+    pub index: usize,
+    /// Where the clip was saved, if it was written to disk.
+    pub path: Option<PathBuf>,
+    /// Where the clip started in the original recording, in frames.
+    pub start_frame: u64,
+    /// The clip's length in frames.
+    pub frames: usize,
+    /// The number of channels.
+    pub channels: usize,
+    /// The sample rate.
+    pub sample_rate: u32,
+    /// When the clip was finished.
+    pub finished: SystemTime,
+}
+
+impl Notification {
+    /// The clip's length in milliseconds.
+    pub fn duration_ms(&self) -> u64 { to_ms(self.frames as u64, self) }
+
+    /// Where the clip started in the original recording, in milliseconds.
+    pub fn start_ms(&self) -> u64 { to_ms(self.start_frame, self) }
+
+    fn path(&self) -> String {
+        self.path
+            .as_ref()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default()
+    }
+
+    /// The details as a JSON object.
+    fn to_json(&self) -> String {
+        let path = match &self.path {
+            Some(_) => json_string(&self.path()),
+            None => String::from("null"),
+        };
+
+        format!(
+            "{{\"index\":{},\"path\":{},\"start_frame\":{},\"frames\":{},\
+             \"channels\":{},\"sample_rate\":{},\"start_ms\":{},\
+             \"duration_ms\":{},\"timestamp\":{}}}",
+            self.index,
+            path,
+            self.start_frame,
+            self.frames,
+            self.channels,
+            self.sample_rate,
+            self.start_ms(),
+            self.duration_ms(),
+            unix_seconds(self.finished),
+        )
+    }
+}
+
+fn to_ms(frames: u64, notification: &Notification) -> u64 {
+    if notification.sample_rate == 0 {
+        return 0;
+    }
+
+    frames * 1000 / u64::from(notification.sample_rate)
+}
+
+/// Calls a webhook with a [`Notification`] whenever a clip is finished.
+///
+/// The URL is generated from a template, where the following placeholders
+/// are replaced with details from the [`Notification`]:
+///
+/// | Placeholder     | Value                                        |
+/// | --------------- | -------------------------------------------- |
+/// | `{index}`       | The transmission number                      |
+/// | `{path}`        | Where the clip was saved (percent-encoded)   |
+/// | `{start_frame}` | Where the clip started, in frames            |
+/// | `{start_ms}`    | Where the clip started, in milliseconds      |
+/// | `{frames}`      | The clip's length in frames                  |
+/// | `{channels}`    | The number of channels                       |
+/// | `{sample_rate}` | The sample rate                              |
+/// | `{duration_ms}` | The clip's length in milliseconds            |
+/// | `{timestamp}`   | Seconds since the Unix epoch when it ended   |
+/// | `{date}`        | The UTC date it ended, as `YYYY-MM-DD`       |
+/// | `{time}`        | The UTC time it ended, as `HHMMSS`           |
+///
+/// Unless the method is `GET`, the same details are sent as a JSON body.
+/// Like the [`HttpUploader`][crate::upload::HttpUploader], any `2xx`
+/// response counts as success and both `http://` and `https://` URLs are
+/// supported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Webhook {
+    url_template: String,
+    method: String,
+    headers: Vec<(String, String)>,
+    timeout: Duration,
+}
+
+impl Webhook {
+    /// Create a [`Webhook`] which `POST`s to a URL generated from
+    /// `url_template`.
+    pub fn new(url_template: &str) -> Result<Self, UploadError> {
+        // catch obviously broken URLs up front instead of on the first clip
+        Endpoint::parse(url_template)?;
+
+        Ok(Webhook {
+            url_template: url_template.to_string(),
+            method: String::from("POST"),
+            headers: Vec::new(),
+            timeout: Duration::from_secs(30),
+        })
+    }
+
+    /// Use a different HTTP method (e.g. `GET` or `PUT`).
+    pub fn with_method<M: Into<String>>(self, method: M) -> Self {
+        Webhook {
+            method: method.into(),
+            ..self
+        }
+    }
+
+    /// Send an extra header with every request (e.g. for authentication).
+    pub fn with_header<N, V>(mut self, name: N, value: V) -> Self
+    where
+        N: Into<String>,
+        V: Into<String>,
+    {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// How long to wait for the server before giving up on an attempt.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Webhook { timeout, ..self }
+    }
+
+    /// Get the URL which will be called for a clip.
+    pub fn url(&self, notification: &Notification) -> String {
+        let timestamp = unix_seconds(notification.finished);
+        let (date, time) = utc(timestamp);
+
+        self.url_template
+            .replace("{index}", &notification.index.to_string())
+            .replace("{path}", &percent_encode(&notification.path()))
+            .replace("{start_frame}", &notification.start_frame.to_string())
+            .replace("{start_ms}", &notification.start_ms().to_string())
+            .replace("{frames}", &notification.frames.to_string())
+            .replace("{channels}", &notification.channels.to_string())
+            .replace("{sample_rate}", &notification.sample_rate.to_string())
+            .replace("{duration_ms}", &notification.duration_ms().to_string())
+            .replace("{timestamp}", &timestamp.to_string())
+            .replace("{date}", &date)
+            .replace("{time}", &time)
+    }
+
+    /// Call the webhook once.
+    pub fn notify(
+        &self,
+        notification: &Notification,
+    ) -> Result<(), UploadError> {
+        let endpoint = Endpoint::parse(&self.url(notification))?;
+
+        let mut headers = Vec::new();
+        let body = if self.method.eq_ignore_ascii_case("GET") {
+            String::new()
+        } else {
+            headers.push((
+                String::from("Content-Type"),
+                String::from("application/json"),
+            ));
+            notification.to_json()
+        };
+        headers.extend(self.headers.iter().cloned());
+
+        endpoint.send(
+            &self.method,
+            &endpoint.path,
+            &headers,
+            body.as_bytes(),
+            self.timeout,
+        )
+    }
+}
+
+/// Quote a string for use in JSON.
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => {
+                quoted.push_str(&format!("\\u{:04x}", c as u32))
+            },
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+/// Escape everything except unreserved characters and `/`, so a path can be
+/// used in a URL.
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_'
+            | b'~' | b'/' => char::from(b).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        thread,
+        time::UNIX_EPOCH,
+    };
+
+    fn notification() -> Notification {
+        Notification {
+            index: 7,
+            path: Some(PathBuf::from("clips/door bell \"7\".wav")),
+            start_frame: 32_000,
+            frames: 24_000,
+            channels: 2,
+            sample_rate: 16_000,
+            finished: UNIX_EPOCH + Duration::from_secs(1_329_264_000 + 3_723),
+        }
+    }
+
+    #[test]
+    fn fill_in_url_templates() {
+        let webhook = Webhook::new(
+            "http://localhost/{date}/{index}?ms={duration_ms}&ch={channels}\
+             &start={start_ms}&path={path}",
+        )
+        .unwrap();
+
+        assert_eq!(
+            webhook.url(&notification()),
+            "http://localhost/2012-02-15/7?ms=1500&ch=2&start=2000\
+             &path=clips/door%20bell%20%227%22.wav"
+        );
+        assert!(Webhook::new("ftp://example.com/{index}").is_err());
+    }
+
+    #[test]
+    fn the_details_are_posted_as_json() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!(
+            "http://{}/clips/{{index}}",
+            listener.local_addr().unwrap()
+        );
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request = String::new();
+            let mut content_length = 0;
+
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                request.push_str(&line);
+
+                let lowercase = line.to_ascii_lowercase();
+                if let Some(value) = lowercase.strip_prefix("content-length:")
+                {
+                    content_length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+            }
+
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            request.push_str(std::str::from_utf8(&body).unwrap());
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                .unwrap();
+            request
+        });
+
+        let webhook = Webhook::new(&url).unwrap().with_header("X-Test", "1");
+        webhook.notify(&notification()).unwrap();
+
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /clips/7 HTTP/1.1\r\n"));
+        assert!(request.contains("X-Test: 1\r\n"));
+        assert!(request.ends_with(
+            "\r\n\r\n{\"index\":7,\"path\":\"clips/door bell \\\"7\\\".wav\",\
+             \"start_frame\":32000,\"frames\":24000,\"channels\":2,\
+             \"sample_rate\":16000,\"start_ms\":2000,\"duration_ms\":1500,\
+             \"timestamp\":1329267723}"
+        ));
+    }
+}
@@ -0,0 +1,449 @@
+//! Streaming gate events and gated audio to browsers over WebSockets, using
+//! [`tungstenite`].
+//!
+//! Messages only ever go from the server to its clients. Anything the
+//! clients send after the handshake is ignored.
+
+use crate::Sink;
+use dasp::{sample::ToSample, Frame, Sample};
+use std::{
+    io,
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, SyncSender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+use tungstenite::{HandshakeError, Message, WebSocket};
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+/// How many messages can be waiting to be sent to a client before it's
+/// considered too slow and disconnected.
+const QUEUE_LENGTH: usize = 64;
+
+/// A WebSocket server which broadcasts messages to everyone connected.
+///
+/// Each client gets its own background thread for the handshake and for
+/// sending messages, so broadcasting never waits on the network. Any client
+/// which can't keep up (i.e. its queue of unsent messages fills up, or
+/// writing to it fails) is disconnected.
+#[derive(Debug, Clone)]
+pub struct Server {
+    clients: Arc<Mutex<Vec<SyncSender<Message>>>>,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl Server {
+    /// Start listening for connections.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Server> {
+        let listener = TcpListener::bind(addr)?;
+        Server::from_listener(listener)
+    }
+
+    /// Accept connections from an existing [`TcpListener`].
+    pub fn from_listener(listener: TcpListener) -> io::Result<Server> {
+        let server = Server {
+            clients: Arc::new(Mutex::new(Vec::new())),
+            dropped: Arc::new(AtomicUsize::new(0)),
+        };
+        let clients = Arc::clone(&server.clients);
+
+        thread::Builder::new()
+            .name(String::from("websocket-accept"))
+            .spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let clients = Arc::clone(&clients);
+                    // a client which never finishes the handshake shouldn't
+                    // stop anyone else from connecting
+                    let _ = thread::Builder::new()
+                        .name(String::from("websocket-client"))
+                        .spawn(move || serve(stream, &clients));
+                }
+            })?;
+
+        Ok(server)
+    }
+
+    /// How many clients are currently connected.
+    pub fn clients(&self) -> usize {
+        self.clients.lock().map(|c| c.len()).unwrap_or(0)
+    }
+
+    /// How many clients have been disconnected because they couldn't keep
+    /// up or the connection failed.
+    pub fn dropped_clients(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Send a text message to every client.
+    pub fn broadcast_text(&self, message: &str) {
+        self.broadcast(Message::text(message));
+    }
+
+    /// Send a binary message to every client.
+    pub fn broadcast_binary(&self, message: &[u8]) {
+        self.broadcast(Message::binary(message.to_vec()));
+    }
+
+    fn broadcast(&self, message: Message) {
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.retain(|client| {
+                let sent = client.try_send(message.clone()).is_ok();
+                if !sent {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                sent
+            });
+        }
+    }
+}
+
+/// A [`Sink`] adapter which streams transmissions to a WebSocket [`Server`].
+///
+/// Each transmission starts with a `{"event":"open"}` text message, followed
+/// by the audio as binary messages of interleaved 16-bit little-endian
+/// samples, and finishes with `{"event":"close","frames":...}`. If the
+/// transmission is thrown away (see [`Sink::discard_transmission()`]), it
+/// finishes with `{"event":"discard","frames":...}` instead, and clients
+/// should drop whatever audio they were sent.
+///
+/// When the gate reports where a transmission started (see
+/// [`Sink::transmission_started()`]), every event also has a `"sample"`
+/// field with the position of its first (for `open`) or one-past-the-last
+/// (for `close` and `discard`) frame.
+///
+/// Audio is sent in chunks of up to `chunk_size` frames. Sending only queues
+/// the message for each client, but it still allocates, so this shouldn't be
+/// used directly from a real-time audio thread. Clients which are dropped
+/// (see [`Server::dropped_clients()`]) are counted as errors.
+///
+/// ```rust,no_run
+/// use noise_gate::{websocket::{Server, WebSocketSink}, NoiseGate, Sink};
+///
+/// # struct Discard;
+/// # impl Sink<[i16; 1]> for Discard {
+/// #     fn record(&mut self, _: [i16; 1]) {}
+/// #     fn end_of_transmission(&mut self) {}
+/// # }
+/// let server = Server::bind("0.0.0.0:8080")?;
+/// let mut sink = WebSocketSink::new(Discard, server, 4096);
+/// let mut gate = NoiseGate::new(300, 12_000);
+///
+/// # let frames = [[0_i16]; 16];
+/// gate.process_frames(&frames, &mut sink);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct WebSocketSink<K> {
+    inner: K,
+    server: Server,
+    chunk_size: usize,
+    buffer: Vec<u8>,
+    buffered_frames: usize,
+    /// How many frames are in the current transmission, if there is one.
+    current: Option<usize>,
+    start_position: Option<u64>,
+}
+
+impl<K> WebSocketSink<K> {
+    /// Wrap a [`Sink`], sending audio to the `server` in chunks of
+    /// `chunk_size` frames.
+    pub fn new(inner: K, server: Server, chunk_size: usize) -> Self {
+        WebSocketSink {
+            inner,
+            server,
+            chunk_size: chunk_size.max(1),
+            buffer: Vec::new(),
+            buffered_frames: 0,
+            current: None,
+            start_position: None,
+        }
+    }
+
+    /// Get a reference to the inner [`Sink`].
+    pub fn inner(&self) -> &K { &self.inner }
+
+    /// Get a mutable reference to the inner [`Sink`].
+    pub fn inner_mut(&mut self) -> &mut K { &mut self.inner }
+
+    /// Consume the adapter, returning the inner [`Sink`].
+    pub fn into_inner(self) -> K { self.inner }
+
+    fn flush(&mut self) {
+        if !self.buffer.is_empty() {
+            self.server.broadcast_binary(&self.buffer);
+            self.buffer.clear();
+            self.buffered_frames = 0;
+        }
+    }
+
+    /// Announce the end of the current transmission, if there is one.
+    fn finished(&mut self, event: &str) {
+        if let Some(frames) = self.current.take() {
+            let end = self.start_position.take().map(|p| p + frames as u64);
+            let message = format!(
+                r#"{{"event":"{}","frames":{}{}}}"#,
+                event,
+                frames,
+                sample_field(end)
+            );
+            self.server.broadcast_text(&message);
+        }
+    }
+}
+
+impl<F, K> Sink<F> for WebSocketSink<K>
+where
+    F: Frame,
+    F::Sample: ToSample<i16>,
+    K: Sink<F>,
+{
+    fn record(&mut self, frame: F) {
+        match self.current.as_mut() {
+            Some(frames) => *frames += 1,
+            None => {
+                self.current = Some(1);
+                let message = format!(
+                    r#"{{"event":"open"{}}}"#,
+                    sample_field(self.start_position)
+                );
+                self.server.broadcast_text(&message);
+            },
+        }
+
+        for sample in frame.channels() {
+            let sample: i16 = sample.to_sample();
+            self.buffer.extend_from_slice(&sample.to_le_bytes());
+        }
+        self.buffered_frames += 1;
+
+        if self.buffered_frames >= self.chunk_size {
+            self.flush();
+        }
+
+        self.inner.record(frame);
+    }
+
+    fn end_of_transmission(&mut self) {
+        self.flush();
+        self.finished("close");
+        self.inner.end_of_transmission();
+    }
+
+    fn discard_transmission(&mut self) {
+        self.buffer.clear();
+        self.buffered_frames = 0;
+        self.finished("discard");
+        self.inner.discard_transmission();
+    }
+
+    fn transmission_started(&mut self, position: u64) {
+        if self.current.is_none() {
+            self.start_position = Some(position);
+        }
+        self.inner.transmission_started(position);
+    }
+
+    fn latency_samples(&self) -> usize { self.inner.latency_samples() }
+
+    fn errors(&self) -> usize {
+        self.server.dropped_clients() + self.inner.errors()
+    }
+}
+
+fn sample_field(position: Option<u64>) -> String {
+    position.map_or_else(String::new, |p| format!(r#","sample":{}"#, p))
+}
+
+/// Accept a new client and send it messages until the connection fails or
+/// the server gives up on it.
+fn serve(stream: TcpStream, clients: &Mutex<Vec<SyncSender<Message>>>) {
+    let mut client = match accept(stream) {
+        Ok(client) => client,
+        Err(_) => return,
+    };
+    let (sender, receiver) = mpsc::sync_channel(QUEUE_LENGTH);
+
+    match clients.lock() {
+        Ok(mut clients) => clients.push(sender),
+        Err(_) => return,
+    }
+
+    // dropping the receiver tells the server this client has gone
+    for message in receiver {
+        if client.send(message).is_err() {
+            return;
+        }
+    }
+}
+
+/// Complete the WebSocket handshake with a new client.
+fn accept(stream: TcpStream) -> io::Result<WebSocket<TcpStream>> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    // a client which stops reading shouldn't tie up its thread forever
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+
+    tungstenite::accept(stream).map_err(|e| match e {
+        HandshakeError::Failure(e) => {
+            io::Error::new(io::ErrorKind::InvalidData, e)
+        },
+        HandshakeError::Interrupted(_) => io::ErrorKind::WouldBlock.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Discard;
+
+    impl Sink<[i16; 2]> for Discard {
+        fn record(&mut self, _: [i16; 2]) {}
+
+        fn end_of_transmission(&mut self) {}
+    }
+
+    /// Remembers how the last transmission ended.
+    #[derive(Debug, Default)]
+    struct Ending {
+        ended: bool,
+        discarded: bool,
+    }
+
+    impl Sink<[i16; 2]> for Ending {
+        fn record(&mut self, _: [i16; 2]) {}
+
+        fn end_of_transmission(&mut self) { self.ended = true; }
+
+        fn discard_transmission(&mut self) { self.discarded = true; }
+    }
+
+    /// Start a server with a single client connected to it.
+    fn connect() -> (Server, WebSocket<TcpStream>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = Server::from_listener(listener).unwrap();
+
+        let stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let (client, _) =
+            tungstenite::client(format!("ws://{}/", addr), stream).unwrap();
+
+        while server.clients() == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        (server, client)
+    }
+
+    #[test]
+    fn transmissions_are_streamed_to_clients() {
+        let (server, mut client) = connect();
+
+        let mut sink = WebSocketSink::new(Discard, server, 2);
+        sink.record_frames(&[[1, -1], [2, -2], [3, -3]]);
+        sink.end_of_transmission();
+
+        assert_eq!(
+            client.read().unwrap(),
+            Message::text(r#"{"event":"open"}"#)
+        );
+        assert_eq!(
+            client.read().unwrap(),
+            Message::binary(vec![1, 0, 0xFF, 0xFF, 2, 0, 0xFE, 0xFF])
+        );
+        assert_eq!(
+            client.read().unwrap(),
+            Message::binary(vec![3, 0, 0xFD, 0xFF])
+        );
+        assert_eq!(
+            client.read().unwrap(),
+            Message::text(r#"{"event":"close","frames":3}"#)
+        );
+    }
+
+    #[test]
+    fn discarded_transmissions_are_cancelled() {
+        let (server, mut client) = connect();
+
+        let mut sink = WebSocketSink::new(Ending::default(), server, 2);
+        sink.record_frames(&[[1, -1], [2, -2], [3, -3]]);
+        sink.discard_transmission();
+
+        assert_eq!(
+            client.read().unwrap(),
+            Message::text(r#"{"event":"open"}"#)
+        );
+        // the first chunk was already sent, but the rest is thrown away
+        assert_eq!(
+            client.read().unwrap(),
+            Message::binary(vec![1, 0, 0xFF, 0xFF, 2, 0, 0xFE, 0xFF])
+        );
+        assert_eq!(
+            client.read().unwrap(),
+            Message::text(r#"{"event":"discard","frames":3}"#)
+        );
+        assert!(sink.inner().discarded);
+        assert!(!sink.inner().ended);
+    }
+
+    #[test]
+    fn stalled_handshakes_dont_block_other_clients() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = Server::from_listener(listener).unwrap();
+
+        // connects, but never says anything
+        let _stalled = TcpStream::connect(addr).unwrap();
+        let (_client, _) =
+            tungstenite::connect(format!("ws://{}/", addr)).unwrap();
+
+        while server.clients() == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn disconnected_clients_are_counted_as_errors() {
+        let (server, client) = connect();
+        let sink = WebSocketSink::new(Discard, server.clone(), 1);
+        drop(client);
+
+        for _ in 0..1000 {
+            if server.clients() == 0 {
+                break;
+            }
+            server.broadcast_text("ping");
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        assert_eq!(server.clients(), 0);
+        assert_eq!(Sink::<[i16; 2]>::errors(&sink), 1);
+    }
+
+    #[test]
+    fn events_include_the_position_when_known() {
+        let (server, mut client) = connect();
+
+        let mut sink = WebSocketSink::new(Discard, server, 16);
+        sink.transmission_started(1000);
+        sink.record_frames(&[[1, -1], [2, -2], [3, -3]]);
+        sink.end_of_transmission();
+
+        assert_eq!(
+            client.read().unwrap(),
+            Message::text(r#"{"event":"open","sample":1000}"#)
+        );
+        assert!(client.read().unwrap().is_binary());
+        assert_eq!(
+            client.read().unwrap(),
+            Message::text(r#"{"event":"close","frames":3,"sample":1003}"#)
+        );
+    }
+}
@@ -0,0 +1,133 @@
+//! Run the gate over the recordings in `data/` and make sure it finds
+//! exactly the same segments as last time.
+//!
+//! If a change is *meant* to alter the gate's behaviour, regenerate the
+//! expected segments with `UPDATE_GOLDEN=1 cargo test --test golden` and
+//! review the diff.
+
+use hound::WavReader;
+use noise_gate::{frames, NoiseGate};
+use std::{
+    fmt::Write as _,
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+const DATA_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/data/");
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/");
+
+/// The (threshold, release time in milliseconds) pairs each recording is
+/// run with.
+const CONFIGURATIONS: &[(i16, u32)] = &[(300, 250), (1000, 1000)];
+
+#[test]
+fn segments_match_the_golden_files() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut mismatches = Vec::new();
+
+    for recording in recordings() {
+        let (sample_rate, frames) = load(&recording);
+        let stem = recording.file_stem().unwrap().to_str().unwrap();
+
+        for &(threshold, release_ms) in CONFIGURATIONS {
+            let release_time = (sample_rate * release_ms / 1000) as usize;
+            let segments = segments(&frames, threshold, release_time);
+            let got = render(threshold, release_ms, sample_rate, &segments);
+
+            let name = format!("{}-t{}-r{}ms.txt", stem, threshold, release_ms);
+            let golden = Path::new(GOLDEN_DIR).join(&name);
+
+            if update {
+                fs::create_dir_all(GOLDEN_DIR).unwrap();
+                fs::write(&golden, &got).unwrap();
+                continue;
+            }
+
+            let expected = fs::read_to_string(&golden).unwrap_or_else(|e| {
+                panic!("Unable to read \"{}\": {}", golden.display(), e)
+            });
+
+            if got != expected {
+                mismatches.push(name);
+            }
+        }
+    }
+
+    assert!(
+        mismatches.is_empty(),
+        "The segments changed for {:?}. Re-run with UPDATE_GOLDEN=1 if this \
+         was intentional.",
+        mismatches
+    );
+}
+
+fn recordings() -> Vec<PathBuf> {
+    let mut recordings: Vec<_> = fs::read_dir(DATA_DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "wav"))
+        .collect();
+    recordings.sort();
+
+    assert!(
+        !recordings.is_empty(),
+        "No recordings found in {}",
+        DATA_DIR
+    );
+    recordings
+}
+
+fn load(path: &Path) -> (u32, Vec<[i16; 1]>) {
+    let reader = WavReader::open(path).unwrap();
+    let spec = reader.spec();
+    assert_eq!(spec.channels, 1, "The fixtures should all be mono");
+
+    let frames = frames::try_from_samples(reader.into_samples::<i16>())
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    (spec.sample_rate, frames)
+}
+
+fn segments(
+    frames: &[[i16; 1]],
+    threshold: i16,
+    release_time: usize,
+) -> Vec<Range<usize>> {
+    let mut gate = NoiseGate::new(threshold, release_time);
+    let mut segments: Vec<_> =
+        gate.segments(frames).map(|(range, _)| range).collect();
+
+    if gate.is_open() {
+        // the recording finished part-way through a segment
+        if let Some(last) = segments.last_mut() {
+            last.end = frames.len();
+        }
+    }
+
+    segments
+}
+
+/// Write the segments out in a format that's easy to diff.
+fn render(
+    threshold: i16,
+    release_ms: u32,
+    sample_rate: u32,
+    segments: &[Range<usize>],
+) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "# threshold={} release={}ms sample_rate={}",
+        threshold, release_ms, sample_rate
+    )
+    .unwrap();
+    writeln!(out, "# start end (frames)").unwrap();
+
+    for segment in segments {
+        writeln!(out, "{} {}", segment.start, segment.end).unwrap();
+    }
+
+    out
+}
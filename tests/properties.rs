@@ -0,0 +1,237 @@
+//! Randomised tests for invariants which should hold no matter what signal
+//! or parameters the gate is given.
+//!
+//! Each case is generated from a seed, so a failure can be reproduced by
+//! re-running that seed.
+
+use noise_gate::{NoiseGate, Sink};
+
+const CASES: u64 = 500;
+
+/// A tiny xorshift PRNG, so the tests are deterministic.
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // xorshift gets stuck on zero
+        Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 { self.next() % n }
+
+    fn i16(&mut self) -> i16 { self.next() as i16 }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len() as u64) as usize]
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Case {
+    threshold: i16,
+    release_time: usize,
+    frames: Vec<[i16; 2]>,
+}
+
+impl Case {
+    fn generate(seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+
+        let threshold = match rng.below(4) {
+            0 => rng.pick(&[0, 1, -1, i16::MIN, i16::MIN + 1, i16::MAX]),
+            _ => rng.i16(),
+        };
+        let release_time = match rng.below(4) {
+            0 => rng.pick(&[0, 1, usize::MAX]),
+            _ => rng.below(50) as usize,
+        };
+
+        // alternate between bursts of noise and near-silence so the gate
+        // actually opens and closes
+        let mut frames = Vec::new();
+        while frames.len() < 1000 {
+            let run = rng.below(100);
+            let shift = rng.pick(&[0, 4, 8, 12, 15]);
+
+            for _ in 0..run {
+                let mut sample = || rng.i16() >> shift;
+                frames.push([sample(), sample()]);
+            }
+
+            if rng.below(10) == 0 {
+                frames.push([i16::MIN, i16::MAX]);
+            }
+        }
+
+        Case {
+            threshold,
+            release_time,
+            frames,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Event {
+    Record([i16; 2]),
+    EndOfTransmission,
+}
+
+#[derive(Debug, Default)]
+struct Recorder(Vec<Event>);
+
+impl Sink<[i16; 2]> for Recorder {
+    fn record(&mut self, frame: [i16; 2]) { self.0.push(Event::Record(frame)); }
+
+    fn end_of_transmission(&mut self) { self.0.push(Event::EndOfTransmission); }
+}
+
+/// A deliberately simple model of the gate, written independently of the
+/// real implementation.
+fn model(case: &Case) -> Vec<Event> {
+    let magnitude = |s: i16| (s as i32).abs();
+    let loud = |frame: [i16; 2]| {
+        frame
+            .iter()
+            .any(|&s| magnitude(s) >= magnitude(case.threshold))
+    };
+
+    let mut events = Vec::new();
+    // None means closed, otherwise how many more quiet frames will be
+    // recorded before the gate closes
+    let mut open: Option<u128> = None;
+
+    for &frame in &case.frames {
+        open = match open {
+            // one quiet frame to start closing, then the release time
+            _ if loud(frame) => Some(case.release_time as u128 + 1),
+            Some(0) | None => None,
+            Some(remaining) => Some(remaining - 1),
+        };
+
+        if open.is_some() {
+            events.push(Event::Record(frame));
+        } else if events
+            .last()
+            .is_some_and(|e| *e != Event::EndOfTransmission)
+        {
+            events.push(Event::EndOfTransmission);
+        }
+    }
+
+    events
+}
+
+fn run(case: &Case, chunk_sizes: &mut Rng) -> Vec<Event> {
+    let mut gate = NoiseGate::new(case.threshold, case.release_time);
+    let mut sink = Recorder::default();
+    let mut remaining = &case.frames[..];
+
+    while !remaining.is_empty() {
+        let size = (chunk_sizes.below(64) as usize).min(remaining.len());
+        let (chunk, rest) = remaining.split_at(size);
+        gate.process_frames(chunk, &mut sink);
+        remaining = rest;
+    }
+
+    sink.0
+}
+
+fn for_each_case(test: impl Fn(u64, &Case, &[Event])) {
+    for seed in 0..CASES {
+        let case = Case::generate(seed);
+        let events = run(&case, &mut Rng::new(seed + CASES));
+        test(seed, &case, &events);
+    }
+}
+
+#[test]
+fn matches_a_simple_model() {
+    for_each_case(|seed, case, events| {
+        assert_eq!(events, &model(case)[..], "seed: {}", seed);
+    });
+}
+
+#[test]
+fn chunk_sizes_make_no_difference() {
+    for_each_case(|seed, case, events| {
+        let mut gate = NoiseGate::new(case.threshold, case.release_time);
+        let mut sink = Recorder::default();
+        gate.process_frames(&case.frames, &mut sink);
+
+        assert_eq!(events, &sink.0[..], "seed: {}", seed);
+    });
+}
+
+#[test]
+fn never_forwards_more_frames_than_it_was_given() {
+    for_each_case(|seed, case, events| {
+        let recorded = events
+            .iter()
+            .filter(|e| matches!(e, Event::Record(_)))
+            .count();
+
+        assert!(recorded <= case.frames.len(), "seed: {}", seed);
+    });
+}
+
+#[test]
+fn recordings_are_never_empty_and_start_loud() {
+    for_each_case(|seed, case, events| {
+        let threshold = (case.threshold as i32).abs();
+        let mut previous = Event::EndOfTransmission;
+
+        for &event in events {
+            if previous == Event::EndOfTransmission {
+                // the frame which opens the gate must be loud, and a clip is
+                // never ended before anything was recorded
+                match event {
+                    Event::Record(frame) => assert!(
+                        frame.iter().any(|&s| (s as i32).abs() >= threshold),
+                        "seed: {}",
+                        seed
+                    ),
+                    Event::EndOfTransmission => {
+                        panic!("Empty recording (seed: {})", seed)
+                    },
+                }
+            }
+
+            previous = event;
+        }
+    });
+}
+
+#[test]
+fn every_recording_eventually_ends() {
+    for seed in 0..CASES {
+        let case = Case::generate(seed);
+        if case.release_time > 10_000 {
+            continue;
+        }
+
+        let mut gate = NoiseGate::new(case.threshold, case.release_time);
+        let mut sink = Recorder::default();
+        gate.process_frames(&case.frames, &mut sink);
+
+        // enough silence that the gate has to close (unless the threshold
+        // is so low that even silence is loud)
+        let silence = vec![[0, 0]; case.release_time + 2];
+        gate.process_frames(&silence, &mut sink);
+
+        if case.threshold != 0 {
+            assert!(gate.is_closed(), "seed: {}", seed);
+            if let Some(last) = sink.0.last() {
+                assert_eq!(*last, Event::EndOfTransmission, "seed: {}", seed);
+            }
+        }
+    }
+}
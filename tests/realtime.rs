@@ -0,0 +1,129 @@
+//! Make sure the gate is safe to use from an audio callback, where
+//! allocating (and possibly blocking on the allocator's lock) isn't allowed.
+
+use noise_gate::{sinks::FadeEdges, NoiseGate, Sink};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+/// A global allocator which keeps track of how many allocations each thread
+/// has made, so tests running in parallel don't interfere with each other.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_size: usize,
+    ) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Count the number of allocations made while running `func`.
+fn allocations<T>(func: impl FnOnce() -> T) -> (usize, T) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let ret = func();
+    let after = ALLOCATIONS.with(Cell::get);
+
+    (after - before, ret)
+}
+
+/// A sink which does nothing but count, like a real-time sink writing into a
+/// preallocated buffer would.
+#[derive(Debug, Default)]
+struct Counter {
+    frames: usize,
+    clips: usize,
+}
+
+impl<F> Sink<F> for Counter {
+    fn record(&mut self, _: F) { self.frames += 1; }
+
+    fn end_of_transmission(&mut self) { self.clips += 1; }
+}
+
+fn bursts<F: Default + Copy>(loud: F) -> Vec<F> {
+    (0..48_000)
+        .map(|i| if i % 4800 < 1000 { loud } else { F::default() })
+        .collect()
+}
+
+#[test]
+fn processing_frames_never_allocates() {
+    let frames = bursts([1000_i16, -1000]);
+    let mut gate = NoiseGate::new(500, 200);
+    let mut sink = Counter::default();
+
+    let (count, _) = allocations(|| {
+        for chunk in frames.chunks(256) {
+            gate.process_frames(chunk, &mut sink);
+        }
+    });
+
+    assert_eq!(count, 0);
+    assert_eq!(sink.clips, 10);
+}
+
+#[test]
+fn iterating_over_segments_never_allocates() {
+    let frames = bursts([0.5_f32]);
+    let mut gate = NoiseGate::new(0.1, 200);
+
+    let (count, segments) = allocations(|| gate.segments(&frames).count());
+
+    assert_eq!(count, 0);
+    assert_eq!(segments, 10);
+}
+
+#[test]
+fn fading_preallocates_its_buffer() {
+    let frames = bursts([1000_i16]);
+    let mut gate = NoiseGate::new(500, 200);
+    let mut sink = FadeEdges::new(Counter::default(), 480);
+
+    let (count, _) = allocations(|| {
+        for chunk in frames.chunks(256) {
+            gate.process_frames(chunk, &mut sink);
+        }
+    });
+
+    assert_eq!(count, 0);
+    assert_eq!(sink.inner().clips, 10);
+}
+
+#[test]
+fn parameter_updates_never_allocate() {
+    let frames = bursts([1000_i16]);
+    let gate = NoiseGate::new(500, 200);
+    let (handle, mut processor) = noise_gate::control::split(gate, 480);
+    let mut sink = Counter::default();
+
+    let (count, _) = allocations(|| {
+        for (i, chunk) in frames.chunks(256).enumerate() {
+            handle.set_open_threshold(400 + i as i16);
+            processor.process_frames(chunk, &mut sink);
+        }
+    });
+
+    assert_eq!(count, 0);
+}